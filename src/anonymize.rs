@@ -0,0 +1,135 @@
+//! Replacing the values in a document with fakes, keeping its shape,
+//! so that payload structures can be shared without sharing their data
+
+use crate::path_value_sink::Rng;
+
+/// Return a document with the same structure and keys as `value`,
+/// with every scalar replaced by a random value of the same type:
+///
+/// - each ASCII letter or digit in a string is replaced by a random one of the same kind,
+///   keeping the string's length and punctuation (so dates and emails keep their format)
+/// - each number is replaced by a random number with the same sign, magnitude,
+///   and (for non-integers) number of decimal places
+/// - each boolean is replaced by a random boolean
+///
+/// The same `seed` always gives the same result.
+pub fn anonymize(value: &serde_json::Value, seed: u64) -> serde_json::Value {
+    let mut rng = Rng::new(seed);
+    anonymize_with(value, &mut rng)
+}
+
+fn anonymize_with(value: &serde_json::Value, rng: &mut Rng) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => serde_json::Value::Object(
+            object
+                .iter()
+                .map(|(k, v)| (k.clone(), anonymize_with(v, rng)))
+                .collect(),
+        ),
+        serde_json::Value::Array(array) => {
+            serde_json::Value::Array(array.iter().map(|v| anonymize_with(v, rng)).collect())
+        }
+        serde_json::Value::String(s) => serde_json::Value::String(fake_string(s, rng)),
+        serde_json::Value::Number(n) => serde_json::Value::Number(fake_number(n, rng)),
+        serde_json::Value::Bool(_) => serde_json::Value::Bool(rng.below(2) == 1),
+        serde_json::Value::Null => serde_json::Value::Null,
+    }
+}
+
+fn fake_string(s: &str, rng: &mut Rng) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_lowercase() {
+                (b'a' + rng.below(26) as u8) as char
+            } else if c.is_ascii_uppercase() {
+                (b'A' + rng.below(26) as u8) as char
+            } else if c.is_ascii_digit() {
+                (b'0' + rng.below(10) as u8) as char
+            } else if c.is_alphanumeric() {
+                (b'a' + rng.below(26) as u8) as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// A random integer with `digits` decimal digits
+fn fake_digits(digits: u32, rng: &mut Rng) -> u64 {
+    let digits = digits.min(19);
+
+    if digits <= 1 {
+        return rng.below(10) as u64;
+    }
+
+    let low = 10u64.pow(digits - 1);
+    low + rng.below((low * 9) as usize) as u64
+}
+
+fn fake_number(n: &serde_json::Number, rng: &mut Rng) -> serde_json::Number {
+    if let Some(u) = n.as_u64() {
+        serde_json::Number::from(fake_digits(u.checked_ilog10().unwrap_or(0) + 1, rng))
+    } else if let Some(i) = n.as_i64() {
+        let digits = i.unsigned_abs().checked_ilog10().unwrap_or(0) + 1;
+        serde_json::Number::from(-(fake_digits(digits.min(18), rng) as i64))
+    } else {
+        let f = n.as_f64().unwrap_or_default();
+
+        if f == 0.0 {
+            return n.clone();
+        }
+
+        let magnitude = 10f64.powi(f.abs().log10().floor() as i32);
+        let fake =
+            f.signum() * magnitude * (1.0 + 9.0 * rng.below(1 << 20) as f64 / (1 << 20) as f64);
+
+        let repr = n.to_string();
+        let fake = match repr.split_once('.') {
+            Some((_, decimals)) if !repr.contains(['e', 'E']) => {
+                let scale = 10f64.powi(decimals.len() as i32);
+                (fake * scale).round() / scale
+            }
+            _ => fake,
+        };
+
+        serde_json::Number::from_f64(fake).unwrap_or_else(|| n.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_shape() {
+        let v = serde_json::json!({
+            "email": "Jane.Doe@example.com",
+            "age": 42,
+            "balance": -1234.56,
+            "debt": -7,
+            "tags": ["a", true, null, 0.5],
+        });
+
+        let fake = anonymize(&v, 7);
+
+        assert_eq!(fake, anonymize(&v, 7));
+
+        let email = fake["email"].as_str().unwrap();
+        assert_eq!(email.len(), v["email"].as_str().unwrap().len());
+        assert_eq!(email.find('@'), Some(8));
+        assert!(email.starts_with(|c: char| c.is_ascii_uppercase()));
+
+        let age = fake["age"].as_u64().unwrap();
+        assert!((10..100).contains(&age));
+
+        let balance = fake["balance"].as_f64().unwrap();
+        assert!((-10000.0..=-1000.0).contains(&balance));
+        assert!(fake["balance"].to_string().split_once('.').unwrap().1.len() <= 2);
+
+        assert!((-9..=-1).contains(&fake["debt"].as_i64().unwrap()));
+
+        assert!(fake["tags"][1].is_boolean());
+        assert!(fake["tags"][2].is_null());
+        assert!(fake["tags"][3].is_f64());
+    }
+}
@@ -0,0 +1,313 @@
+//! Writing `PathValue`s as an [Avro](https://avro.apache.org/docs/1.11.1/specification/)
+//! Object Container File, with a schema inferred from the values
+
+use crate::path_format::write_json_pointer;
+use crate::path_value_sink::{PathValueSink, Rng};
+use crate::PathValue;
+use anyhow::Result;
+use std::io::Write;
+
+const MAGIC: &[u8] = b"Obj\x01";
+
+/// The most records written in each block of the file
+const BLOCK_LEN: usize = 4096;
+
+/// A value of the `value` field of a record, as one of the branches of its union type
+#[derive(Clone, Debug, PartialEq)]
+enum AvroValue {
+    Null,
+    Boolean(bool),
+    Long(i64),
+    Double(f64),
+    String(String),
+}
+
+impl AvroValue {
+    /// The Avro type of this value
+    fn type_name(&self) -> &'static str {
+        match self {
+            AvroValue::Null => "null",
+            AvroValue::Boolean(_) => "boolean",
+            AvroValue::Long(_) => "long",
+            AvroValue::Double(_) => "double",
+            AvroValue::String(_) => "string",
+        }
+    }
+}
+
+impl From<&serde_json::Value> for AvroValue {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => AvroValue::Null,
+            serde_json::Value::Bool(b) => AvroValue::Boolean(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => AvroValue::Long(i),
+                // integers beyond the range of a long, too
+                None => AvroValue::Double(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => AvroValue::String(s.clone()),
+            // empty objects and arrays, as JSON
+            container => AvroValue::String(container.to_string()),
+        }
+    }
+}
+
+/// Write each `PathValue` as a record with a `path` (a JSON Pointer) and a `value`,
+/// in an Avro Object Container File.
+///
+/// The `value` field is a union of the types of the values seen,
+/// from `null`, `boolean`, `long`, `double`, and `string`
+/// (as which empty objects and arrays are written, as JSON),
+/// so the records are kept in memory until `finish` is called,
+/// when the schema is known and the whole file is written.
+#[derive(Debug)]
+pub struct AvroWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: AvroWriterOptions,
+    records: Vec<(String, AvroValue)>,
+}
+
+impl<'writer, W: Write + ?Sized> AvroWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: AvroWriterOptions) -> Self {
+        Self {
+            writer,
+            options,
+            records: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AvroWriterOptions {
+    pub skip_empty_containers: bool,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// The marker written between blocks, which is random unless given,
+    /// for reproducible output
+    pub sync_marker: Option<[u8; 16]>,
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for AvroWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let skip = match pathvalue.value {
+            serde_json::Value::Array(a) => !a.is_empty() || self.options.skip_empty_containers,
+            serde_json::Value::Object(o) => !o.is_empty() || self.options.skip_empty_containers,
+            _ => false,
+        };
+
+        if skip || pathvalue.path_components.is_empty() {
+            return Ok(());
+        }
+
+        let mut pointer = Vec::new();
+        write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+
+        self.records
+            .push((String::from_utf8(pointer)?, pathvalue.value.into()));
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let types = self.union_types();
+
+        let schema = serde_json::json!({
+            "type": "record",
+            "name": "PathValue",
+            "namespace": "jindex",
+            "fields": [
+                {"name": "path", "type": "string"},
+                {"name": "value", "type": types},
+            ]
+        });
+
+        let sync_marker = self.options.sync_marker.unwrap_or_else(random_sync_marker);
+
+        self.writer.write_all(MAGIC)?;
+        // the file metadata, a map with one block of two entries
+        write_long(self.writer, 2)?;
+        write_bytes(self.writer, b"avro.schema")?;
+        write_bytes(self.writer, schema.to_string().as_bytes())?;
+        write_bytes(self.writer, b"avro.codec")?;
+        write_bytes(self.writer, b"null")?;
+        write_long(self.writer, 0)?;
+        self.writer.write_all(&sync_marker)?;
+
+        let mut block = Vec::new();
+
+        for records in self.records.chunks(BLOCK_LEN) {
+            block.clear();
+
+            for (path, value) in records {
+                write_bytes(&mut block, path.as_bytes())?;
+
+                let branch = types
+                    .iter()
+                    .position(|t| *t == value.type_name())
+                    .expect("every type is in the union");
+                write_long(&mut block, branch as i64)?;
+
+                match value {
+                    AvroValue::Null => (),
+                    AvroValue::Boolean(b) => block.push(u8::from(*b)),
+                    AvroValue::Long(i) => write_long(&mut block, *i)?,
+                    AvroValue::Double(d) => block.extend_from_slice(&d.to_le_bytes()),
+                    AvroValue::String(s) => write_bytes(&mut block, s.as_bytes())?,
+                }
+            }
+
+            write_long(self.writer, records.len() as i64)?;
+            write_long(self.writer, block.len() as i64)?;
+            self.writer.write_all(&block)?;
+            self.writer.write_all(&sync_marker)?;
+        }
+
+        self.records.clear();
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> AvroWriter<'_, W> {
+    /// The branches of the union type of the `value` field, in a fixed order
+    fn union_types(&self) -> Vec<&'static str> {
+        let types: Vec<&'static str> = ["null", "boolean", "long", "double", "string"]
+            .into_iter()
+            .filter(|t| self.records.iter().any(|(_, v)| v.type_name() == *t))
+            .collect();
+
+        if types.is_empty() {
+            // a union must have at least one branch
+            vec!["null"]
+        } else {
+            types
+        }
+    }
+}
+
+fn random_sync_marker() -> [u8; 16] {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+
+    let mut rng = Rng::new(seed);
+    let mut marker = [0; 16];
+    marker[..8].copy_from_slice(&rng.next_u64().to_le_bytes());
+    marker[8..].copy_from_slice(&rng.next_u64().to_le_bytes());
+    marker
+}
+
+/// Write `n` as a zig-zag encoded variable-length integer
+fn write_long<W: Write + ?Sized>(writer: &mut W, n: i64) -> Result<()> {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+
+    loop {
+        let byte = (zigzag & 0x7f) as u8;
+        zigzag >>= 7;
+
+        if zigzag == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(());
+        }
+
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Write `bytes` (or a string) as its length and then its contents
+fn write_bytes<W: Write + ?Sized>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    write_long(writer, bytes.len() as i64)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex;
+
+    fn long(n: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_long(&mut out, n).unwrap();
+        out
+    }
+
+    #[test]
+    fn longs() {
+        assert_eq!(long(0), [0x00]);
+        assert_eq!(long(-1), [0x01]);
+        assert_eq!(long(1), [0x02]);
+        assert_eq!(long(-64), [0x7f]);
+        assert_eq!(long(64), [0x80, 0x01]);
+        assert_eq!(long(i64::MIN).len(), 10);
+    }
+
+    #[test]
+    fn container_file() {
+        let mut output = Vec::new();
+        let mut sink = AvroWriter::new(
+            &mut output,
+            AvroWriterOptions {
+                sync_marker: Some([7; 16]),
+                ..Default::default()
+            },
+        );
+
+        jindex(&mut sink, &serde_json::json!({"a": 1, "b": [null]})).unwrap();
+        sink.finish().unwrap();
+
+        let schema = serde_json::json!({
+            "type": "record",
+            "name": "PathValue",
+            "namespace": "jindex",
+            "fields": [
+                {"name": "path", "type": "string"},
+                {"name": "value", "type": ["null", "long"]},
+            ]
+        })
+        .to_string();
+
+        let mut expected = b"Obj\x01".to_vec();
+        expected.extend(long(2));
+        expected.extend(long(11));
+        expected.extend(b"avro.schema");
+        expected.extend(long(schema.len() as i64));
+        expected.extend(schema.as_bytes());
+        expected.extend(long(10));
+        expected.extend(b"avro.codec");
+        expected.extend(long(4));
+        expected.extend(b"null");
+        expected.extend(long(0));
+        expected.extend([7; 16]);
+
+        assert_eq!(&output[..expected.len()], expected);
+
+        let block = &output[expected.len()..];
+        // two records, in traversal order
+        assert_eq!(block[0], 4);
+        assert_eq!(&block[block.len() - 16..], [7; 16]);
+
+        let mut records = block[2..block.len() - 16].to_vec();
+        let a = [long(2), b"/a".to_vec(), long(1), long(1)].concat();
+        let b = [long(4), b"/b/0".to_vec(), long(0)].concat();
+        assert_eq!(block[1..2], long((a.len() + b.len()) as i64));
+
+        if records.starts_with(&a) {
+            records.drain(..a.len());
+            assert_eq!(records, b);
+        } else {
+            records.drain(..b.len());
+            assert_eq!(records, a);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Handling long strings that look like base64-encoded binary data,
+//! which make flattened output hard to read
+
+use crate::stats::looks_like_base64;
+use crate::{PathValue, ValueTransform};
+use anyhow::Result;
+use base64::Engine;
+use std::path::PathBuf;
+
+/// What [BinaryValues] does with a string that looks like base64
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum BinaryPolicy {
+    /// Leave the string as it is
+    #[default]
+    Keep,
+    /// Replace the string with the SHA-256 of its decoded bytes, like `"sha256:9f86d0..."`
+    Hash,
+    /// Replace the string with a placeholder, like `"<binary 1024 bytes>"`
+    Placeholder,
+    /// Write the decoded bytes to a file in this directory, named for their SHA-256,
+    /// like `9f86d0....bin`, and replace the string with the path of the file.
+    /// Values with the same bytes share a file, whichever document they are in
+    SideFiles(PathBuf),
+}
+
+/// A [ValueTransform] that applies `policy` to each string
+/// of at least `min_len` bytes that looks like base64 (see [looks_like_base64])
+/// and decodes as standard or URL-safe base64
+#[derive(Debug)]
+pub struct BinaryValues {
+    pub policy: BinaryPolicy,
+    pub min_len: usize,
+    /// The first error writing a side file, as `transform` cannot return errors
+    error: Option<anyhow::Error>,
+}
+
+impl Default for BinaryValues {
+    fn default() -> Self {
+        Self::new(BinaryPolicy::default())
+    }
+}
+
+impl BinaryValues {
+    pub fn new(policy: BinaryPolicy) -> Self {
+        Self {
+            policy,
+            min_len: 256,
+            error: None,
+        }
+    }
+
+    /// Return the first error from writing a side file since this was last called, if any.
+    /// Callers using [BinaryPolicy::SideFiles] should call this after each document.
+    pub fn take_error(&mut self) -> Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Write `bytes` to a file in `dir` named for their hash, so that names are short,
+    /// and the files of different values (as at the same path in different documents)
+    /// do not overwrite each other
+    fn write_side_file(&self, dir: &std::path::Path, bytes: &[u8]) -> Result<PathBuf> {
+        let path = dir.join(format!("{}.bin", sha256_hex(bytes)));
+
+        if !path.exists() {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(&path, bytes)?;
+        }
+
+        Ok(path)
+    }
+}
+
+/// The SHA-256 of `bytes`, in lowercase hex
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+impl ValueTransform for BinaryValues {
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value> {
+        if self.policy == BinaryPolicy::Keep {
+            return None;
+        }
+
+        let s = pathvalue.value.as_str()?;
+
+        if s.len() < self.min_len || !looks_like_base64(s) {
+            return None;
+        }
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(s)
+            .or_else(|_| base64::engine::general_purpose::URL_SAFE.decode(s))
+            .ok()?;
+
+        let replacement = match &self.policy {
+            BinaryPolicy::Keep => return None,
+            BinaryPolicy::Hash => format!("sha256:{}", sha256_hex(&bytes)),
+            BinaryPolicy::Placeholder => format!("<binary {} bytes>", bytes.len()),
+            BinaryPolicy::SideFiles(dir) => match self.write_side_file(dir, &bytes) {
+                Ok(path) => path.display().to_string(),
+                Err(error) => {
+                    self.error.get_or_insert(error);
+                    format!("<binary {} bytes>", bytes.len())
+                }
+            },
+        };
+
+        Some(serde_json::Value::String(replacement))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jindex_with_transform, path_value_sink::PathValueSink, JindexOptions};
+
+    #[derive(Default)]
+    struct Collect(Vec<serde_json::Value>);
+
+    impl PathValueSink for Collect {
+        fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+            if !pathvalue.path_components.is_empty() {
+                self.0.push(pathvalue.value.clone());
+            }
+            Ok(())
+        }
+    }
+
+    fn run(transform: &mut BinaryValues, value: &serde_json::Value) -> Vec<serde_json::Value> {
+        let mut sink = Collect::default();
+        jindex_with_transform(&mut sink, value, &JindexOptions::default(), transform).unwrap();
+        sink.0
+    }
+
+    #[test]
+    fn policies() {
+        // "hello world!" four times
+        let blob = "aGVsbG8gd29ybGQhaGVsbG8gd29ybGQhaGVsbG8gd29ybGQhaGVsbG8gd29ybGQh";
+        let value = serde_json::json!({"blob": blob});
+
+        let mut transform = BinaryValues {
+            min_len: 16,
+            ..BinaryValues::new(BinaryPolicy::Placeholder)
+        };
+        assert_eq!(
+            run(&mut transform, &value),
+            vec![serde_json::json!("<binary 48 bytes>")]
+        );
+
+        transform.policy = BinaryPolicy::Hash;
+        let hashed = run(&mut transform, &value);
+        assert!(hashed[0].as_str().unwrap().starts_with("sha256:"));
+        assert_eq!(hashed[0].as_str().unwrap().len(), 7 + 64);
+
+        transform.policy = BinaryPolicy::Keep;
+        assert_eq!(run(&mut transform, &value), vec![serde_json::json!(blob)]);
+
+        transform.policy = BinaryPolicy::Placeholder;
+        transform.min_len = 1024;
+        assert_eq!(run(&mut transform, &value), vec![serde_json::json!(blob)]);
+
+        let dir = std::env::temp_dir().join(format!("jindex-binary-{}", std::process::id()));
+        transform.policy = BinaryPolicy::SideFiles(dir.clone());
+        transform.min_len = 16;
+        let written = run(&mut transform, &value);
+        transform.take_error().unwrap();
+        assert_eq!(
+            std::fs::read(written[0].as_str().unwrap()).unwrap(),
+            b"hello world!".repeat(4)
+        );
+
+        // at the root, with a path too long for a file name, and at the same path as before
+        let other = "Z29vZGJ5ZSB3b3JsZCFnb29kYnllIHdvcmxkIWdvb2RieWUgd29ybGQh";
+        let mut deep = serde_json::json!({"blob": other});
+        for _ in 0..100 {
+            deep = serde_json::json!({"a_long_key_for_a_long_path": deep});
+        }
+
+        for value in [
+            serde_json::json!([blob]),
+            deep,
+            serde_json::json!({"blob": other}),
+        ] {
+            let written = run(&mut transform, &value);
+            transform.take_error().unwrap();
+            assert!(written.last().unwrap().as_str().unwrap().ends_with(".bin"));
+        }
+
+        assert_eq!(
+            std::fs::read(dir.join(format!("{}.bin", sha256_hex(&b"hello world!".repeat(4)))))
+                .unwrap(),
+            b"hello world!".repeat(4)
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+//! ANSI colors for output written to a terminal, in the style of gron
+
+use anyhow::Result;
+use std::io::Write;
+
+/// The color of paths
+pub const PATH: &str = "\x1b[34m";
+/// The color of strings, and of the keys of objects within values
+pub const STRING: &str = "\x1b[32m";
+pub const NUMBER: &str = "\x1b[36m";
+pub const BOOL: &str = "\x1b[33m";
+pub const NULL: &str = "\x1b[35m";
+/// Ends a color
+pub const RESET: &str = "\x1b[0m";
+
+/// The color of a scalar `value`, or `None` for objects and arrays,
+/// whose brackets and punctuation are not colored
+pub fn value_color(value: &serde_json::Value) -> Option<&'static str> {
+    match value {
+        serde_json::Value::Null => Some(NULL),
+        serde_json::Value::Bool(_) => Some(BOOL),
+        serde_json::Value::Number(_) => Some(NUMBER),
+        serde_json::Value::String(_) => Some(STRING),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => None,
+    }
+}
+
+/// Write `bytes` in `color`
+pub fn write_colored<W: Write + ?Sized>(writer: &mut W, color: &str, bytes: &[u8]) -> Result<()> {
+    writer.write_all(color.as_bytes())?;
+    writer.write_all(bytes)?;
+    writer.write_all(RESET.as_bytes())?;
+    Ok(())
+}
+
+/// Write `value` as compact JSON, like `serde_json::to_writer`,
+/// with each scalar (and each key) in its color
+pub fn write_colored_value<W: Write + ?Sized>(
+    writer: &mut W,
+    value: &serde_json::Value,
+) -> Result<()> {
+    match value {
+        serde_json::Value::Array(a) => {
+            writer.write_all(b"[")?;
+
+            for (i, element) in a.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_colored_value(writer, element)?;
+            }
+
+            writer.write_all(b"]")?;
+        }
+        serde_json::Value::Object(o) => {
+            writer.write_all(b"{")?;
+
+            for (i, (k, v)) in o.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_colored(writer, STRING, serde_json::to_string(k)?.as_bytes())?;
+                writer.write_all(b":")?;
+                write_colored_value(writer, v)?;
+            }
+
+            writer.write_all(b"}")?;
+        }
+        scalar => {
+            let color = value_color(scalar).expect("scalars have colors");
+            write_colored(writer, color, serde_json::to_string(scalar)?.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colored_values() {
+        let mut output = Vec::new();
+        write_colored_value(&mut output, &serde_json::json!({"a": [1, "x", true, null]})).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "{\x1b[32m\"a\"\x1b[0m:[\x1b[36m1\x1b[0m,\x1b[32m\"x\"\x1b[0m,\x1b[33mtrue\x1b[0m,\x1b[35mnull\x1b[0m]}"
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Structural comparison of documents
+
+use crate::path_format::escape_json_pointer_token;
+use crate::path_value_sink::PathValueSink;
+use crate::{jindex_owned, PathValue};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Options for [first_difference]
+#[derive(Debug, Default)]
+pub struct EqOptions {
+    /// Compare arrays as multisets, ignoring the order of their elements
+    pub ignore_array_order: bool,
+    /// Compare numbers by their value as `f64`, so that `1`, `1.0`, and `1e0` are equal
+    pub numeric_values: bool,
+}
+
+/// Compare `a` and `b` for structural equality, regardless of the order of object keys,
+/// returning the JSON Pointer of the first place they differ, or `None` if they are equal.
+///
+/// With `ignore_array_order`, an array is reported as a whole (rather than any one element)
+/// when its elements cannot be paired up.
+pub fn first_difference(
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+    options: &EqOptions,
+) -> Option<String> {
+    let mut pointer = String::new();
+
+    if difference_at(a, b, options, &mut pointer) {
+        Some(pointer)
+    } else {
+        None
+    }
+}
+
+/// Whether `a` and `b` differ, leaving the pointer to the difference in `pointer` if they do
+fn difference_at(
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+    options: &EqOptions,
+    pointer: &mut String,
+) -> bool {
+    match (a, b) {
+        (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
+            for (k, a_v) in a {
+                let len = pointer.len();
+                pointer.push('/');
+                pointer.push_str(&escape_json_pointer_token(k));
+
+                match b.get(k) {
+                    Some(b_v) => {
+                        if difference_at(a_v, b_v, options, pointer) {
+                            return true;
+                        }
+                    }
+                    None => return true,
+                }
+
+                pointer.truncate(len);
+            }
+
+            if let Some(k) = b.keys().find(|k| !a.contains_key(*k)) {
+                pointer.push('/');
+                pointer.push_str(&escape_json_pointer_token(k));
+                return true;
+            }
+
+            false
+        }
+        (serde_json::Value::Array(a), serde_json::Value::Array(b)) => {
+            if options.ignore_array_order {
+                return a.len() != b.len() || !is_permutation(a, b, options);
+            }
+
+            for (i, (a_v, b_v)) in a.iter().zip(b).enumerate() {
+                let len = pointer.len();
+                pointer.push('/');
+                pointer.push_str(itoa::Buffer::new().format(i));
+
+                if difference_at(a_v, b_v, options, pointer) {
+                    return true;
+                }
+
+                pointer.truncate(len);
+            }
+
+            if a.len() != b.len() {
+                pointer.push('/');
+                pointer.push_str(itoa::Buffer::new().format(a.len().min(b.len())));
+                return true;
+            }
+
+            false
+        }
+        (serde_json::Value::Number(a), serde_json::Value::Number(b)) if options.numeric_values => {
+            a.as_f64() != b.as_f64()
+        }
+        (a, b) => a != b,
+    }
+}
+
+/// Whether every element of `a` can be paired with an equal element of `b`
+fn is_permutation(a: &[serde_json::Value], b: &[serde_json::Value], options: &EqOptions) -> bool {
+    let mut used = vec![false; b.len()];
+
+    a.iter().all(|a_v| {
+        let found = b
+            .iter()
+            .enumerate()
+            .position(|(i, b_v)| !used[i] && !difference_at(a_v, b_v, options, &mut String::new()));
+
+        match found {
+            Some(i) => {
+                used[i] = true;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// The leaves of `value` (scalars, and empty objects and arrays) by their JSON Pointers,
+/// as a baseline for a [DiffSink]
+pub fn baseline(value: serde_json::Value) -> BTreeMap<String, serde_json::Value> {
+    jindex_owned(value)
+        .map(|pathvalue| (pathvalue.as_pathvalue().to_json_pointer(), pathvalue.value))
+        .collect()
+}
+
+/// Pass through to `sink` only the leaves of a document that differ from a baseline:
+/// those whose pointers are not in the baseline, or whose values are not equal
+/// to the baseline's under `options`.
+/// Objects and arrays that are not empty are never passed through,
+/// since whatever differs beneath them is.
+///
+/// The leaves of the baseline that the document does not have are
+/// [removed](DiffSink::removed) once it has been traversed.
+/// A `DiffSink` compares one document; to compare another, start a new one.
+#[derive(Debug)]
+pub struct DiffSink<'sink, S: PathValueSink> {
+    sink: &'sink mut S,
+    baseline: BTreeMap<String, serde_json::Value>,
+    options: EqOptions,
+}
+
+impl<'sink, S: PathValueSink> DiffSink<'sink, S> {
+    pub fn new(
+        sink: &'sink mut S,
+        baseline: BTreeMap<String, serde_json::Value>,
+        options: EqOptions,
+    ) -> Self {
+        Self {
+            sink,
+            baseline,
+            options,
+        }
+    }
+
+    /// The leaves of the baseline that have not been reached, by their JSON Pointers
+    pub fn removed(&self) -> &BTreeMap<String, serde_json::Value> {
+        &self.baseline
+    }
+
+    /// Like [DiffSink::removed], taking the leaves
+    pub fn into_removed(self) -> BTreeMap<String, serde_json::Value> {
+        self.baseline
+    }
+}
+
+impl<'sink, S: PathValueSink> PathValueSink for DiffSink<'sink, S> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        match pathvalue.value {
+            serde_json::Value::Array(a) if !a.is_empty() => return Ok(()),
+            serde_json::Value::Object(o) if !o.is_empty() => return Ok(()),
+            _ => (),
+        }
+
+        match self.baseline.remove(&pathvalue.to_json_pointer()) {
+            Some(baseline_value)
+                if first_difference(&baseline_value, pathvalue.value, &self.options).is_none() =>
+            {
+                Ok(())
+            }
+            _ => self.sink.handle_pathvalue(pathvalue),
+        }
+    }
+
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        self.sink.start_document(document_index)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.sink.finish()
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sink.sort_keys()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences() {
+        let a = serde_json::json!({"a": [1, 2, {"b/c": 1.0}], "d": true});
+
+        assert_eq!(
+            first_difference(&a, &a.clone(), &EqOptions::default()),
+            None
+        );
+
+        let b = serde_json::json!({"d": true, "a": [1, 2, {"b/c": 1}]});
+
+        assert_eq!(
+            first_difference(&a, &b, &EqOptions::default()),
+            Some("/a/2/b~1c".to_string())
+        );
+
+        let numeric = EqOptions {
+            numeric_values: true,
+            ..Default::default()
+        };
+
+        assert_eq!(first_difference(&a, &b, &numeric), None);
+
+        let c = serde_json::json!({"d": true, "a": [{"b/c": 1}, 2, 1], "e": null});
+
+        assert_eq!(first_difference(&a, &c, &numeric), Some("/a/0".to_string()));
+
+        let unordered = EqOptions {
+            ignore_array_order: true,
+            numeric_values: true,
+        };
+
+        assert_eq!(first_difference(&a, &c, &unordered), Some("/e".to_string()));
+
+        assert_eq!(
+            first_difference(
+                &serde_json::json!([1, 1]),
+                &serde_json::json!([1, 2]),
+                &unordered
+            ),
+            Some("".to_string())
+        );
+    }
+
+    #[test]
+    fn diff_sink() {
+        use crate::path_value_sink::{GronWriter, GronWriterOptions};
+        use crate::{jindex_with_options, JindexOptions, TraversalOrder};
+
+        let old = serde_json::json!({"a": [1, 2], "b": {"c": "x", "d": 1.0}, "e": {}});
+        let new = serde_json::json!({"a": [1, 3, 4], "b": {"c": "x", "d": 1}, "e": []});
+
+        let mut output = Vec::new();
+        let mut writer = GronWriter::new(&mut output, GronWriterOptions::default());
+        let mut sink = DiffSink::new(
+            &mut writer,
+            baseline(old),
+            EqOptions {
+                numeric_values: true,
+                ..Default::default()
+            },
+        );
+
+        jindex_with_options(
+            &mut sink,
+            &new,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(sink.removed().is_empty());
+        sink.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "json.a[1] = 3;\njson.a[2] = 4;\njson.e = [];\n"
+        );
+
+        let mut output = Vec::new();
+        let mut writer = GronWriter::new(&mut output, GronWriterOptions::default());
+        let mut sink = DiffSink::new(
+            &mut writer,
+            baseline(serde_json::json!({"a": 1, "b": [true]})),
+            EqOptions::default(),
+        );
+        jindex_with_options(
+            &mut sink,
+            &serde_json::json!({"a": 1}),
+            &JindexOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            sink.into_removed().into_keys().collect::<Vec<_>>(),
+            vec!["/b/0"]
+        );
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn diff_sink_containers() {
+        use crate::jindex;
+        use crate::path_value_sink::{TypesWriter, TypesWriterOptions};
+
+        let old = serde_json::json!({"a": {"b": 1}, "c": [[true]]});
+
+        let mut output = Vec::new();
+        let mut writer = TypesWriter::new(&mut output, TypesWriterOptions::default());
+        let mut sink = DiffSink::new(&mut writer, baseline(old.clone()), EqOptions::default());
+        jindex(&mut sink, &old).unwrap();
+        sink.finish().unwrap();
+
+        assert!(output.is_empty());
+
+        let mut output = Vec::new();
+        let mut writer = TypesWriter::new(&mut output, TypesWriterOptions::default());
+        let mut sink = DiffSink::new(&mut writer, baseline(old), EqOptions::default());
+        jindex(
+            &mut sink,
+            &serde_json::json!({"a": {"b": "x"}, "c": [[true]]}),
+        )
+        .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "json.a.b\tstring\n");
+    }
+}
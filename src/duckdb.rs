@@ -0,0 +1,52 @@
+//! Loading records written by [JSONWriter](crate::path_value_sink::JSONWriter)
+//! into [DuckDB](https://duckdb.org)
+
+use anyhow::Result;
+use std::io::Write;
+
+/// Write a SQL script that (re)creates the table `table` in DuckDB
+/// from the newline-delimited JSON records in the file at `records_path`,
+/// so that running the script, as with `duckdb my.db < script.sql`,
+/// is all it takes to query them
+pub fn write_duckdb_script<W: Write>(
+    writer: &mut W,
+    table: &str,
+    records_path: &str,
+) -> Result<()> {
+    writeln!(
+        writer,
+        "CREATE OR REPLACE TABLE {} AS\nSELECT * FROM read_json_auto({}, format = 'newline_delimited');",
+        sql_identifier(table),
+        sql_string(records_path)
+    )?;
+
+    Ok(())
+}
+
+/// `s` as a quoted SQL identifier, like `"my table"`
+fn sql_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// `s` as a SQL string literal, like `'it''s'`
+fn sql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let mut script = Vec::new();
+        write_duckdb_script(&mut script, "my \"table\"", "out/it's.jsonl").unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&script).unwrap(),
+            "CREATE OR REPLACE TABLE \"my \"\"table\"\"\" AS
+SELECT * FROM read_json_auto('out/it''s.jsonl', format = 'newline_delimited');
+"
+        );
+    }
+}
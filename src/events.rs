@@ -0,0 +1,249 @@
+//! Traversal as a stream of events, for sinks that keep their own state for the current path,
+//! like one growing buffer with the path rendered as a string,
+//! rather than receiving the whole path of every node as a [PathValue](crate::PathValue)
+
+use crate::{
+    check_depth, is_excluded, root_pathvalue, sampled_indexes, JindexOptions, PathComponent,
+    SampledIndexes, TraversalOrder,
+};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::Result;
+
+/// Receives the events of [jindex_events].
+///
+/// For `{"a": [1]}`, these are `value({"a": [1]})`, `enter(a)`, `value([1])`, `enter(0)`,
+/// `value(1)`, `leave()`, `leave()`: `enter` and `leave` are always balanced,
+/// and the current path is the components entered and not yet left.
+pub trait PathEventSink {
+    /// Descend to the child at `component` of the current node
+    fn enter(&mut self, component: PathComponent) -> Result<()>;
+
+    /// Return to the parent of the current node, undoing the last `enter`
+    fn leave(&mut self) -> Result<()>;
+
+    /// Handle the value at the current path, once for every node,
+    /// before entering any of its children
+    fn value(&mut self, value: &serde_json::Value) -> Result<()>;
+
+    /// Whether to visit the keys of each object in sorted order;
+    /// see [PathValueSink::sort_keys](crate::path_value_sink::PathValueSink::sort_keys)
+    fn sort_keys(&self) -> bool {
+        false
+    }
+}
+
+/// Like [jindex_with_options](crate::jindex_with_options), but passing `sink`
+/// an event for each step of the traversal, rather than a path for each node.
+///
+/// The path to the root given by `options.root` and `options.path_prefix`
+/// is entered before its value, and left after the last event.
+/// Excluded nodes are never entered.
+pub fn jindex_events<'pv, S: PathEventSink>(
+    sink: &mut S,
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+) -> Result<()> {
+    let root = root_pathvalue(json, options)?;
+
+    for component in &root.path_components {
+        sink.enter(component.clone())?;
+    }
+
+    let mut traversal = Traversal {
+        options,
+        sort_keys: sink.sort_keys(),
+        path_components: root.path_components,
+        stack: vec![],
+    };
+
+    let root_depth = traversal.path_components.len();
+    traversal.visit(sink, root.value)?;
+
+    while let Some(frame) = traversal.stack.last_mut() {
+        let Some((component, value)) = frame.next_child(options.order) else {
+            traversal.stack.pop();
+            if traversal.path_components.len() > root_depth {
+                traversal.path_components.pop();
+                sink.leave()?;
+            }
+            continue;
+        };
+
+        traversal.path_components.push(component.clone());
+
+        if is_excluded(&traversal.path_components, options) {
+            traversal.path_components.pop();
+            continue;
+        }
+
+        sink.enter(component)?;
+
+        if !traversal.visit(sink, value)? {
+            traversal.path_components.pop();
+            sink.leave()?;
+        }
+    }
+
+    for _ in 0..root_depth {
+        sink.leave()?;
+    }
+
+    Ok(())
+}
+
+struct Traversal<'pv, 'options> {
+    options: &'options JindexOptions<'pv>,
+    sort_keys: bool,
+    /// The path to the current node, for exclusions and errors
+    path_components: Vec<PathComponent<'pv>>,
+    /// The objects and arrays whose children are being visited, innermost last
+    stack: Vec<Frame<'pv>>,
+}
+
+impl<'pv> Traversal<'pv, '_> {
+    /// Pass the value at the current path to `sink`,
+    /// returning whether it has children to visit
+    fn visit<S: PathEventSink>(
+        &mut self,
+        sink: &mut S,
+        value: &'pv serde_json::Value,
+    ) -> Result<bool> {
+        check_depth(&self.path_components, self.options)?;
+
+        sink.value(value)?;
+
+        let children = match value {
+            serde_json::Value::Object(object) if !object.is_empty() => {
+                if self.sort_keys {
+                    let mut entries: Vec<_> = object.iter().collect();
+                    entries.sort_by_key(|(k, _)| *k);
+                    Children::SortedObject(entries.into_iter())
+                } else {
+                    Children::Object(object.iter())
+                }
+            }
+            serde_json::Value::Array(array) if !array.is_empty() => Children::Array(
+                array,
+                sampled_indexes(self.options.array_sample, array.len()),
+            ),
+            _leaf => return Ok(false),
+        };
+
+        self.stack.push(Frame { children });
+
+        Ok(true)
+    }
+}
+
+/// An object or array whose children are being visited
+struct Frame<'pv> {
+    children: Children<'pv>,
+}
+
+enum Children<'pv> {
+    Object(serde_json::map::Iter<'pv>),
+    SortedObject(vec::IntoIter<(&'pv String, &'pv serde_json::Value)>),
+    Array(&'pv [serde_json::Value], SampledIndexes),
+}
+
+impl<'pv> Frame<'pv> {
+    /// The next child to visit, first child first in [TraversalOrder::Document],
+    /// and last child first otherwise, but for sorted keys, which are visited
+    /// in ascending order unless in [TraversalOrder::Reverse]
+    fn next_child(
+        &mut self,
+        order: TraversalOrder,
+    ) -> Option<(PathComponent<'pv>, &'pv serde_json::Value)> {
+        let forward = order == TraversalOrder::Document;
+
+        let entry = match &mut self.children {
+            Children::Object(entries) if forward => entries.next(),
+            Children::Object(entries) => entries.next_back(),
+            Children::SortedObject(entries) if order != TraversalOrder::Reverse => entries.next(),
+            Children::SortedObject(entries) => entries.next_back(),
+            Children::Array(array, indexes) => {
+                let i = if forward {
+                    indexes.next()
+                } else {
+                    indexes.next_back()
+                }?;
+
+                return Some((PathComponent::Index(i), &array[i]));
+            }
+        };
+
+        entry.map(|(k, v)| {
+            let component = PathComponent::key(k);
+
+            (component, v)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_pattern::PathPattern;
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl PathEventSink for Events {
+        fn enter(&mut self, component: PathComponent) -> Result<()> {
+            self.0
+                .push(format!("enter {}", serde_json::to_string(&component)?));
+            Ok(())
+        }
+
+        fn leave(&mut self) -> Result<()> {
+            self.0.push("leave".to_string());
+            Ok(())
+        }
+
+        fn value(&mut self, value: &serde_json::Value) -> Result<()> {
+            self.0.push(format!("value {}", value));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events() {
+        let v = serde_json::json!({"a": [1, {}], "b": {"c": null}});
+
+        let mut events = Events::default();
+        jindex_events(
+            &mut events,
+            &v,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                exclude: vec![PathPattern::glob("*.b.c")],
+                path_prefix: vec![PathComponent::Index(7)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            events.0,
+            vec![
+                "enter 7",
+                "value {\"a\":[1,{}],\"b\":{\"c\":null}}",
+                "enter \"a\"",
+                "value [1,{}]",
+                "enter 0",
+                "value 1",
+                "leave",
+                "enter 1",
+                "value {}",
+                "leave",
+                "leave",
+                "enter \"b\"",
+                "value {\"c\":null}",
+                "leave",
+                "leave",
+            ]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::Write;
+
+/// A writer that flushes the writer it wraps (usually a `BufWriter`)
+/// after every `every` records, so that output piped into interactive tools
+/// appears as it is produced rather than in bursts of the buffer's size.
+///
+/// Records are the runs of bytes ending with `record_terminator`.
+#[derive(Debug)]
+pub struct FlushingWriter<W: Write> {
+    inner: W,
+    record_terminator: Vec<u8>,
+    every: usize,
+    records_since_flush: usize,
+    /// How many bytes of `record_terminator` the bytes written so far end with
+    matched: usize,
+}
+
+impl<W: Write> FlushingWriter<W> {
+    pub fn new(inner: W, record_terminator: &[u8], every: usize) -> Self {
+        Self {
+            inner,
+            record_terminator: record_terminator.to_vec(),
+            every: every.max(1),
+            records_since_flush: 0,
+            matched: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for FlushingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+
+        if self.record_terminator.is_empty() {
+            return Ok(written);
+        }
+
+        for byte in &buf[..written] {
+            if *byte == self.record_terminator[self.matched] {
+                self.matched += 1;
+            } else {
+                self.matched = usize::from(*byte == self.record_terminator[0]);
+            }
+
+            if self.matched == self.record_terminator.len() {
+                self.matched = 0;
+                self.records_since_flush += 1;
+            }
+        }
+
+        if self.records_since_flush >= self.every {
+            self.records_since_flush = 0;
+            self.inner.flush()?;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufWriter;
+
+    #[test]
+    fn flushes_every_n_records() {
+        let mut out = Vec::new();
+
+        {
+            let mut writer = FlushingWriter::new(BufWriter::new(&mut out), b"\r\n", 2);
+
+            writer.write_all(b"a\r\nb\r").unwrap();
+            assert_eq!(writer.get_ref_len(), 0);
+
+            writer.write_all(b"\n").unwrap();
+            assert_eq!(writer.get_ref_len(), 6);
+
+            writer.write_all(b"c\r\n").unwrap();
+            assert_eq!(writer.get_ref_len(), 6);
+        }
+
+        assert_eq!(out, b"a\r\nb\r\nc\r\n");
+    }
+
+    impl FlushingWriter<BufWriter<&mut Vec<u8>>> {
+        /// The number of bytes that have been flushed through to the `Vec`
+        fn get_ref_len(&self) -> usize {
+            self.inner.get_ref().len()
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Entry points for fuzzing harnesses, like those of cargo-fuzz,
+//! that flatten arbitrary input in any of the formats of [SinkRegistry::builtin]
+//! and report every failure as an error rather than a panic:
+//!
+//! ```text
+//! fuzz_target!(|data: &[u8]| {
+//!     for sink in jindex::registry::SinkRegistry::builtin().iter() {
+//!         let _ = jindex::fuzz::try_flatten_bytes(sink.name, data);
+//!     }
+//! });
+//! ```
+
+use crate::registry::SinkRegistry;
+use crate::{jindex_with_options, JindexOptions, TraversalOrder};
+use anyhow::{anyhow, Result};
+
+/// The deepest path flattened, the same as serde_json's recursion limit for parsing,
+/// so that a constructed value cannot be deeper than one that could be parsed
+pub const MAX_DEPTH: usize = 128;
+
+/// Parse `input` as JSON and flatten it with the sink registered as `format`
+/// in [SinkRegistry::builtin], returning the sink's output.
+///
+/// Returns an error, rather than panicking, for an unknown format,
+/// input that is not a JSON object or array, or a document deeper than [MAX_DEPTH].
+/// The output is the same for the same input, as children are visited in document order
+pub fn try_flatten_bytes(format: &str, input: &[u8]) -> Result<Vec<u8>> {
+    let value: serde_json::Value = serde_json::from_slice(input)?;
+
+    try_flatten_value(format, &value)
+}
+
+/// Like [try_flatten_bytes], for a value that has already been parsed or constructed
+pub fn try_flatten_value(format: &str, value: &serde_json::Value) -> Result<Vec<u8>> {
+    let registry = SinkRegistry::builtin();
+    let registered = registry
+        .get(format)
+        .ok_or_else(|| anyhow!("unknown format: {:?}", format))?;
+
+    let mut output = Vec::new();
+    let mut sink = (registered.new)(&mut output);
+
+    jindex_with_options(
+        &mut sink,
+        value,
+        &JindexOptions {
+            order: TraversalOrder::Document,
+            max_depth: Some(MAX_DEPTH),
+            ..Default::default()
+        },
+    )?;
+    sink.finish()?;
+    drop(sink);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_bytes() {
+        assert_eq!(
+            try_flatten_bytes("gron", br#"{"a": [1, "b"]}"#).unwrap(),
+            b"json.a[0] = 1;\njson.a[1] = \"b\";\n"
+        );
+
+        assert!(try_flatten_bytes("nope", b"{}").is_err());
+        assert!(try_flatten_bytes("gron", b"{\"a\":").is_err());
+        assert!(try_flatten_bytes("gron", b"1").is_err());
+        assert!(try_flatten_bytes("gron", &[0xff, 0xfe]).is_err());
+
+        let mut deep = serde_json::json!([]);
+        for _ in 0..MAX_DEPTH + 1 {
+            deep = serde_json::json!([deep]);
+        }
+        assert!(try_flatten_value("json", &deep).is_err());
+    }
+
+    #[cfg(feature = "testing")]
+    mod properties {
+        use super::*;
+        use crate::testing::arb_value;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn no_panics_on_values(value in arb_value()) {
+                for sink in SinkRegistry::builtin().iter() {
+                    let output = try_flatten_value(sink.name, &value);
+                    prop_assert_eq!(output.ok(), try_flatten_value(sink.name, &value).ok());
+                }
+            }
+
+            #[test]
+            fn no_panics_on_bytes(input in any::<Vec<u8>>()) {
+                for sink in SinkRegistry::builtin().iter() {
+                    let _ = try_flatten_bytes(sink.name, &input);
+                }
+            }
+        }
+    }
+}
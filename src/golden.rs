@@ -0,0 +1,105 @@
+//! Comparing output against golden files, for snapshot-testing sinks
+//! the way this crate tests its own writers against `fixtures/`
+//! (which `jindex gen-fixture` generates)
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+/// Compare two outputs as multisets of lines, ignoring their order
+/// (which depends on [crate::TraversalOrder] and the order of object keys)
+/// and any empty lines, returning an error listing the lines
+/// that are missing from `actual` and the lines that are only in `actual`
+pub fn compare_lines(expected: &str, actual: &str) -> Result<()> {
+    fn sorted_lines(s: &str) -> Vec<&str> {
+        let mut lines: Vec<&str> = s.lines().filter(|line| !line.is_empty()).collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    let expected = sorted_lines(expected);
+    let actual = sorted_lines(actual);
+
+    if expected == actual {
+        return Ok(());
+    }
+
+    let mut missing = vec![];
+    let mut unexpected = vec![];
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        match (expected.get(i), actual.get(j)) {
+            (Some(e), Some(a)) if e == a => {
+                i += 1;
+                j += 1;
+            }
+            (Some(e), Some(a)) if e < a => {
+                missing.push(*e);
+                i += 1;
+            }
+            (Some(e), None) => {
+                missing.push(*e);
+                i += 1;
+            }
+            (_, Some(a)) => {
+                unexpected.push(*a);
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    let mut message = String::from("output does not match the golden output");
+
+    for line in missing {
+        message.push_str("\n- ");
+        message.push_str(line);
+    }
+
+    for line in unexpected {
+        message.push_str("\n+ ");
+        message.push_str(line);
+    }
+
+    Err(anyhow!(message))
+}
+
+/// Compare `actual` to the contents of the golden file at `golden_path` with [compare_lines]
+pub fn compare_to_golden_file<P: AsRef<Path>>(golden_path: P, actual: &str) -> Result<()> {
+    let golden_path = golden_path.as_ref();
+
+    let expected = std::fs::read_to_string(golden_path)
+        .map_err(|error| anyhow!("could not read {}: {}", golden_path.display(), error))?;
+
+    compare_lines(&expected, actual)
+        .map_err(|error| anyhow!("{}: {}", golden_path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex;
+    use crate::path_value_sink::{GronWriter, GronWriterOptions};
+
+    #[test]
+    fn compare() {
+        assert!(compare_lines("a\nb\n\nb\n", "b\nb\na").is_ok());
+
+        let error = compare_lines("a\nb\nc\n", "c\nd\na\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "output does not match the golden output\n- b\n+ d"
+        );
+
+        let input = std::fs::read_to_string("fixtures/one.json").unwrap();
+        let parsed = serde_json::from_str(&input).unwrap();
+
+        let mut challenge = Vec::new();
+        let mut sink = GronWriter::new(&mut challenge, GronWriterOptions::default());
+        jindex(&mut sink, &parsed).unwrap();
+
+        compare_to_golden_file("fixtures/one.gron", &String::from_utf8(challenge).unwrap())
+            .unwrap();
+    }
+}
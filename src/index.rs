@@ -0,0 +1,488 @@
+//! A persistent index of a JSON document: the JSON Pointer of every value,
+//! with the type of the value and where its text is in the document,
+//! so that values can be looked up by pointer by reading only the index
+//! and the value's bytes, rather than parsing the whole document again.
+//!
+//! An index file is:
+//!
+//! ```text
+//! b"JDX2"
+//! u32 length, and bytes, of the path of the document (as UTF-8 on platforms other than Unix)
+//! u64 length of the document, and u64 seconds and u32 nanoseconds of its modification time
+//!   since the Unix epoch, when it was indexed
+//! u64 number of entries
+//! u64 position of each entry, from the start of the first, in order of their pointers
+//! each entry: u32 length, and bytes, of its pointer; u64 offset and u64 length
+//!   of its value's text in the document; u8 type of its value
+//! ```
+//!
+//! with integers little-endian. As the positions have a fixed width,
+//! [PathIndex::get] binary searches the entries, reading only `log2(n)` of them.
+//!
+//! [PathIndex::open_source] refuses to open a document whose length or modification time
+//! differs from when it was indexed, as the offsets of its values may have changed.
+
+use crate::path_format::escape_json_pointer_token;
+use anyhow::{anyhow, Result};
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &[u8; 4] = b"JDX2";
+
+/// The state of a document when it was indexed, to tell whether it has changed since
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceState {
+    /// The length of the document, in bytes
+    pub len: u64,
+    /// The document's modification time, as seconds and nanoseconds since the Unix epoch
+    pub modified: (u64, u32),
+}
+
+impl SourceState {
+    /// The state of the file with `metadata`
+    pub fn of(metadata: &Metadata) -> Result<Self> {
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(Self {
+            len: metadata.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+        })
+    }
+}
+
+/// The JSON type of an indexed value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl ValueKind {
+    /// The type of the value whose text is `raw`
+    fn of(raw: &str) -> Self {
+        match raw.as_bytes().first() {
+            Some(b'n') => ValueKind::Null,
+            Some(b't' | b'f') => ValueKind::Boolean,
+            Some(b'"') => ValueKind::String,
+            Some(b'[') => ValueKind::Array,
+            Some(b'{') => ValueKind::Object,
+            _ => ValueKind::Number,
+        }
+    }
+
+    /// The type's name, like `boolean`
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueKind::Null => "null",
+            ValueKind::Boolean => "boolean",
+            ValueKind::Number => "number",
+            ValueKind::String => "string",
+            ValueKind::Array => "array",
+            ValueKind::Object => "object",
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self> {
+        Ok(match byte {
+            0 => ValueKind::Null,
+            1 => ValueKind::Boolean,
+            2 => ValueKind::Number,
+            3 => ValueKind::String,
+            4 => ValueKind::Array,
+            5 => ValueKind::Object,
+            other => return Err(anyhow!("invalid value type {} in index", other)),
+        })
+    }
+}
+
+/// A value of an indexed document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The JSON Pointer to the value
+    pub pointer: String,
+    /// The offset of the value's text in the document, in bytes
+    pub offset: u64,
+    /// The length of the value's text, in bytes
+    pub len: u64,
+    pub kind: ValueKind,
+}
+
+/// An entry for every value of `json`, including the root, in order of their pointers.
+///
+/// The text of each object and array is parsed again to find its children,
+/// so indexing takes time proportional to the size of the document times its depth.
+/// A byte order mark at the start of `json` is skipped, but counted in offsets
+pub fn index_document(json: &str) -> Result<Vec<IndexEntry>> {
+    let root: &RawValue = serde_json::from_str(json.strip_prefix('\u{feff}').unwrap_or(json))?;
+
+    let mut entries = vec![];
+    index_value(json, root, &mut String::new(), &mut entries)?;
+    entries.sort_unstable_by(|a, b| a.pointer.cmp(&b.pointer));
+
+    Ok(entries)
+}
+
+fn index_value(
+    json: &str,
+    raw: &RawValue,
+    pointer: &mut String,
+    entries: &mut Vec<IndexEntry>,
+) -> Result<()> {
+    let text = raw.get();
+    let kind = ValueKind::of(text);
+
+    entries.push(IndexEntry {
+        pointer: pointer.clone(),
+        // `raw` borrows its text from `json`
+        offset: (text.as_ptr() as usize - json.as_ptr() as usize) as u64,
+        len: text.len() as u64,
+        kind,
+    });
+
+    let len = pointer.len();
+
+    match kind {
+        ValueKind::Object => {
+            let object: BTreeMap<String, &RawValue> = serde_json::from_str(text)?;
+
+            for (k, v) in object {
+                pointer.push('/');
+                pointer.push_str(&escape_json_pointer_token(&k));
+                index_value(json, v, pointer, entries)?;
+                pointer.truncate(len);
+            }
+        }
+        ValueKind::Array => {
+            let array: Vec<&RawValue> = serde_json::from_str(text)?;
+
+            for (i, v) in array.into_iter().enumerate() {
+                pointer.push('/');
+                pointer.push_str(&i.to_string());
+                index_value(json, v, pointer, entries)?;
+                pointer.truncate(len);
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Write an index of the document at `source`, in `state`, with `entries`,
+/// which must be in order of their pointers, as from [index_document]
+pub fn write_index<W: Write>(
+    writer: &mut W,
+    source: &Path,
+    state: SourceState,
+    entries: &[IndexEntry],
+) -> Result<()> {
+    let source = path_bytes(source)?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(source.len() as u32).to_le_bytes())?;
+    writer.write_all(&source)?;
+    writer.write_all(&state.len.to_le_bytes())?;
+    writer.write_all(&state.modified.0.to_le_bytes())?;
+    writer.write_all(&state.modified.1.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    let mut position = 0u64;
+    for entry in entries {
+        writer.write_all(&position.to_le_bytes())?;
+        position += 4 + entry.pointer.len() as u64 + 8 + 8 + 1;
+    }
+
+    for entry in entries {
+        writer.write_all(&(entry.pointer.len() as u32).to_le_bytes())?;
+        writer.write_all(entry.pointer.as_bytes())?;
+        writer.write_all(&entry.offset.to_le_bytes())?;
+        writer.write_all(&entry.len.to_le_bytes())?;
+        writer.write_all(&[entry.kind as u8])?;
+    }
+
+    Ok(())
+}
+
+/// An index file written by [write_index], read on demand
+#[derive(Debug)]
+pub struct PathIndex<R> {
+    reader: R,
+    source: PathBuf,
+    source_state: SourceState,
+    len: u64,
+    /// The position in the index of the table of entry positions
+    table_start: u64,
+    /// The position in the index of the first entry
+    entries_start: u64,
+}
+
+impl<R: Read + Seek> PathIndex<R> {
+    /// Read the header of the index in `reader`
+    pub fn open(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(anyhow!("not a jindex index file"));
+        }
+
+        let source_len = read_u32(&mut reader)?;
+        let mut source = vec![0; source_len as usize];
+        reader.read_exact(&mut source)?;
+        let source = path_from_bytes(source)?;
+
+        let source_state = SourceState {
+            len: read_u64(&mut reader)?,
+            modified: (read_u64(&mut reader)?, read_u32(&mut reader)?),
+        };
+
+        let len = read_u64(&mut reader)?;
+        let table_start = 4 + 4 + source_len as u64 + 8 + 8 + 4 + 8;
+
+        Ok(Self {
+            reader,
+            source,
+            source_state,
+            len,
+            table_start,
+            entries_start: table_start + len * 8,
+        })
+    }
+
+    /// The path of the indexed document
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The state of the indexed document when it was indexed
+    pub fn source_state(&self) -> SourceState {
+        self.source_state
+    }
+
+    /// Open the indexed document, to [read_value]s from it,
+    /// failing if it has changed since it was indexed
+    pub fn open_source(&self) -> Result<File> {
+        let file = File::open(&self.source)?;
+
+        if SourceState::of(&file.metadata()?)? != self.source_state {
+            return Err(anyhow!(
+                "the index is stale: {} has changed since it was indexed",
+                self.source.display()
+            ));
+        }
+
+        Ok(file)
+    }
+
+    /// The number of entries
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `i`th entry, in order of their pointers
+    pub fn entry(&mut self, i: u64) -> Result<IndexEntry> {
+        if i >= self.len {
+            return Err(anyhow!("entry {} is past the end of the index", i));
+        }
+
+        self.reader
+            .seek(SeekFrom::Start(self.table_start + i * 8))?;
+        let position = read_u64(&mut self.reader)?;
+        self.reader
+            .seek(SeekFrom::Start(self.entries_start + position))?;
+
+        let pointer_len = read_u32(&mut self.reader)?;
+        let mut pointer = vec![0; pointer_len as usize];
+        self.reader.read_exact(&mut pointer)?;
+
+        let offset = read_u64(&mut self.reader)?;
+        let len = read_u64(&mut self.reader)?;
+        let mut kind = [0];
+        self.reader.read_exact(&mut kind)?;
+
+        Ok(IndexEntry {
+            pointer: String::from_utf8(pointer)?,
+            offset,
+            len,
+            kind: ValueKind::from_byte(kind[0])?,
+        })
+    }
+
+    /// The index of the first entry whose pointer is not less than `pointer`,
+    /// or [PathIndex::len] if there is none
+    pub fn lower_bound(&mut self, pointer: &str) -> Result<u64> {
+        let (mut low, mut high) = (0, self.len);
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            if self.entry(middle)?.pointer.as_str() < pointer {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// The entry for the value at `pointer`, if there is one
+    pub fn get(&mut self, pointer: &str) -> Result<Option<IndexEntry>> {
+        let i = self.lower_bound(pointer)?;
+
+        if i == self.len {
+            return Ok(None);
+        }
+
+        let entry = self.entry(i)?;
+        Ok((entry.pointer == pointer).then_some(entry))
+    }
+}
+
+/// The text of the value of `entry` in the indexed `document`
+pub fn read_value<R: Read + Seek>(document: &mut R, entry: &IndexEntry) -> Result<Vec<u8>> {
+    let mut value = vec![0; entry.len as usize];
+    document.seek(SeekFrom::Start(entry.offset))?;
+    document.read_exact(&mut value)?;
+
+    Ok(value)
+}
+
+/// The bytes of `path`, as the OS has them on Unix, or as UTF-8 elsewhere
+fn path_bytes(path: &Path) -> Result<Cow<'_, [u8]>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(Cow::Borrowed(path.as_os_str().as_bytes()))
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_str()
+            .map(|path| Cow::Borrowed(path.as_bytes()))
+            .ok_or_else(|| anyhow!("path is not valid Unicode: {}", path.display()))
+    }
+}
+
+/// The inverse of [path_bytes]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(PathBuf::from(String::from_utf8(bytes)?))
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn index() {
+        let json = "\u{feff}{\"a\": [1, {\"b/c\": \"x\"}], \"d~\": null, \"e\": {}}";
+
+        let entries = index_document(json).unwrap();
+        let mut buf = Vec::new();
+        write_index(
+            &mut buf,
+            Path::new("doc.json"),
+            SourceState::default(),
+            &entries,
+        )
+        .unwrap();
+
+        let mut index = PathIndex::open(Cursor::new(buf)).unwrap();
+        assert_eq!(index.source(), Path::new("doc.json"));
+        assert_eq!(index.len(), 7);
+
+        let mut document = Cursor::new(json.as_bytes());
+        for (pointer, value, kind) in [
+            ("", &json[3..], ValueKind::Object),
+            ("/a", "[1, {\"b/c\": \"x\"}]", ValueKind::Array),
+            ("/a/0", "1", ValueKind::Number),
+            ("/a/1/b~1c", "\"x\"", ValueKind::String),
+            ("/d~0", "null", ValueKind::Null),
+            ("/e", "{}", ValueKind::Object),
+        ] {
+            let entry = index.get(pointer).unwrap().unwrap();
+            assert_eq!(entry.kind, kind);
+            assert_eq!(read_value(&mut document, &entry).unwrap(), value.as_bytes());
+        }
+
+        for pointer in ["/a/2", "/b", "/e/f", "/z"] {
+            assert_eq!(index.get(pointer).unwrap(), None);
+        }
+
+        assert!(PathIndex::open(Cursor::new(b"JDX0")).is_err());
+    }
+
+    #[test]
+    fn stale_source() {
+        let dir = std::env::temp_dir().join(format!("jindex-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        #[cfg(unix)]
+        let name = {
+            use std::os::unix::ffi::OsStrExt;
+            std::ffi::OsStr::from_bytes(b"doc-\xff.json")
+        };
+        #[cfg(not(unix))]
+        let name = "doc.json";
+        let source = dir.join(name);
+
+        let json = r#"{"a": "xyz"}"#;
+        std::fs::write(&source, json).unwrap();
+
+        let mut buf = Vec::new();
+        let state = SourceState::of(&std::fs::metadata(&source).unwrap()).unwrap();
+        write_index(&mut buf, &source, state, &index_document(json).unwrap()).unwrap();
+
+        let mut index = PathIndex::open(Cursor::new(buf)).unwrap();
+        assert_eq!(index.source(), source);
+        assert_eq!(index.source_state(), state);
+
+        let entry = index.get("/a").unwrap().unwrap();
+        let mut document = index.open_source().unwrap();
+        assert_eq!(read_value(&mut document, &entry).unwrap(), b"\"xyz\"");
+
+        std::fs::write(&source, r#"{"abc": "xyz"}"#).unwrap();
+        assert!(index
+            .open_source()
+            .unwrap_err()
+            .to_string()
+            .contains("stale"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,814 @@
+use anyhow::{anyhow, Result};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+
+/// The result of [parse_lenient]
+#[derive(Debug)]
+pub struct LenientParse {
+    pub value: serde_json::Value,
+    /// Why `input` is not strict JSON, if it is not
+    pub strict_error: Option<serde_json::Error>,
+    /// The defects in `input` that were repaired, in order,
+    /// which are none if it is strict JSON
+    pub repairs: Vec<Repair>,
+}
+
+/// A defect in a document that [parse_lenient] repaired
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Repair {
+    pub kind: RepairKind,
+    /// The line of the defect, from 1
+    pub line: usize,
+    /// The column of the defect in its line, in bytes, from 1
+    pub column: usize,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+/// The kinds of defect that [parse_lenient] repairs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepairKind {
+    /// A comma after the last element of an array or object, like `[1, 2,]`
+    TrailingComma,
+    /// A key that is not quoted, like `{a: 1}`
+    UnquotedKey,
+    /// A string in single quotes, like `'a'`
+    SingleQuotedString,
+    /// A `//` or `/* */` comment
+    Comment,
+    /// `NaN` or `Infinity`, which become `null`
+    NonFiniteNumber,
+    /// A number that only JSON5 accepts, like `0x1F`, `+1`, `.5`, or `5.`
+    Json5Number,
+}
+
+impl fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RepairKind::TrailingComma => "trailing comma",
+            RepairKind::UnquotedKey => "unquoted key",
+            RepairKind::SingleQuotedString => "single-quoted string",
+            RepairKind::Comment => "comment",
+            RepairKind::NonFiniteNumber => "NaN or Infinity (as null)",
+            RepairKind::Json5Number => "JSON5 number",
+        })
+    }
+}
+
+/// Parse `input` as JSON, falling back to the forgiving
+/// [JSON5](https://json5.org) grammar if it is not strict JSON.
+/// JSON5 accepts common defects like trailing commas, unquoted keys,
+/// single-quoted strings, comments, and `NaN` and `Infinity`
+/// (which become `null`, as JSON cannot represent them).
+pub fn parse_lenient(input: &str) -> Result<LenientParse> {
+    match serde_json::from_str(input) {
+        Ok(value) => Ok(LenientParse {
+            value,
+            strict_error: None,
+            repairs: vec![],
+        }),
+        Err(strict_error) => {
+            let value = json5::from_str(input)
+                .map_err(|e| anyhow!("could not parse input, even leniently: {}", e))?;
+
+            Ok(LenientParse {
+                value,
+                strict_error: Some(strict_error),
+                repairs: lenient_repairs(input),
+            })
+        }
+    }
+}
+
+/// The characters of a document, with the position of each
+struct Scanner<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    /// The bytes of the current line before the next character
+    column: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += c.len_utf8();
+        }
+
+        Some(c)
+    }
+
+    /// A repair of `kind` at the next character
+    fn repair(&self, kind: RepairKind) -> Repair {
+        Repair {
+            kind,
+            line: self.line,
+            column: self.column + 1,
+        }
+    }
+}
+
+/// The defects in `input`, a JSON5 document, that strict JSON does not accept
+fn lenient_repairs(input: &str) -> Vec<Repair> {
+    let mut scanner = Scanner {
+        chars: input.chars().peekable(),
+        line: 1,
+        column: 0,
+    };
+    let mut repairs = vec![];
+
+    // whether each container that is open is an object
+    let mut objects: Vec<bool> = vec![];
+    // the last punctuation, and its repair if it is a trailing comma,
+    // or `None` after a value
+    let mut last: Option<(char, Repair)> = None;
+
+    while let Some(c) = scanner.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                scanner.next();
+            }
+            '/' => {
+                repairs.push(scanner.repair(RepairKind::Comment));
+                scanner.next();
+
+                match scanner.next() {
+                    Some('/') => while scanner.next().is_some_and(|c| c != '\n') {},
+                    Some('*') => {
+                        let mut previous = None;
+                        while let Some(c) = scanner.next() {
+                            if previous == Some('*') && c == '/' {
+                                break;
+                            }
+                            previous = Some(c);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            '"' | '\'' => {
+                if c == '\'' {
+                    repairs.push(scanner.repair(RepairKind::SingleQuotedString));
+                }
+                scanner.next();
+
+                while let Some(next) = scanner.next() {
+                    match next {
+                        '\\' => {
+                            scanner.next();
+                        }
+                        next if next == c => break,
+                        _ => {}
+                    }
+                }
+
+                last = None;
+            }
+            '{' | '[' | ',' | ':' => {
+                if c != ',' && c != ':' {
+                    objects.push(c == '{');
+                }
+                last = Some((c, scanner.repair(RepairKind::TrailingComma)));
+                scanner.next();
+            }
+            '}' | ']' => {
+                if let Some((',', trailing_comma)) = last {
+                    repairs.push(trailing_comma);
+                }
+                objects.pop();
+                last = None;
+                scanner.next();
+            }
+            _ => {
+                let at_key = objects.last() == Some(&true)
+                    && matches!(last, Some(('{', _)) | Some((',', _)));
+                let start = scanner.repair(RepairKind::UnquotedKey);
+                let mut word = String::new();
+
+                while let Some(c) = scanner.peek() {
+                    if c.is_whitespace() || "{}[],:\"'/".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    scanner.next();
+                }
+
+                let digits = word.trim_start_matches(['+', '-']);
+                let kind = if at_key {
+                    Some(RepairKind::UnquotedKey)
+                } else if digits == "NaN" || digits == "Infinity" {
+                    Some(RepairKind::NonFiniteNumber)
+                } else if word.starts_with('+')
+                    || digits.starts_with("0x")
+                    || digits.starts_with("0X")
+                    || digits.starts_with('.')
+                    || digits.ends_with('.')
+                    || digits.contains(".e")
+                    || digits.contains(".E")
+                {
+                    Some(RepairKind::Json5Number)
+                } else {
+                    None
+                };
+
+                repairs.extend(kind.map(|kind| Repair { kind, ..start }));
+                last = None;
+            }
+        }
+    }
+
+    repairs
+}
+
+/// Strip a UTF-8 byte order mark from the start of `buf`,
+/// or transcode `buf` to UTF-8 if it is UTF-16,
+/// as detected by its byte order mark or (as JSON text starts with an ASCII character)
+/// by a zero byte in the first or second position
+pub fn decode_bom(buf: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let (utf16, big_endian) = match buf {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => return Ok(Cow::Borrowed(rest)),
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        [0, b, ..] if *b != 0 => (buf, true),
+        [b, 0, ..] if *b != 0 => (buf, false),
+        _ => return Ok(Cow::Borrowed(buf)),
+    };
+
+    if utf16.len() % 2 != 0 {
+        return Err(anyhow!("UTF-16 input has an odd number of bytes"));
+    }
+
+    let units = utf16.chunks_exact(2).map(|pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    });
+
+    let decoded = char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| anyhow!("invalid UTF-16 input: {}", e))?;
+
+    Ok(Cow::Owned(decoded.into_bytes()))
+}
+
+/// The encoding of the input of a [DecodingReader]
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    /// Not enough of the input has been read to tell
+    Undetected,
+    Utf8,
+    Utf16 {
+        big_endian: bool,
+    },
+}
+
+/// Reads the text of `inner` as UTF-8, like [decode_bom] but incrementally:
+/// a UTF-8 byte order mark is stripped, and UTF-16 input is transcoded as it is read,
+/// so that input can be split into lines (as with `--ndjson`) before it is parsed
+#[derive(Debug)]
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: Encoding,
+    /// Bytes read from `inner` that have not been decoded yet,
+    /// like half of a UTF-16 code unit
+    raw: Vec<u8>,
+    decoded: Vec<u8>,
+    /// How much of `decoded` has been consumed
+    consumed: usize,
+    /// The number of bytes read from `inner`
+    position: u64,
+    /// Whether `inner` may grow after its end has been reached, like a file being appended to,
+    /// so that bytes left undecoded at its end are kept for what follows them
+    tailing: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            encoding: Encoding::Undetected,
+            raw: vec![],
+            decoded: vec![],
+            consumed: 0,
+            position: 0,
+            tailing: false,
+        }
+    }
+
+    /// Like [DecodingReader::new], for input that may be appended to after its end is read
+    pub fn tailing(inner: R) -> Self {
+        Self {
+            tailing: true,
+            ..Self::new(inner)
+        }
+    }
+
+    /// The number of bytes read from the underlying reader
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Decode as much of `raw` into `decoded` as can be
+    fn decode(&mut self, at_end: bool) -> std::io::Result<()> {
+        if let Encoding::Undetected = self.encoding {
+            // a byte order mark is up to 3 bytes
+            if self.raw.is_empty() || (self.raw.len() < 3 && (!at_end || self.tailing)) {
+                return Ok(());
+            }
+
+            let (encoding, bom_len) = match self.raw.as_slice() {
+                [0xEF, 0xBB, 0xBF, ..] => (Encoding::Utf8, 3),
+                [0xFE, 0xFF, ..] => (Encoding::Utf16 { big_endian: true }, 2),
+                [0xFF, 0xFE, ..] => (Encoding::Utf16 { big_endian: false }, 2),
+                [0, b, ..] if *b != 0 => (Encoding::Utf16 { big_endian: true }, 0),
+                [b, 0, ..] if *b != 0 => (Encoding::Utf16 { big_endian: false }, 0),
+                _ => (Encoding::Utf8, 0),
+            };
+
+            self.encoding = encoding;
+            self.raw.drain(..bom_len);
+        }
+
+        match self.encoding {
+            Encoding::Undetected => (),
+            Encoding::Utf8 => self.decoded.append(&mut self.raw),
+            Encoding::Utf16 { big_endian } => {
+                let unit = |pair: &[u8]| {
+                    if big_endian {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                };
+
+                let mut len = self.raw.len() / 2 * 2;
+
+                // keep a high surrogate for the low surrogate after it
+                if len >= 2 && (0xD800..0xDC00).contains(&unit(&self.raw[len - 2..len])) {
+                    len -= 2;
+                }
+
+                for c in char::decode_utf16(self.raw[..len].chunks_exact(2).map(unit)) {
+                    let c = c.map_err(|e| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("invalid UTF-16 input: {}", e),
+                        )
+                    })?;
+
+                    self.decoded
+                        .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+
+                self.raw.drain(..len);
+
+                if at_end && !self.tailing && !self.raw.is_empty() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        if !self.raw.len().is_multiple_of(2) {
+                            "UTF-16 input has an odd number of bytes"
+                        } else {
+                            "invalid UTF-16 input: unpaired surrogate at the end"
+                        },
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for DecodingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        while self.consumed == self.decoded.len() {
+            self.decoded.clear();
+            self.consumed = 0;
+
+            let mut chunk = [0; 8192];
+            let bytes_read = self.inner.read(&mut chunk)?;
+            self.position += bytes_read as u64;
+            self.raw.extend_from_slice(&chunk[..bytes_read]);
+
+            self.decode(bytes_read == 0)?;
+
+            if bytes_read == 0 {
+                break;
+            }
+        }
+
+        Ok(&self.decoded[self.consumed..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed = (self.consumed + amount).min(self.decoded.len());
+    }
+}
+
+/// Decode `buf` as UTF-8, replacing each invalid sequence with U+FFFD,
+/// and return the decoded string with the number of replacements made
+pub fn decode_lossy(buf: &[u8]) -> (Cow<'_, str>, usize) {
+    let replacements = buf
+        .utf8_chunks()
+        .filter(|chunk| !chunk.invalid().is_empty())
+        .count();
+
+    (String::from_utf8_lossy(buf), replacements)
+}
+
+/// Expand each of `paths` that contains glob syntax (`*`, `?`, or `[`) and is not itself
+/// the path of an existing file into the paths it matches, in sorted order,
+/// so that globs like `data/**/*.json` work in shells that do not expand them (like cmd.exe).
+/// A glob that matches no files is a [NoFilesMatch] error.
+pub fn expand_globs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = vec![];
+
+    for path in paths {
+        let pattern = path.to_string_lossy();
+
+        if !pattern.contains(['*', '?', '[']) || path.exists() {
+            expanded.push(long_path(path).into_owned());
+            continue;
+        }
+
+        let mut matches = glob::glob(&pattern)?
+            .filter(|entry| entry.as_ref().map_or(true, |path| path.is_file()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if matches.is_empty() {
+            return Err(NoFilesMatch {
+                pattern: pattern.into_owned(),
+            }
+            .into());
+        }
+
+        matches.sort();
+
+        expanded.extend(matches.iter().map(|path| long_path(path).into_owned()));
+    }
+
+    Ok(expanded)
+}
+
+/// A failure to parse a document as JSON, with the offset in the document
+/// of the byte at which it was found (the last byte, if the document ended too soon)
+#[derive(Debug)]
+pub struct ParseError {
+    pub error: serde_json::Error,
+    pub offset: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse `buf` as JSON, like `serde_json::from_slice`,
+/// but with the byte offset of any error as well as its line and column
+pub fn parse_json(buf: &[u8]) -> Result<serde_json::Value, ParseError> {
+    serde_json::from_slice(buf).map_err(|error| {
+        // the line is counted from 1, and the column, in bytes, from 1
+        let line_start = buf
+            .split_inclusive(|b| *b == b'\n')
+            .take(error.line().saturating_sub(1))
+            .map(<[u8]>::len)
+            .sum::<usize>();
+
+        ParseError {
+            offset: (line_start + error.column().saturating_sub(1)).min(buf.len()),
+            error,
+        }
+    })
+}
+
+/// A glob given to [expand_globs] that matches no files
+#[derive(Debug)]
+pub struct NoFilesMatch {
+    pub pattern: String,
+}
+
+impl fmt::Display for NoFilesMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no files match {}", self.pattern)
+    }
+}
+
+impl std::error::Error for NoFilesMatch {}
+
+/// Read a document that is a single JSON array from `reader`,
+/// passing each of its elements (and its index) to `f` as soon as the element is parsed,
+/// so that only one element at a time is in memory however large the array is
+pub fn for_each_array_element<R: Read, F: FnMut(usize, serde_json::Value) -> Result<()>>(
+    reader: R,
+    f: F,
+) -> Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut visitor = ArrayElements { f, error: None };
+
+    let parsed = deserializer.deserialize_seq(&mut visitor);
+
+    // an error from `f` is returned in preference to the parse error wrapping it
+    if let Some(error) = visitor.error {
+        return Err(error);
+    }
+
+    parsed?;
+    deserializer.end()?;
+
+    Ok(())
+}
+
+/// Passes each element of an array to `f`, for [for_each_array_element]
+struct ArrayElements<F> {
+    f: F,
+    /// The error returned by `f`, which stops the parse
+    error: Option<anyhow::Error>,
+}
+
+impl<'de, F: FnMut(usize, serde_json::Value) -> Result<()>> Visitor<'de> for &mut ArrayElements<F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut i = 0;
+
+        while let Some(element) = seq.next_element()? {
+            if let Err(error) = (self.f)(i, element) {
+                self.error = Some(error);
+                return Err(serde::de::Error::custom("stopped"));
+            }
+
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// On Windows, `path` as an absolute path with the `\\?\` prefix
+/// if it is longer than the 260 character `MAX_PATH` limit,
+/// so that it can be opened regardless of the system's long path setting.
+/// Elsewhere, `path` unchanged.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+
+        if path.as_os_str().len() >= MAX_PATH
+            && !path.as_os_str().to_string_lossy().starts_with(r"\\?\")
+        {
+            if let Ok(absolute) = std::path::absolute(path) {
+                let mut prefixed = std::ffi::OsString::from(r"\\?\");
+                prefixed.push(absolute.as_os_str());
+                return Cow::Owned(PathBuf::from(prefixed));
+            }
+        }
+    }
+
+    Cow::Borrowed(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenient() {
+        let parsed = parse_lenient(r#"{"a": 1}"#).unwrap();
+        assert_eq!(parsed.value, serde_json::json!({"a": 1}));
+        assert!(parsed.strict_error.is_none());
+
+        assert!(parsed.repairs.is_empty());
+
+        let parsed = parse_lenient("{a: 'b', c: [1, 2,], d: NaN,}").unwrap();
+        assert_eq!(
+            parsed.value,
+            serde_json::json!({"a": "b", "c": [1, 2], "d": null})
+        );
+        assert!(parsed.strict_error.is_some());
+
+        let repair = |kind, line, column| Repair { kind, line, column };
+        assert_eq!(
+            parsed.repairs,
+            vec![
+                repair(RepairKind::UnquotedKey, 1, 2),
+                repair(RepairKind::SingleQuotedString, 1, 5),
+                repair(RepairKind::UnquotedKey, 1, 10),
+                repair(RepairKind::TrailingComma, 1, 18),
+                repair(RepairKind::UnquotedKey, 1, 22),
+                repair(RepairKind::NonFiniteNumber, 1, 25),
+                repair(RepairKind::TrailingComma, 1, 28),
+            ]
+        );
+
+        let parsed = parse_lenient("// é\n[\"a,]\", /* } */ +1, .5, 0x1F, -Infinity]").unwrap();
+        assert_eq!(
+            parsed.repairs,
+            vec![
+                repair(RepairKind::Comment, 1, 1),
+                repair(RepairKind::Comment, 2, 9),
+                repair(RepairKind::Json5Number, 2, 17),
+                repair(RepairKind::Json5Number, 2, 21),
+                repair(RepairKind::Json5Number, 2, 25),
+                repair(RepairKind::NonFiniteNumber, 2, 31),
+            ]
+        );
+        assert_eq!(parsed.repairs[0].to_string(), "comment at line 1 column 1");
+
+        assert!(parse_lenient("{a: ").is_err());
+    }
+
+    #[test]
+    fn decoding_reader() {
+        let text = "\u{feff}{\"a\": \"😀\"}\n[1]\n";
+
+        let utf16 = |big_endian: bool| -> Vec<u8> {
+            text.encode_utf16()
+                .flat_map(|unit| {
+                    if big_endian {
+                        unit.to_be_bytes()
+                    } else {
+                        unit.to_le_bytes()
+                    }
+                })
+                .collect()
+        };
+
+        for input in [text.as_bytes().to_vec(), utf16(true), utf16(false)] {
+            for capacity in [1, 8192] {
+                // with a capacity of 1, the input is read one byte at a time,
+                // splitting code units and surrogate pairs across reads
+                let reader =
+                    DecodingReader::new(std::io::BufReader::with_capacity(capacity, &input[..]));
+
+                let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+                assert_eq!(lines, vec!["{\"a\": \"😀\"}", "[1]"]);
+            }
+        }
+
+        // without a byte order mark, and after the first line
+        let mut input = utf16(false);
+        input.drain(..2);
+        let mut decoded = String::new();
+        DecodingReader::new(&input[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, &text[3..]);
+
+        let mut odd = utf16(true);
+        odd.pop();
+        assert!(DecodingReader::new(&odd[..])
+            .read_to_end(&mut vec![])
+            .is_err());
+
+        // the end of a file being appended to is kept for what follows it
+        let mut tailing = DecodingReader::tailing(&odd[..]);
+        let mut decoded = vec![];
+        tailing.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, &text.as_bytes()[3..text.len() - 1]);
+        assert_eq!(tailing.position(), odd.len() as u64);
+    }
+
+    #[test]
+    fn parse_error_offset() {
+        assert_eq!(parse_json(b"[1]").unwrap(), serde_json::json!([1]));
+
+        let buf = b"{\n  \"a\": 1,\n  \"b\" 2\n}";
+        let error = parse_json(buf).unwrap_err();
+        assert_eq!((error.error.line(), error.error.column()), (3, 7));
+        assert_eq!(error.offset, 18);
+        assert_eq!(buf[error.offset], b'2');
+        assert_eq!(error.to_string(), error.error.to_string());
+
+        // the last byte, at an unexpected end
+        assert_eq!(parse_json(b"[1, 2").unwrap_err().offset, 4);
+    }
+
+    #[test]
+    fn array_elements() {
+        let mut elements = Vec::new();
+        for_each_array_element(&br#"[{"a": 1}, 2, "x"]"#[..], |i, element| {
+            elements.push((i, element));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            elements,
+            vec![
+                (0, serde_json::json!({"a": 1})),
+                (1, serde_json::json!(2)),
+                (2, serde_json::json!("x"))
+            ]
+        );
+
+        assert!(for_each_array_element(&b"{}"[..], |_, _| Ok(())).is_err());
+        assert!(for_each_array_element(&b"[1] [2]"[..], |_, _| Ok(())).is_err());
+
+        let err = for_each_array_element(&b"[1, 2, 3]"[..], |i, _| match i {
+            1 => Err(anyhow!("stop at 1")),
+            _ => Ok(()),
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "stop at 1");
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(decode_bom(b"\xEF\xBB\xBF{}").unwrap().as_ref(), b"{}");
+        assert_eq!(decode_bom(b"{}").unwrap().as_ref(), b"{}");
+
+        let utf16le: Vec<u8> = "\u{FEFF}{\"é\": 1}"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(
+            decode_bom(&utf16le).unwrap().as_ref(),
+            "{\"é\": 1}".as_bytes()
+        );
+
+        let utf16be: Vec<u8> = "[1]".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode_bom(&utf16be).unwrap().as_ref(), b"[1]");
+
+        assert!(decode_bom(b"\xFF\xFE{").is_err());
+    }
+
+    #[test]
+    fn lossy() {
+        let (decoded, replacements) = decode_lossy(b"{\"a\": \"ok\"}");
+        assert!(matches!(decoded, Cow::Borrowed(_)));
+        assert_eq!(replacements, 0);
+
+        let (decoded, replacements) = decode_lossy(b"{\"a\": \"b\xff\xfe\", \"c\": \"\xc3\"}");
+        assert_eq!(
+            decoded,
+            "{\"a\": \"b\u{FFFD}\u{FFFD}\", \"c\": \"\u{FFFD}\"}"
+        );
+        assert_eq!(replacements, 3);
+    }
+
+    #[test]
+    fn globs() {
+        let dir = std::env::temp_dir().join(format!("jindex-globs-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+
+        for name in ["b.json", "a.json", "c.txt", "nested/d.json"] {
+            std::fs::write(dir.join(name), "{}").unwrap();
+        }
+
+        let expanded =
+            expand_globs(&[dir.join("*.json"), dir.join("**/d.json"), dir.join("c.txt")]).unwrap();
+
+        assert_eq!(
+            expanded,
+            vec![
+                dir.join("a.json"),
+                dir.join("b.json"),
+                dir.join("nested/d.json"),
+                dir.join("c.txt"),
+            ]
+        );
+
+        assert!(expand_globs(&[dir.join("*.yaml")]).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
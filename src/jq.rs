@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Ctx, Filter, Native, RcIter};
+use jaq_json::Val;
+
+/// A compiled jq program (using [jaq](https://github.com/01mf02/jaq)),
+/// for transforming documents before they are traversed
+pub struct JqFilter {
+    filter: Filter<Native<Val>>,
+}
+
+impl std::fmt::Debug for JqFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JqFilter").finish_non_exhaustive()
+    }
+}
+
+impl JqFilter {
+    /// Parse and compile `program`, with jq's standard library available
+    pub fn new(program: &str) -> Result<Self> {
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+
+        let modules = loader
+            .load(
+                &arena,
+                File {
+                    code: program,
+                    path: (),
+                },
+            )
+            .map_err(|errors| anyhow!("could not parse jq program: {:?}", errors))?;
+
+        let filter = jaq_core::Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errors| anyhow!("could not compile jq program: {:?}", errors))?;
+
+        Ok(Self { filter })
+    }
+
+    /// Run the program on `input`, returning all of its outputs
+    pub fn run(&self, input: serde_json::Value) -> Result<Vec<serde_json::Value>> {
+        let inputs = RcIter::new(core::iter::empty());
+
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(input)))
+            .map(|output| {
+                output
+                    .map(serde_json::Value::from)
+                    .map_err(|error| anyhow!("jq error: {}", error))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run() {
+        let filter = JqFilter::new(".items[] | {id}").unwrap();
+
+        let outputs = filter
+            .run(serde_json::json!({"items": [{"id": 1, "x": 2}, {"id": 3}]}))
+            .unwrap();
+
+        assert_eq!(
+            outputs,
+            vec![serde_json::json!({"id": 1}), serde_json::json!({"id": 3})]
+        );
+
+        assert!(JqFilter::new(".items[").is_err());
+    }
+}
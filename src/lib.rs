@@ -1,12 +1,100 @@
 #![forbid(unsafe_code)]
-
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! With the default `std` feature off, only the traversal and path types are built,
+//! which need only `alloc`, for targets like `wasm32-unknown-unknown`.
+
+#[cfg(feature = "std")]
+pub mod anonymize;
+#[cfg(feature = "std")]
+pub mod avro;
+#[cfg(feature = "std")]
+pub mod binary;
+#[cfg(feature = "std")]
+pub mod color;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod duckdb;
+pub mod events;
+#[cfg(feature = "std")]
+pub mod flushing_writer;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod index;
+#[cfg(feature = "std")]
+pub mod input;
+#[cfg(feature = "jq")]
+pub mod jq;
+#[cfg(feature = "std")]
+pub mod limits;
+#[cfg(feature = "std")]
+pub mod merge;
+#[cfg(feature = "std")]
+pub mod number;
+#[cfg(feature = "std")]
+pub mod otel;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod patch;
+pub mod path_format;
+#[cfg(feature = "std")]
+pub mod path_pattern;
 pub mod path_value_sink;
-
-use anyhow::{anyhow, Result};
+#[cfg(feature = "std")]
+pub mod pick;
+#[cfg(feature = "std")]
+pub mod prometheus;
+#[cfg(feature = "std")]
+pub mod raw;
+#[cfg(feature = "std")]
+pub mod redact;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod rendered;
+#[cfg(feature = "std")]
+pub mod rotating_writer;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "std")]
+pub mod search;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod spill;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod traversable;
+#[cfg(feature = "std")]
+pub mod unflatten;
+#[cfg(feature = "tantivy")]
+pub mod value_search;
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::Result;
+use core::fmt::{self, Write as _};
+use path_format::{escape_json_pointer_token, is_identifier};
+#[cfg(feature = "std")]
+use path_format::{write_gron_path, write_json_pointer, write_jsonpath, write_xpath};
+#[cfg(feature = "std")]
+use path_pattern::PathPattern;
 use path_value_sink::PathValueSink;
 use serde::Serialize;
 
-const DEFAULT_PATH_COMPONENTS_CAPACITY: usize = std::mem::size_of::<usize>();
+const DEFAULT_PATH_COMPONENTS_CAPACITY: usize = core::mem::size_of::<usize>();
 
 /// Enumerate the paths through a JSON document
 ///
@@ -15,24 +103,207 @@ const DEFAULT_PATH_COMPONENTS_CAPACITY: usize = std::mem::size_of::<usize>();
 /// containing the path to reach that node (as `Vec` of [PathComponent]),
 /// and the value ([serde_json::Value]) at that node.
 pub fn jindex<S: PathValueSink>(sink: &mut S, json: &serde_json::Value) -> Result<()> {
-    if !json.is_object() && !json.is_array() {
-        return Err(anyhow!(
-            "input value must be either a JSON array or JSON object, got: {}",
-            json
-        ));
+    jindex_with_options(sink, json, &JindexOptions::default())
+}
+
+/// Like [jindex], but taking ownership of `json` and returning an iterator
+/// of [OwnedPathValue]s rather than passing borrowed [PathValue]s to a sink,
+/// for callers that cannot keep `json` borrowed for the whole traversal.
+///
+/// Only leaves are yielded: scalars, and empty objects and arrays.
+/// Each object and array is dropped as soon as its children have been reached,
+/// so memory is released as the traversal proceeds.
+pub fn jindex_owned(json: serde_json::Value) -> JindexOwned {
+    JindexOwned {
+        traversal_stack: vec![(vec![], json)],
+    }
+}
+
+/// The iterator returned by [jindex_owned]
+#[derive(Debug)]
+pub struct JindexOwned {
+    traversal_stack: Vec<(Vec<PathComponent<'static>>, serde_json::Value)>,
+}
+
+impl Iterator for JindexOwned {
+    type Item = OwnedPathValue;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path_components, value)) = self.traversal_stack.pop() {
+            match value {
+                serde_json::Value::Object(object) if !object.is_empty() => self
+                    .traversal_stack
+                    .extend(object.into_iter().map(|(k, v)| {
+                        let mut cloned = path_components.clone();
+                        cloned.push(PathComponent::key(k));
+                        (cloned, v)
+                    })),
+                serde_json::Value::Array(array) if !array.is_empty() => self
+                    .traversal_stack
+                    .extend(array.into_iter().enumerate().map(|(i, v)| {
+                        let mut cloned = path_components.clone();
+                        cloned.push(PathComponent::Index(i));
+                        (cloned, v)
+                    })),
+                value => {
+                    return Some(OwnedPathValue {
+                        path_components,
+                        value,
+                    })
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Options that control how [jindex_with_options] traverses a document
+#[derive(Clone, Debug, Default)]
+pub struct JindexOptions<'options> {
+    /// A JSON Pointer (like `/d/e`) to the value at which to start traversal.
+    /// Only that value and its descendants are passed to the sink.
+    pub root: Option<&'options str>,
+    /// When `root` is given, whether paths passed to the sink include
+    /// the path to `root` (`true`), or are relative to it (`false`)
+    pub absolute_paths: bool,
+    /// Nodes whose paths match any of these patterns are skipped,
+    /// along with all of their descendants, which are never visited
+    #[cfg(feature = "std")]
+    pub exclude: Vec<PathPattern>,
+    /// Visit only a sample of the elements of each array.
+    /// Sampled elements keep their original indexes in their paths.
+    pub array_sample: Option<ArraySample>,
+    /// Path components to prepend to every path passed to the sink,
+    /// like the index of the document when traversing many documents.
+    /// With a prefix, the document may be a scalar, as it has a path of its own
+    pub path_prefix: Vec<PathComponent<'options>>,
+    /// The order in which the children of each object and array are visited
+    pub order: TraversalOrder,
+    /// Fail, naming its path, on reaching a node more deeply nested than this,
+    /// the depth of a node being the number of components in its path (not counting `path_prefix`).
+    /// `None`, the default, is unlimited
+    pub max_depth: Option<usize>,
+}
+
+/// The order in which [jindex_with_options] visits the children of each object and array.
+/// Either way, every node is visited before its children.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Whatever order is fastest, which is currently the same as `Reverse`
+    #[default]
+    Unspecified,
+    /// First child first
+    Document,
+    /// Last child first
+    Reverse,
+}
+
+/// Which elements of each array to visit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArraySample {
+    /// The first `n` elements
+    Head(usize),
+    /// The last `n` elements
+    Tail(usize),
+    /// The first `n` and the last `n` elements
+    HeadAndTail(usize),
+    /// Every `n`th element, starting with the first
+    Stride(usize),
+}
+
+/// The indexes of the elements to visit in an array, in increasing order
+type SampledIndexes =
+    core::iter::Chain<core::iter::StepBy<core::ops::Range<usize>>, core::ops::Range<usize>>;
+
+/// The indexes of the elements to visit in an array of length `len`
+fn sampled_indexes(array_sample: Option<ArraySample>, len: usize) -> SampledIndexes {
+    match array_sample {
+        None => (0..len).step_by(1).chain(0..0),
+        Some(ArraySample::Head(n)) => (0..n.min(len)).step_by(1).chain(0..0),
+        Some(ArraySample::Tail(n)) => (len.saturating_sub(n)..len).step_by(1).chain(0..0),
+        Some(ArraySample::HeadAndTail(n)) => {
+            let head_end = n.min(len);
+            let tail_start = len.saturating_sub(n).max(head_end);
+            (0..head_end).step_by(1).chain(tail_start..len)
+        }
+        Some(ArraySample::Stride(n)) => (0..len).step_by(n.max(1)).chain(0..0),
+    }
+}
+
+/// Like [jindex], but with [JindexOptions] controlling the traversal
+pub fn jindex_with_options<'pv, S: PathValueSink>(
+    sink: &mut S,
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+) -> Result<()> {
+    #[cfg(feature = "std")]
+    if let Some(style) = sink.rendered_path_style() {
+        return rendered::jindex_rendered(sink, json, options, style);
     }
 
-    let root_pathvalue = PathValue::new(json, Vec::new());
+    jindex_with_transform(sink, json, options, &mut NoTransform)
+}
 
-    let mut traversal_stack: Vec<PathValue> = vec![root_pathvalue];
+/// Rewrites values as they are visited, before they are passed to the sink,
+/// like hashing personal information or truncating long strings.
+///
+/// Implemented for closures of the same signature as `transform`.
+pub trait ValueTransform {
+    /// Return a new value to pass to the sink in place of `pathvalue.value`,
+    /// or `None` to pass `pathvalue` through unchanged.
+    ///
+    /// The descendants of a value that is replaced are not visited,
+    /// so replacing an object or array also hides everything inside it.
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value>;
+}
+
+impl<F: FnMut(&PathValue) -> Option<serde_json::Value>> ValueTransform for F {
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value> {
+        self(pathvalue)
+    }
+}
+
+struct NoTransform;
+
+impl ValueTransform for NoTransform {
+    #[inline]
+    fn transform(&mut self, _pathvalue: &PathValue) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+/// Like [jindex_with_options], but passing every visited value
+/// through `transform` before it reaches the sink
+pub fn jindex_with_transform<'pv, S: PathValueSink, T: ValueTransform>(
+    sink: &mut S,
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+    transform: &mut T,
+) -> Result<()> {
+    let mut traversal_stack: Vec<PathValue> = vec![root_pathvalue(json, options)?];
 
     while let Some(pathvalue) = traversal_stack.pop() {
+        check_depth(&pathvalue.path_components, options)?;
+
+        if let Some(transformed) = transform.transform(&pathvalue) {
+            let pathvalue = PathValue::new(&transformed, pathvalue.path_components);
+            sink.handle_pathvalue(&pathvalue)?;
+            continue;
+        }
+
         match pathvalue.value {
             serde_json::Value::Object(object) => {
-                traverse_object(&mut traversal_stack, object, &pathvalue);
+                traverse_object(
+                    &mut traversal_stack,
+                    object,
+                    &pathvalue,
+                    options,
+                    sink.sort_keys(),
+                );
             }
             serde_json::Value::Array(array) => {
-                traverse_array(&mut traversal_stack, array, &pathvalue);
+                traverse_array(&mut traversal_stack, array, &pathvalue, options);
             }
             _terminal_value => (),
         }
@@ -43,6 +314,106 @@ pub fn jindex<S: PathValueSink>(sink: &mut S, json: &serde_json::Value) -> Resul
     Ok(())
 }
 
+/// The value at which to start traversing `json`, with its path,
+/// as given by `options.root`, `options.absolute_paths`, and `options.path_prefix`
+fn root_pathvalue<'pv>(
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+) -> Result<PathValue<'pv>> {
+    let root_pathvalue = if let Some(pointer) = options.root {
+        let root_pathvalue = resolve_pointer(json, pointer)?;
+
+        if options.absolute_paths {
+            root_pathvalue
+        } else {
+            PathValue::new(root_pathvalue.value, Vec::new())
+        }
+    } else {
+        PathValue::new(json, Vec::new())
+    };
+
+    if root_pathvalue.path_components.is_empty()
+        && options.path_prefix.is_empty()
+        && !root_pathvalue.value.is_object()
+        && !root_pathvalue.value.is_array()
+    {
+        return Err(RootError::msg(format!(
+            "input value must be either a JSON array or JSON object, got: {}",
+            root_pathvalue.value
+        )));
+    }
+
+    if options.path_prefix.is_empty() {
+        Ok(root_pathvalue)
+    } else {
+        let mut path_components = options.path_prefix.clone();
+        path_components.extend(root_pathvalue.path_components);
+        Ok(PathValue::new(root_pathvalue.value, path_components))
+    }
+}
+
+/// Fail if the node at `path_components` is nested more deeply than `options.max_depth`
+fn check_depth(path_components: &[PathComponent], options: &JindexOptions) -> Result<()> {
+    if let Some(max_depth) = options.max_depth {
+        if path_components.len() - options.path_prefix.len() > max_depth {
+            return Err(LimitError::at(path_components, |pointer| {
+                format!("{} is nested more than {} levels deep", pointer, max_depth)
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the value that the JSON Pointer `pointer` refers to in `json`,
+/// along with the path to it.
+/// Path components borrow their keys from `json` rather than from `pointer`.
+fn resolve_pointer<'pv>(json: &'pv serde_json::Value, pointer: &str) -> Result<PathValue<'pv>> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(RootError::msg(format!(
+            "JSON Pointer must be empty or start with '/', got: {}",
+            pointer
+        )));
+    }
+
+    let mut value = json;
+    let mut path_components = Vec::with_capacity(DEFAULT_PATH_COMPONENTS_CAPACITY);
+
+    for token in pointer.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+
+        match value {
+            serde_json::Value::Object(object) => {
+                let (k, v) = object
+                    .get_key_value(&token)
+                    .ok_or_else(|| no_value_at(pointer))?;
+
+                path_components.push(PathComponent::key(k));
+
+                value = v;
+            }
+            serde_json::Value::Array(array) => {
+                let i = token
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|_| token == "0" || !token.starts_with('0'))
+                    .ok_or_else(|| no_value_at(pointer))?;
+
+                value = array.get(i).ok_or_else(|| no_value_at(pointer))?;
+
+                path_components.push(PathComponent::Index(i));
+            }
+            _ => return Err(no_value_at(pointer)),
+        }
+    }
+
+    Ok(PathValue::new(value, path_components))
+}
+
+fn no_value_at(pointer: &str) -> anyhow::Error {
+    RootError::msg(format!("no value at JSON Pointer {}", pointer))
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct PathValue<'pv> {
     pub path_components: Vec<PathComponent<'pv>>,
@@ -58,67 +429,1154 @@ impl<'pv> PathValue<'pv> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize)]
+/// A key or index in a path.
+///
+/// Keys are usually borrowed from the document being traversed,
+/// but may be owned, as when a traversal cannot keep the document borrowed,
+/// so that the same sink code handles both.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
 #[serde(untagged)]
 pub enum PathComponent<'pc> {
-    Identifier(&'pc str),
-    NonIdentifier(&'pc str),
+    Identifier(Cow<'pc, str>),
+    NonIdentifier(Cow<'pc, str>),
     Index(usize),
 }
 
+impl<'pc> PathComponent<'pc> {
+    /// The component for the key `k`: an `Identifier` if it is one, or else a `NonIdentifier`
+    pub fn key(k: impl Into<Cow<'pc, str>>) -> Self {
+        let k = k.into();
+
+        if is_identifier(&k) {
+            PathComponent::Identifier(k)
+        } else {
+            PathComponent::NonIdentifier(k)
+        }
+    }
+
+    /// This component, borrowing its key (if any) from `self`
+    pub fn reborrow(&self) -> PathComponent<'_> {
+        match self {
+            PathComponent::Identifier(k) => PathComponent::Identifier(Cow::Borrowed(k)),
+            PathComponent::NonIdentifier(k) => PathComponent::NonIdentifier(Cow::Borrowed(k)),
+            PathComponent::Index(i) => PathComponent::Index(*i),
+        }
+    }
+
+    /// This component, owning its key (if any)
+    pub fn into_owned(self) -> PathComponent<'static> {
+        match self {
+            PathComponent::Identifier(k) => PathComponent::Identifier(Cow::Owned(k.into_owned())),
+            PathComponent::NonIdentifier(k) => {
+                PathComponent::NonIdentifier(Cow::Owned(k.into_owned()))
+            }
+            PathComponent::Index(i) => PathComponent::Index(i),
+        }
+    }
+}
+
+impl<'pv> PathValue<'pv> {
+    /// Copy this `PathValue` (including its value) into an [OwnedPathValue],
+    /// for sinks that need to keep it beyond a call to `handle_pathvalue`
+    pub fn to_owned_pathvalue(&self) -> OwnedPathValue {
+        OwnedPathValue {
+            path_components: self
+                .path_components
+                .iter()
+                .map(|path_component| path_component.clone().into_owned())
+                .collect(),
+            value: self.value.clone(),
+        }
+    }
+
+    /// Render this path as a JSON Pointer, like `/items/0/content-type`
+    #[cfg(feature = "std")]
+    pub fn to_json_pointer(&self) -> String {
+        render(|pointer| write_json_pointer(pointer, &self.path_components))
+    }
+
+    /// Render this path as a gron path starting with `prefix`,
+    /// like `json.items[0]["content-type"]`
+    #[cfg(feature = "std")]
+    pub fn to_gron_path(&self, prefix: &str) -> String {
+        render(|path| write_gron_path(path, prefix, &self.path_components))
+    }
+
+    /// Render this path as a JSONPath expression, like `$.items[0]['content-type']`
+    #[cfg(feature = "std")]
+    pub fn to_jsonpath(&self) -> String {
+        render(|path| write_jsonpath(path, &self.path_components))
+    }
+
+    /// Render this path as an XPath, like `/root/item[1]/@id`; see [write_xpath]
+    #[cfg(feature = "std")]
+    pub fn to_xpath(&self) -> String {
+        render(|path| write_xpath(path, &self.path_components))
+    }
+}
+
+/// The text written by `write`, which renders a path
+#[cfg(feature = "std")]
+fn render(write: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+    let mut path = Vec::new();
+    // writing to a `Vec` cannot fail
+    write(&mut path).unwrap();
+    // paths are made of `str` path components, so they are valid UTF-8
+    String::from_utf8(path).unwrap()
+}
+
+/// A [PathValue] that owns its path and value
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OwnedPathValue {
+    pub path_components: Vec<PathComponent<'static>>,
+    pub value: serde_json::Value,
+}
+
+impl OwnedPathValue {
+    /// Borrow this `OwnedPathValue` as a [PathValue], to pass it to a sink
+    pub fn as_pathvalue(&self) -> PathValue<'_> {
+        PathValue::new(
+            &self.value,
+            self.path_components
+                .iter()
+                .map(PathComponent::reborrow)
+                .collect(),
+        )
+    }
+}
+
 fn traverse_object<'pv>(
     traversal_stack: &mut Vec<PathValue<'pv>>,
     object: &'pv serde_json::Map<String, serde_json::Value>,
     pathvalue: &PathValue<'pv>,
+    options: &JindexOptions,
+    sort_keys: bool,
 ) {
-    traversal_stack.extend(object.iter().map(|(k, v)| {
+    let start = traversal_stack.len();
+
+    traversal_stack.extend(object.iter().filter_map(|(k, v)| {
         let mut cloned = Vec::with_capacity(DEFAULT_PATH_COMPONENTS_CAPACITY);
 
         cloned.clone_from(&pathvalue.path_components);
 
-        let component = if is_identifier(k) {
-            PathComponent::Identifier(k)
-        } else {
-            PathComponent::NonIdentifier(k)
-        };
+        let component = PathComponent::key(k);
 
         cloned.push(component);
 
-        PathValue::new(v, cloned)
-    }))
+        if is_excluded(&cloned, options) {
+            None
+        } else {
+            Some(PathValue::new(v, cloned))
+        }
+    }));
+
+    // the stack is popped from its end, so the first child to visit goes last
+    if sort_keys && options.order == TraversalOrder::Reverse {
+        traversal_stack[start..].sort_by(|a, b| object_key(a).cmp(object_key(b)));
+    } else if sort_keys {
+        traversal_stack[start..].sort_by(|a, b| object_key(b).cmp(object_key(a)));
+    } else if options.order == TraversalOrder::Document {
+        traversal_stack[start..].reverse();
+    }
+}
+
+/// The key of a `pathvalue` that is a child of an object
+fn object_key<'a>(pathvalue: &'a PathValue) -> &'a str {
+    match pathvalue.path_components.last() {
+        Some(PathComponent::Identifier(k) | PathComponent::NonIdentifier(k)) => k,
+        _ => "",
+    }
 }
 
 fn traverse_array<'pv>(
     traversal_stack: &mut Vec<PathValue<'pv>>,
     array: &'pv [serde_json::Value],
     pathvalue: &PathValue<'pv>,
+    options: &JindexOptions,
 ) {
-    traversal_stack.extend(array.iter().enumerate().map(|(i, v)| {
+    let indexes = sampled_indexes(options.array_sample, array.len());
+
+    let start = traversal_stack.len();
+
+    traversal_stack.extend(indexes.filter_map(|i| {
+        let v = &array[i];
+
         let mut cloned = Vec::with_capacity(DEFAULT_PATH_COMPONENTS_CAPACITY);
 
         cloned.clone_from(&pathvalue.path_components);
 
         cloned.push(PathComponent::Index(i));
 
-        PathValue::new(v, cloned)
-    }))
+        if is_excluded(&cloned, options) {
+            None
+        } else {
+            Some(PathValue::new(v, cloned))
+        }
+    }));
+
+    if options.order == TraversalOrder::Document {
+        traversal_stack[start..].reverse();
+    }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn is_excluded(path_components: &[PathComponent], options: &JindexOptions) -> bool {
+    options
+        .exclude
+        .iter()
+        .any(|pattern| pattern.matches(path_components))
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn is_excluded(_path_components: &[PathComponent], _options: &JindexOptions) -> bool {
+    false
+}
+
+/// A node of a document that exceeds a limit, like [JindexOptions::max_depth],
+/// naming the node by its JSON Pointer, so that callers can report where a
+/// traversal failed without parsing the message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimitError {
+    /// The JSON Pointer to the node, cut short with `...` after 100 bytes
+    pub pointer: String,
+    /// What limit the node exceeds, naming it by `pointer`
+    pub message: String,
+}
+
+impl LimitError {
+    /// The error for the node at `path_components`, as an [anyhow::Error]
+    /// from which the `LimitError` can be recovered with `downcast_ref`
+    pub(crate) fn at(
+        path_components: &[PathComponent],
+        message: impl FnOnce(&str) -> String,
+    ) -> anyhow::Error {
+        let pointer = pointer_prefix(path_components);
+
+        // rather than `into`, which needs `std` for `anyhow` to convert errors
+        anyhow::Error::msg(Self {
+            message: message(&pointer),
+            pointer,
+        })
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for LimitError {}
+
+/// The value at which to start a traversal cannot be found, like a [JindexOptions::root]
+/// that refers to no value, or is a scalar, which cannot be traversed without a path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RootError {
+    pub message: String,
 }
 
-fn is_identifier(s: &str) -> bool {
-    let mut chars = s.chars();
+impl RootError {
+    /// The error, as an [anyhow::Error] from which the `RootError`
+    /// can be recovered with `downcast_ref`
+    pub(crate) fn msg(message: String) -> anyhow::Error {
+        anyhow::Error::msg(Self { message })
+    }
+}
 
-    chars.next().map_or(false, unicode_ident::is_xid_start)
-        && chars.all(unicode_ident::is_xid_continue)
+impl fmt::Display for RootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for RootError {}
+
+/// The JSON Pointer to `path_components`, cut short with `...` after 100 bytes,
+/// for naming a path that may be huge in an error
+pub(crate) fn pointer_prefix(path_components: &[PathComponent]) -> String {
+    let mut pointer = String::new();
+
+    for path_component in path_components {
+        pointer.push('/');
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+                pointer.push_str(&escape_json_pointer_token(s))
+            }
+            // writing to a `String` cannot fail
+            PathComponent::Index(i) => write!(pointer, "{}", i).unwrap(),
+        }
+
+        if pointer.len() > 100 {
+            break;
+        }
+    }
+
+    if pointer.len() > 100 {
+        let mut end = 100;
+        while !pointer.is_char_boundary(end) {
+            end -= 1;
+        }
+        pointer.truncate(end);
+        pointer.push_str("...");
+    }
+
+    pointer
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod options {
+        use super::*;
+        use crate::path_value_sink::{JSONPointerWriter, JSONPointerWriterOptions};
+        use crate::redact::Redactor;
+        use std::collections::HashSet;
+
+        fn pointers(v: &serde_json::Value, options: &JindexOptions) -> Result<HashSet<String>> {
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    ..Default::default()
+                },
+            );
+
+            jindex_with_options(&mut sink, v, options)?;
+
+            Ok(std::str::from_utf8(&challenge)
+                .unwrap()
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect())
+        }
+
+        #[test]
+        fn root() {
+            let v: serde_json::Value = serde_json::json!(
+                {
+                    "a": 1,
+                    "d": {"e/x": {"f": [{}, 9, "g"]}}
+                }
+            );
+
+            let relative = pointers(
+                &v,
+                &JindexOptions {
+                    root: Some("/d/e~1x"),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                relative,
+                HashSet::from([
+                    r#"/f/0@@@{}"#.to_string(),
+                    r#"/f/1@@@9"#.to_string(),
+                    r#"/f/2@@@"g""#.to_string(),
+                ])
+            );
+
+            let absolute = pointers(
+                &v,
+                &JindexOptions {
+                    root: Some("/d/e~1x/f/1"),
+                    absolute_paths: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(absolute, HashSet::from([r#"/d/e~1x/f/1@@@9"#.to_string()]));
+
+            let err = pointers(
+                &v,
+                &JindexOptions {
+                    root: Some("/d/nope"),
+                    ..Default::default()
+                },
+            )
+            .unwrap_err();
+
+            assert!(err.downcast_ref::<RootError>().is_some());
+        }
+
+        #[test]
+        fn array_sample() {
+            let v: serde_json::Value = serde_json::json!({"a": [0, 1, 2, 3, 4, 5, 6]});
+
+            let sample = |array_sample| {
+                let mut challenge: Vec<String> = pointers(
+                    &v,
+                    &JindexOptions {
+                        array_sample: Some(array_sample),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .collect();
+                challenge.sort();
+                challenge
+            };
+
+            assert_eq!(sample(ArraySample::Head(2)), vec!["/a/0@@@0", "/a/1@@@1"]);
+            assert_eq!(sample(ArraySample::Tail(2)), vec!["/a/5@@@5", "/a/6@@@6"]);
+            assert_eq!(
+                sample(ArraySample::HeadAndTail(1)),
+                vec!["/a/0@@@0", "/a/6@@@6"]
+            );
+            assert_eq!(sample(ArraySample::HeadAndTail(100)).len(), 7);
+            assert_eq!(
+                sample(ArraySample::Stride(3)),
+                vec!["/a/0@@@0", "/a/3@@@3", "/a/6@@@6"]
+            );
+        }
+
+        #[test]
+        fn path_prefix() {
+            let v: serde_json::Value = serde_json::json!({"a": [1]});
+
+            let challenge = pointers(
+                &v,
+                &JindexOptions {
+                    path_prefix: vec![PathComponent::Index(3)],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(challenge, HashSet::from([r#"/3/a/0@@@1"#.to_string()]));
+
+            let prefixed = pointers(
+                &serde_json::json!(2),
+                &JindexOptions {
+                    path_prefix: vec![PathComponent::Index(1)],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(prefixed, HashSet::from([r#"/1@@@2"#.to_string()]));
+
+            assert!(pointers(&serde_json::json!(2), &JindexOptions::default()).is_err());
+        }
+
+        #[test]
+        fn value_transform() {
+            let v: serde_json::Value = serde_json::json!(
+                {"name": "a long name", "secret": {"a": 1}, "n": 2}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    ..Default::default()
+                },
+            );
+
+            jindex_with_transform(
+                &mut sink,
+                &v,
+                &JindexOptions::default(),
+                &mut |pathvalue: &PathValue| match (
+                    pathvalue.path_components.last(),
+                    pathvalue.value,
+                ) {
+                    (Some(PathComponent::Identifier(k)), _) if k == "secret" => {
+                        Some(serde_json::json!("[redacted]"))
+                    }
+                    (_, serde_json::Value::String(s)) => Some(serde_json::json!(&s[..6])),
+                    _ => None,
+                },
+            )
+            .unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .lines()
+                .collect::<HashSet<&str>>();
+
+            assert_eq!(
+                challenge,
+                HashSet::from([
+                    r#"/name@@@"a long""#,
+                    r#"/secret@@@"[redacted]""#,
+                    r#"/n@@@2"#,
+                ])
+            );
+        }
+
+        #[test]
+        fn redact() {
+            let v: serde_json::Value = serde_json::json!(
+                {"user": {"name": "a", "apiKey": "xyz", "ssn": "123"}, "db": {"password": {"x": 1}}}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    ..Default::default()
+                },
+            );
+
+            let mut redactor = Redactor {
+                patterns: vec![PathPattern::glob("**.ssn")],
+                detect_secrets: true,
+                ..Default::default()
+            };
+
+            jindex_with_transform(&mut sink, &v, &JindexOptions::default(), &mut redactor).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .lines()
+                .collect::<HashSet<&str>>();
+
+            assert_eq!(
+                challenge,
+                HashSet::from([
+                    r#"/user/name@@@"a""#,
+                    r#"/user/apiKey@@@"[REDACTED]""#,
+                    r#"/user/ssn@@@"[REDACTED]""#,
+                    r#"/db/password@@@"[REDACTED]""#,
+                ])
+            );
+        }
+
+        #[test]
+        fn exclude() {
+            let v: serde_json::Value = serde_json::json!(
+                {
+                    "a": 1,
+                    "items": [
+                        {"id": 1, "rawPayload": {"huge": [1, 2, 3]}},
+                        {"id": 2, "rawPayload": "x"}
+                    ],
+                    "other": {"rawPayload": true, "keep": null}
+                }
+            );
+
+            let challenge = pointers(
+                &v,
+                &JindexOptions {
+                    exclude: vec![PathPattern::glob("items.*.rawPayload")],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                challenge,
+                HashSet::from([
+                    r#"/a@@@1"#.to_string(),
+                    r#"/items/0/id@@@1"#.to_string(),
+                    r#"/items/1/id@@@2"#.to_string(),
+                    r#"/other/rawPayload@@@true"#.to_string(),
+                    r#"/other/keep@@@null"#.to_string(),
+                ])
+            );
+
+            let challenge = pointers(
+                &v,
+                &JindexOptions {
+                    exclude: vec![
+                        PathPattern::glob("**.raw*"),
+                        PathPattern::regex("^/items/1").unwrap(),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                challenge,
+                HashSet::from([
+                    r#"/a@@@1"#.to_string(),
+                    r#"/items/0/id@@@1"#.to_string(),
+                    r#"/other/keep@@@null"#.to_string(),
+                ])
+            );
+        }
+
+        #[test]
+        fn exclude_folded_keys() {
+            use crate::path_pattern::KeyMatching;
+
+            let v: serde_json::Value = serde_json::json!(
+                {"Id": 1, "ID": 2, "name": 3, "caf\u{e9}": 4, "cafe\u{301}": 5}
+            );
+
+            let key_matching = KeyMatching {
+                case_insensitive: true,
+                normalize: true,
+            };
+
+            let challenge = pointers(
+                &v,
+                &JindexOptions {
+                    exclude: vec![
+                        PathPattern::glob_with("id", key_matching),
+                        PathPattern::regex_with("^/caf\u{e9}$", key_matching).unwrap(),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(challenge, HashSet::from([r#"/name@@@3"#.to_string()]));
+
+            let challenge = pointers(
+                &v,
+                &JindexOptions {
+                    exclude: vec![PathPattern::glob("id"), PathPattern::glob("caf\u{e9}")],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(challenge.len(), 4);
+        }
+
+        #[test]
+        fn max_depth() {
+            let v: serde_json::Value = serde_json::json!({"a": [{"b": {"c": 1}}], "d": 2});
+
+            let within = pointers(
+                &v,
+                &JindexOptions {
+                    max_depth: Some(4),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                within,
+                HashSet::from([r#"/a/0/b/c@@@1"#.to_string(), r#"/d@@@2"#.to_string()])
+            );
+
+            let err = pointers(
+                &v,
+                &JindexOptions {
+                    max_depth: Some(3),
+                    path_prefix: vec![PathComponent::Index(0)],
+                    ..Default::default()
+                },
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                "/0/a/0/b/c is nested more than 3 levels deep"
+            );
+            assert_eq!(
+                err.downcast_ref::<LimitError>().unwrap().pointer,
+                "/0/a/0/b/c"
+            );
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn render_paths() {
+            let v = serde_json::json!({"items": [{"content-type": "a", "it's": "b"}]});
+
+            struct Renderings(Vec<(String, String, String)>);
+
+            impl PathValueSink for Renderings {
+                fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+                    self.0.push((
+                        pathvalue.to_json_pointer(),
+                        pathvalue.to_gron_path("json"),
+                        pathvalue.to_jsonpath(),
+                    ));
+                    Ok(())
+                }
+            }
+
+            let mut renderings = Renderings(vec![]);
+
+            jindex(&mut renderings, &v).unwrap();
+
+            let mut challenge = renderings.0;
+            challenge.sort();
+
+            assert_eq!(
+                challenge,
+                vec![
+                    ("".to_string(), "json".to_string(), "$".to_string()),
+                    (
+                        "/items".to_string(),
+                        "json.items".to_string(),
+                        "$.items".to_string()
+                    ),
+                    (
+                        "/items/0".to_string(),
+                        "json.items[0]".to_string(),
+                        "$.items[0]".to_string()
+                    ),
+                    (
+                        "/items/0/content-type".to_string(),
+                        "json.items[0][\"content-type\"]".to_string(),
+                        "$.items[0]['content-type']".to_string()
+                    ),
+                    (
+                        "/items/0/it's".to_string(),
+                        "json.items[0][\"it's\"]".to_string(),
+                        "$.items[0]['it\\'s']".to_string()
+                    ),
+                ]
+            );
+        }
+    }
+
+    mod owned {
+        use super::*;
+
+        #[test]
+        fn leaves() {
+            let v = serde_json::json!({"a": [1, {"b c": null}], "d": {}, "e": []});
+
+            let mut challenge = jindex_owned(v)
+                .map(|pathvalue| (pathvalue.as_pathvalue().to_json_pointer(), pathvalue.value))
+                .collect::<Vec<_>>();
+
+            challenge.sort_by(|a, b| a.0.cmp(&b.0));
+
+            assert_eq!(
+                challenge,
+                vec![
+                    ("/a/0".to_string(), serde_json::json!(1)),
+                    ("/a/1/b c".to_string(), serde_json::json!(null)),
+                    ("/d".to_string(), serde_json::json!({})),
+                    ("/e".to_string(), serde_json::json!([])),
+                ]
+            );
+
+            let scalar = jindex_owned(serde_json::json!(1)).collect::<Vec<_>>();
+            assert_eq!(scalar.len(), 1);
+            assert!(scalar[0].path_components.is_empty());
+        }
+
+        #[test]
+        fn owned_keys() {
+            let value = serde_json::json!(1);
+            let pathvalue = PathValue::new(
+                &value,
+                vec![
+                    PathComponent::key(String::from("a b")),
+                    PathComponent::Identifier(String::from("c").into()),
+                    PathComponent::Index(0),
+                ],
+            );
+
+            assert_eq!(pathvalue.to_gron_path("json"), "json[\"a b\"].c[0]");
+            assert_eq!(
+                pathvalue.path_components[0],
+                PathComponent::NonIdentifier("a b".into())
+            );
+        }
+    }
+
+    mod order {
+        use super::*;
+        use crate::path_value_sink::{JSONPointerWriter, JSONPointerWriterOptions};
+
+        fn pointers(v: &serde_json::Value, order: TraversalOrder) -> Vec<String> {
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    only_scalars: false,
+                    ..Default::default()
+                },
+            );
+
+            jindex_with_options(
+                &mut sink,
+                v,
+                &JindexOptions {
+                    order,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            std::str::from_utf8(&challenge)
+                .unwrap()
+                .lines()
+                .map(|line| line.split('\t').next().unwrap().to_string())
+                .collect()
+        }
+
+        #[test]
+        fn document_and_reverse() {
+            let v = serde_json::json!({"a": [1, 2], "b": {"c": 3}});
+
+            assert_eq!(
+                pointers(&v, TraversalOrder::Document),
+                vec!["/a", "/a/0", "/a/1", "/b", "/b/c"]
+            );
+
+            assert_eq!(
+                pointers(&v, TraversalOrder::Reverse),
+                vec!["/b", "/b/c", "/a", "/a/1", "/a/0"]
+            );
+        }
+    }
+
+    mod sampling {
+        use super::*;
+        use crate::path_value_sink::{
+            JSONPointerWriter, JSONPointerWriterOptions, PathValueSink, SamplingSink,
+            SamplingSinkOptions,
+        };
+
+        #[test]
+        fn sample_size() {
+            let v: serde_json::Value = serde_json::json!({"a": (0..1000).collect::<Vec<_>>()});
+
+            let mut challenge = Vec::new();
+            let mut writer =
+                JSONPointerWriter::new(&mut challenge, JSONPointerWriterOptions::default());
+            let mut sink = SamplingSink::new(
+                &mut writer,
+                SamplingSinkOptions {
+                    size: 10,
+                    seed: Some(42),
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+            sink.finish().unwrap();
+
+            let challenge = std::str::from_utf8(&challenge).unwrap();
+
+            assert_eq!(challenge.lines().count(), 10);
+        }
+    }
+
+    mod dedup_shapes {
+        use super::*;
+        use crate::path_value_sink::{PathValueSink, ShapeDedupWriter, ShapeDedupWriterOptions};
+
+        #[test]
+        fn counts() {
+            let v: serde_json::Value = serde_json::json!(
+                {"items": [{"id": 1, "tags": ["a"]}, {"id": 2, "tags": ["b", "c"]}], "x": true}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink =
+                ShapeDedupWriter::new(&mut challenge, ShapeDedupWriterOptions::default());
+
+            jindex(&mut sink, &v).unwrap();
+            sink.finish().unwrap();
+
+            let challenge = String::from_utf8(challenge).unwrap();
+            let mut challenge: Vec<&str> = challenge
+                .lines()
+                .map(|line| line.split(" = ").next().unwrap())
+                .collect();
+            challenge.sort_unstable();
+
+            assert_eq!(
+                challenge,
+                vec![
+                    "1\tjson.x",
+                    "2\tjson.items[*].id",
+                    "3\tjson.items[*].tags[*]"
+                ]
+            );
+        }
+    }
+
+    mod types {
+        use super::*;
+        use crate::path_value_sink::{TypesWriter, TypesWriterOptions};
+
+        #[test]
+        fn annotations() {
+            let v: serde_json::Value = serde_json::json!(
+                {"items": [{"id": 1}, {"id": "2", "score": 0.5, "ok": true, "x": null}]}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = TypesWriter::new(&mut challenge, TypesWriterOptions::default());
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = String::from_utf8(challenge).unwrap();
+            let mut challenge: Vec<&str> = challenge.lines().collect();
+            challenge.sort_unstable();
+
+            assert_eq!(
+                challenge,
+                vec![
+                    "json.items\tarray[2]",
+                    "json.items[0]\tobject{1}",
+                    "json.items[0].id\tnumber",
+                    "json.items[1]\tobject{4}",
+                    "json.items[1].id\tstring",
+                    "json.items[1].ok\tboolean",
+                    "json.items[1].score\tnumber",
+                    "json.items[1].x\tnull",
+                ]
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = TypesWriter::new(
+                &mut challenge,
+                TypesWriterOptions {
+                    only_scalars: true,
+                    number_types: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = String::from_utf8(challenge).unwrap();
+            let mut challenge: Vec<&str> = challenge.lines().collect();
+            challenge.sort_unstable();
+
+            assert_eq!(
+                challenge,
+                vec![
+                    "json.items[0].id\ti64",
+                    "json.items[1].id\tstring",
+                    "json.items[1].ok\tboolean",
+                    "json.items[1].score\tf64",
+                    "json.items[1].x\tnull",
+                ]
+            );
+        }
+    }
+
+    mod indented {
+        use super::*;
+        use crate::path_value_sink::{IndentedWriter, IndentedWriterOptions};
+
+        #[test]
+        fn nesting() {
+            let v: serde_json::Value = serde_json::json!(
+                {"b": {"c": null}, "items": [{"content-type": "text/plain", "id": 1}, []]}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = IndentedWriter::new(&mut challenge, IndentedWriterOptions::default());
+
+            jindex_with_options(
+                &mut sink,
+                &v,
+                &JindexOptions {
+                    order: TraversalOrder::Document,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "b:\n  c: null\nitems:\n  [0]:\n    \"content-type\": \"text/plain\"\n    id: 1\n  [1]: []\n"
+            );
+        }
+    }
+
+    mod yaml {
+        use super::*;
+        use crate::path_value_sink::{PathValueSink, YamlWriter, YamlWriterOptions};
+
+        #[test]
+        fn records() {
+            let mut challenge = Vec::new();
+            let mut sink = YamlWriter::new(&mut challenge, YamlWriterOptions::default());
+
+            jindex(&mut sink, &serde_json::json!({"a/b": [true]})).unwrap();
+            sink.finish().unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "- pointer: \"/a~1b/0\"\n  type: boolean\n  value: true\n"
+            );
+
+            let mut empty = Vec::new();
+            let mut sink = YamlWriter::new(&mut empty, YamlWriterOptions::default());
+
+            jindex(&mut sink, &serde_json::json!({})).unwrap();
+            sink.finish().unwrap();
+
+            assert_eq!(std::str::from_utf8(&empty).unwrap(), "[]\n");
+        }
+    }
+
+    mod toml_writer {
+        use super::*;
+        use crate::path_value_sink::{TomlWriter, TomlWriterOptions};
+
+        #[test]
+        fn dotted_keys() {
+            let v = serde_json::json!(
+                {"server": {"host name": "a\"b", "ports": [8080], "tls": null, "tags": {}}}
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = TomlWriter::new(&mut challenge, TomlWriterOptions::default());
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = String::from_utf8(challenge).unwrap();
+            let mut lines: Vec<&str> = challenge.lines().collect();
+            lines.sort_unstable();
+
+            assert_eq!(
+                lines,
+                vec![
+                    r#"server."host name" = "a\"b""#,
+                    r#"server.ports."0" = 8080"#,
+                    "server.tags = {}",
+                ]
+            );
+
+            #[cfg(feature = "toml")]
+            assert_eq!(
+                challenge.parse::<toml::Table>().unwrap()["server"]["ports"]["0"],
+                toml::Value::Integer(8080)
+            );
+        }
+    }
+
+    mod table {
+        use super::*;
+        use crate::path_value_sink::{TableWriter, TableWriterOptions};
+        use crate::spill::SpillOptions;
+
+        #[test]
+        fn aligned_columns() {
+            let v = serde_json::json!({"a": [true, "a long string"], "bb": null});
+
+            // with every row in memory, and with every row spilled to its own file
+            for memory_limit in [usize::MAX, 0] {
+                let mut challenge = Vec::new();
+                let mut sink = TableWriter::new(
+                    &mut challenge,
+                    TableWriterOptions {
+                        max_width: Some(8),
+                        spill: SpillOptions {
+                            memory_limit,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                );
+
+                jindex_with_options(
+                    &mut sink,
+                    &v,
+                    &JindexOptions {
+                        order: TraversalOrder::Document,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+                sink.finish().unwrap();
+
+                assert_eq!(
+                    std::str::from_utf8(&challenge).unwrap(),
+                    "PATH  TYPE     VALUE
+/a/0  boolean  true
+/a/1  string   \"a long…
+/bb   null     null
+"
+                );
+            }
+        }
+    }
+
+    mod logfmt {
+        use super::*;
+        use crate::path_value_sink::{LogfmtWriter, LogfmtWriterOptions};
+
+        #[test]
+        fn line_per_document() {
+            let documents = [
+                serde_json::json!({"a b": "x=y", "n": 1, "s": "ok", "t": "true", "xs": [null, {}]}),
+                serde_json::json!({"e": ""}),
+            ];
+            let jindex_options = JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            };
+
+            let mut challenge = Vec::new();
+            let mut sink = LogfmtWriter::new(&mut challenge, LogfmtWriterOptions::default());
+
+            for (i, document) in documents.iter().enumerate() {
+                sink.start_document(i).unwrap();
+                jindex_with_options(&mut sink, document, &jindex_options).unwrap();
+            }
+            sink.finish().unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "a_b=\"x=y\" n=1 s=ok t=\"true\" xs.0=null xs.1={}\ne=\"\"\n"
+            );
+        }
+
+        #[test]
+        fn line_per_record() {
+            let mut challenge = Vec::new();
+            let mut sink = LogfmtWriter::new(
+                &mut challenge,
+                LogfmtWriterOptions {
+                    line_per_record: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!({"a": [1]})).unwrap();
+            sink.finish().unwrap();
+
+            assert_eq!(std::str::from_utf8(&challenge).unwrap(), "a.0=1\n");
+        }
+    }
+
     mod gron {
         use super::*;
         use crate::path_value_sink::{GronWriter, GronWriterOptions};
 
+        #[test]
+        fn colored() {
+            let mut challenge = Vec::new();
+            let mut sink = GronWriter::new(
+                &mut challenge,
+                GronWriterOptions {
+                    color: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!({"a": "b"})).unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "\x1b[34mjson.a\x1b[0m = \x1b[32m\"b\"\x1b[0m;\n"
+            );
+        }
+
         #[test]
         fn one() {
             let expected = std::fs::read_to_string("fixtures/one.gron").unwrap();
@@ -253,7 +1711,42 @@ mod tests {
             let mut challenge: Vec<&str> = challenge.split('\n').collect();
             challenge.sort_unstable();
 
-            assert_eq!(expected, challenge);
+            assert_eq!(expected, challenge);
+        }
+
+        #[test]
+        fn record_terminator() {
+            let v: serde_json::Value = serde_json::json!({"a": 1, "b": [true]});
+
+            let mut challenge = Vec::new();
+            let options = GronWriterOptions {
+                record_terminator: "\r\n",
+                ..Default::default()
+            };
+            let mut sink = GronWriter::new(&mut challenge, options);
+            jindex(&mut sink, &v).unwrap();
+            let challenge = String::from_utf8(challenge).unwrap();
+
+            let mut challenge: Vec<&str> = challenge.split_inclusive("\r\n").collect();
+            challenge.sort_unstable();
+
+            assert_eq!(challenge, vec!["json.a = 1;\r\n", "json.b[0] = true;\r\n"]);
+        }
+
+        #[test]
+        fn assignment_style() {
+            let v: serde_json::Value = serde_json::json!({"a": 1});
+
+            let mut challenge = Vec::new();
+            let options = GronWriterOptions {
+                assignment: ":",
+                semicolon: false,
+                ..Default::default()
+            };
+            let mut sink = GronWriter::new(&mut challenge, options);
+            jindex(&mut sink, &v).unwrap();
+
+            assert_eq!(String::from_utf8(challenge).unwrap(), "json.a:1\n");
         }
 
         #[test]
@@ -273,9 +1766,52 @@ mod tests {
 
     mod json_pointer {
         use super::*;
-        use crate::path_value_sink::{JSONPointerWriter, JSONPointerWriterOptions};
+        use crate::path_value_sink::{
+            JSONPointerWriter, JSONPointerWriterOptions, SeparatorCollision,
+        };
         use std::collections::HashSet;
 
+        #[test]
+        fn sort_keys() {
+            let v: serde_json::Value =
+                serde_json::from_str(r#"{"b": 1, "c": {"z": 2, "y": 3}, "a": 4}"#).unwrap();
+
+            for (order, expected) in [
+                (
+                    TraversalOrder::Unspecified,
+                    "/a\t4\n/b\t1\n/c/y\t3\n/c/z\t2\n",
+                ),
+                (TraversalOrder::Document, "/a\t4\n/b\t1\n/c/y\t3\n/c/z\t2\n"),
+                (TraversalOrder::Reverse, "/c/z\t2\n/c/y\t3\n/b\t1\n/a\t4\n"),
+            ] {
+                let mut challenge = Vec::new();
+                let mut sink = JSONPointerWriter::new(
+                    &mut challenge,
+                    JSONPointerWriterOptions {
+                        sort_keys: true,
+                        ..Default::default()
+                    },
+                );
+
+                jindex_with_options(
+                    &mut sink,
+                    &v,
+                    &JindexOptions {
+                        order,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+
+                assert_eq!(
+                    std::str::from_utf8(&challenge).unwrap(),
+                    expected,
+                    "{:?}",
+                    order
+                );
+            }
+        }
+
         #[test]
         fn simple_document() {
             let v: serde_json::Value = serde_json::json!(
@@ -293,6 +1829,7 @@ mod tests {
                 JSONPointerWriterOptions {
                     separator: "@@@",
                     only_scalars: false,
+                    ..Default::default()
                 },
             );
 
@@ -339,6 +1876,7 @@ mod tests {
                 JSONPointerWriterOptions {
                     separator: "@@@",
                     only_scalars: true,
+                    ..Default::default()
                 },
             );
 
@@ -365,6 +1903,191 @@ mod tests {
             assert_eq!(challenge, expected);
         }
 
+        #[test]
+        fn skip_empty_containers() {
+            let v: serde_json::Value = serde_json::json!(
+                {
+                    "a": 1,
+                    "b": {},
+                    "c": ["x", []],
+                    "d": {"e": {"f": [{}, 9, "g", []]}}
+                }
+            );
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    skip_empty_containers: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .collect::<HashSet<&str>>();
+
+            let expected = HashSet::from([
+                r#"/a@@@1"#,
+                r#"/c/0@@@"x""#,
+                r#"/d/e/f/1@@@9"#,
+                r#"/d/e/f/2@@@"g""#,
+            ]);
+
+            assert_eq!(challenge, expected);
+        }
+
+        #[test]
+        fn separator_collision() {
+            let v: serde_json::Value = serde_json::json!({"a": "x@@@y", "b": 1});
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    separator_collision: SeparatorCollision::Escape,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .collect::<HashSet<&str>>();
+
+            let expected = HashSet::from([r#"/a@@@"x\u0040@@y""#, r#"/b@@@1"#]);
+
+            assert_eq!(challenge, expected);
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    separator_collision: SeparatorCollision::Error,
+                    ..Default::default()
+                },
+            );
+
+            assert!(jindex(&mut sink, &v).is_err());
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: ",",
+                    separator_collision: SeparatorCollision::Escape,
+                    only_scalars: false,
+                    ..Default::default()
+                },
+            );
+
+            assert!(jindex(&mut sink, &serde_json::json!({"a": [1, 2]})).is_err());
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "\t",
+                    separator_collision: SeparatorCollision::Escape,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!({"a\tb": "c\td", "e": 1})).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .collect::<HashSet<&str>>();
+
+            let expected = HashSet::from(["#/a%09b\t\"c\\td\"", "/e\t1"]);
+
+            assert_eq!(challenge, expected);
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "z",
+                    separator_collision: SeparatorCollision::Escape,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!({"xyz": 1})).unwrap();
+
+            assert_eq!(std::str::from_utf8(&challenge).unwrap(), "#/xy%7Az1\n");
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "/",
+                    separator_collision: SeparatorCollision::Escape,
+                    ..Default::default()
+                },
+            );
+
+            assert!(jindex(&mut sink, &serde_json::json!({"a": 1})).is_err());
+        }
+
+        /// See https://datatracker.ietf.org/doc/html/rfc6901#section-6
+        #[test]
+        fn uri_fragment() {
+            let v: serde_json::Value = serde_json::json!(
+            {
+                "foo": ["bar"],
+                "a/b": 1,
+                "c%d": 2,
+                "e^f": 3,
+                " ": 7,
+                "m~n": 8,
+                "é": 9
+             }
+            );
+
+            let expected = HashSet::from([
+                r#"#/foo/0@@@"bar""#,
+                r#"#/a~1b@@@1"#,
+                r#"#/c%25d@@@2"#,
+                r#"#/e%5Ef@@@3"#,
+                r#"#/%20@@@7"#,
+                r#"#/m~0n@@@8"#,
+                r#"#/%C3%A9@@@9"#,
+            ]);
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONPointerWriter::new(
+                &mut challenge,
+                JSONPointerWriterOptions {
+                    separator: "@@@",
+                    uri_fragment: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge)
+                .unwrap()
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .collect::<HashSet<&str>>();
+
+            assert_eq!(challenge, expected);
+        }
+
         /// This test exists to handle an edgecase in the RFC.
         ///
         /// Specifically:
@@ -413,6 +2136,7 @@ mod tests {
                 JSONPointerWriterOptions {
                     separator: "@@@",
                     only_scalars: false,
+                    ..Default::default()
                 },
             );
 
@@ -429,7 +2153,7 @@ mod tests {
     }
 
     mod json {
-        use crate::path_value_sink::{JSONWriter, JsonWriterOptions};
+        use crate::path_value_sink::{JSONWriter, JsonField, JsonWriterOptions, PathValueSink};
 
         use super::*;
         use std::collections::HashSet;
@@ -450,6 +2174,7 @@ mod tests {
                 &mut challenge,
                 JsonWriterOptions {
                     only_scalars: false,
+                    ..Default::default()
                 },
             );
 
@@ -491,8 +2216,13 @@ mod tests {
             );
 
             let mut challenge = Vec::new();
-            let mut sink =
-                JSONWriter::new(&mut challenge, JsonWriterOptions { only_scalars: true });
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    only_scalars: true,
+                    ..Default::default()
+                },
+            );
 
             jindex(&mut sink, &v).unwrap();
 
@@ -516,5 +2246,194 @@ mod tests {
 
             assert_eq!(challenge, expected);
         }
+
+        #[test]
+        fn extra_fields() {
+            let v: serde_json::Value = serde_json::json!({"a": {"b/c": [null]}});
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    include_pointer: true,
+                    include_type: true,
+                    include_depth: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+
+            let challenge = std::str::from_utf8(&challenge).unwrap();
+
+            assert_eq!(
+                challenge,
+                r#"{"path_components":["a","b/c",0],"value":null,"pointer":"/a/b~1c/0","type":"null","depth":3}"#
+                    .to_string()
+                    + "\n"
+            );
+        }
+
+        #[test]
+        fn number_types() {
+            let v = serde_json::json!([-1, 18446744073709551615u64, 0.5]);
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    fields: Some(&[JsonField::Type]),
+                    number_types: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex_with_options(
+                &mut sink,
+                &v,
+                &JindexOptions {
+                    order: TraversalOrder::Document,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "{\"type\":\"i64\"}\n{\"type\":\"u64\"}\n{\"type\":\"f64\"}\n"
+            );
+        }
+
+        #[test]
+        fn envelope() {
+            let v: serde_json::Value = serde_json::json!({"a": [1, 2]});
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    envelope: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &v).unwrap();
+            sink.finish().unwrap();
+
+            let challenge: serde_json::Value = serde_json::from_slice(&challenge).unwrap();
+
+            assert_eq!(challenge.as_array().unwrap().len(), 2);
+
+            let mut empty = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut empty,
+                JsonWriterOptions {
+                    envelope: true,
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!({})).unwrap();
+            sink.finish().unwrap();
+
+            assert_eq!(std::str::from_utf8(&empty).unwrap(), "[]\n");
+
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    envelope: true,
+                    record_terminator: "\0",
+                    ..Default::default()
+                },
+            );
+
+            jindex(&mut sink, &serde_json::json!([1, 2])).unwrap();
+            sink.finish().unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "[\n{\"path_components\":[1],\"value\":2},\n{\"path_components\":[0],\"value\":1}\n]\0"
+            );
+        }
+
+        #[test]
+        fn include_document() {
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    include_document: true,
+                    ..Default::default()
+                },
+            );
+
+            for (i, v) in [serde_json::json!({"a": 1}), serde_json::json!({"b": 2})]
+                .iter()
+                .enumerate()
+            {
+                sink.start_document(i).unwrap();
+                jindex(&mut sink, v).unwrap();
+            }
+
+            let challenge = std::str::from_utf8(&challenge).unwrap();
+
+            assert_eq!(
+                challenge.lines().collect::<Vec<_>>(),
+                vec![
+                    r#"{"path_components":["a"],"value":1,"doc":0}"#,
+                    r#"{"path_components":["b"],"value":2,"doc":1}"#
+                ]
+            );
+        }
+
+        #[test]
+        fn fields() {
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    fields: Some(&[JsonField::Pointer, JsonField::Value, JsonField::Document]),
+                    ..Default::default()
+                },
+            );
+
+            sink.start_document(2).unwrap();
+            jindex(&mut sink, &serde_json::json!({"a": {"b~": "x"}})).unwrap();
+
+            assert_eq!(
+                std::str::from_utf8(&challenge).unwrap(),
+                "{\"pointer\":\"/a/b~0\",\"value\":\"x\",\"doc\":2}\n"
+            );
+        }
+
+        #[test]
+        fn include_timestamp() {
+            let mut challenge = Vec::new();
+            let mut sink = JSONWriter::new(
+                &mut challenge,
+                JsonWriterOptions {
+                    include_timestamp: true,
+                    ..Default::default()
+                },
+            );
+
+            sink.start_document(0).unwrap();
+            jindex(&mut sink, &serde_json::json!({"a": 1, "b": 2})).unwrap();
+
+            let records: Vec<serde_json::Value> = std::str::from_utf8(&challenge)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+
+            let ts = records[0]["ts"].as_str().unwrap();
+
+            // like 2024-01-31T12:00:00.000Z
+            assert_eq!(ts.len(), 24);
+            assert!(ts.ends_with('Z'));
+            assert_eq!(&ts[10..11], "T");
+            assert_eq!(records[1]["ts"], ts);
+        }
     }
 }
@@ -0,0 +1,408 @@
+//! Limits that protect jindex, and the systems its output is loaded into,
+//! from pathological documents
+
+use crate::path_format::write_json_pointer;
+use crate::path_value_sink::PathValueSink;
+use crate::{pointer_prefix, LimitError, PathComponent, PathValue};
+use anyhow::Result;
+use serde::de::{DeserializeSeed, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+
+/// What [PathLengthLimitSink] does with a path that is too long
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathLengthPolicy {
+    /// Keep as many leading path components as fit,
+    /// and replace the rest with a single key holding a hash of the whole path,
+    /// like `/a/b/sha256-9f86d081884c7d65`, so that distinct paths stay distinct
+    #[default]
+    TruncateWithHash,
+    /// Skip the value
+    Skip,
+    /// Fail with an error naming the start of the path
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathLengthLimitSinkOptions {
+    /// The maximum length of a path in bytes, as a JSON Pointer
+    pub max_len: usize,
+    pub policy: PathLengthPolicy,
+}
+
+/// Pass each value whose path fits within `max_len` through to `sink`,
+/// applying `policy` to the others.
+///
+/// Paths are measured as JSON Pointers, whatever the format `sink` writes,
+/// so allow some headroom for formats with more punctuation, like gron.
+#[derive(Debug)]
+pub struct PathLengthLimitSink<'sink, S: PathValueSink> {
+    sink: &'sink mut S,
+    options: PathLengthLimitSinkOptions,
+}
+
+impl<'sink, S: PathValueSink> PathLengthLimitSink<'sink, S> {
+    pub fn new(sink: &'sink mut S, options: PathLengthLimitSinkOptions) -> Self {
+        Self { sink, options }
+    }
+}
+
+/// The length of `/sha256-` and 16 hex digits
+const HASH_COMPONENT_LEN: usize = 24;
+
+impl<'sink, S: PathValueSink> PathValueSink for PathLengthLimitSink<'sink, S> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let len: usize = pathvalue
+            .path_components
+            .iter()
+            .map(pointer_component_len)
+            .sum();
+
+        if len <= self.options.max_len {
+            return self.sink.handle_pathvalue(pathvalue);
+        }
+
+        match self.options.policy {
+            PathLengthPolicy::TruncateWithHash => {
+                use sha2::{Digest, Sha256};
+
+                let mut pointer = Vec::with_capacity(len);
+                write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+
+                let hash: String = Sha256::digest(&pointer)
+                    .iter()
+                    .take(8)
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                let hash = format!("sha256-{}", hash);
+
+                let budget = self.options.max_len.saturating_sub(HASH_COMPONENT_LEN);
+
+                let mut prefix_len = 0;
+                let mut path_components = Vec::new();
+
+                for path_component in &pathvalue.path_components {
+                    prefix_len += pointer_component_len(path_component);
+                    if prefix_len > budget {
+                        break;
+                    }
+                    path_components.push(path_component.reborrow());
+                }
+
+                path_components.push(PathComponent::NonIdentifier(Cow::Borrowed(&hash)));
+
+                self.sink.handle_pathvalue(&PathValue {
+                    path_components,
+                    value: pathvalue.value,
+                })
+            }
+            PathLengthPolicy::Skip => Ok(()),
+            PathLengthPolicy::Error => Err(LimitError::at(&pathvalue.path_components, |pointer| {
+                format!(
+                    "path {} is {} bytes, more than the limit of {} bytes",
+                    pointer, len, self.options.max_len
+                )
+            })),
+        }
+    }
+
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        self.sink.start_document(document_index)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.sink.finish()
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sink.sort_keys()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Limits on the size of the values of untrusted input, enforced as it is parsed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseLimits {
+    /// The maximum length in bytes of each string, and of each key
+    pub max_string_len: Option<usize>,
+    /// The maximum number of elements of each array, and of entries of each object
+    pub max_container_len: Option<usize>,
+}
+
+/// Parse the JSON document in `input`, failing as soon as it breaks one of `limits`,
+/// with a [LimitError] naming the offending value by its path and giving its line and column
+pub fn parse_with_limits(input: &[u8], limits: &ParseLimits) -> Result<serde_json::Value> {
+    let mut deserializer = serde_json::Deserializer::from_slice(input);
+    let mut path = Vec::new();
+    let violation = Cell::new(None);
+
+    let value = LimitedValue {
+        limits,
+        path: &mut path,
+        violation: &violation,
+    }
+    .deserialize(&mut deserializer)
+    .map_err(|error| match violation.take() {
+        // serde_json's error only gives the position of the violation
+        Some(mut violation) => {
+            violation.message = format!(
+                "{} at line {} column {}",
+                violation.message,
+                error.line(),
+                error.column()
+            );
+            anyhow::Error::msg(violation)
+        }
+        None => error.into(),
+    })?;
+
+    deserializer.end()?;
+
+    Ok(value)
+}
+
+/// Deserializes a [serde_json::Value] at `path`, checking it against `limits`
+struct LimitedValue<'a> {
+    limits: &'a ParseLimits,
+    /// The keys and indexes leading to the value
+    path: &'a mut Vec<String>,
+    /// The limit that the document breaks, once it is found
+    violation: &'a Cell<Option<LimitError>>,
+}
+
+impl LimitedValue<'_> {
+    /// Fail if `s` is too long to be a string (or key) of `what`
+    fn check_string_len<E: Error>(&self, s: &str, what: &str) -> Result<(), E> {
+        match self.limits.max_string_len {
+            Some(max) if s.len() > max => Err(self.violate(|pointer| {
+                format!(
+                    "{} at {:?} is longer than the limit of {} bytes",
+                    what, pointer, max
+                )
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if `len` is too many `items` for `what`
+    fn check_container_len<E: Error>(&self, len: usize, what: &str, items: &str) -> Result<(), E> {
+        match self.limits.max_container_len {
+            Some(max) if len > max => Err(self.violate(|pointer| {
+                format!(
+                    "{} at {:?} has more than the limit of {} {}",
+                    what, pointer, max, items
+                )
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the violation of a limit by the value at `path`, returning an error
+    /// that stops the deserializer so that [parse_with_limits] can report it
+    fn violate<E: Error>(&self, message: impl FnOnce(&str) -> String) -> E {
+        let path_components: Vec<PathComponent> = self
+            .path
+            .iter()
+            .map(|s| PathComponent::NonIdentifier(Cow::Borrowed(s)))
+            .collect();
+        let pointer = pointer_prefix(&path_components);
+        let message = message(&pointer);
+
+        self.violation.set(Some(LimitError {
+            pointer,
+            message: message.clone(),
+        }));
+
+        E::custom(message)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for LimitedValue<'_> {
+    type Value = serde_json::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LimitedValue<'_> {
+    type Value = serde_json::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(serde_json::Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> Result<Self::Value, E> {
+        Ok(serde_json::Value::Number(i.into()))
+    }
+
+    fn visit_u64<E>(self, u: u64) -> Result<Self::Value, E> {
+        Ok(serde_json::Value::Number(u.into()))
+    }
+
+    fn visit_f64<E>(self, f: f64) -> Result<Self::Value, E> {
+        Ok(serde_json::Number::from_f64(f)
+            .map_or(serde_json::Value::Null, serde_json::Value::Number))
+    }
+
+    fn visit_str<E: Error>(self, s: &str) -> Result<Self::Value, E> {
+        self.check_string_len(s, "string")?;
+        Ok(serde_json::Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: Error>(self, s: String) -> Result<Self::Value, E> {
+        self.check_string_len(&s, "string")?;
+        Ok(serde_json::Value::String(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(serde_json::Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut array = Vec::new();
+
+        loop {
+            self.path.push(array.len().to_string());
+            let element = seq.next_element_seed(LimitedValue {
+                limits: self.limits,
+                path: &mut *self.path,
+                violation: self.violation,
+            });
+            self.path.pop();
+
+            match element? {
+                Some(element) => {
+                    array.push(element);
+                    self.check_container_len(array.len(), "array", "elements")?;
+                }
+                None => return Ok(serde_json::Value::Array(array)),
+            }
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut object = serde_json::Map::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            self.check_string_len(&key, "key of the object")?;
+            self.check_container_len(object.len() + 1, "object", "entries")?;
+
+            self.path.push(key);
+            let value = map.next_value_seed(LimitedValue {
+                limits: self.limits,
+                path: &mut *self.path,
+                violation: self.violation,
+            });
+            let key = self.path.pop().expect("the key was just pushed");
+
+            object.insert(key, value?);
+        }
+
+        Ok(serde_json::Value::Object(object))
+    }
+}
+
+/// The length of `path_component` in a JSON Pointer, with its `/` and escapes
+fn pointer_component_len(path_component: &PathComponent) -> usize {
+    1 + match path_component {
+        PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+            s.len() + s.bytes().filter(|b| *b == b'~' || *b == b'/').count()
+        }
+        PathComponent::Index(i) => i.checked_ilog10().unwrap_or(0) as usize + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_value_sink::{JSONPointerWriter, JSONPointerWriterOptions};
+    use crate::{jindex_with_options, JindexOptions, TraversalOrder};
+
+    fn limited(v: &serde_json::Value, policy: PathLengthPolicy) -> Result<String> {
+        let mut output = Vec::new();
+        let mut writer = JSONPointerWriter::new(&mut output, JSONPointerWriterOptions::default());
+
+        jindex_with_options(
+            &mut PathLengthLimitSink::new(
+                &mut writer,
+                PathLengthLimitSinkOptions {
+                    max_len: 30,
+                    policy,
+                },
+            ),
+            v,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+        )?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn parse_limits() {
+        let limits = ParseLimits {
+            max_string_len: Some(3),
+            max_container_len: Some(2),
+        };
+
+        assert_eq!(
+            parse_with_limits(br#"{"a": ["xyz", 1], "b/c": {"d": null}}"#, &limits).unwrap(),
+            serde_json::json!({"a": ["xyz", 1], "b/c": {"d": null}})
+        );
+
+        assert_eq!(
+            parse_with_limits(br#"{"a": [1, {"b": "long"}]}"#, &limits)
+                .unwrap_err()
+                .to_string(),
+            "string at \"/a/1/b\" is longer than the limit of 3 bytes at line 1 column 22"
+        );
+
+        let err = parse_with_limits(br#"{"a/b": [1, 2, 3]}"#, &limits).unwrap_err();
+        assert_eq!(err.downcast_ref::<LimitError>().unwrap().pointer, "/a~1b");
+        assert_eq!(
+            err.to_string(),
+            "array at \"/a~1b\" has more than the limit of 2 elements at line 1 column 17"
+        );
+
+        assert_eq!(
+            parse_with_limits(br#"{"a": 1, "b": 2, "c": 3}"#, &limits)
+                .unwrap_err()
+                .to_string(),
+            "object at \"\" has more than the limit of 2 entries at line 1 column 20"
+        );
+
+        let err = parse_with_limits(br#"{"a": 1} 2"#, &ParseLimits::default()).unwrap_err();
+        assert!(err.downcast_ref::<LimitError>().is_none());
+    }
+
+    #[test]
+    fn path_length_limit() {
+        let v = serde_json::json!({"a": {"bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb": [1]}, "c": 2});
+
+        let truncated = limited(&v, PathLengthPolicy::TruncateWithHash).unwrap();
+        let (first, rest) = truncated.split_once('\n').unwrap();
+        let (path, value) = first.split_once('\t').unwrap();
+        assert!(path.starts_with("/a/sha256-"));
+        assert_eq!(path.len(), 26);
+        assert_eq!(value, "1");
+        assert_eq!(rest, "/c\t2\n");
+
+        assert_eq!(limited(&v, PathLengthPolicy::Skip).unwrap(), "/c\t2\n");
+
+        assert_eq!(
+            limited(&v, PathLengthPolicy::Error).unwrap_err().to_string(),
+            "path /a/bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb is 43 bytes, more than the limit of 30 bytes"
+        );
+    }
+}
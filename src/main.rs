@@ -2,80 +2,3663 @@
 #[global_allocator]
 static ALLOC: jemalloc::Jemalloc = jemalloc::Jemalloc;
 
-use anyhow::Result;
-use clap::{Parser, ValueEnum};
-use jindex::jindex;
+use anyhow::{anyhow, Result};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use jindex::anonymize::anonymize;
+use jindex::avro::{AvroWriter, AvroWriterOptions};
+use jindex::binary::{BinaryPolicy, BinaryValues};
+use jindex::compare::{first_difference, EqOptions};
+use jindex::duckdb::write_duckdb_script;
+use jindex::flushing_writer::FlushingWriter;
+use jindex::index::{index_document, read_value, write_index, PathIndex, SourceState};
+use jindex::input::{
+    decode_bom, decode_lossy, expand_globs, for_each_array_element, parse_json, parse_lenient,
+    DecodingReader, NoFilesMatch, ParseError,
+};
+#[cfg(feature = "jq")]
+use jindex::jq::JqFilter;
+use jindex::limits::{
+    parse_with_limits, ParseLimits, PathLengthLimitSink, PathLengthLimitSinkOptions,
+    PathLengthPolicy,
+};
+use jindex::merge::{merge, MergeConflict, MergeOptions};
+use jindex::number::{CanonicalNumbers, FloatFormat, RoundFloats};
+use jindex::patch::{apply_json_patch, apply_merge_patch, parse_path_mappings, rename};
+use jindex::path_format::{
+    parse_gron_path, parse_json_pointer, write_gron_path, write_gron_path_with_style,
+    write_json_pointer, write_json_pointer_with_index_width, write_jsonpath,
+    write_uri_fragment_json_pointer_with_index_width, write_xpath, GronPathStyle, GronQuoting,
+    IdentifierPolicy,
+};
+use jindex::path_pattern::{KeyMatching, PathPattern};
 use jindex::path_value_sink::{
-    GronWriter, GronWriterOptions, JSONPointerWriter, JSONPointerWriterOptions, JSONWriter,
-    JsonWriterOptions,
+    GronWriter, GronWriterOptions, IndentedWriter, IndentedWriterOptions, JSONPointerWriter,
+    JSONPointerWriterOptions, JSONWriter, JsonField, JsonWriterOptions, LogfmtWriter,
+    LogfmtWriterOptions, PathValueSink, SamplingSink, SamplingSinkOptions, SeparatorCollision,
+    ShapeDedupWriter, ShapeDedupWriterOptions, TableWriter, TableWriterOptions, TomlWriter,
+    TomlWriterOptions, TypesWriter, TypesWriterOptions, XPathWriter, XPathWriterOptions,
+    YamlWriter, YamlWriterOptions,
+};
+use jindex::pick::write_picked;
+use jindex::prometheus::{PrometheusWriter, PrometheusWriterOptions};
+use jindex::raw::parse_raw;
+use jindex::redact::Redactor;
+use jindex::registry::{RegisteredSink, SinkRegistry};
+use jindex::rotating_writer::{RotatingWriter, RotatingWriterOptions};
+use jindex::schema::{SchemaFormat, SchemaWriter, SchemaWriterOptions};
+use jindex::search::{entry_path, search_paths, PathQuery};
+use jindex::shared::write_shared_subtrees;
+use jindex::spill::{RecordSpill, SpillLines, SpillOptions};
+use jindex::stats::{
+    FieldCoverageSink, NumericStatsSink, StringProfileSink, TimestampSink, TypeConsistencySink,
 };
-use std::io::{BufWriter, Write};
+use jindex::traversable::traverse_in_order;
+use jindex::unflatten::{extract, ungron};
+#[cfg(feature = "tantivy")]
+use jindex::value_search::{index_values, search_values};
+use jindex::{
+    jindex, jindex_owned, jindex_with_options, jindex_with_transform, ArraySample, JindexOptions,
+    LimitError, PathComponent, PathValue, RootError, TraversalOrder, ValueTransform,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
 use std::mem::ManuallyDrop;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// Enumerate the paths through a JSON document.
-#[derive(Parser, Debug)]
-#[clap(author, version, about, name = "jindex")]
+#[derive(Parser, Clone, Debug)]
+#[clap(
+    author,
+    version,
+    about,
+    name = "jindex",
+    args_conflicts_with_subcommands = true,
+    after_help = EXIT_CODES
+)]
 struct Options {
-    /// gron, json_pointer, json
-    #[arg(short, long, value_enum, default_value_t = OutputFormat::Gron)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The output format; see --list-formats (or --format list) for the options specific to each
+    #[arg(short, long, value_parser = output_format_parser(), default_value = "gron")]
     format: OutputFormat,
 
-    /// A JSON file path
+    /// A JSON Pointer to the value to start at; only it and its descendants are output
+    #[arg(long)]
+    root: Option<String>,
+
+    /// With --root, output paths from the document root rather than relative to --root
+    #[arg(long, requires = "root")]
+    absolute_paths: bool,
+
+    /// Skip nodes (and their descendants) whose paths match this glob, like `**.rawPayload`.
+    /// Segments are separated by `.`, `*` matches one path component, `**` matches any number.
+    /// May be given multiple times
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Skip nodes (and their descendants) whose JSON Pointer paths match this regex.
+    /// May be given multiple times
+    #[arg(long)]
+    exclude_regex: Vec<String>,
+
+    /// Match keys in --exclude, --exclude-regex, and --redact regardless of case,
+    /// so that `id` matches `Id` and `ID`
+    #[arg(long)]
+    ignore_key_case: bool,
+
+    /// Match keys in --exclude, --exclude-regex, and --redact
+    /// in Unicode Normalization Form C, so that differently composed accents match
+    #[arg(long)]
+    normalize_keys: bool,
+
+    /// Visit only N elements of each array, chosen by --array-sample-mode
+    #[arg(long, value_name = "N")]
+    array_sample: Option<usize>,
+
+    /// Which elements --array-sample visits: the first N, the last N,
+    /// the first N and last N (ends), or every Nth (stride)
+    #[arg(long, value_enum, default_value_t = ArraySampleMode::Ends, requires = "array_sample")]
+    array_sample_mode: ArraySampleMode,
+
+    /// Output a uniformly random sample of N paths rather than all of them
+    #[arg(long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Seed for --sample, for reproducible samples
+    #[arg(long, requires = "sample")]
+    sample_seed: Option<u64>,
+
+    /// Collapse paths that differ only in array indexes into one line per path shape,
+    /// prefixed with the number of occurrences. Shapes are written in gron style,
+    /// so this cannot be given with --format
+    #[arg(long, conflicts_with = "format")]
+    dedup_shapes: bool,
+
+    /// Replace values at paths matching this glob (like --exclude) with "[REDACTED]".
+    /// May be given multiple times
+    #[arg(long)]
+    redact: Vec<String>,
+
+    /// What to do with long strings that look like base64-encoded binary data:
+    /// keep them, replace them with the SHA-256 of their bytes (hash),
+    /// replace them with `<binary N bytes>` (placeholder),
+    /// or write their bytes to files in --binary-dir and replace them with the files' paths (side-files)
+    #[arg(long, value_enum, default_value_t = BinaryMode::Keep)]
+    binary: BinaryMode,
+
+    /// The directory for --binary side-files
+    #[arg(long, value_name = "DIR")]
+    binary_dir: Option<PathBuf>,
+
+    /// The minimum length in bytes of strings that --binary applies to
+    #[arg(long, value_name = "N", default_value_t = 256)]
+    binary_min_len: usize,
+
+    /// Round every float to at most N decimal places, so that output is stable for diffing.
+    /// Numbers are otherwise written in the shortest form that parses back to the same value;
+    /// for the exact text of each number in the input, use --raw-values
+    #[arg(long, value_name = "N")]
+    float_decimals: Option<usize>,
+
+    /// How --float-decimals rounds floats, and with --raw-values, how floats are written.
+    /// Without --raw-values, floats are always written in their shortest form,
+    /// so `fixed` can only be given with --raw-values
+    #[arg(long, value_enum, default_value_t = FloatMode::AtMost, requires_ifs = [
+        ("fixed", "raw_values"), ("fixed", "float_decimals"),
+    ])]
+    float_format: FloatMode,
+
+    /// The maximum length in bytes of each path, measured as a JSON Pointer,
+    /// for documents whose paths are too long for the systems the output is loaded into
+    #[arg(long, value_name = "N")]
+    max_path_len: Option<usize>,
+
+    /// What to do with paths longer than --max-path-len:
+    /// replace their end with a hash of the whole path (truncate-with-hash),
+    /// skip them, or fail with an error
+    #[arg(long, value_enum, default_value_t = PathLenPolicy::TruncateWithHash, requires = "max_path_len")]
+    path_len_policy: PathLenPolicy,
+
+    /// Fail on reaching a value nested more than N levels deep,
+    /// naming its path, rather than allocating ever longer paths
+    #[arg(long, value_name = "N", default_value_t = 128)]
+    max_depth: usize,
+
+    /// Fail on parsing a string (or key) longer than SIZE bytes,
+    /// with an optional K, M, or G suffix, for untrusted input
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with = "lenient")]
+    max_string_len: Option<usize>,
+
+    /// Fail on parsing an array or object with more than N elements or entries,
+    /// for untrusted input
+    #[arg(long, value_name = "N", conflicts_with = "lenient")]
+    max_container_len: Option<usize>,
+
+    /// Replace values whose keys look like credentials
+    /// (password, token, apiKey, and the like) with "[REDACTED]"
+    #[arg(long)]
+    redact_secrets: bool,
+
+    /// The order in which to output the children of each object and array:
+    /// first child first (document), last child first (reverse),
+    /// or whichever is fastest (unspecified)
+    #[arg(long, value_enum, default_value_t = Order::Unspecified)]
+    order: Order,
+
+    /// Visit the keys of each object in sorted order,
+    /// even when jindex is built with the `preserve_order` feature
+    #[arg(long)]
+    sort_keys: bool,
+
+    /// Omit empty objects and arrays (`{}` and `[]`) from the output
+    #[arg(long)]
+    skip_empty_containers: bool,
+
+    /// The string written after each record: lf, crlf, nul,
+    /// or any other string (which may contain \n, \r, \t, \0, and \\ escapes)
+    #[arg(long, default_value = "lf", value_parser = parse_record_terminator)]
+    record_terminator: String,
+
+    /// Make the output byte-for-byte reproducible, for golden-file tests:
+    /// records are terminated by \n and sorted (or, for formats that are not a record per line,
+    /// written in document order), with object keys in sorted order
+    /// and numbers in one canonical form, like `1` for `1.0` or `1e0`
+    /// (except with --raw-values, which are written as they are in the input,
+    /// and when the output tells integers from floats, as with --number-types)
+    #[arg(long, conflicts_with_all = ["record_terminator", "envelope", "follow", "order"])]
+    deterministic: bool,
+
+    /// gron format only: the string written between each path and value
+    #[arg(long, default_value = " = ")]
+    gron_assignment: String,
+
+    /// gron format only: do not write a `;` after each value
+    #[arg(long)]
+    gron_no_semicolon: bool,
+
+    /// gron format only: quote string values, and keys in brackets, with `'` rather than `"`
+    #[arg(long)]
+    gron_single_quotes: bool,
+
+    /// gron format only: escape non-ASCII characters in string values,
+    /// and in keys in brackets, as `\uXXXX`
+    #[arg(long)]
+    gron_ascii: bool,
+
+    /// gron format only: which keys are written as bare identifiers, like `json.key`,
+    /// rather than in brackets, like `json["key"]`
+    #[arg(long, value_enum, default_value_t = GronIdentifiers::UnicodeXid)]
+    gron_identifiers: GronIdentifiers,
+
+    /// gron and json-pointer formats only: color paths and values with ANSI escapes.
+    /// `auto` colors output written to a terminal, unless the NO_COLOR environment variable is set
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// json-pointer and xpath formats only: the string written between each path and value.
+    /// With --pick, the string written between each value
+    #[arg(long, default_value = "\t")]
+    separator: String,
+
+    /// json-pointer format only: what to do when the separator appears in a path or value.
+    /// `escape` writes \uXXXX escapes in values, and writes paths as URI fragments
+    /// with the separator percent-encoded
+    #[arg(long, value_enum, default_value_t = SeparatorCollisionPolicy::Ignore)]
+    separator_collision: SeparatorCollisionPolicy,
+
+    /// json-pointer format only: write pointers as URI fragments, like `#/a/b%20c`
+    #[arg(long)]
+    uri_fragment: bool,
+
+    /// gron and json-pointer formats only: pad array indexes with leading zeros
+    /// to at least N digits, like `json.items[00042]`, so that sorted output
+    /// has indexes in numeric order
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    index_width: usize,
+
+    /// json format only: include a rendered JSON Pointer `pointer` field in each record
+    #[arg(long)]
+    include_pointer: bool,
+
+    /// json format only: include a `type` field in each record
+    #[arg(long)]
+    include_type: bool,
+
+    /// With --include-type, write the `type` of numbers as `i64`, `u64`, or `f64`
+    /// (the narrowest that holds them) rather than as `number`,
+    /// as does the types format
+    #[arg(long, requires = "include_type")]
+    number_types: bool,
+
+    /// json format only: include a `depth` field in each record
+    #[arg(long)]
+    include_depth: bool,
+
+    /// json format only: include a `doc` field with the index of the document
+    /// each record came from
+    #[arg(long)]
+    include_document: bool,
+
+    /// Start each record with the time (RFC 3339, UTC) its document was read:
+    /// a `ts` field in json format, or a leading column in gron and json-pointer formats.
+    /// Useful with --ndjson or --follow to correlate output with other logs
+    #[arg(long)]
+    timestamp: bool,
+
+    /// json format only: write exactly these fields in each record, in this order,
+    /// from `path`, `value`, `pointer`, `type`, `depth`, `doc`, and `ts`
+    #[arg(long, value_delimiter = ',', value_parser = parse_json_field, conflicts_with_all = [
+        "include_pointer", "include_type", "include_depth", "include_document", "timestamp",
+    ])]
+    fields: Option<Vec<JsonField>>,
+
+    /// openapi and proto formats only: the name of the schema of the root of the input
+    #[arg(long, default_value = "Root")]
+    schema_name: String,
+
+    /// duckdb format only: the file to write the records to,
+    /// as JSON Lines with a `pointer` and `type` in each record
+    #[arg(long, value_name = "PATH", required_if_eq("format", "duckdb"))]
+    duckdb_records: Option<PathBuf>,
+
+    /// duckdb format only: the name of the table to create
+    #[arg(long, default_value = "jindex")]
+    duckdb_table: String,
+
+    /// prometheus format only: prepended to every metric name, with a `_`
+    #[arg(long, default_value = "")]
+    metric_prefix: String,
+
+    /// prometheus format only: write the key at this depth (0 being the top level)
+    /// as a label with this name, rather than as part of the metric name,
+    /// like `1=service`. May be given more than once
+    #[arg(long, value_name = "DEPTH=NAME", value_parser = parse_metric_label)]
+    metric_label: Vec<(usize, String)>,
+
+    /// logfmt format only: write each `key=value` pair on its own line,
+    /// rather than one line for each document
+    #[arg(long)]
+    logfmt_line_per_record: bool,
+
+    /// table format only: truncate paths and values longer than this many characters
+    #[arg(long, value_name = "N")]
+    table_max_width: Option<usize>,
+
+    /// Keep at most SIZE bytes (with an optional K, M, or G suffix) of the records
+    /// that are buffered before being written, like the rows of the table format
+    /// or the records sorted by --deterministic, in memory, spilling the rest to temporary files
+    /// of at least 64K each
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    spill_memory: Option<usize>,
+
+    /// The directory for the files of --spill-memory, rather than the system's temporary directory
+    #[arg(long, value_name = "DIR", requires = "spill_memory")]
+    spill_dir: Option<PathBuf>,
+
+    /// Compress the files of --spill-memory with gzip
+    #[cfg(feature = "gzip")]
+    #[arg(long, requires = "spill_memory")]
+    spill_gzip: bool,
+
+    /// json format only: write all records as a single JSON array instead of JSON Lines
+    #[arg(long)]
+    envelope: bool,
+
+    /// Treat the input as newline-delimited JSON (one document per line),
+    /// writing the output for each document as soon as it is read
+    #[arg(long)]
+    ndjson: bool,
+
+    /// Like `tail -f`: keep reading newline-delimited documents
+    /// as they are appended to the file. Implies --ndjson
+    #[arg(long)]
+    follow: bool,
+
+    /// Treat the input as one large array, reading and writing its elements one at a time,
+    /// with paths rooted at their indexes like `json[3].foo`,
+    /// so that memory use is proportional to the largest element rather than the whole array.
+    /// The array itself is not written
+    #[arg(long, conflicts_with_all = [
+        "ndjson", "follow", "merge", "root", "document_prefix", "raw_values", "lenient",
+        "lossy_utf8", "max_string_len", "max_container_len", "extract", "pick", "shared_subtrees",
+    ])]
+    stream_array: bool,
+
+    /// Prefix each path with the index of the document it came from, like `json[3].foo`,
+    /// when reading multiple files or newline-delimited JSON
+    #[arg(long)]
+    document_prefix: bool,
+
+    /// Read and traverse up to N of the given files at once, on their own threads.
+    /// The output for each file is buffered until the output for the files before it
+    /// has been written, so it is the same as with one job.
+    /// Formats that write their output when finished, --envelope,
+    /// and --document-prefix or --include-document with --ndjson or --jq,
+    /// which number documents across files, still read one file at a time
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1, conflicts_with_all = [
+        "follow", "merge", "sample", "dedup_shapes", "raw_values",
+    ])]
+    jobs: usize,
+
+    /// The index of the first document of `json_locations`,
+    /// when they are some of the files given, traversed by one of --jobs
+    #[arg(skip)]
+    first_document_index: usize,
+
+    /// Accept slightly broken JSON: trailing commas, unquoted keys, single quotes,
+    /// comments, and NaN/Infinity (as null). Writes each defect repaired,
+    /// and where it is, to stderr
+    #[arg(long)]
+    lenient: bool,
+
+    /// Replace invalid UTF-8 in the input with U+FFFD rather than failing.
+    /// Writes the number of replacements in each such document to stderr
+    #[arg(long)]
+    lossy_utf8: bool,
+
+    /// How to write an error to stderr: as text, or as a JSON object with a `code`
+    /// (parse, io, invalid-root, no-matches, limit, pipe-closed, or other), the `message`, the byte `offset`, `line`, and `column`
+    /// of a parse error, and the JSON Pointer `path` of the node that exceeded a limit,
+    /// each null when it does not apply
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    errors: ErrorFormat,
+
+    /// Output each scalar value exactly as it appears in the input,
+    /// without reformatting numbers or re-escaping strings.
+    /// Only scalars and empty objects and arrays are output in this mode
+    #[arg(long, conflicts_with_all = [
+        "root", "exclude", "exclude_regex", "array_sample", "sample", "dedup_shapes",
+        "redact", "redact_secrets", "merge", "follow", "lenient", "lossy_utf8",
+        "include_pointer", "include_type", "include_depth", "include_document", "envelope",
+        "timestamp", "fields", "separator_collision", "skip_empty_containers",
+        "gron_single_quotes", "gron_ascii", "number_types",
+        "max_path_len", "max_depth", "max_string_len", "max_container_len",
+    ])]
+    raw_values: bool,
+
+    /// Instead of writing to stdout, write the output for each top-level key
+    /// (or index) of the input to its own file in this directory,
+    /// named for the key, like `users.gron`
+    #[arg(long, value_name = "DIR", conflicts_with_all = [
+        "dedup_shapes", "sample", "deterministic", "raw_values", "envelope", "include_document",
+    ])]
+    split_by_top_level_key: Option<PathBuf>,
+
+    /// Instead of writing to stdout, write the output to numbered files
+    /// (named by --split-prefix), starting a new file after every N records
+    #[arg(long, value_name = "N", conflicts_with = "split_by_top_level_key")]
+    split_lines: Option<usize>,
+
+    /// Instead of writing to stdout, write the output to numbered files
+    /// (named by --split-prefix) of at most SIZE bytes each, like 100K, 10M, or 1G.
+    /// Records are never split across files
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with = "split_by_top_level_key")]
+    split_bytes: Option<usize>,
+
+    /// With --split-lines or --split-bytes, the path of the output files,
+    /// to which a zero-padded number is appended, like `part-00000`
+    #[arg(long, value_name = "PREFIX", default_value = "part-")]
+    split_prefix: PathBuf,
+
+    /// Rather than enumerating paths, write a new JSON document for each input document,
+    /// containing only the values at these JSON Pointers, nested as they were.
+    /// May be given multiple times
+    #[arg(long, value_name = "POINTER", conflicts_with_all = [
+        "raw_values", "merge", "follow", "split_by_top_level_key", "split_lines", "split_bytes",
+        "deterministic", "manifest",
+    ])]
+    extract: Vec<String>,
+
+    /// Rather than enumerating paths, write one line for each input document
+    /// with the values at these JSON Pointers, separated by --separator,
+    /// like `jindex --ndjson --pick /id --pick /status` for a TSV of ids and statuses.
+    /// Strings are written without quotes. May be given multiple times
+    #[arg(long, value_name = "POINTER", conflicts_with_all = [
+        "extract", "raw_values", "merge", "follow", "split_by_top_level_key", "split_lines",
+        "split_bytes", "deterministic", "manifest",
+    ])]
+    pick: Vec<String>,
+
+    /// Write gron in which each object or array that appears more than once
+    /// is written once, rooted at `ref:<hash>`, and each occurrence as `path = ref:<hash>;`,
+    /// for documents with heavy duplication. Ignores --format
+    #[arg(long, conflicts_with_all = [
+        "extract", "pick", "raw_values", "merge", "follow", "split_by_top_level_key",
+        "split_lines", "split_bytes", "deterministic", "manifest", "dedup_shapes",
+    ])]
+    shared_subtrees: bool,
+
+    /// Deep-merge all input documents, in order, into one document before traversing it
+    #[arg(long)]
+    merge: bool,
+
+    /// With --merge, which value to keep when two documents
+    /// have different non-object values at the same path
+    #[arg(long, value_enum, default_value_t = MergeConflictPolicy::LastWins, requires = "merge")]
+    merge_conflict: MergeConflictPolicy,
+
+    /// With --merge, append arrays at the same path rather than treating them as a conflict
+    #[arg(long, requires = "merge")]
+    merge_concat_arrays: bool,
+
+    /// Transform each input document with this jq program before traversing it.
+    /// Each output of the program is traversed as its own document
+    #[cfg(feature = "jq")]
+    #[arg(long)]
+    jq: Option<String>,
+
+    /// Flush the output after every record, for piping into interactive tools
+    #[arg(long, conflicts_with = "flush_every")]
+    line_buffered: bool,
+
+    /// Flush the output after every N records, rather than whenever the output buffer is full
+    #[arg(long, value_name = "N")]
+    flush_every: Option<usize>,
+
+    /// After writing the output, print the elapsed time, the number of nodes traversed,
+    /// and memory usage (peak RSS, and jemalloc's stats when built with jemalloc) to stderr
+    #[arg(long)]
+    timing: bool,
+
+    /// List the output formats, with the options specific to each, and exit.
+    /// Also given by --format list
+    #[arg(long, default_value_if("format", "list", "true"))]
+    list_formats: bool,
+
+    /// With --list-formats, write the list as a JSON array of formats,
+    /// each with its `name`, `description`, `extension`, whether it is `line_per_record`
+    /// and `writes_when_finished`, and the `name` and `help` of its `options`
+    #[arg(long)]
+    json: bool,
+
+    /// Parse and traverse the input, honoring --root, --exclude, and limits like --max-depth,
+    /// but write nothing but the elapsed time, the number of nodes traversed,
+    /// and memory usage to stderr, as with --timing, for checking documents in CI
+    /// by the exit code alone
+    #[arg(long, conflicts_with_all = [
+        "extract", "pick", "shared_subtrees", "follow", "split_by_top_level_key",
+        "split_lines", "split_bytes", "manifest",
+    ])]
+    validate: bool,
+
+    /// Also write a JSON manifest to this path, recording the SHA-256 hash of each input file,
+    /// the number of nodes traversed, the jindex version, and the arguments jindex was run with
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "json_locations",
+        conflicts_with = "follow"
+    )]
+    manifest: Option<PathBuf>,
+
+    /// JSON file paths, or glob patterns like `data/**/*.json`
+    /// (for shells that do not expand them), or `-` for stdin.
+    /// Reads from stdin if none are given and stdin is not a terminal
     #[arg()]
-    json_location: Option<PathBuf>,
+    json_locations: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum Command {
+    /// Time parsing, traversal, and each output format on a file, in-process
+    Bench {
+        /// The JSON file to benchmark with
+        json_location: PathBuf,
+
+        /// How many times to run each stage
+        #[arg(long, short = 'n', default_value_t = 10)]
+        iterations: usize,
+    },
+    /// Check that a document survives a round trip through jindex:
+    /// flatten it to gron, rebuild it from the gron, and report any paths
+    /// whose values differ from the original
+    Verify {
+        /// The JSON file to verify
+        json_location: PathBuf,
+    },
+    /// Compare two JSON documents for structural equality, regardless of key order.
+    /// If they differ, print the JSON Pointer of the first difference and exit nonzero
+    Eq {
+        a: PathBuf,
+
+        b: PathBuf,
+
+        /// Compare arrays regardless of the order of their elements
+        #[arg(long)]
+        ignore_array_order: bool,
+
+        /// Compare numbers by value, so that `1`, `1.0`, and `1e0` are equal
+        #[arg(long)]
+        numeric_values: bool,
+    },
+    /// Apply a JSON Patch (RFC 6902) to a document, and write the result as JSON
+    Patch {
+        json_location: PathBuf,
+
+        patch_location: PathBuf,
+
+        /// The patch is a JSON Merge Patch (RFC 7386) rather than a JSON Patch
+        #[arg(long)]
+        merge: bool,
+
+        /// Write the result as gron rather than JSON
+        #[arg(long)]
+        flatten: bool,
+    },
+    /// Move values between paths in a document, as given by a mapping file
+    /// of `from-pointer -> to-pointer` lines, and write the result as JSON
+    Rename {
+        json_location: PathBuf,
+
+        mapping_location: PathBuf,
+
+        /// Write the JSON Patch that makes the changes, rather than the changed document
+        #[arg(long)]
+        emit_patch: bool,
+    },
+    /// Write a document with the same structure and keys as the input,
+    /// but with every value replaced by a random value of the same type and size,
+    /// for sharing the shape of a payload without sharing its data
+    Sample {
+        json_location: PathBuf,
+
+        /// Seed the random values, to get the same output each time
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Profile a document, reporting on the values at each path
+    /// with its array indexes wildcarded, like `json.items[*].id`
+    Stats {
+        json_location: PathBuf,
+
+        #[command(flatten)]
+        reports: StatsReports,
+    },
+    /// Write the output of a file in each of the given formats to golden files,
+    /// named for the file with each format's extension (like `one.gron`),
+    /// for snapshot tests
+    GenFixture {
+        json_location: PathBuf,
+
+        /// The formats to write, separated by commas, or `all`
+        #[arg(long, value_delimiter = ',', default_value = "all")]
+        formats: Vec<String>,
+
+        /// The directory to write the golden files to
+        #[arg(long, default_value = "fixtures")]
+        dir: PathBuf,
+    },
+    /// Read lines of jindex (or gron) output on stdin, and rewrite the path
+    /// at the start of each line from one path syntax to another,
+    /// leaving the rest of the line unchanged
+    ConvertPaths {
+        #[arg(long, value_enum)]
+        from: PathSyntax,
+
+        #[arg(long, value_enum)]
+        to: PathSyntax,
+
+        /// With --from pointer, the separator between the path and the value
+        #[arg(long, default_value = "\t")]
+        separator: String,
+    },
+    /// Write an index of a JSON file, with the JSON Pointer of every value
+    /// and where its text is in the file, for `lookup`
+    Index {
+        json_location: PathBuf,
+
+        /// The index file to write, like `file.jdx`
+        #[arg(long, short = 'o')]
+        output: PathBuf,
+
+        /// Also index the file's scalar values for full-text search with `search-values`,
+        /// into this directory
+        #[cfg(feature = "tantivy")]
+        #[arg(long, value_name = "DIR")]
+        values: Option<PathBuf>,
+    },
+    /// Write the values at JSON Pointers in a file indexed by `index`, one on each line,
+    /// reading only the index and the values' text rather than parsing the file
+    Lookup {
+        index_location: PathBuf,
+
+        #[arg(required = true)]
+        pointers: Vec<String>,
+    },
+    /// Write the values in a file indexed by `index` whose paths match a glob pattern,
+    /// like `users.*.email`, flattened in a format of `--list-formats`.
+    /// Values under a value that matches are written only once, as part of it
+    Search {
+        index_location: PathBuf,
+
+        /// A glob pattern, or with --prefix or --fuzzy, a JSON Pointer
+        query: String,
+
+        /// Match the value at the JSON Pointer and all of its descendants
+        #[arg(long, conflicts_with = "fuzzy")]
+        prefix: bool,
+
+        /// Match the values whose JSON Pointers are within this many edits of the JSON Pointer
+        #[arg(long, value_name = "EDITS")]
+        fuzzy: Option<usize>,
+
+        #[arg(long, default_value = "gron")]
+        format: String,
+    },
+    /// Write the values in a directory written by `index --values` that match a query,
+    /// like `+timeout -retry`, one on each line after its JSON Pointer,
+    /// most relevant first
+    #[cfg(feature = "tantivy")]
+    SearchValues {
+        index_dir: PathBuf,
+
+        query: String,
+
+        /// The most values to write
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+
+        /// Write each value's relevance score before its JSON Pointer
+        #[arg(long)]
+        scores: bool,
+    },
+}
+
+/// The reports written by the `stats` subcommand, at least one of which is required
+#[derive(Args, Clone, Debug)]
+#[command(group(clap::ArgGroup::new("report").required(true).multiple(true)))]
+struct StatsReports {
+    /// Report the paths whose values have more than one JSON type,
+    /// with the number of values of each type
+    #[arg(long, group = "report")]
+    inconsistent_types: bool,
+
+    /// For arrays of objects, report the percentage of elements in which
+    /// each field is present, `null`, and absent
+    #[arg(long, group = "report")]
+    coverage: bool,
+
+    /// Report the count, minimum, maximum, and mean of the numbers at each path
+    #[arg(long, group = "report")]
+    numeric: bool,
+
+    /// Report the maximum and average length of the strings at each path,
+    /// and whether they are all ASCII, all base64, or have other UTF-8 characters
+    #[arg(long, group = "report")]
+    strings: bool,
+
+    /// Report the paths whose values look like timestamps
+    /// (ISO 8601 dates and times, or epoch seconds or milliseconds),
+    /// with how many of the values there are in each format
+    #[arg(long, group = "report")]
+    timestamps: bool,
+
+    /// With --inconsistent-types, count numbers as `i64`, `u64`, or `f64`
+    /// (the narrowest that holds them) rather than as `number`
+    #[arg(long, requires = "inconsistent_types")]
+    number_types: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PathSyntax {
+    /// `json.items[0]["content-type"]`
+    Gron,
+    /// `/items/0/content-type`
+    Pointer,
+    /// `$.items[0]['content-type']` (output only)
+    Jsonpath,
+    /// `/root/item[1]/@id`, for documents converted from XML (output only)
+    Xpath,
 }
 
 #[derive(Clone, Debug, Default, ValueEnum)]
 enum OutputFormat {
+    /// Each value as an assignment to its path, like `json.items[0].id = 1;`, as in gron
     #[default]
     Gron,
+    /// The JSON Pointer of each value, the separator, and the value, like `/items/0/id\t1`
     JSONPointer,
+    /// Each value as a JSON object with its path components and value,
+    /// like `{"path_components":["items",0,"id"],"value":1}`, one per line
     Json,
+    /// The path of each node, a tab, and its type,
+    /// like `json.items[0].id\tnumber` or `json.items\tarray[3]`
+    Types,
+    /// Each node as `key: value`, indented by its depth, like YAML.
+    /// Children are written in document order unless --order is given
+    Indented,
+    /// A YAML sequence of records, each with a JSON Pointer, a type, and a value
+    Yaml,
+    /// Scalars as TOML dotted keys, like `server.ports."0" = 8080`.
+    /// Nulls are skipped
+    Toml,
+    /// The XPath of each value, the separator, and the value, like `/root/item[1]/@id\t"a"`,
+    /// for documents converted from XML, with `@` attributes and `#text` content
+    Xpath,
+    /// A GraphQL query selecting every field seen in the input
+    Graphql,
+    /// An OpenAPI 3.1 `components.schemas` fragment describing the input, named by --schema-name
+    Openapi,
+    /// A sketch of a proto3 message describing the input, named by --schema-name
+    Proto,
+    /// An Avro Object Container File of records with a `path` (a JSON Pointer) and a `value`
+    Avro,
+    /// A SQL script that loads the records, which are written to --duckdb-records
+    /// as in the json format, into a DuckDB table, like `jindex -f duckdb ... | duckdb my.db`
+    Duckdb,
+    /// Numbers and bools as Prometheus gauges named for their paths,
+    /// with array indexes (and keys chosen with --metric-label) as labels
+    Prometheus,
+    /// Scalars as logfmt `key=value` pairs, like `user.name="Ada Lovelace" user.roles.0=admin`,
+    /// one line for each document unless --logfmt-line-per-record is given
+    Logfmt,
+    /// The pointer, type, and value of each scalar in aligned columns, with a header,
+    /// for reading small documents. Values are truncated with --table-max-width
+    Table,
 }
 
-fn main() -> Result<()> {
-    // https://github.com/rust-lang/rust/issues/46016
-    #[cfg(target_family = "unix")]
-    {
-        use nix::sys::signal;
-        let _ = unsafe { signal::signal(signal::Signal::SIGPIPE, signal::SigHandler::SigDfl)? };
+/// The values of `--format`: the output formats, and `list`, which sets --list-formats
+fn output_format_parser() -> impl TypedValueParser<Value = OutputFormat> {
+    let list = PossibleValue::new("list").help("List the output formats, as --list-formats does");
+
+    PossibleValuesParser::new(
+        OutputFormat::value_variants()
+            .iter()
+            .filter_map(ValueEnum::to_possible_value)
+            .chain([list]),
+    )
+    .map(|name| {
+        // --list-formats is set, so the format is never written
+        OutputFormat::from_str(&name, false).unwrap_or_default()
+    })
+}
+
+impl OutputFormat {
+    /// The sink registered for this format, by its name
+    fn registered(&self) -> RegisteredSink {
+        let value = self.to_possible_value().expect("no variants are skipped");
+
+        *SinkRegistry::builtin()
+            .get(value.get_name())
+            .expect("every output format is registered")
     }
 
-    let options = Options::parse();
+    /// The file extension for output in this format
+    fn extension(&self) -> &'static str {
+        match self {
+            // the script, rather than the records that the registered sink writes
+            OutputFormat::Duckdb => ".sql",
+            _ => self.registered().extension,
+        }
+    }
 
-    let value: serde_json::Value = if let Some(json_location) = &options.json_location {
-        let buf = std::fs::read(json_location)?;
-        serde_json::from_slice(&buf)?
-    } else {
-        serde_json::from_reader(std::io::stdin())?
-    };
+    /// Whether this format writes each record on its own line,
+    /// so that its output can be sorted line by line
+    fn is_line_per_record(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Gron
+                | OutputFormat::JSONPointer
+                | OutputFormat::Json
+                | OutputFormat::Types
+                | OutputFormat::Toml
+                | OutputFormat::Xpath
+                | OutputFormat::Logfmt
+        )
+    }
+
+    /// Whether this format writes all of its output when it is finished,
+    /// rather than as each record is visited
+    fn writes_when_finished(&self) -> bool {
+        self.schema_format().is_some()
+            || matches!(
+                self,
+                OutputFormat::Avro
+                    | OutputFormat::Duckdb
+                    | OutputFormat::Prometheus
+                    | OutputFormat::Table
+            )
+    }
 
-    let leaked_value = ManuallyDrop::new(value);
+    /// The schema format for output formats that write the inferred shape of the input
+    /// rather than a record for each value
+    fn schema_format(&self) -> Option<SchemaFormat> {
+        match self {
+            OutputFormat::Graphql => Some(SchemaFormat::GraphQl),
+            OutputFormat::Openapi => Some(SchemaFormat::OpenApi),
+            OutputFormat::Proto => Some(SchemaFormat::Proto),
+            _ => None,
+        }
+    }
+}
 
-    let stdout = std::io::stdout();
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum ArraySampleMode {
+    Head,
+    Tail,
+    #[default]
+    Ends,
+    Stride,
+}
 
-    let mut lock = BufWriter::new(stdout.lock());
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum Order {
+    #[default]
+    Unspecified,
+    Document,
+    Reverse,
+}
 
-    match options.format {
-        OutputFormat::Gron => {
-            let gron_writer_options = GronWriterOptions::default();
-            let mut sink = GronWriter::new(&mut lock, gron_writer_options);
-            jindex(&mut sink, &leaked_value)?;
+impl From<Order> for TraversalOrder {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Unspecified => TraversalOrder::Unspecified,
+            Order::Document => TraversalOrder::Document,
+            Order::Reverse => TraversalOrder::Reverse,
         }
-        OutputFormat::JSONPointer => {
-            let json_pointer_writer_options = JSONPointerWriterOptions::default();
-            let mut sink = JSONPointerWriter::new(&mut lock, json_pointer_writer_options);
-            jindex(&mut sink, &leaked_value)?;
+    }
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum MergeConflictPolicy {
+    #[default]
+    LastWins,
+    FirstWins,
+    Error,
+}
+
+impl From<MergeConflictPolicy> for MergeConflict {
+    fn from(policy: MergeConflictPolicy) -> Self {
+        match policy {
+            MergeConflictPolicy::LastWins => MergeConflict::LastWins,
+            MergeConflictPolicy::FirstWins => MergeConflict::FirstWins,
+            MergeConflictPolicy::Error => MergeConflict::Error,
         }
-        OutputFormat::Json => {
-            let json_writer_options = JsonWriterOptions::default();
-            let mut sink = JSONWriter::new(&mut lock, json_writer_options);
-            jindex(&mut sink, &leaked_value)?;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum PathLenPolicy {
+    #[default]
+    TruncateWithHash,
+    Skip,
+    Error,
+}
+
+impl From<PathLenPolicy> for PathLengthPolicy {
+    fn from(policy: PathLenPolicy) -> Self {
+        match policy {
+            PathLenPolicy::TruncateWithHash => PathLengthPolicy::TruncateWithHash,
+            PathLenPolicy::Skip => PathLengthPolicy::Skip,
+            PathLenPolicy::Error => PathLengthPolicy::Error,
         }
     }
+}
 
-    lock.flush()?;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum FloatMode {
+    /// At most --float-decimals decimal places, like `2.5` with 2
+    #[default]
+    AtMost,
+    /// Exactly --float-decimals decimal places, like `2.50` with 2
+    Fixed,
+    /// The shortest form that parses back to the same float, like `2.5` for `2.50`,
+    /// without rounding
+    Shortest,
+}
+
+impl From<FloatMode> for FloatFormat {
+    fn from(mode: FloatMode) -> Self {
+        match mode {
+            FloatMode::AtMost => FloatFormat::AtMost,
+            FloatMode::Fixed => FloatFormat::Fixed,
+            FloatMode::Shortest => FloatFormat::Shortest,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum BinaryMode {
+    #[default]
+    Keep,
+    Hash,
+    Placeholder,
+    SideFiles,
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum GronIdentifiers {
+    /// Unicode identifiers, like `json.café`
+    #[default]
+    UnicodeXid,
+    /// ASCII JavaScript identifiers, like `json.$ref`
+    AsciiJs,
+    /// Write every key in brackets
+    AlwaysBracket,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<GronIdentifiers> for IdentifierPolicy {
+    fn from(policy: GronIdentifiers) -> Self {
+        match policy {
+            GronIdentifiers::UnicodeXid => IdentifierPolicy::UnicodeXid,
+            GronIdentifiers::AsciiJs => IdentifierPolicy::AsciiJs,
+            GronIdentifiers::AlwaysBracket => IdentifierPolicy::AlwaysBracket,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum SeparatorCollisionPolicy {
+    #[default]
+    Ignore,
+    Escape,
+    Error,
+}
+
+impl From<SeparatorCollisionPolicy> for SeparatorCollision {
+    fn from(policy: SeparatorCollisionPolicy) -> Self {
+        match policy {
+            SeparatorCollisionPolicy::Ignore => SeparatorCollision::Ignore,
+            SeparatorCollisionPolicy::Escape => SeparatorCollision::Escape,
+            SeparatorCollisionPolicy::Error => SeparatorCollision::Error,
+        }
+    }
+}
+
+fn parse_json_field(s: &str) -> Result<JsonField> {
+    let field = match s {
+        "path" => JsonField::Path,
+        "value" => JsonField::Value,
+        "pointer" => JsonField::Pointer,
+        "type" => JsonField::Type,
+        "depth" => JsonField::Depth,
+        "doc" => JsonField::Document,
+        "ts" => JsonField::Timestamp,
+        other => {
+            return Err(anyhow!(
+                "unknown field {:?}: expected one of path, value, pointer, type, depth, doc, ts",
+                other
+            ))
+        }
+    };
+
+    Ok(field)
+}
+
+fn parse_metric_label(s: &str) -> Result<(usize, String)> {
+    let (depth, name) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected DEPTH=NAME, like 1=service"))?;
+
+    Ok((depth.parse()?, name.to_string()))
+}
+
+fn parse_record_terminator(s: &str) -> Result<String> {
+    let terminator = match s {
+        "lf" => "\n".to_string(),
+        "crlf" => "\r\n".to_string(),
+        "nul" => "\0".to_string(),
+        custom => {
+            let mut terminator = String::with_capacity(custom.len());
+            let mut chars = custom.chars();
+
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    terminator.push(c);
+                    continue;
+                }
+
+                terminator.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some(other) => {
+                        return Err(anyhow!("unknown escape \\{} in {:?}", other, custom))
+                    }
+                    None => return Err(anyhow!("trailing \\ in {:?}", custom)),
+                });
+            }
+
+            terminator
+        }
+    };
+
+    Ok(terminator)
+}
+
+/// Counts the nodes passed to the sink it wraps
+struct CountingSink<'sink, S: PathValueSink> {
+    sink: &'sink mut S,
+    count: usize,
+}
+
+impl<'sink, S: PathValueSink> PathValueSink for CountingSink<'sink, S> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        self.count += 1;
+        self.sink.handle_pathvalue(pathvalue)
+    }
+
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        self.sink.start_document(document_index)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.sink.finish()
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sink.sort_keys()
+    }
+
+    fn rendered_path_style(&self) -> Option<GronPathStyle> {
+        self.sink.rendered_path_style()
+    }
+
+    fn handle_rendered_leaf(&mut self, path: &[u8], value: &serde_json::Value) -> Result<()> {
+        self.count += 1;
+        self.sink.handle_rendered_leaf(path, value)
+    }
+
+    fn handle_rendered_branch(&mut self, value: &serde_json::Value) -> Result<()> {
+        self.count += 1;
+        self.sink.handle_rendered_branch(value)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Parse a size in bytes, with an optional K, M, or G (binary) suffix
+fn parse_size(s: &str) -> Result<usize> {
+    let (digits, multiplier) = match s.as_bytes().last() {
+        Some(b'K' | b'k') => (&s[..s.len() - 1], 1 << 10),
+        Some(b'M' | b'm') => (&s[..s.len() - 1], 1 << 20),
+        Some(b'G' | b'g') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+
+    let n: usize = digits
+        .parse()
+        .map_err(|_| anyhow!("invalid size: {:?}", s))?;
+
+    n.checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("size too large: {:?}", s))
+}
+
+/// Whether --jobs applies: the output for each file can be written on its own
+/// and the outputs concatenated, and the files' documents can be numbered
+/// before they are read, one to a file
+fn can_write_files_in_parallel(options: &Options) -> bool {
+    #[cfg(feature = "jq")]
+    let documents_per_file_vary = options.ndjson || options.jq.is_some();
+    #[cfg(not(feature = "jq"))]
+    let documents_per_file_vary = options.ndjson;
+
+    options.jobs > 1
+        && options.json_locations.len() > 1
+        && !options.format.writes_when_finished()
+        && !options.envelope
+        && !(documents_per_file_vary && (options.document_prefix || options.include_document))
+}
+
+/// Write the output for each file in `options` to its own buffer,
+/// up to `options.jobs` files at a time, and the buffers to `writer`
+/// in the order the files were given, returning the number of nodes traversed.
+/// Stops at the first file (in that order) that fails,
+/// after writing the output for the files before it
+fn write_files_in_parallel<W: Write>(
+    writer: &mut W,
+    options: &Options,
+    jindex_options: JindexOptions,
+) -> Result<usize> {
+    let next_file = AtomicUsize::new(0);
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.min(options.json_locations.len()) {
+            let sender = sender.clone();
+            let next_file = &next_file;
+            let jindex_options = &jindex_options;
+
+            scope.spawn(move || loop {
+                let i = next_file.fetch_add(1, Ordering::Relaxed);
+                let Some(json_location) = options.json_locations.get(i) else {
+                    break;
+                };
+
+                let file_options = Options {
+                    json_locations: vec![json_location.clone()],
+                    first_document_index: i,
+                    ..options.clone()
+                };
+
+                let mut output = Vec::new();
+                let result = write_output(&mut output, &file_options, jindex_options.clone())
+                    .map(|node_count| (output, node_count));
+
+                // the receiver is gone once an earlier file has failed
+                if sender.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut finished = BTreeMap::new();
+        let mut next_to_write = 0;
+        let mut node_count = 0;
+
+        for (i, result) in receiver {
+            finished.insert(i, result);
+
+            while let Some(result) = finished.remove(&next_to_write) {
+                let (output, file_node_count) = result?;
+                writer.write_all(&output)?;
+                node_count += file_node_count;
+                next_to_write += 1;
+            }
+        }
+
+        Ok(node_count)
+    })
+}
+
+/// Traverse the input into `sink`, wrapping `sink` as the CLI options require,
+/// and return the number of nodes traversed
+fn run<S: PathValueSink>(
+    sink: &mut S,
+    options: &Options,
+    jindex_options: JindexOptions,
+) -> Result<usize> {
+    if let Some(max_len) = options.max_path_len {
+        let mut sink = PathLengthLimitSink::new(
+            sink,
+            PathLengthLimitSinkOptions {
+                max_len,
+                policy: options.path_len_policy.into(),
+            },
+        );
+        run_counted(&mut sink, options, jindex_options)
+    } else {
+        run_counted(sink, options, jindex_options)
+    }
+}
+
+/// Traverse the input into `sink`, returning the number of values visited
+fn run_counted<S: PathValueSink>(
+    sink: &mut S,
+    options: &Options,
+    jindex_options: JindexOptions,
+) -> Result<usize> {
+    let mut sink = CountingSink { sink, count: 0 };
+
+    let mut traverser = DocumentTraverser {
+        options,
+        jindex_options,
+        document_index: options.first_document_index,
+        redactor: if options.redact.is_empty() && !options.redact_secrets {
+            None
+        } else {
+            Some(Redactor {
+                patterns: options
+                    .redact
+                    .iter()
+                    .map(|pattern| PathPattern::glob_with(pattern, key_matching(options)))
+                    .collect(),
+                detect_secrets: options.redact_secrets,
+                ..Default::default()
+            })
+        },
+        binary_values: binary_values(options)?,
+        round_floats: match (options.float_decimals, options.float_format) {
+            (_, FloatMode::Fixed) => {
+                return Err(anyhow!(
+                    "--float-format fixed requires --raw-values, as floats are otherwise \
+                     written in their shortest form"
+                ))
+            }
+            // as floats are already written
+            (_, FloatMode::Shortest) => None,
+            (decimals, format) => decimals.map(|decimals| RoundFloats {
+                decimals,
+                format: format.into(),
+            }),
+        },
+        // which would turn floats into integers, not just write them differently
+        canonical_numbers: (options.deterministic && !options.number_types)
+            .then_some(CanonicalNumbers),
+        #[cfg(feature = "jq")]
+        jq_filter: options.jq.as_deref().map(JqFilter::new).transpose()?,
+    };
+
+    if let Some(size) = options.sample {
+        let mut sink = SamplingSink::new(
+            &mut sink,
+            SamplingSinkOptions {
+                size,
+                seed: options.sample_seed,
+                skip_empty_containers: options.skip_empty_containers,
+                ..Default::default()
+            },
+        );
+        traverser.traverse_input(&mut sink)?;
+        sink.finish()?;
+    } else {
+        traverser.traverse_input(&mut sink)?;
+        sink.finish()?;
+    }
+
+    Ok(sink.count)
+}
+
+/// The --binary transform given in `options`, if any
+fn binary_values(options: &Options) -> Result<Option<BinaryValues>> {
+    let policy = match options.binary {
+        BinaryMode::Keep => return Ok(None),
+        BinaryMode::Hash => BinaryPolicy::Hash,
+        BinaryMode::Placeholder => BinaryPolicy::Placeholder,
+        BinaryMode::SideFiles => BinaryPolicy::SideFiles(
+            options
+                .binary_dir
+                .clone()
+                .ok_or_else(|| anyhow!("--binary side-files requires --binary-dir"))?,
+        ),
+    };
+
+    let mut binary_values = BinaryValues::new(policy);
+    binary_values.min_len = options.binary_min_len;
+
+    Ok(Some(binary_values))
+}
+
+/// The `--timing` report: print `elapsed` and `node_count`,
+/// and whatever memory statistics are available on this platform, to stderr
+fn report_timing(elapsed: std::time::Duration, node_count: usize) -> Result<()> {
+    eprintln!("elapsed         {:.3?}", elapsed);
+    eprintln!("nodes           {}", node_count);
+
+    #[cfg(target_family = "unix")]
+    {
+        let usage = nix::sys::resource::getrusage(nix::sys::resource::UsageWho::RUSAGE_SELF)?;
+
+        // kilobytes on Linux, but bytes on macOS
+        let max_rss = usage.max_rss() as u64;
+        let max_rss = if cfg!(target_os = "macos") {
+            max_rss
+        } else {
+            max_rss * 1024
+        };
+
+        eprintln!("peak rss        {} bytes", max_rss);
+    }
+
+    #[cfg(feature = "jemalloc")]
+    {
+        // jemalloc's stats are cached until the epoch is advanced
+        jemalloc_ctl::epoch::advance().map_err(|error| anyhow!(error))?;
+
+        eprintln!(
+            "allocated       {} bytes",
+            jemalloc_ctl::stats::allocated::read().map_err(|error| anyhow!(error))?
+        );
+        eprintln!(
+            "resident        {} bytes",
+            jemalloc_ctl::stats::resident::read().map_err(|error| anyhow!(error))?
+        );
+    }
+
+    Ok(())
+}
+
+/// The length and SHA-256 hash of the bytes of each input read so far, by path,
+/// for --manifest, or `None` if they are not being recorded
+static INPUT_DIGESTS: Mutex<Option<HashMap<PathBuf, (u64, Sha256)>>> = Mutex::new(None);
+
+/// Record `bytes`, `hasher` having hashed all of them, as the input at `path`,
+/// if inputs are being recorded
+fn record_input(path: &Path, bytes: u64, hasher: Sha256) {
+    if let Some(digests) = INPUT_DIGESTS.lock().unwrap().as_mut() {
+        digests.insert(path.to_path_buf(), (bytes, hasher));
+    }
+}
+
+fn recording_inputs() -> bool {
+    INPUT_DIGESTS.lock().unwrap().is_some()
+}
+
+/// A reader that hashes the bytes read from it,
+/// and records them with [record_input] when it is dropped
+struct HashingReader<R: Read> {
+    path: PathBuf,
+    inner: R,
+    bytes: u64,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: Read> Drop for HashingReader<R> {
+    fn drop(&mut self) {
+        // the rest of the input, after the last document, is part of it too
+        let _ = std::io::copy(self, &mut std::io::sink());
+        record_input(&self.path, self.bytes, std::mem::take(&mut self.hasher));
+    }
+}
+
+/// Write the manifest for a run that traversed `node_count` nodes to `path`,
+/// with the inputs recorded as they were read
+fn write_manifest(path: &Path, options: &Options, node_count: usize) -> Result<()> {
+    let digests = INPUT_DIGESTS.lock().unwrap().take().unwrap_or_default();
+
+    let inputs = options
+        .json_locations
+        .iter()
+        .map(|json_location| {
+            let (bytes, hasher) = digests
+                .get(json_location)
+                .ok_or_else(|| anyhow!("{} was not read", json_location.display()))?;
+            let sha256: String = hasher
+                .clone()
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+
+            Ok(serde_json::json!({
+                "path": json_location,
+                "bytes": bytes,
+                "sha256": sha256,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let manifest = serde_json::json!({
+        "jindex_version": env!("CARGO_PKG_VERSION"),
+        "inputs": inputs,
+        "node_count": node_count,
+        "arguments": std::env::args().skip(1).collect::<Vec<_>>(),
+    });
+
+    let mut file = BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, &manifest)?;
+    writeln!(file)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Reads documents from the input and traverses them,
+/// keeping track of the index of each document
+struct DocumentTraverser<'a> {
+    options: &'a Options,
+    jindex_options: JindexOptions<'a>,
+    document_index: usize,
+    redactor: Option<Redactor>,
+    binary_values: Option<BinaryValues>,
+    round_floats: Option<RoundFloats>,
+    canonical_numbers: Option<CanonicalNumbers>,
+    #[cfg(feature = "jq")]
+    jq_filter: Option<JqFilter>,
+}
+
+impl<'a> DocumentTraverser<'a> {
+    /// Read the input document(s) from the files given in `options` or stdin,
+    /// and traverse them into `sink`
+    fn traverse_input<S: PathValueSink>(&mut self, sink: &mut S) -> Result<()> {
+        if self.options.follow {
+            let [json_location] = self.options.json_locations.as_slice() else {
+                return Err(anyhow!("--follow requires exactly one file path"));
+            };
+
+            self.follow_ndjson(sink, json_location)?;
+        } else if self.options.merge {
+            let merged = self.read_merged()?;
+            self.traverse_leaked(sink, merged)?;
+        } else if self.options.stream_array {
+            if self.options.json_locations.is_empty() {
+                self.traverse_array_elements(sink, DecodingReader::new(std::io::stdin().lock()))?;
+            } else {
+                for json_location in &self.options.json_locations {
+                    self.traverse_array_elements(sink, open_location(json_location)?)?;
+                }
+            }
+        } else if self.options.json_locations.is_empty() {
+            if self.options.ndjson {
+                self.traverse_ndjson(sink, DecodingReader::new(std::io::stdin().lock()))?;
+            } else {
+                let mut buf = Vec::new();
+                std::io::stdin().read_to_end(&mut buf)?;
+                let value = self.parse(&buf)?;
+                self.traverse_leaked(sink, value)?;
+            }
+        } else {
+            for json_location in &self.options.json_locations {
+                if self.options.ndjson {
+                    let reader = open_location(json_location)?;
+                    self.traverse_ndjson(sink, reader)?;
+                } else {
+                    let buf = read_location(json_location)?;
+                    let value = self.parse(&buf)?;
+
+                    if self.options.json_locations.len() == 1 && self.options.jobs == 1 {
+                        self.traverse_leaked(sink, value)?;
+                    } else {
+                        self.traverse(sink, &value)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read every input document and deep-merge them, in order, into one document
+    fn read_merged(&self) -> Result<serde_json::Value> {
+        let merge_options = MergeOptions {
+            conflict: self.options.merge_conflict.clone().into(),
+            concat_arrays: self.options.merge_concat_arrays,
+        };
+
+        let mut documents: Vec<serde_json::Value> = vec![];
+
+        let mut read_documents = |reader: &mut dyn BufRead| -> Result<()> {
+            if self.options.ndjson {
+                for line in reader.split(b'\n') {
+                    let line = line?;
+                    if !line.trim_ascii().is_empty() {
+                        documents.push(self.parse(&line)?);
+                    }
+                }
+            } else {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)?;
+                documents.push(self.parse(&buf)?);
+            }
+            Ok(())
+        };
+
+        if self.options.json_locations.is_empty() {
+            read_documents(&mut DecodingReader::new(std::io::stdin().lock()))?;
+        } else {
+            for json_location in &self.options.json_locations {
+                read_documents(&mut open_location(json_location)?)?;
+            }
+        }
+
+        let mut documents = documents.into_iter();
+
+        let mut merged = documents
+            .next()
+            .ok_or_else(|| anyhow!("no documents to merge"))?;
+
+        for document in documents {
+            merge(&mut merged, document, &merge_options)?;
+        }
+
+        Ok(merged)
+    }
+
+    /// Parse one input document, as [parse_document] does
+    fn parse(&self, buf: &[u8]) -> Result<serde_json::Value> {
+        parse_document(self.options, self.document_index, buf)
+    }
+
+    /// Traverse a single document into `sink`
+    fn traverse<S: PathValueSink>(
+        &mut self,
+        sink: &mut S,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        #[cfg(feature = "jq")]
+        if let Some(jq_filter) = &self.jq_filter {
+            // every output of the jq program is traversed as its own document
+            for output in jq_filter.run(value.clone())? {
+                self.traverse_document(sink, &output)?;
+            }
+
+            return Ok(());
+        }
+
+        self.traverse_document(sink, value)
+    }
+
+    fn traverse_document<S: PathValueSink>(
+        &mut self,
+        sink: &mut S,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        if self.options.document_prefix {
+            self.jindex_options.path_prefix = vec![PathComponent::Index(self.document_index)];
+        }
+
+        sink.start_document(self.document_index)?;
+
+        match (
+            &mut self.redactor,
+            &mut self.binary_values,
+            &mut self.round_floats,
+            self.canonical_numbers,
+        ) {
+            (None, None, None, None) => jindex_with_options(sink, value, &self.jindex_options)?,
+            (Some(redactor), None, None, None) => {
+                jindex_with_transform(sink, value, &self.jindex_options, redactor)?
+            }
+            (redactor, binary_values, round_floats, canonical_numbers) => {
+                jindex_with_transform(
+                    sink,
+                    value,
+                    &self.jindex_options,
+                    &mut |pathvalue: &PathValue| {
+                        let transformed = redactor
+                            .as_mut()
+                            .and_then(|redactor| redactor.transform(pathvalue))
+                            .or_else(|| binary_values.as_mut()?.transform(pathvalue))
+                            .or_else(|| round_floats.as_mut()?.transform(pathvalue));
+
+                        // after rounding, which can make a float integral
+                        match (canonical_numbers, transformed) {
+                            (Some(_), Some(serde_json::Value::Number(n))) => {
+                                Some(serde_json::Value::Number(
+                                    CanonicalNumbers::number(&n).unwrap_or(n),
+                                ))
+                            }
+                            (Some(mut canonical_numbers), None) => {
+                                canonical_numbers.transform(pathvalue)
+                            }
+                            (_, transformed) => transformed,
+                        }
+                    },
+                )?;
+
+                if let Some(binary_values) = binary_values {
+                    binary_values.take_error()?;
+                }
+            }
+        }
+
+        self.document_index += 1;
+
+        Ok(())
+    }
+
+    /// Traverse a document without running its destructor afterward,
+    /// which is faster than freeing it when the document is
+    /// the last thing jindex does before exiting
+    fn traverse_leaked<S: PathValueSink>(
+        &mut self,
+        sink: &mut S,
+        value: serde_json::Value,
+    ) -> Result<()> {
+        let leaked_value = ManuallyDrop::new(value);
+        self.traverse(sink, &leaked_value)
+    }
+
+    /// Traverse each newline-delimited document from `reader`,
+    /// flushing the output for each as soon as its line arrives,
+    /// so that jindex can sit in a pipeline like `tail -f log.ndjson | jindex --ndjson`
+    fn traverse_ndjson<S: PathValueSink, R: BufRead>(
+        &mut self,
+        sink: &mut S,
+        reader: R,
+    ) -> Result<()> {
+        for line in reader.split(b'\n') {
+            let line = line?;
+
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+
+            let value = self.parse(&line)?;
+            self.traverse(sink, &value)?;
+            sink.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Traverse each element of the array read from `reader` as a document of its own,
+    /// with paths rooted at its index, parsing only one element at a time
+    fn traverse_array_elements<S: PathValueSink, R: BufRead>(
+        &mut self,
+        sink: &mut S,
+        reader: R,
+    ) -> Result<()> {
+        for_each_array_element(reader, |i, element| {
+            self.jindex_options.path_prefix = vec![PathComponent::Index(i)];
+            self.traverse(sink, &element)
+        })
+    }
+
+    /// Like `tail -f`, traverse each newline-delimited document in the file at `path`,
+    /// then wait for more documents to be appended, forever.
+    /// If the file is truncated, start again from its beginning.
+    fn follow_ndjson<S: PathValueSink>(&mut self, sink: &mut S, path: &Path) -> Result<()> {
+        const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+        let mut reader = DecodingReader::tailing(std::fs::File::open(path)?);
+        let mut line = Vec::new();
+
+        loop {
+            let bytes_read = reader.read_until(b'\n', &mut line)?;
+
+            if line.ends_with(b"\n") {
+                if !line.trim_ascii().is_empty() {
+                    let value = self.parse(&line)?;
+                    self.traverse(sink, &value)?;
+                    sink.flush()?;
+                }
+
+                line.clear();
+            } else if bytes_read == 0 {
+                // at EOF, possibly with a partial line in `line`
+                if std::fs::metadata(path)?.len() < reader.position() {
+                    reader = DecodingReader::tailing(std::fs::File::open(path)?);
+                    line.clear();
+                } else {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+/// Whether `path` is `-`, which means stdin
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Read the whole of the file at `path`, or of stdin if `path` is `-`
+fn read_location(path: &Path) -> Result<Vec<u8>> {
+    let buf = if is_stdin(path) {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else {
+        std::fs::read(path)?
+    };
+
+    if recording_inputs() {
+        record_input(path, buf.len() as u64, Sha256::new_with_prefix(&buf));
+    }
+
+    Ok(buf)
+}
+
+/// Open the file at `path` for buffered reading, or stdin if `path` is `-`,
+/// decoded to UTF-8 (see [DecodingReader]) so that it can be split into lines
+fn open_location(path: &Path) -> Result<Box<dyn BufRead>> {
+    let reader: Box<dyn Read> = if is_stdin(path) {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(path)?)
+    };
+
+    if recording_inputs() {
+        Ok(Box::new(DecodingReader::new(HashingReader {
+            path: path.to_path_buf(),
+            inner: reader,
+            bytes: 0,
+            hasher: Sha256::new(),
+        })))
+    } else {
+        Ok(Box::new(DecodingReader::new(reader)))
+    }
+}
+
+/// Parse input document number `document_index`, leniently if `options` ask for it.
+/// Byte order marks are stripped, and UTF-16 documents are transcoded to UTF-8
+fn parse_document(
+    options: &Options,
+    document_index: usize,
+    buf: &[u8],
+) -> Result<serde_json::Value> {
+    let buf = decode_bom(buf)?;
+    let buf = buf.as_ref();
+
+    let decoded;
+
+    let buf = if options.lossy_utf8 {
+        let replacements;
+        (decoded, replacements) = decode_lossy(buf);
+
+        if replacements > 0 {
+            eprintln!(
+                "jindex: document {} contains {} invalid UTF-8 sequence(s), replaced with U+FFFD",
+                document_index, replacements
+            );
+        }
+
+        decoded.as_bytes()
+    } else {
+        buf
+    };
+
+    if options.lenient {
+        let parsed = parse_lenient(std::str::from_utf8(buf)?)?;
+
+        for repair in &parsed.repairs {
+            eprintln!(
+                "jindex: document {} is not strict JSON; repaired {}",
+                document_index, repair
+            );
+        }
+
+        // a defect that JSON5 accepts but is not among the repairs reported
+        if let (Some(strict_error), true) = (parsed.strict_error, parsed.repairs.is_empty()) {
+            eprintln!(
+                "jindex: document {} is not strict JSON ({}); parsed it leniently",
+                document_index, strict_error
+            );
+        }
+
+        Ok(parsed.value)
+    } else if options.max_string_len.is_some() || options.max_container_len.is_some() {
+        parse_with_limits(
+            buf,
+            &ParseLimits {
+                max_string_len: options.max_string_len,
+                max_container_len: options.max_container_len,
+            },
+        )
+    } else {
+        Ok(parse_json(buf)?)
+    }
+}
+
+/// Pass each document of the inputs given in `options`, or of stdin, to `f`:
+/// each line that is not blank with `--ndjson`, as soon as it is read,
+/// or else each whole input
+fn for_each_document(options: &Options, mut f: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+    let stdin = [PathBuf::from("-")];
+    let json_locations = if options.json_locations.is_empty() {
+        &stdin[..]
+    } else {
+        &options.json_locations
+    };
+
+    for json_location in json_locations {
+        if options.ndjson {
+            for line in open_location(json_location)?.split(b'\n') {
+                let line = line?;
+
+                if !line.trim_ascii().is_empty() {
+                    f(&line)?;
+                }
+            }
+        } else {
+            f(&read_location(json_location)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [for_each_document], but with each document parsed as --lenient,
+/// --lossy-utf8, and the parse limits ask
+fn for_each_value(
+    options: &Options,
+    mut f: impl FnMut(serde_json::Value) -> Result<()>,
+) -> Result<()> {
+    let mut document_index = options.first_document_index;
+
+    for_each_document(options, |document| {
+        let value = parse_document(options, document_index, document)?;
+        document_index += 1;
+        f(value)
+    })
+}
+
+/// The `--extract` mode: write a document containing only the values
+/// at the `--extract` pointers of each input document to `writer`, as JSON
+fn write_extracted_output<W: Write>(writer: &mut W, options: &Options) -> Result<()> {
+    let pointers: Vec<&str> = options.extract.iter().map(String::as_str).collect();
+
+    for_each_value(options, |value| {
+        serde_json::to_writer(&mut *writer, &extract(&value, &pointers)?)?;
+        writer.write_all(b"\n")?;
+        Ok(writer.flush()?)
+    })
+}
+
+/// The `--pick` mode: write the values at the `--pick` pointers
+/// of each input document to `writer`, on one line for each document
+fn write_picked_output<W: Write>(writer: &mut W, options: &Options) -> Result<()> {
+    let pointers: Vec<&str> = options.pick.iter().map(String::as_str).collect();
+
+    for_each_value(options, |value| {
+        write_picked(writer, &value, &pointers, &options.separator)?;
+        Ok(writer.flush()?)
+    })
+}
+
+/// The `--shared-subtrees` mode: write each input document to `writer`
+/// with its repeated subtrees written once
+fn write_shared_output<W: Write>(writer: &mut W, options: &Options) -> Result<()> {
+    for_each_value(options, |value| {
+        write_shared_subtrees(writer, &value, "json")?;
+        Ok(writer.flush()?)
+    })
+}
+
+/// The `--raw-values` mode: write each leaf of each input document to `writer`
+/// with the exact text it had in the input, returning the number of nodes traversed
+fn write_raw_output<W: Write>(
+    writer: &mut W,
+    options: &Options,
+    order: TraversalOrder,
+) -> Result<usize> {
+    #[cfg(feature = "jq")]
+    if options.jq.is_some() {
+        return Err(anyhow!("--jq cannot be used with --raw-values"));
+    }
+
+    if matches!(
+        options.format,
+        OutputFormat::Types
+            | OutputFormat::Indented
+            | OutputFormat::Yaml
+            | OutputFormat::Toml
+            | OutputFormat::Logfmt
+    ) || options.format.writes_when_finished()
+    {
+        return Err(anyhow!(
+            "--format {} cannot be used with --raw-values",
+            options
+                .format
+                .to_possible_value()
+                .expect("no variants are skipped")
+                .get_name()
+        ));
+    }
+
+    let round_floats = (options.float_decimals.is_some()
+        || options.float_format != FloatMode::AtMost)
+        .then(|| RoundFloats {
+            decimals: options.float_decimals.unwrap_or_default(),
+            format: options.float_format.into(),
+        });
+
+    let mut node_count = 0;
+
+    for_each_document(options, |document| {
+        let document = decode_bom(document)?;
+        let mut node = parse_raw(std::str::from_utf8(&document)?)?;
+
+        if options.sort_keys {
+            node.sort_keys();
+        }
+
+        // sorted keys are visited first to last, unless in reverse order
+        let order = match order {
+            TraversalOrder::Reverse => TraversalOrder::Reverse,
+            _ if options.sort_keys => TraversalOrder::Document,
+            order => order,
+        };
+
+        traverse_in_order(&node, order, |path_components, node| {
+            node_count += 1;
+
+            let Some(raw) = node.raw() else {
+                return Ok(());
+            };
+
+            let formatted = round_floats.and_then(|round_floats| round_floats.format_raw(raw));
+            let raw = formatted.as_deref().unwrap_or(raw);
+
+            if path_components.is_empty() {
+                return Ok(());
+            }
+
+            match options.format {
+                OutputFormat::Gron => {
+                    write_gron_path_with_style(
+                        writer,
+                        "json",
+                        path_components,
+                        GronPathStyle {
+                            identifier_policy: options.gron_identifiers.clone().into(),
+                            index_width: options.index_width,
+                            ..Default::default()
+                        },
+                    )?;
+                    writer.write_all(options.gron_assignment.as_bytes())?;
+                    writer.write_all(raw.as_bytes())?;
+                    if !options.gron_no_semicolon {
+                        writer.write_all(b";")?;
+                    }
+                }
+                OutputFormat::JSONPointer => {
+                    if options.uri_fragment {
+                        write_uri_fragment_json_pointer_with_index_width(
+                            writer,
+                            path_components,
+                            options.index_width,
+                        )?;
+                    } else {
+                        write_json_pointer_with_index_width(
+                            writer,
+                            path_components,
+                            options.index_width,
+                        )?;
+                    }
+                    writer.write_all(options.separator.as_bytes())?;
+                    writer.write_all(raw.as_bytes())?;
+                }
+                OutputFormat::Xpath => {
+                    write_xpath(writer, path_components)?;
+                    writer.write_all(options.separator.as_bytes())?;
+                    writer.write_all(raw.as_bytes())?;
+                }
+                OutputFormat::Json => {
+                    writer.write_all(b"{\"path_components\":")?;
+                    serde_json::to_writer(&mut *writer, path_components)?;
+                    writer.write_all(b",\"value\":")?;
+                    writer.write_all(raw.as_bytes())?;
+                    writer.write_all(b"}")?;
+                }
+                OutputFormat::Types
+                | OutputFormat::Indented
+                | OutputFormat::Yaml
+                | OutputFormat::Toml
+                | OutputFormat::Graphql
+                | OutputFormat::Openapi
+                | OutputFormat::Proto
+                | OutputFormat::Avro
+                | OutputFormat::Duckdb
+                | OutputFormat::Prometheus
+                | OutputFormat::Logfmt
+                | OutputFormat::Table => {
+                    unreachable!("checked above")
+                }
+            }
+
+            writer.write_all(options.record_terminator.as_bytes())?;
+
+            Ok(())
+        })
+    })?;
+
+    Ok(node_count)
+}
+
+fn key_matching(options: &Options) -> KeyMatching {
+    KeyMatching {
+        case_insensitive: options.ignore_key_case,
+        normalize: options.normalize_keys,
+    }
+}
+
+fn gron_writer_options(options: &Options) -> GronWriterOptions<'_> {
+    GronWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        assignment: &options.gron_assignment,
+        semicolon: !options.gron_no_semicolon,
+        identifier_policy: options.gron_identifiers.clone().into(),
+        quoting: GronQuoting {
+            single_quotes: options.gron_single_quotes,
+            ascii_only: options.gron_ascii,
+        },
+        index_width: options.index_width,
+        include_timestamp: options.timestamp,
+        color: options.color == ColorChoice::Always,
+        ..Default::default()
+    }
+}
+
+fn json_pointer_writer_options(options: &Options) -> JSONPointerWriterOptions<'_> {
+    JSONPointerWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        separator: &options.separator,
+        separator_collision: options.separator_collision.clone().into(),
+        uri_fragment: options.uri_fragment,
+        index_width: options.index_width,
+        include_timestamp: options.timestamp,
+        color: options.color == ColorChoice::Always,
+        ..Default::default()
+    }
+}
+
+fn json_writer_options(options: &Options) -> JsonWriterOptions<'_> {
+    JsonWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        include_pointer: options.include_pointer,
+        include_type: options.include_type,
+        number_types: options.number_types,
+        include_depth: options.include_depth,
+        envelope: options.envelope,
+        include_document: options.include_document,
+        include_timestamp: options.timestamp,
+        fields: options.fields.as_deref(),
+        ..Default::default()
+    }
+}
+
+fn indented_writer_options(options: &Options) -> IndentedWriterOptions<'_> {
+    IndentedWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        ..Default::default()
+    }
+}
+
+fn yaml_writer_options(options: &Options) -> YamlWriterOptions<'_> {
+    YamlWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        ..Default::default()
+    }
+}
+
+fn toml_writer_options(options: &Options) -> TomlWriterOptions<'_> {
+    TomlWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+    }
+}
+
+fn logfmt_writer_options(options: &Options) -> LogfmtWriterOptions<'_> {
+    LogfmtWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        line_per_record: options.logfmt_line_per_record,
+    }
+}
+
+fn table_writer_options(options: &Options) -> TableWriterOptions<'_> {
+    TableWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        max_width: options.table_max_width,
+        spill: spill_options(options),
+        ..Default::default()
+    }
+}
+
+fn spill_options(options: &Options) -> SpillOptions {
+    SpillOptions {
+        memory_limit: options.spill_memory.unwrap_or(usize::MAX),
+        dir: options.spill_dir.clone(),
+        #[cfg(feature = "gzip")]
+        compress: options.spill_gzip,
+    }
+}
+
+fn xpath_writer_options(options: &Options) -> XPathWriterOptions<'_> {
+    XPathWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        separator: &options.separator,
+        ..Default::default()
+    }
+}
+
+fn schema_writer_options(options: &Options) -> SchemaWriterOptions {
+    SchemaWriterOptions {
+        format: options
+            .format
+            .schema_format()
+            .expect("only called for schema formats"),
+        name: options.schema_name.clone(),
+    }
+}
+
+fn avro_writer_options(options: &Options) -> AvroWriterOptions {
+    AvroWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        sync_marker: options.deterministic.then_some([0; 16]),
+    }
+}
+
+fn prometheus_writer_options(options: &Options) -> PrometheusWriterOptions<'_> {
+    PrometheusWriterOptions {
+        prefix: &options.metric_prefix,
+        labels: &options.metric_label,
+        sort_keys: options.sort_keys,
+    }
+}
+
+/// The options for the records of the duckdb format, which are always JSON Lines
+fn duckdb_json_writer_options(options: &Options) -> JsonWriterOptions<'_> {
+    JsonWriterOptions {
+        include_pointer: options.fields.is_none(),
+        include_type: options.fields.is_none(),
+        envelope: false,
+        ..json_writer_options(options)
+    }
+}
+
+fn types_writer_options(options: &Options) -> TypesWriterOptions<'_> {
+    TypesWriterOptions {
+        sort_keys: options.sort_keys,
+        skip_empty_containers: options.skip_empty_containers,
+        record_terminator: &options.record_terminator,
+        number_types: options.number_types,
+        ..Default::default()
+    }
+}
+
+/// Write the output for `options` to `writer`,
+/// returning the number of nodes traversed
+fn write_output<W: Write>(
+    writer: &mut W,
+    options: &Options,
+    jindex_options: JindexOptions,
+) -> Result<usize> {
+    if can_write_files_in_parallel(options) {
+        return write_files_in_parallel(writer, options, jindex_options);
+    }
+
+    if options.dedup_shapes {
+        let shape_dedup_writer_options = ShapeDedupWriterOptions {
+            sort_keys: options.sort_keys,
+            skip_empty_containers: options.skip_empty_containers,
+            record_terminator: &options.record_terminator,
+            ..Default::default()
+        };
+        let mut sink = ShapeDedupWriter::new(writer, shape_dedup_writer_options);
+        return run(&mut sink, options, jindex_options);
+    }
+
+    match options.format {
+        OutputFormat::Gron => {
+            let mut sink = GronWriter::new(writer, gron_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::JSONPointer => {
+            let mut sink = JSONPointerWriter::new(writer, json_pointer_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Json => {
+            let mut sink = JSONWriter::new(writer, json_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Types => {
+            let mut sink = TypesWriter::new(writer, types_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Indented => {
+            let mut sink = IndentedWriter::new(writer, indented_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Yaml => {
+            let mut sink = YamlWriter::new(writer, yaml_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Toml => {
+            let mut sink = TomlWriter::new(writer, toml_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Xpath => {
+            let mut sink = XPathWriter::new(writer, xpath_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Graphql | OutputFormat::Openapi | OutputFormat::Proto => {
+            let mut sink = SchemaWriter::new(writer, schema_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Avro => {
+            let mut sink = AvroWriter::new(writer, avro_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Duckdb => {
+            let records_path = options
+                .duckdb_records
+                .as_deref()
+                .expect("required by clap with --format duckdb");
+
+            let mut records = BufWriter::new(std::fs::File::create(records_path)?);
+            let mut sink = JSONWriter::new(&mut records, duckdb_json_writer_options(options));
+            let node_count = run(&mut sink, options, jindex_options)?;
+            records.flush()?;
+
+            write_duckdb_script(
+                writer,
+                &options.duckdb_table,
+                &records_path.to_string_lossy(),
+            )?;
+
+            Ok(node_count)
+        }
+        OutputFormat::Prometheus => {
+            let mut sink = PrometheusWriter::new(writer, prometheus_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Logfmt => {
+            let mut sink = LogfmtWriter::new(writer, logfmt_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+        OutputFormat::Table => {
+            let mut sink = TableWriter::new(writer, table_writer_options(options));
+            run(&mut sink, options, jindex_options)
+        }
+    }
+}
+
+/// Writes the subtree under each top-level key (or index) of the input
+/// to its own file in `dir`, in the output format given in `options`.
+///
+/// Only the file of the subtree being traversed is open, as each subtree
+/// of a document is traversed before the next; a file is appended to
+/// when its key is seen again, as in another document
+struct SplitSink<'a> {
+    options: &'a Options,
+    dir: &'a Path,
+    /// The name of the file being written, and the file
+    current: Option<(String, BufWriter<std::fs::File>)>,
+    /// The names of the files that have been created
+    created: HashSet<String>,
+}
+
+impl<'a> SplitSink<'a> {
+    /// The name of the file for the top-level `path_component`,
+    /// percent-encoding any byte that might not be safe in a file name
+    fn file_name(&self, path_component: &PathComponent) -> String {
+        let mut file_name = String::new();
+
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+                for byte in s.bytes() {
+                    if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+                        file_name.push(byte as char);
+                    } else {
+                        file_name.push_str(&format!("%{:02X}", byte));
+                    }
+                }
+            }
+            PathComponent::Index(i) => file_name.push_str(&i.to_string()),
+        }
+
+        file_name.push_str(self.options.format.extension());
+
+        file_name
+    }
+}
+
+impl<'a> PathValueSink for SplitSink<'a> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let Some(top_level) = pathvalue.path_components.first() else {
+            return Ok(());
+        };
+
+        let file_name = self.file_name(top_level);
+
+        let file = match &mut self.current {
+            Some((name, file)) if *name == file_name => file,
+            current => {
+                if let Some((_, mut file)) = current.take() {
+                    file.flush()?;
+                }
+
+                let path = self.dir.join(&file_name);
+                let file = if self.created.insert(file_name.clone()) {
+                    std::fs::File::create(path)?
+                } else {
+                    std::fs::OpenOptions::new().append(true).open(path)?
+                };
+
+                &mut current.insert((file_name, BufWriter::new(file))).1
+            }
+        };
+
+        match self.options.format {
+            OutputFormat::Gron => {
+                GronWriter::new(file, gron_writer_options(self.options)).handle_pathvalue(pathvalue)
+            }
+            OutputFormat::JSONPointer => {
+                JSONPointerWriter::new(file, json_pointer_writer_options(self.options))
+                    .handle_pathvalue(pathvalue)
+            }
+            OutputFormat::Json => {
+                JSONWriter::new(file, json_writer_options(self.options)).handle_pathvalue(pathvalue)
+            }
+            OutputFormat::Types => TypesWriter::new(file, types_writer_options(self.options))
+                .handle_pathvalue(pathvalue),
+            OutputFormat::Indented => {
+                IndentedWriter::new(file, indented_writer_options(self.options))
+                    .handle_pathvalue(pathvalue)
+            }
+            OutputFormat::Yaml => {
+                YamlWriter::new(file, yaml_writer_options(self.options)).handle_pathvalue(pathvalue)
+            }
+            OutputFormat::Toml => {
+                TomlWriter::new(file, toml_writer_options(self.options)).handle_pathvalue(pathvalue)
+            }
+            OutputFormat::Xpath => XPathWriter::new(file, xpath_writer_options(self.options))
+                .handle_pathvalue(pathvalue),
+            // each record goes to its own writer, so none of them could end a document's line
+            OutputFormat::Logfmt => LogfmtWriter::new(
+                file,
+                LogfmtWriterOptions {
+                    line_per_record: true,
+                    ..logfmt_writer_options(self.options)
+                },
+            )
+            .handle_pathvalue(pathvalue),
+            OutputFormat::Graphql
+            | OutputFormat::Openapi
+            | OutputFormat::Proto
+            | OutputFormat::Avro
+            | OutputFormat::Duckdb
+            | OutputFormat::Prometheus
+            | OutputFormat::Table => unreachable!("checked in main"),
+        }
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some((_, file)) = &mut self.current {
+            file.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The `eq` subcommand
+fn eq(a: &Path, b: &Path, eq_options: &EqOptions) -> Result<()> {
+    let read = |path: &Path| -> Result<serde_json::Value> {
+        let buf = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&decode_bom(&buf)?)?)
+    };
+
+    match first_difference(&read(a)?, &read(b)?, eq_options) {
+        Some(pointer) => {
+            println!("{}", pointer);
+            Err(anyhow!(
+                "{} and {} differ at {:?}",
+                a.display(),
+                b.display(),
+                pointer
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// The `patch` subcommand
+fn patch(json_location: &Path, patch_location: &Path, merge: bool, flatten: bool) -> Result<()> {
+    let read = |path: &Path| -> Result<serde_json::Value> {
+        let buf = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&decode_bom(&buf)?)?)
+    };
+
+    let mut document = read(json_location)?;
+    let patch = read(patch_location)?;
+
+    if merge {
+        apply_merge_patch(&mut document, patch);
+    } else {
+        apply_json_patch(&mut document, &patch)?;
+    }
+
+    let stdout = std::io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    if flatten {
+        let mut sink = GronWriter::new(&mut writer, GronWriterOptions::default());
+        jindex(&mut sink, &document)?;
+    } else {
+        serde_json::to_writer(&mut writer, &document)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The `rename` subcommand
+fn rename_paths(json_location: &Path, mapping_location: &Path, emit_patch: bool) -> Result<()> {
+    let buf = read_location(json_location)?;
+    let mut document: serde_json::Value = serde_json::from_slice(&decode_bom(&buf)?)?;
+
+    let mappings = parse_path_mappings(&std::fs::read_to_string(mapping_location)?)?;
+
+    let patch = rename(&mut document, &mappings)?;
+
+    let stdout = std::io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    serde_json::to_writer(&mut writer, if emit_patch { &patch } else { &document })?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The `index` subcommand: index the file at `json_location`, writing the index to `output`
+fn write_index_file(json_location: &Path, output: &Path) -> Result<()> {
+    if is_stdin(json_location) {
+        return Err(anyhow!(
+            "only a file can be indexed, as lookups read it again"
+        ));
+    }
+
+    let mut file = std::fs::File::open(json_location)?;
+    // before reading it, so that a change while it is read makes the index stale
+    let state = SourceState::of(&file.metadata()?)?;
+
+    let mut json = Vec::new();
+    file.read_to_end(&mut json)?;
+    let entries = index_document(&String::from_utf8(json)?)?;
+
+    // so that the index can be used from any directory
+    let source = std::fs::canonicalize(json_location)?;
+
+    let mut writer = BufWriter::new(std::fs::File::create(output)?);
+    write_index(&mut writer, &source, state, &entries)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The `lookup` subcommand
+fn lookup(index_location: &Path, pointers: &[String]) -> Result<()> {
+    let mut index = PathIndex::open(BufReader::new(std::fs::File::open(index_location)?))?;
+    let mut document = index.open_source()?;
+
+    let stdout = std::io::stdout();
+    let mut lock = BufWriter::new(stdout.lock());
+
+    for pointer in pointers {
+        let entry = index
+            .get(pointer)?
+            .ok_or_else(|| anyhow!("no value at JSON Pointer {}", pointer))?;
+
+        lock.write_all(&read_value(&mut document, &entry)?)?;
+        lock.write_all(b"\n")?;
+    }
+
+    lock.flush()?;
+
+    Ok(())
+}
+
+/// The `search` subcommand
+fn search(index_location: &Path, query: &PathQuery, format: &str) -> Result<()> {
+    let registry = SinkRegistry::builtin();
+    let registered = registry
+        .get(format)
+        .ok_or_else(|| anyhow!("unknown format: {:?}", format))?;
+
+    let mut index = PathIndex::open(BufReader::new(std::fs::File::open(index_location)?))?;
+    let mut document = index.open_source()?;
+
+    let stdout = std::io::stdout();
+    let mut lock = BufWriter::new(stdout.lock());
+    let mut sink = (registered.new)(&mut lock);
+
+    let mut written: Option<String> = None;
+
+    for entry in search_paths(&mut index, query)? {
+        // matches are in order of their pointers, so descendants follow their ancestors
+        if let Some(ancestor) = &written {
+            if entry.pointer.starts_with(ancestor.as_str())
+                && entry.pointer[ancestor.len()..].starts_with('/')
+            {
+                continue;
+            }
+        }
+
+        let value: serde_json::Value = serde_json::from_slice(&read_value(&mut document, &entry)?)?;
+        let path_prefix = entry_path(&mut index, &entry.pointer)?;
+
+        jindex_with_options(
+            &mut sink,
+            &value,
+            &JindexOptions {
+                path_prefix,
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+        )?;
+
+        written = Some(entry.pointer);
+    }
+
+    sink.finish()?;
+    drop(sink);
+
+    lock.flush()?;
+
+    Ok(())
+}
+
+/// The `search-values` subcommand
+#[cfg(feature = "tantivy")]
+fn search_values_command(index_dir: &Path, query: &str, limit: usize, scores: bool) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut lock = BufWriter::new(stdout.lock());
+
+    for value_match in search_values(index_dir, query, limit)? {
+        if scores {
+            write!(lock, "{}\t", value_match.score)?;
+        }
+        writeln!(lock, "{}\t{}", value_match.pointer, value_match.value)?;
+    }
+
+    lock.flush()?;
+
+    Ok(())
+}
+
+/// The `verify` subcommand
+fn verify(json_location: &Path) -> Result<()> {
+    let buf = read_location(json_location)?;
+    let value: serde_json::Value = serde_json::from_slice(&decode_bom(&buf)?)?;
+
+    // jindex writes no line for the root, so start with gron's, like `json = {};`,
+    // without which an empty root object or array would be rebuilt as nothing
+    let mut gron = Vec::new();
+    match &value {
+        serde_json::Value::Array(_) => gron.extend(b"json = [];\n"),
+        _ => gron.extend(b"json = {};\n"),
+    }
+
+    let mut sink = GronWriter::new(&mut gron, GronWriterOptions::default());
+    jindex(&mut sink, &value)?;
+
+    let rebuilt = ungron(std::str::from_utf8(&gron)?)?;
+
+    let leaves = |value: serde_json::Value| -> BTreeMap<String, serde_json::Value> {
+        jindex_owned(value)
+            .map(|pathvalue| (pathvalue.as_pathvalue().to_json_pointer(), pathvalue.value))
+            .collect()
+    };
+
+    let expected = leaves(value);
+    let actual = leaves(rebuilt);
+
+    let mut failures = 0;
+
+    for (pointer, expected_value) in &expected {
+        match actual.get(pointer) {
+            Some(actual_value) if actual_value == expected_value => (),
+            Some(actual_value) => {
+                failures += 1;
+                println!(
+                    "changed\t{}\t{} -> {}",
+                    pointer, expected_value, actual_value
+                );
+            }
+            None => {
+                failures += 1;
+                println!("missing\t{}\t{}", pointer, expected_value);
+            }
+        }
+    }
+
+    for (pointer, actual_value) in &actual {
+        if !expected.contains_key(pointer) {
+            failures += 1;
+            println!("extra\t{}\t{}", pointer, actual_value);
+        }
+    }
+
+    if failures > 0 {
+        Err(anyhow!(
+            "{} of {} paths did not round-trip",
+            failures,
+            expected.len()
+        ))
+    } else {
+        println!("ok: {} paths round-tripped", expected.len());
+        Ok(())
+    }
+}
+
+/// The `convert-paths` subcommand
+fn convert_paths(from: PathSyntax, to: PathSyntax, separator: &str) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+
+        let (path_components, rest) = match from {
+            PathSyntax::Gron => parse_gron_path(&line, "json")?,
+            PathSyntax::Pointer => {
+                let (pointer, rest) = match line.find(separator) {
+                    Some(i) => line.split_at(i),
+                    None => (line.as_str(), ""),
+                };
+                (parse_json_pointer(pointer)?, rest)
+            }
+            PathSyntax::Jsonpath => return Err(anyhow!("JSONPath can only be converted to")),
+            PathSyntax::Xpath => return Err(anyhow!("XPath can only be converted to")),
+        };
+
+        match to {
+            PathSyntax::Gron => write_gron_path(&mut writer, "json", &path_components)?,
+            PathSyntax::Pointer => write_json_pointer(&mut writer, &path_components)?,
+            PathSyntax::Jsonpath => write_jsonpath(&mut writer, &path_components)?,
+            PathSyntax::Xpath => write_xpath(&mut writer, &path_components)?,
+        }
+
+        writer.write_all(rest.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// A sink that does nothing, for timing traversal alone, and for --validate
+struct DiscardSink;
+
+impl PathValueSink for DiscardSink {
+    fn handle_pathvalue(&mut self, _pathvalue: &PathValue) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Run `f` `iterations` times, and print its mean and minimum duration
+fn time_stage(name: &str, iterations: usize, mut f: impl FnMut() -> Result<()>) -> Result<()> {
+    let mut total = std::time::Duration::ZERO;
+    let mut min = std::time::Duration::MAX;
+
+    for _ in 0..iterations {
+        let start = std::time::Instant::now();
+        f()?;
+        let elapsed = start.elapsed();
+        total += elapsed;
+        min = min.min(elapsed);
+    }
+
+    println!(
+        "{:<26}mean {:>12.3?}  min {:>12.3?}",
+        name,
+        total / iterations as u32,
+        min
+    );
+
+    Ok(())
+}
+
+/// The `bench` subcommand: time each stage of a run on `json_location`
+fn bench(json_location: &Path, iterations: usize) -> Result<()> {
+    if iterations == 0 {
+        return Err(anyhow!("--iterations must be at least 1"));
+    }
+
+    let buf = read_location(json_location)?;
+    let value: serde_json::Value = serde_json::from_slice(&buf)?;
+
+    println!(
+        "{} ({} bytes), {} iterations",
+        json_location.display(),
+        buf.len(),
+        iterations
+    );
+
+    time_stage("parse", iterations, || {
+        serde_json::from_slice::<serde_json::Value>(&buf)?;
+        Ok(())
+    })?;
+
+    time_stage("traverse", iterations, || jindex(&mut DiscardSink, &value))?;
+
+    for registered in SinkRegistry::builtin().iter() {
+        time_stage(
+            &format!("traverse + {}", registered.name),
+            iterations,
+            || {
+                let mut writer = Vec::new();
+                let mut sink = (registered.new)(&mut writer);
+                jindex(&mut sink, &value)?;
+                sink.finish()
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write the output for `options` to `writer`, sorting it if `--deterministic` is given,
+/// returning the number of nodes traversed
+fn write_to<W: Write>(
+    writer: &mut W,
+    options: &Options,
+    jindex_options: JindexOptions,
+) -> Result<usize> {
+    if options.deterministic && options.format.is_line_per_record() {
+        // object keys are visited in sorted order,
+        // so sorting the records makes the output independent of traversal order.
+        // Beyond --spill-memory, the records are sorted by an external merge sort
+        let mut spill = RecordSpill::sorted(spill_options(options));
+
+        let mut lines = SpillLines::new(&mut spill);
+        let node_count = if options.raw_values {
+            write_raw_output(&mut lines, options, jindex_options.order)?
+        } else {
+            write_output(&mut lines, options, jindex_options)?
+        };
+        lines.finish()?;
+
+        for record in spill.into_sorted_records()? {
+            let record = record?;
+
+            if !record.is_empty() {
+                writer.write_all(&record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(node_count)
+    } else if options.raw_values {
+        write_raw_output(writer, options, jindex_options.order)
+    } else {
+        write_output(writer, options, jindex_options)
+    }
+}
+
+/// `--list-formats`: write each output format, with its description, file extension,
+/// and the options specific to it, as registered in [SinkRegistry::builtin],
+/// as text or as a JSON array
+fn list_formats(as_json: bool) -> Result<()> {
+    let command = Options::command();
+
+    let formats: Vec<serde_json::Value> = OutputFormat::value_variants()
+        .iter()
+        .map(|format| {
+            let value = format.to_possible_value().expect("no variants are skipped");
+
+            let format_options: Vec<serde_json::Value> = format
+                .registered()
+                .options
+                .iter()
+                .map(|long| {
+                    let arg = command
+                        .get_arguments()
+                        .find(|arg| arg.get_long() == Some(long))
+                        .ok_or_else(|| anyhow!("--{} is registered but not an option", long))?;
+
+                    Ok(serde_json::json!({
+                        "name": format!("--{}", long),
+                        "help": arg.get_help().map(ToString::to_string),
+                    }))
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(serde_json::json!({
+                "name": value.get_name(),
+                "description": value.get_help().map(ToString::to_string),
+                "extension": format.extension(),
+                "line_per_record": format.is_line_per_record(),
+                "writes_when_finished": format.writes_when_finished(),
+                "options": format_options,
+            }))
+        })
+        .collect::<Result<_>>()?;
+
+    let stdout = std::io::stdout();
+    let mut lock = BufWriter::new(stdout.lock());
+
+    if as_json {
+        serde_json::to_writer_pretty(&mut lock, &formats)?;
+        writeln!(lock)?;
+    } else {
+        // the names, then the descriptions in a column after the longest name
+        let width = OutputFormat::value_variants()
+            .iter()
+            .filter_map(|format| format.to_possible_value())
+            .map(|value| value.get_name().len())
+            .max()
+            .unwrap_or_default()
+            + 2;
+
+        for format in &formats {
+            writeln!(
+                lock,
+                "{:<width$}{}",
+                format["name"].as_str().unwrap_or_default(),
+                format["description"].as_str().unwrap_or_default(),
+            )?;
+
+            for format_option in format["options"].as_array().into_iter().flatten() {
+                writeln!(
+                    lock,
+                    "{:<width$}{}",
+                    "",
+                    format_option["name"].as_str().unwrap_or_default()
+                )?;
+            }
+        }
+    }
+
+    lock.flush()?;
+
+    Ok(())
+}
+
+/// The `gen-fixture` subcommand: write the output for the file at `json_location`
+/// in each of `formats` to a golden file in `dir`, with sorted keys in document order
+/// so that the golden files are stable
+fn gen_fixture(json_location: &Path, formats: &[String], dir: &Path) -> Result<()> {
+    let formats: Vec<OutputFormat> = if formats.iter().any(|format| format == "all") {
+        OutputFormat::value_variants().to_vec()
+    } else {
+        formats
+            .iter()
+            .map(|format| OutputFormat::from_str(format, true).map_err(|error| anyhow!(error)))
+            .collect::<Result<_>>()?
+    };
+
+    let buf = read_location(json_location)?;
+    let value: serde_json::Value = serde_json::from_slice(&decode_bom(&buf)?)?;
+
+    let stem = json_location
+        .file_stem()
+        .ok_or_else(|| anyhow!("{} has no file name", json_location.display()))?
+        .to_string_lossy();
+
+    let jindex_options = JindexOptions {
+        order: TraversalOrder::Document,
+        ..Default::default()
+    };
+
+    std::fs::create_dir_all(dir)?;
+
+    for format in formats {
+        let mut output = Vec::new();
+
+        match format {
+            OutputFormat::Gron => {
+                let options = GronWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut GronWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::JSONPointer => {
+                let options = JSONPointerWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut JSONPointerWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Json => {
+                let options = JsonWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut JSONWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Types => {
+                let options = TypesWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut TypesWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Indented => {
+                let options = IndentedWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut IndentedWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Yaml => {
+                let options = YamlWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                let mut sink = YamlWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+            OutputFormat::Toml => {
+                let options = TomlWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut TomlWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Xpath => {
+                let options = XPathWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                jindex_with_options(
+                    &mut XPathWriter::new(&mut output, options),
+                    &value,
+                    &jindex_options,
+                )?;
+            }
+            OutputFormat::Graphql | OutputFormat::Openapi | OutputFormat::Proto => {
+                let options = SchemaWriterOptions {
+                    format: format.schema_format().expect("a schema format"),
+                    ..Default::default()
+                };
+                let mut sink = SchemaWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+            OutputFormat::Avro => {
+                let options = AvroWriterOptions {
+                    sort_keys: true,
+                    sync_marker: Some([0; 16]),
+                    ..Default::default()
+                };
+                let mut sink = AvroWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+            OutputFormat::Duckdb => {
+                let options = JsonWriterOptions {
+                    sort_keys: true,
+                    include_pointer: true,
+                    include_type: true,
+                    ..Default::default()
+                };
+                let records_path = dir.join(format!("{}.duckdb.jsonl", stem));
+                let mut records = Vec::new();
+                jindex_with_options(
+                    &mut JSONWriter::new(&mut records, options),
+                    &value,
+                    &jindex_options,
+                )?;
+                std::fs::write(&records_path, records)?;
+                eprintln!("wrote {}", records_path.display());
+
+                write_duckdb_script(&mut output, "jindex", &records_path.to_string_lossy())?;
+            }
+            OutputFormat::Prometheus => {
+                let options = PrometheusWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                let mut sink = PrometheusWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+            OutputFormat::Logfmt => {
+                let options = LogfmtWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                let mut sink = LogfmtWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+            OutputFormat::Table => {
+                let options = TableWriterOptions {
+                    sort_keys: true,
+                    ..Default::default()
+                };
+                let mut sink = TableWriter::new(&mut output, options);
+                jindex_with_options(&mut sink, &value, &jindex_options)?;
+                sink.finish()?;
+            }
+        }
+
+        let path = dir.join(format!("{}{}", stem, format.extension()));
+        std::fs::write(&path, output)?;
+        eprintln!("wrote {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// The `stats` subcommand: write each of the requested reports on the file at `json_location`
+fn stats(json_location: &Path, reports: &StatsReports) -> Result<()> {
+    let buf = read_location(json_location)?;
+    let value: serde_json::Value = serde_json::from_slice(&decode_bom(&buf)?)?;
+
+    let stdout = std::io::stdout();
+    let mut lock = BufWriter::new(stdout.lock());
+
+    if reports.inconsistent_types {
+        let mut sink = if reports.number_types {
+            TypeConsistencySink::with_number_types()
+        } else {
+            TypeConsistencySink::new()
+        };
+        jindex(&mut sink, &value)?;
+        sink.write_report(&mut lock)?;
+    }
+
+    if reports.coverage {
+        let mut sink = FieldCoverageSink::new();
+        jindex(&mut sink, &value)?;
+        sink.write_report(&mut lock)?;
+    }
+
+    if reports.numeric {
+        let mut sink = NumericStatsSink::new();
+        jindex(&mut sink, &value)?;
+        sink.write_report(&mut lock)?;
+    }
+
+    if reports.strings {
+        let mut sink = StringProfileSink::new();
+        jindex(&mut sink, &value)?;
+        sink.write_report(&mut lock)?;
+    }
+
+    if reports.timestamps {
+        let mut sink = TimestampSink::new();
+        jindex(&mut sink, &value)?;
+        sink.write_report(&mut lock)?;
+    }
+
+    lock.flush()?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // https://github.com/rust-lang/rust/issues/46016
+    #[cfg(target_family = "unix")]
+    {
+        use nix::sys::signal;
+        let _ = unsafe { signal::signal(signal::Signal::SIGPIPE, signal::SigHandler::SigDfl)? };
+    }
+
+    let options = Options::parse();
+    let errors = options.errors;
+
+    if let Err(error) = run_command_line(options) {
+        match errors {
+            // as `main` writes an error it returns
+            ErrorFormat::Text => eprintln!("Error: {:?}", error),
+            ErrorFormat::Json => eprintln!("{}", error_report(&error)),
+        }
+
+        std::process::exit(Failure::of(&error).exit_code());
+    }
+
+    Ok(())
+}
+
+/// The kinds of failure, each with its own exit code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    Other,
+    Parse,
+    Io,
+    InvalidRoot,
+    NoMatches,
+    Limit,
+    PipeClosed,
+}
+
+/// The exit codes of [Failure], for --help
+const EXIT_CODES: &str = "Exit codes:
+  0    success
+  1    any other error
+  2    invalid arguments
+  3    the input is not valid JSON
+  4    a file could not be read or written
+  5    --root refers to no value, or the root is not an object or array
+  6    a glob matched no files
+  7    a node exceeded a limit, like --max-depth
+  141  the output was closed before it was all written";
+
+impl Failure {
+    fn of(error: &anyhow::Error) -> Self {
+        // the library's errors are messages rather than causes, so are only found by `downcast_ref`
+        if error.downcast_ref::<LimitError>().is_some() {
+            return Self::Limit;
+        }
+
+        if error.downcast_ref::<RootError>().is_some() {
+            return Self::InvalidRoot;
+        }
+
+        for cause in error.chain() {
+            if cause.is::<ParseError>() {
+                return Self::Parse;
+            }
+
+            if cause.is::<NoFilesMatch>() {
+                return Self::NoMatches;
+            }
+
+            // an IO error parsing JSON is followed by its cause
+            if let Some(error) = cause.downcast_ref::<serde_json::Error>() {
+                if !error.is_io() {
+                    return Self::Parse;
+                }
+            }
+
+            if let Some(error) = cause.downcast_ref::<std::io::Error>() {
+                return if error.kind() == std::io::ErrorKind::BrokenPipe {
+                    Self::PipeClosed
+                } else {
+                    Self::Io
+                };
+            }
+        }
+
+        Self::Other
+    }
+
+    /// The failure's name, for --errors json
+    fn name(self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::Parse => "parse",
+            Self::Io => "io",
+            Self::InvalidRoot => "invalid-root",
+            Self::NoMatches => "no-matches",
+            Self::Limit => "limit",
+            Self::PipeClosed => "pipe-closed",
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Parse => 3,
+            Self::Io => 4,
+            Self::InvalidRoot => 5,
+            Self::NoMatches => 6,
+            Self::Limit => 7,
+            // as when killed by SIGPIPE
+            Self::PipeClosed => 128 + 13,
+        }
+    }
+}
+
+/// `error` as a JSON object, for --errors json
+fn error_report(error: &anyhow::Error) -> serde_json::Value {
+    let mut report = serde_json::json!({
+        "code": Failure::of(error).name(),
+        "message": format!("{:#}", error),
+        "offset": null,
+        "line": null,
+        "column": null,
+        "path": error
+            .downcast_ref::<LimitError>()
+            .map(|error| error.pointer.clone()),
+    });
+
+    for cause in error.chain() {
+        let parse_error = if let Some(error) = cause.downcast_ref::<ParseError>() {
+            report["offset"] = error.offset.into();
+            &error.error
+        } else if let Some(error) = cause.downcast_ref::<serde_json::Error>() {
+            error
+        } else {
+            continue;
+        };
+
+        // serde_json gives line 0 for errors that are not in the input
+        if parse_error.line() > 0 {
+            report["line"] = parse_error.line().into();
+            report["column"] = parse_error.column().into();
+        }
+    }
+
+    report
+}
+
+fn run_command_line(mut options: Options) -> Result<()> {
+    options.json_locations = expand_globs(&options.json_locations)?;
+
+    if let Some(Command::Bench {
+        json_location,
+        iterations,
+    }) = &options.command
+    {
+        return bench(json_location, *iterations);
+    }
+
+    if let Some(Command::Eq {
+        a,
+        b,
+        ignore_array_order,
+        numeric_values,
+    }) = &options.command
+    {
+        return eq(
+            a,
+            b,
+            &EqOptions {
+                ignore_array_order: *ignore_array_order,
+                numeric_values: *numeric_values,
+            },
+        );
+    }
+
+    if let Some(Command::Patch {
+        json_location,
+        patch_location,
+        merge,
+        flatten,
+    }) = &options.command
+    {
+        return patch(json_location, patch_location, *merge, *flatten);
+    }
+
+    if let Some(Command::Rename {
+        json_location,
+        mapping_location,
+        emit_patch,
+    }) = &options.command
+    {
+        return rename_paths(json_location, mapping_location, *emit_patch);
+    }
+
+    if let Some(Command::Sample {
+        json_location,
+        seed,
+    }) = &options.command
+    {
+        let buf = read_location(json_location)?;
+        let value: serde_json::Value = serde_json::from_slice(&decode_bom(&buf)?)?;
+
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+
+        println!("{}", anonymize(&value, seed));
+
+        return Ok(());
+    }
+
+    if let Some(Command::Stats {
+        json_location,
+        reports,
+    }) = &options.command
+    {
+        return stats(json_location, reports);
+    }
+
+    if let Some(Command::GenFixture {
+        json_location,
+        formats,
+        dir,
+    }) = &options.command
+    {
+        return gen_fixture(json_location, formats, dir);
+    }
+
+    if let Some(Command::Verify { json_location }) = &options.command {
+        return verify(json_location);
+    }
+
+    if let Some(Command::Index {
+        json_location,
+        output,
+        #[cfg(feature = "tantivy")]
+        values,
+    }) = &options.command
+    {
+        write_index_file(json_location, output)?;
+
+        #[cfg(feature = "tantivy")]
+        if let Some(dir) = values {
+            let buf = read_location(json_location)?;
+            index_values(dir, serde_json::from_slice(&decode_bom(&buf)?)?)?;
+        }
+
+        return Ok(());
+    }
+
+    if let Some(Command::Lookup {
+        index_location,
+        pointers,
+    }) = &options.command
+    {
+        return lookup(index_location, pointers);
+    }
+
+    if let Some(Command::Search {
+        index_location,
+        query,
+        prefix,
+        fuzzy,
+        format,
+    }) = &options.command
+    {
+        let query = match fuzzy {
+            Some(max_distance) => PathQuery::Fuzzy {
+                pointer: query.clone(),
+                max_distance: *max_distance,
+            },
+            None if *prefix => PathQuery::Prefix(query.clone()),
+            None => PathQuery::Glob(PathPattern::glob(query)),
+        };
+
+        return search(index_location, &query, format);
+    }
+
+    #[cfg(feature = "tantivy")]
+    if let Some(Command::SearchValues {
+        index_dir,
+        query,
+        limit,
+        scores,
+    }) = &options.command
+    {
+        return search_values_command(index_dir, query, *limit, *scores);
+    }
+
+    if let Some(Command::ConvertPaths {
+        from,
+        to,
+        separator,
+    }) = &options.command
+    {
+        return convert_paths(*from, *to, separator);
+    }
+
+    if options.list_formats {
+        return list_formats(options.json);
+    } else if options.json {
+        // rather than `requires`, which is not met by --format list setting --list-formats
+        return Err(anyhow!("--json requires --list-formats or --format list"));
+    }
+
+    if options.json_locations.is_empty() && std::io::stdin().is_terminal() {
+        // rather than waiting for input that the user probably did not mean to type
+        eprintln!("{}", Options::command().render_help());
+        return Err(anyhow!(
+            "no input: give a file path, or `-` to read from stdin"
+        ));
+    }
+
+    if options.manifest.is_some() {
+        *INPUT_DIGESTS.lock().unwrap() = Some(HashMap::new());
+    }
+
+    if options.deterministic {
+        options.record_terminator = "\n".to_string();
+        options.sort_keys = true;
+    }
+
+    if options.color == ColorChoice::Auto {
+        let to_terminal = std::io::stdout().is_terminal()
+            && options.split_by_top_level_key.is_none()
+            && options.split_lines.is_none()
+            && options.split_bytes.is_none();
+
+        options.color = if to_terminal && std::env::var_os("NO_COLOR").is_none() {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        };
+    }
+
+    let mut exclude: Vec<PathPattern> = options
+        .exclude
+        .iter()
+        .map(|pattern| PathPattern::glob_with(pattern, key_matching(&options)))
+        .collect();
+
+    for pattern in &options.exclude_regex {
+        exclude.push(PathPattern::regex_with(pattern, key_matching(&options))?);
+    }
+
+    let jindex_options = JindexOptions {
+        root: options.root.as_deref(),
+        absolute_paths: options.absolute_paths,
+        exclude,
+        array_sample: options
+            .array_sample
+            .map(|n| match options.array_sample_mode {
+                ArraySampleMode::Head => ArraySample::Head(n),
+                ArraySampleMode::Tail => ArraySample::Tail(n),
+                ArraySampleMode::Ends => ArraySample::HeadAndTail(n),
+                ArraySampleMode::Stride => ArraySample::Stride(n),
+            }),
+        path_prefix: vec![],
+        order: match (&options.format, &options.order) {
+            // for the formats whose records are not sorted afterwards
+            _ if options.deterministic => TraversalOrder::Document,
+            // the indented format is unreadable with siblings in reverse
+            (OutputFormat::Indented, Order::Unspecified) => TraversalOrder::Document,
+            (_, order) => order.clone().into(),
+        },
+        max_depth: Some(options.max_depth),
+    };
+
+    if !options.extract.is_empty() {
+        let stdout = std::io::stdout();
+        let mut lock = BufWriter::new(stdout.lock());
+        write_extracted_output(&mut lock, &options)?;
+        lock.flush()?;
+        return Ok(());
+    }
+
+    if !options.pick.is_empty() {
+        let stdout = std::io::stdout();
+        let mut lock = BufWriter::new(stdout.lock());
+        write_picked_output(&mut lock, &options)?;
+        lock.flush()?;
+        return Ok(());
+    }
+
+    if options.shared_subtrees {
+        let stdout = std::io::stdout();
+        let mut lock = BufWriter::new(stdout.lock());
+        write_shared_output(&mut lock, &options)?;
+        lock.flush()?;
+        return Ok(());
+    }
+
+    if options.validate {
+        let start = std::time::Instant::now();
+        let node_count = run(&mut DiscardSink, &options, jindex_options)?;
+        return report_timing(start.elapsed(), node_count);
+    }
+
+    let splitting = options.split_by_top_level_key.is_some()
+        || options.split_lines.is_some()
+        || options.split_bytes.is_some();
+
+    if splitting && options.format.writes_when_finished() {
+        return Err(anyhow!(
+            "--split-* options cannot be used with --format {}",
+            options
+                .format
+                .to_possible_value()
+                .expect("no variants are skipped")
+                .get_name()
+        ));
+    }
+
+    if let Some(dir) = &options.split_by_top_level_key {
+        std::fs::create_dir_all(dir)?;
+
+        let mut sink = SplitSink {
+            options: &options,
+            dir,
+            current: None,
+            created: HashSet::new(),
+        };
+
+        let node_count = run(&mut sink, &options, jindex_options)?;
+
+        if let Some(manifest) = &options.manifest {
+            write_manifest(manifest, &options, node_count)?;
+        }
+
+        return Ok(());
+    }
+
+    let start = std::time::Instant::now();
+
+    let node_count = if options.split_lines.is_some() || options.split_bytes.is_some() {
+        let mut writer = RotatingWriter::new(RotatingWriterOptions {
+            prefix: options.split_prefix.clone(),
+            record_terminator: options.record_terminator.as_bytes().to_vec(),
+            max_records: options.split_lines,
+            max_bytes: options.split_bytes,
+        });
+
+        let node_count = write_to(&mut writer, &options, jindex_options)?;
+        writer.finish()?;
+        node_count
+    } else {
+        let stdout = std::io::stdout();
+        let mut lock = BufWriter::new(stdout.lock());
+
+        let flush_every = if options.line_buffered {
+            Some(1)
+        } else {
+            options.flush_every
+        };
+
+        let node_count = if let Some(flush_every) = flush_every {
+            let mut writer =
+                FlushingWriter::new(&mut lock, options.record_terminator.as_bytes(), flush_every);
+            write_to(&mut writer, &options, jindex_options)?
+        } else {
+            write_to(&mut lock, &options, jindex_options)?
+        };
+
+        lock.flush()?;
+        node_count
+    };
+
+    if options.timing {
+        report_timing(start.elapsed(), node_count)?;
+    }
+
+    if let Some(manifest) = &options.manifest {
+        write_manifest(manifest, &options, node_count)?;
+    }
 
     Ok(())
 }
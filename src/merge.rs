@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+
+/// How [merge] resolves two values at the same path
+/// when they cannot both be kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// Keep the value from the later document
+    #[default]
+    LastWins,
+    /// Keep the value from the earlier document
+    FirstWins,
+    /// Return an error naming the path of the conflict
+    Error,
+}
+
+#[derive(Debug, Default)]
+pub struct MergeOptions {
+    pub conflict: MergeConflict,
+    /// Append the elements of arrays at the same path
+    /// rather than treating them as a conflict
+    pub concat_arrays: bool,
+}
+
+/// Deep-merge `overlay` into `base`.
+///
+/// Objects are merged key by key, recursively.
+/// Any other pair of values at the same path
+/// (unless they are equal, or both arrays with `concat_arrays`)
+/// is a conflict, resolved according to `options.conflict`.
+pub fn merge(
+    base: &mut serde_json::Value,
+    overlay: serde_json::Value,
+    options: &MergeOptions,
+) -> Result<()> {
+    let mut pointer = String::new();
+    merge_at(base, overlay, options, &mut pointer)
+}
+
+fn merge_at(
+    base: &mut serde_json::Value,
+    overlay: serde_json::Value,
+    options: &MergeOptions,
+    pointer: &mut String,
+) -> Result<()> {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                if let Some(base_v) = base.get_mut(&k) {
+                    let len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                    merge_at(base_v, v, options, pointer)?;
+                    pointer.truncate(len);
+                } else {
+                    base.insert(k, v);
+                }
+            }
+        }
+        (serde_json::Value::Array(base), serde_json::Value::Array(overlay))
+            if options.concat_arrays =>
+        {
+            base.extend(overlay);
+        }
+        (base, overlay) if *base == overlay => (),
+        (base, overlay) => match options.conflict {
+            MergeConflict::LastWins => *base = overlay,
+            MergeConflict::FirstWins => (),
+            MergeConflict::Error => {
+                return Err(anyhow!(
+                    "merge conflict at {:?}: {} and {}",
+                    pointer,
+                    base,
+                    overlay
+                ))
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_merge() {
+        let mut base = serde_json::json!({"a": {"b": 1, "c": [1]}, "d": true});
+        let overlay = serde_json::json!({"a": {"b": 2, "c": [2], "e": null}});
+
+        merge(&mut base, overlay.clone(), &MergeOptions::default()).unwrap();
+
+        assert_eq!(
+            base,
+            serde_json::json!({"a": {"b": 2, "c": [2], "e": null}, "d": true})
+        );
+
+        let mut base = serde_json::json!({"a": {"b": 1, "c": [1]}, "d": true});
+
+        merge(
+            &mut base,
+            overlay.clone(),
+            &MergeOptions {
+                conflict: MergeConflict::FirstWins,
+                concat_arrays: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            base,
+            serde_json::json!({"a": {"b": 1, "c": [1, 2], "e": null}, "d": true})
+        );
+
+        let mut base = serde_json::json!({"a": {"b": 1}});
+
+        let error = merge(
+            &mut base,
+            overlay,
+            &MergeOptions {
+                conflict: MergeConflict::Error,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("/a/b"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Controlling how numbers are written, so that output is stable for diffing and hashing.
+//!
+//! By default, every sink writes numbers as serde_json does:
+//! integers as they are, and floats in the shortest form that parses back to the same value.
+//! For the exact text of each number in the input, see the [raw](crate::raw) module.
+
+use crate::{PathValue, ValueTransform};
+
+/// How [RoundFloats] writes each float
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// Rounded to at most `decimals` decimal places, in the shortest form of the rounded float,
+    /// like `2.5` for `2.5` with 2
+    #[default]
+    AtMost,
+    /// Rounded to exactly `decimals` decimal places, like `2.50` for `2.5` with 2
+    Fixed,
+    /// In the shortest form that parses back to the same float, ignoring `decimals`,
+    /// like `2.5` for `2.50` or `1000.0` for `1e3`, as serde_json writes floats
+    Shortest,
+}
+
+/// A [ValueTransform] that rounds every float to `decimals` decimal places
+/// as given by `format`, like `1.23456` to `1.23` with 2, leaving integers as they are.
+///
+/// Ties are rounded to even, as by `format!`, so `0.125` is `0.12` with 2.
+/// Floats that round to zero are `0.0`, never `-0.0`.
+///
+/// Values are written by serde_json, which writes every float in its shortest form,
+/// so as a transform [FloatFormat::Fixed] rounds like [FloatFormat::AtMost],
+/// and [FloatFormat::Shortest] changes nothing. For the text of a number in each format,
+/// as for raw values (see the [raw](crate::raw) module), use [RoundFloats::format_raw].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundFloats {
+    pub decimals: usize,
+    pub format: FloatFormat,
+}
+
+impl RoundFloats {
+    /// `f` rounded to `decimals` decimal places, without a negative zero
+    fn round(&self, f: f64) -> Option<f64> {
+        let rounded: f64 = format!("{:.*}", self.decimals, f).parse().ok()?;
+
+        // `+ 0.0` turns `-0.0` into `0.0`
+        Some(rounded + 0.0)
+    }
+
+    /// The text of the number `raw`, as it appears in a document, in `format`,
+    /// or `None` if it is an integer (without a fraction or an exponent),
+    /// which is left as it is
+    pub fn format_raw(&self, raw: &str) -> Option<String> {
+        if !raw.contains(['.', 'e', 'E']) {
+            return None;
+        }
+
+        let f: f64 = raw.parse().ok()?;
+
+        match self.format {
+            FloatFormat::AtMost => {
+                serde_json::Number::from_f64(self.round(f)?).map(|rounded| rounded.to_string())
+            }
+            FloatFormat::Fixed => {
+                let fixed = format!("{:.*}", self.decimals, f);
+
+                // without a negative zero, like `-0.00`
+                match fixed.strip_prefix('-') {
+                    Some(digits) if digits.bytes().all(|b| b == b'0' || b == b'.') => {
+                        Some(digits.to_string())
+                    }
+                    _ => Some(fixed),
+                }
+            }
+            FloatFormat::Shortest => serde_json::Number::from_f64(f).map(|n| n.to_string()),
+        }
+    }
+}
+
+/// The largest integer below which every integer is exactly a float, 2^53
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// A [ValueTransform] that writes every number in one canonical form,
+/// so that numbers that are equal are written alike, however they appear in the input:
+/// floats with integral values up to 2^53 as integers, like `1` for `1.0` or `1e0`,
+/// `-0.0` as `0`, and other floats in their shortest form, like `2.5` for `2.50`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalNumbers;
+
+impl CanonicalNumbers {
+    /// `n` in canonical form, or `None` if it already is
+    pub fn number(n: &serde_json::Number) -> Option<serde_json::Number> {
+        let f = n.as_f64().filter(|_| n.is_f64())?;
+
+        (f.fract() == 0.0 && f.abs() <= MAX_EXACT_INTEGER).then(|| (f as i64).into())
+    }
+}
+
+impl ValueTransform for CanonicalNumbers {
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value> {
+        match pathvalue.value {
+            serde_json::Value::Number(n) => Self::number(n).map(serde_json::Value::Number),
+            _ => None,
+        }
+    }
+}
+
+impl ValueTransform for RoundFloats {
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value> {
+        match pathvalue.value {
+            serde_json::Value::Number(n) if n.is_f64() => match self.format {
+                FloatFormat::AtMost | FloatFormat::Fixed => {
+                    serde_json::Number::from_f64(self.round(n.as_f64()?)?)
+                        .map(serde_json::Value::Number)
+                }
+                FloatFormat::Shortest => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_value_sink::{JSONPointerWriter, JSONPointerWriterOptions};
+    use crate::{jindex_with_transform, JindexOptions, TraversalOrder};
+
+    #[test]
+    fn round_floats() {
+        let v = serde_json::json!([1.23456, 0.125, 2.5, 7, -0.004, 1e300]);
+
+        let mut output = Vec::new();
+        jindex_with_transform(
+            &mut JSONPointerWriter::new(&mut output, JSONPointerWriterOptions::default()),
+            &v,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+            &mut RoundFloats {
+                decimals: 2,
+                format: FloatFormat::AtMost,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "/0\t1.23\n/1\t0.12\n/2\t2.5\n/3\t7\n/4\t0.0\n/5\t1e300\n"
+        );
+    }
+
+    #[test]
+    fn canonical_numbers() {
+        let v = serde_json::json!([1, 1.0, -0.0, 2.5, 9007199254740992.0, 1e300, -3.0]);
+
+        let mut output = Vec::new();
+        jindex_with_transform(
+            &mut JSONPointerWriter::new(&mut output, JSONPointerWriterOptions::default()),
+            &v,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+            &mut CanonicalNumbers,
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "/0\t1\n/1\t1\n/2\t0\n/3\t2.5\n/4\t9007199254740992\n/5\t1e300\n/6\t-3\n"
+        );
+    }
+
+    #[test]
+    fn format_raw() {
+        let formatted = |format| -> Vec<Option<String>> {
+            let round_floats = RoundFloats {
+                decimals: 2,
+                format,
+            };
+
+            ["1.23456", "0.125", "2.50", "7", "-0.004", "1e3", "-5E-1"]
+                .iter()
+                .map(|raw| round_floats.format_raw(raw))
+                .collect()
+        };
+        let some = |texts: &[&str]| -> Vec<Option<String>> {
+            texts
+                .iter()
+                .map(|text| (!text.is_empty()).then(|| text.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            formatted(FloatFormat::AtMost),
+            some(&["1.23", "0.12", "2.5", "", "0.0", "1000.0", "-0.5"])
+        );
+        assert_eq!(
+            formatted(FloatFormat::Fixed),
+            some(&["1.23", "0.12", "2.50", "", "0.00", "1000.00", "-0.50"])
+        );
+        assert_eq!(
+            formatted(FloatFormat::Shortest),
+            some(&["1.23456", "0.125", "2.5", "", "-0.004", "1000.0", "-0.5"])
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Flattening documents into [OpenTelemetry attributes](https://opentelemetry.io/docs/specs/otel/common/#attribute),
+//! like those of a span or a log record
+
+use crate::path_value_sink::PathValueSink;
+use crate::{jindex_with_options, JindexOptions, PathComponent, PathValue, TraversalOrder};
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// The value of an attribute, mirroring the `Value` of the `opentelemetry` crate,
+/// so that converting to it is a single `match`
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    Bool(bool),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Array(AttributeArray),
+}
+
+/// An array attribute value, whose elements are all of the same type,
+/// mirroring the `Array` of the `opentelemetry` crate
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeArray {
+    Bool(Vec<bool>),
+    I64(Vec<i64>),
+    F64(Vec<f64>),
+    String(Vec<String>),
+}
+
+/// Flatten `json` into attributes, in document order,
+/// with keys joined by `.` like `http.request.method`; see [OtelAttributeSink].
+///
+/// Fails with a [RootError](crate::RootError) if `json` is not an object or array,
+/// since a scalar has no key to be an attribute of.
+pub fn otel_attributes(json: &serde_json::Value) -> Result<Vec<(String, AttributeValue)>> {
+    let mut sink = OtelAttributeSink::new();
+
+    jindex_with_options(
+        &mut sink,
+        json,
+        &JindexOptions {
+            order: TraversalOrder::Document,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(sink.attributes)
+}
+
+/// Collect an attribute for every scalar of the documents passed to `jindex`,
+/// keyed by its path with components joined by `.`, like `items.0.id`.
+///
+/// Attribute values cannot be null or nested, so:
+/// - nulls, and empty objects and arrays, are skipped
+/// - an array of only bools, only strings, or only numbers is a single array attribute,
+///   of floats if any of its numbers is not an `i64`
+/// - the elements of any other array are attributes of their own, keyed by their indexes
+/// - integers beyond the range of an `i64` are strings, so that they are not rounded
+#[derive(Debug, Default)]
+pub struct OtelAttributeSink {
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// The keys of the arrays written as array attributes,
+    /// whose elements are skipped when they are visited
+    array_keys: HashSet<String>,
+}
+
+impl OtelAttributeSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PathValueSink for OtelAttributeSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if let Some((PathComponent::Index(_), parent)) = pathvalue.path_components.split_last() {
+            if !self.array_keys.is_empty() && self.array_keys.contains(&attribute_key(parent)) {
+                return Ok(());
+            }
+        }
+
+        let value = match pathvalue.value {
+            serde_json::Value::Null | serde_json::Value::Object(_) => return Ok(()),
+            serde_json::Value::Array(a) => match attribute_array(a) {
+                Some(array) => {
+                    self.array_keys
+                        .insert(attribute_key(&pathvalue.path_components));
+                    AttributeValue::Array(array)
+                }
+                None => return Ok(()),
+            },
+            serde_json::Value::Bool(b) => AttributeValue::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => AttributeValue::I64(i),
+                None if n.is_f64() => AttributeValue::F64(n.as_f64().unwrap_or(f64::NAN)),
+                None => AttributeValue::String(n.to_string()),
+            },
+            serde_json::Value::String(s) => AttributeValue::String(s.clone()),
+        };
+
+        self.attributes
+            .push((attribute_key(&pathvalue.path_components), value));
+
+        Ok(())
+    }
+}
+
+/// The `path_components` joined by `.`
+fn attribute_key(path_components: &[PathComponent]) -> String {
+    let mut key = String::new();
+
+    for path_component in path_components {
+        if !key.is_empty() {
+            key.push('.');
+        }
+
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => key.push_str(s),
+            PathComponent::Index(i) => key.push_str(&i.to_string()),
+        }
+    }
+
+    key
+}
+
+/// `array` as a single attribute value, if its elements are all bools,
+/// all strings, or all numbers that fit in an `i64` or `f64`
+fn attribute_array(array: &[serde_json::Value]) -> Option<AttributeArray> {
+    match array.first()? {
+        serde_json::Value::Bool(_) => array
+            .iter()
+            .map(serde_json::Value::as_bool)
+            .collect::<Option<_>>()
+            .map(AttributeArray::Bool),
+        serde_json::Value::String(_) => array
+            .iter()
+            .map(|v| v.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .map(AttributeArray::String),
+        serde_json::Value::Number(_) => {
+            if let Some(integers) = array.iter().map(serde_json::Value::as_i64).collect() {
+                return Some(AttributeArray::I64(integers));
+            }
+
+            array
+                .iter()
+                .map(|v| match v {
+                    serde_json::Value::Number(n) if n.is_i64() || n.is_f64() => n.as_f64(),
+                    _ => None,
+                })
+                .collect::<Option<_>>()
+                .map(AttributeArray::F64)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes() {
+        let v = serde_json::json!({
+            "a": {"b": true, "c": null, "d": {}},
+            "big": 18446744073709551615u64,
+            "floats": [1, 2.5],
+            "ints": [1, 2],
+            "mixed": [1, "x", null, {"y": 1.5}],
+            "strings": ["x", "y"]
+        });
+
+        assert_eq!(
+            otel_attributes(&v).unwrap(),
+            vec![
+                ("a.b".to_string(), AttributeValue::Bool(true)),
+                (
+                    "big".to_string(),
+                    AttributeValue::String("18446744073709551615".to_string())
+                ),
+                (
+                    "floats".to_string(),
+                    AttributeValue::Array(AttributeArray::F64(vec![1.0, 2.5]))
+                ),
+                (
+                    "ints".to_string(),
+                    AttributeValue::Array(AttributeArray::I64(vec![1, 2]))
+                ),
+                ("mixed.0".to_string(), AttributeValue::I64(1)),
+                (
+                    "mixed.1".to_string(),
+                    AttributeValue::String("x".to_string())
+                ),
+                ("mixed.3.y".to_string(), AttributeValue::F64(1.5)),
+                (
+                    "strings".to_string(),
+                    AttributeValue::Array(AttributeArray::String(vec![
+                        "x".to_string(),
+                        "y".to_string()
+                    ]))
+                ),
+            ]
+        );
+
+        assert_eq!(otel_attributes(&serde_json::json!([])).unwrap(), Vec::new());
+
+        let err = otel_attributes(&serde_json::json!(5)).unwrap_err();
+        assert!(err.downcast_ref::<crate::RootError>().is_some());
+    }
+}
@@ -0,0 +1,443 @@
+//! Traversing one document on many threads, including a document
+//! whose values are nearly all under one key, by splitting large objects and arrays
+//! into a task for each of their children, which idle threads steal
+
+use crate::path_value_sink::PathValueSink;
+use crate::{
+    check_depth, is_excluded, root_pathvalue, sampled_indexes, JindexOptions, OwnedPathValue,
+    PathComponent, PathValue,
+};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Objects and arrays with at least this many nodes (counting themselves)
+/// are split into a task for each of their children
+const SPLIT_MIN_NODES: u64 = 4096;
+
+/// Receives the nodes visited by one thread of [jindex_parallel]
+pub trait SequencedSink {
+    /// Handle `pathvalue`, which is node number `sequence` of a traversal of the whole document
+    /// that visits every node before its children, and the children of each node in order
+    /// (with sorted keys if [SequencedSink::sort_keys]), starting with `0` at the root.
+    ///
+    /// Sequence numbers do not depend on the number of threads or on which thread
+    /// visits which node, so sorting the nodes of every sink by sequence number
+    /// gives the order of [TraversalOrder::Document](crate::TraversalOrder::Document).
+    /// Excluded and unsampled nodes, and their descendants, keep their numbers,
+    /// so the numbers of visited nodes are not always consecutive.
+    fn handle_sequenced(&mut self, sequence: u64, pathvalue: &PathValue) -> Result<()>;
+
+    /// As [PathValueSink::sort_keys]. Only the first sink passed to [jindex_parallel] is asked
+    fn sort_keys(&self) -> bool {
+        false
+    }
+}
+
+/// A [SequencedSink] that passes each node to a [PathValueSink] as it arrives,
+/// ignoring its sequence number, for sinks that do not depend on the order of nodes
+#[derive(Debug)]
+pub struct Unsequenced<S>(pub S);
+
+impl<S: PathValueSink> SequencedSink for Unsequenced<S> {
+    fn handle_sequenced(&mut self, _sequence: u64, pathvalue: &PathValue) -> Result<()> {
+        self.0.handle_pathvalue(pathvalue)
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.0.sort_keys()
+    }
+}
+
+/// A [SequencedSink] that keeps every node with its sequence number
+#[derive(Debug, Default)]
+pub struct SequencedRecords {
+    pub records: Vec<(u64, OwnedPathValue)>,
+}
+
+impl SequencedSink for SequencedRecords {
+    fn handle_sequenced(&mut self, sequence: u64, pathvalue: &PathValue) -> Result<()> {
+        self.records
+            .push((sequence, pathvalue.to_owned_pathvalue()));
+        Ok(())
+    }
+}
+
+/// Like [jindex_with_options](crate::jindex_with_options), but on a thread for each of `sinks`,
+/// each of which receives some of the nodes, tagged with their sequence numbers.
+///
+/// Each thread keeps a queue of tasks (subtrees to traverse).
+/// When a thread reaches an object or array of at least 4096 nodes,
+/// it queues a task for each of its children rather than traversing them itself,
+/// and a thread whose queue is empty steals the task nearest the root of the document
+/// from another thread's queue, so that work is shared however the document is shaped.
+/// A thread that finds nothing to steal sleeps until another queues tasks.
+///
+/// `options.order` is ignored, as the order in which nodes reach each sink
+/// depends on which threads steal which tasks; use the sequence numbers to reorder them.
+pub fn jindex_parallel<'pv, S: SequencedSink + Send>(
+    sinks: &mut [S],
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+) -> Result<()> {
+    let Some(first_sink) = sinks.first() else {
+        return Err(anyhow!("jindex_parallel requires at least one sink"));
+    };
+
+    let root = root_pathvalue(json, options)?;
+
+    let mut large_subtree_sizes = HashMap::new();
+    count_nodes(root.value, Some(&mut large_subtree_sizes));
+
+    let work = Work {
+        options,
+        sort_keys: first_sink.sort_keys(),
+        large_subtree_sizes,
+        queues: sinks.iter().map(|_| Mutex::new(VecDeque::new())).collect(),
+        pending: AtomicUsize::new(1),
+        failed: AtomicBool::new(false),
+        error: Mutex::new(None),
+        events: Mutex::new(0),
+        wakeup: Condvar::new(),
+    };
+
+    work.queues[0].lock().unwrap().push_back(Task {
+        pathvalue: root,
+        sequence: 0,
+    });
+
+    std::thread::scope(|scope| {
+        for (i, sink) in sinks.iter_mut().enumerate() {
+            let work = &work;
+            scope.spawn(move || work.run(i, sink));
+        }
+    });
+
+    match work.error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// A subtree to traverse, and the sequence number of its root
+struct Task<'pv> {
+    pathvalue: PathValue<'pv>,
+    sequence: u64,
+}
+
+/// A child of an object or array, and whether to visit it
+enum Child<'pv> {
+    Visit(PathValue<'pv>),
+    /// Excluded or unsampled, so only its sequence numbers are counted
+    Skip(&'pv serde_json::Value),
+}
+
+/// The state shared by the threads of [jindex_parallel]
+struct Work<'pv, 'options> {
+    options: &'options JindexOptions<'pv>,
+    sort_keys: bool,
+    /// The number of nodes in each object or array of at least [SPLIT_MIN_NODES],
+    /// by its address
+    large_subtree_sizes: HashMap<usize, u64>,
+    /// A queue of tasks for each thread, to which it pushes and from which it pops at the back,
+    /// and from which other threads steal at the front
+    queues: Vec<Mutex<VecDeque<Task<'pv>>>>,
+    /// The number of tasks that are queued or running
+    pending: AtomicUsize,
+    failed: AtomicBool,
+    /// The first error of any thread
+    error: Mutex<Option<anyhow::Error>>,
+    /// The number of times tasks have been queued, the last task has finished,
+    /// or a thread has failed, any of which wakes the threads waiting on `wakeup`
+    events: Mutex<u64>,
+    wakeup: Condvar,
+}
+
+impl<'pv> Work<'pv, '_> {
+    /// Run tasks on thread `me` until there are none left, or a thread has failed
+    fn run<S: SequencedSink>(&self, me: usize, sink: &mut S) {
+        while !self.failed.load(Ordering::Acquire) {
+            // before looking for a task, so that no event after looking is missed
+            let seen = *self.events.lock().unwrap();
+
+            match self.next_task(me) {
+                Some(task) => {
+                    if let Err(e) = self.run_task(me, task, sink) {
+                        self.error.lock().unwrap().get_or_insert(e);
+                        self.failed.store(true, Ordering::Release);
+                        self.notify();
+                    }
+                    if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                        self.notify();
+                    }
+                }
+                None if self.pending.load(Ordering::Acquire) == 0 => break,
+                None => {
+                    let events = self.events.lock().unwrap();
+                    let _events = self
+                        .wakeup
+                        .wait_while(events, |events| *events == seen)
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    /// Wake the threads waiting for tasks to look again
+    fn notify(&self) {
+        *self.events.lock().unwrap() += 1;
+        self.wakeup.notify_all();
+    }
+
+    /// The newest task in this thread's own queue, or else the oldest in another thread's,
+    /// which is one of the nearest the root, so the largest, that it has left
+    fn next_task(&self, me: usize) -> Option<Task<'pv>> {
+        if let Some(task) = self.queues[me].lock().unwrap().pop_back() {
+            return Some(task);
+        }
+
+        (1..self.queues.len())
+            .map(|offset| (me + offset) % self.queues.len())
+            .find_map(|victim| self.queues[victim].lock().unwrap().pop_front())
+    }
+
+    /// Traverse the subtree of `task`, queueing its large objects and arrays as new tasks
+    fn run_task<S: SequencedSink>(&self, me: usize, task: Task<'pv>, sink: &mut S) -> Result<()> {
+        let mut traversal_stack = vec![Child::Visit(task.pathvalue)];
+        let mut sequence = task.sequence;
+
+        while let Some(child) = traversal_stack.pop() {
+            let pathvalue = match child {
+                Child::Visit(pathvalue) => pathvalue,
+                Child::Skip(value) => {
+                    sequence += self.node_count(value);
+                    continue;
+                }
+            };
+
+            check_depth(&pathvalue.path_components, self.options)?;
+
+            sink.handle_sequenced(sequence, &pathvalue)?;
+
+            if let Some(size) = self.large_subtree_size(pathvalue.value) {
+                self.split(me, &pathvalue, sequence + 1);
+                sequence += size;
+            } else {
+                let start = traversal_stack.len();
+                self.for_each_child(&pathvalue, |child| traversal_stack.push(child));
+                traversal_stack[start..].reverse();
+                sequence += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queue a task for each visited child of `pathvalue`,
+    /// the first of which has sequence number `sequence`
+    fn split(&self, me: usize, pathvalue: &PathValue<'pv>, mut sequence: u64) {
+        let mut tasks = vec![];
+
+        self.for_each_child(pathvalue, |child| match child {
+            Child::Visit(pathvalue) => {
+                let size = self.node_count(pathvalue.value);
+                tasks.push(Task {
+                    pathvalue,
+                    sequence,
+                });
+                sequence += size;
+            }
+            Child::Skip(value) => sequence += self.node_count(value),
+        });
+
+        self.pending.fetch_add(tasks.len(), Ordering::AcqRel);
+        // reversed, so that this thread pops the first child first
+        self.queues[me]
+            .lock()
+            .unwrap()
+            .extend(tasks.into_iter().rev());
+        self.notify();
+    }
+
+    /// Pass each child of `pathvalue` to `f`, in order
+    fn for_each_child(&self, pathvalue: &PathValue<'pv>, mut f: impl FnMut(Child<'pv>)) {
+        let child = |component, value| {
+            let mut path_components = pathvalue.path_components.clone();
+            path_components.push(component);
+
+            if is_excluded(&path_components, self.options) {
+                Child::Skip(value)
+            } else {
+                Child::Visit(PathValue::new(value, path_components))
+            }
+        };
+
+        match pathvalue.value {
+            serde_json::Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                if self.sort_keys {
+                    entries.sort_by_key(|(k, _)| *k);
+                }
+
+                for (k, v) in entries {
+                    let component = PathComponent::key(k);
+                    f(child(component, v));
+                }
+            }
+            serde_json::Value::Array(array) => {
+                let mut sampled =
+                    sampled_indexes(self.options.array_sample, array.len()).peekable();
+
+                for (i, v) in array.iter().enumerate() {
+                    if sampled.next_if_eq(&i).is_some() {
+                        f(child(PathComponent::Index(i), v));
+                    } else {
+                        f(Child::Skip(v));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn large_subtree_size(&self, value: &serde_json::Value) -> Option<u64> {
+        self.large_subtree_sizes
+            .get(&(value as *const _ as usize))
+            .copied()
+    }
+
+    fn node_count(&self, value: &serde_json::Value) -> u64 {
+        self.large_subtree_size(value)
+            .unwrap_or_else(|| count_nodes(value, None))
+    }
+}
+
+/// The number of nodes in `value`, counting itself,
+/// recording the sizes of large objects and arrays in `large_subtree_sizes`.
+/// Counts with a stack rather than recursion, so that it handles documents of any depth
+fn count_nodes(
+    value: &serde_json::Value,
+    mut large_subtree_sizes: Option<&mut HashMap<usize, u64>>,
+) -> u64 {
+    // the objects and arrays whose children are being counted, with their counts so far
+    let mut open: Vec<(&serde_json::Value, u64)> = vec![];
+    // the values to count, and `None` after the children of each open object or array
+    let mut stack = vec![Some(value)];
+    let mut total = 0;
+
+    while let Some(next) = stack.pop() {
+        let size = match next {
+            Some(value) => {
+                let start = stack.len();
+
+                match value {
+                    serde_json::Value::Object(object) => stack.extend(object.values().map(Some)),
+                    serde_json::Value::Array(array) => stack.extend(array.iter().map(Some)),
+                    _ => (),
+                }
+
+                if stack.len() > start {
+                    stack.insert(start, None);
+                    open.push((value, 1));
+                    continue;
+                }
+
+                1
+            }
+            None => {
+                let (value, size) = open.pop().expect("one open value for each `None`");
+
+                if size >= SPLIT_MIN_NODES {
+                    if let Some(large_subtree_sizes) = large_subtree_sizes.as_deref_mut() {
+                        large_subtree_sizes.insert(value as *const _ as usize, size);
+                    }
+                }
+
+                size
+            }
+        };
+
+        match open.last_mut() {
+            Some((_, parent_size)) => *parent_size += size,
+            None => total += size,
+        }
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_pattern::PathPattern;
+    use crate::{jindex_with_options, ArraySample, TraversalOrder};
+
+    struct Collect(Vec<OwnedPathValue>);
+
+    impl PathValueSink for Collect {
+        fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+            self.0.push(pathvalue.to_owned_pathvalue());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn skewed_document() {
+        let big: Vec<_> = (0..3000)
+            .map(|i| serde_json::json!({"id": i, "tags": ["a", "b"], "skip": {"x": i}}))
+            .collect();
+        let v = serde_json::json!({"a": 1, "big": big, "z": {"c": [true, null]}});
+
+        for options in [
+            JindexOptions::default(),
+            JindexOptions {
+                exclude: vec![PathPattern::glob("big.*.skip")],
+                array_sample: Some(ArraySample::Stride(3)),
+                ..Default::default()
+            },
+        ] {
+            let mut sinks: Vec<SequencedRecords> = (0..4).map(|_| Default::default()).collect();
+            jindex_parallel(&mut sinks, &v, &options).unwrap();
+
+            let mut records: Vec<_> = sinks.into_iter().flat_map(|sink| sink.records).collect();
+            records.sort_by_key(|(sequence, _)| *sequence);
+
+            let mut sequential = Collect(vec![]);
+            jindex_with_options(
+                &mut sequential,
+                &v,
+                &JindexOptions {
+                    order: TraversalOrder::Document,
+                    ..options
+                },
+            )
+            .unwrap();
+
+            assert!(records.windows(2).all(|w| w[0].0 < w[1].0));
+            assert_eq!(
+                records.into_iter().map(|(_, r)| r).collect::<Vec<_>>(),
+                sequential.0
+            );
+        }
+    }
+
+    #[test]
+    fn deep_document() {
+        let mut v = serde_json::json!(1);
+        for _ in 0..100_000 {
+            v = serde_json::Value::Array(vec![v]);
+        }
+
+        let mut large_subtree_sizes = HashMap::new();
+        assert_eq!(count_nodes(&v, Some(&mut large_subtree_sizes)), 100_001);
+        assert_eq!(
+            large_subtree_sizes.get(&(&v as *const _ as usize)),
+            Some(&100_001)
+        );
+        assert_eq!(large_subtree_sizes.len(), 100_001 - 4096 + 1);
+
+        // dropping a value this deep would overflow the stack
+        std::mem::forget(v);
+    }
+}
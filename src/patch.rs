@@ -0,0 +1,318 @@
+//! Applying JSON Patches (RFC 6902) and JSON Merge Patches (RFC 7386)
+
+use crate::path_format::{escape_json_pointer_token, json_pointer_tokens, parse_array_index};
+use anyhow::{anyhow, Result};
+
+/// Apply a JSON Patch (https://datatracker.ietf.org/doc/html/rfc6902) to `document`.
+///
+/// The patch is applied atomically: if any operation fails,
+/// `document` is left unchanged and the error names the failing operation.
+pub fn apply_json_patch(document: &mut serde_json::Value, patch: &serde_json::Value) -> Result<()> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| anyhow!("a JSON Patch must be an array of operations"))?;
+
+    let mut patched = document.clone();
+
+    for (i, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation)
+            .map_err(|e| anyhow!("JSON Patch operation {} failed: {}", i, e))?;
+    }
+
+    *document = patched;
+
+    Ok(())
+}
+
+fn apply_operation(document: &mut serde_json::Value, operation: &serde_json::Value) -> Result<()> {
+    let member = |name: &str| {
+        operation
+            .get(name)
+            .ok_or_else(|| anyhow!("missing {:?} member", name))
+    };
+
+    let string_member = |name: &str| -> Result<&str> {
+        member(name)?
+            .as_str()
+            .ok_or_else(|| anyhow!("{:?} member must be a string", name))
+    };
+
+    let path = string_member("path")?;
+
+    match string_member("op")? {
+        "add" => add(document, path, member("value")?.clone()),
+        "remove" => remove(document, path).map(|_| ()),
+        "replace" => {
+            *get_mut(document, path)? = member("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = string_member("from")?;
+
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(anyhow!(
+                    "cannot move {:?} into its own child {:?}",
+                    from,
+                    path
+                ));
+            }
+
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        "copy" => {
+            let value = get_mut(document, string_member("from")?)?.clone();
+            add(document, path, value)
+        }
+        "test" => {
+            if *get_mut(document, path)? == *member("value")? {
+                Ok(())
+            } else {
+                Err(anyhow!("test failed at {:?}", path))
+            }
+        }
+        op => Err(anyhow!("unknown op {:?}", op)),
+    }
+}
+
+/// Split `pointer` into the pointer to its parent and its last token
+fn split_last(pointer: &str) -> Result<Option<(Vec<String>, String)>> {
+    let mut tokens = json_pointer_tokens(pointer)?;
+    Ok(tokens.pop().map(|last| (tokens, last)))
+}
+
+fn get_tokens_mut<'v>(
+    document: &'v mut serde_json::Value,
+    tokens: &[String],
+) -> Result<&'v mut serde_json::Value> {
+    tokens.iter().try_fold(document, |node, token| match node {
+        serde_json::Value::Object(object) => object
+            .get_mut(token)
+            .ok_or_else(|| anyhow!("no key {:?}", token)),
+        serde_json::Value::Array(array) => parse_array_index(token)
+            .and_then(|i| array.get_mut(i))
+            .ok_or_else(|| anyhow!("no index {:?}", token)),
+        _ => Err(anyhow!("cannot index into a scalar with {:?}", token)),
+    })
+}
+
+fn get_mut<'v>(
+    document: &'v mut serde_json::Value,
+    pointer: &str,
+) -> Result<&'v mut serde_json::Value> {
+    get_tokens_mut(document, &json_pointer_tokens(pointer)?)
+}
+
+fn add(document: &mut serde_json::Value, pointer: &str, value: serde_json::Value) -> Result<()> {
+    let Some((parent, last)) = split_last(pointer)? else {
+        *document = value;
+        return Ok(());
+    };
+
+    match get_tokens_mut(document, &parent)? {
+        serde_json::Value::Object(object) => {
+            object.insert(last, value);
+            Ok(())
+        }
+        serde_json::Value::Array(array) => {
+            if last == "-" {
+                array.push(value);
+                return Ok(());
+            }
+
+            match parse_array_index(&last) {
+                Some(i) if i <= array.len() => {
+                    array.insert(i, value);
+                    Ok(())
+                }
+                _ => Err(anyhow!("cannot add at index {:?}", last)),
+            }
+        }
+        _ => Err(anyhow!("cannot add to a scalar at {:?}", pointer)),
+    }
+}
+
+fn remove(document: &mut serde_json::Value, pointer: &str) -> Result<serde_json::Value> {
+    let Some((parent, last)) = split_last(pointer)? else {
+        return Err(anyhow!("cannot remove the whole document"));
+    };
+
+    match get_tokens_mut(document, &parent)? {
+        serde_json::Value::Object(object) => object
+            .remove(&last)
+            .ok_or_else(|| anyhow!("no key {:?}", last)),
+        serde_json::Value::Array(array) => match parse_array_index(&last) {
+            Some(i) if i < array.len() => Ok(array.remove(i)),
+            _ => Err(anyhow!("no index {:?}", last)),
+        },
+        _ => Err(anyhow!("cannot remove from a scalar at {:?}", pointer)),
+    }
+}
+
+/// Parse a mapping file of `from-pointer -> to-pointer` lines, for [rename].
+/// Blank lines and lines starting with `#` are ignored.
+pub fn parse_path_mappings(text: &str) -> Result<Vec<(String, String)>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (from, to) = line
+                .split_once("->")
+                .ok_or_else(|| anyhow!("expected `from -> to` in mapping: {:?}", line))?;
+
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Move the value at each `from` pointer in `mappings` to its `to` pointer, in order,
+/// creating any missing objects along the way to `to`.
+/// Mappings whose `from` does not exist in `document` are skipped.
+///
+/// Returns the JSON Patch of the changes made, which applied to the original `document`
+/// gives the same result.
+pub fn rename(
+    document: &mut serde_json::Value,
+    mappings: &[(String, String)],
+) -> Result<serde_json::Value> {
+    let mut operations = vec![];
+
+    let mut apply = |document: &mut serde_json::Value, operation: serde_json::Value| {
+        apply_operation(document, &operation)?;
+        operations.push(operation);
+        Ok::<(), anyhow::Error>(())
+    };
+
+    for (from, to) in mappings {
+        if document.pointer(from).is_none() {
+            continue;
+        }
+
+        let mut ancestor = String::new();
+        let to_tokens = json_pointer_tokens(to)?;
+
+        for token in to_tokens.iter().take(to_tokens.len().saturating_sub(1)) {
+            ancestor.push('/');
+            ancestor.push_str(&escape_json_pointer_token(token));
+
+            if document.pointer(&ancestor).is_none() {
+                apply(
+                    document,
+                    serde_json::json!({"op": "add", "path": ancestor, "value": {}}),
+                )?;
+            }
+        }
+
+        apply(
+            document,
+            serde_json::json!({"op": "move", "from": from, "path": to}),
+        )
+        .map_err(|e| anyhow!("could not move {:?} to {:?}: {}", from, to, e))?;
+    }
+
+    Ok(serde_json::Value::Array(operations))
+}
+
+/// Apply a JSON Merge Patch (https://datatracker.ietf.org/doc/html/rfc7386) to `document`:
+/// objects are merged recursively, `null` removes a key, and anything else replaces
+pub fn apply_merge_patch(document: &mut serde_json::Value, patch: serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *document = patch;
+        return;
+    };
+
+    if !document.is_object() {
+        *document = serde_json::Value::Object(serde_json::Map::new());
+    }
+
+    if let serde_json::Value::Object(object) = document {
+        for (k, v) in patch {
+            if v.is_null() {
+                object.remove(&k);
+            } else {
+                apply_merge_patch(object.entry(k).or_insert(serde_json::Value::Null), v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_patch() {
+        let mut document = serde_json::json!({"a": {"b": [1, 2]}, "c": "d"});
+
+        apply_json_patch(
+            &mut document,
+            &serde_json::json!([
+                {"op": "add", "path": "/a/b/1", "value": 9},
+                {"op": "add", "path": "/a/b/-", "value": 3},
+                {"op": "remove", "path": "/c"},
+                {"op": "copy", "from": "/a/b/0", "path": "/e"},
+                {"op": "move", "from": "/e", "path": "/f~1g"},
+                {"op": "replace", "path": "/a/b/0", "value": 0},
+                {"op": "test", "path": "/a/b", "value": [0, 9, 2, 3]},
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            document,
+            serde_json::json!({"a": {"b": [0, 9, 2, 3]}, "f/g": 1})
+        );
+
+        let error = apply_json_patch(
+            &mut document,
+            &serde_json::json!([
+                {"op": "remove", "path": "/f~1g"},
+                {"op": "test", "path": "/a/b/0", "value": 1},
+            ]),
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("operation 1"));
+        // the failed patch was not partially applied
+        assert_eq!(document["f/g"], 1);
+    }
+
+    #[test]
+    fn rename_paths() {
+        let mappings = parse_path_mappings(
+            "# migrate to v2\n/user/name -> /profile/full name\n\n/missing -> /x\n/id->/user/id\n",
+        )
+        .unwrap();
+
+        let original = serde_json::json!({"user": {"name": "a"}, "id": 1});
+        let mut document = original.clone();
+
+        let patch = rename(&mut document, &mappings).unwrap();
+
+        assert_eq!(
+            document,
+            serde_json::json!({"user": {"id": 1}, "profile": {"full name": "a"}})
+        );
+
+        let mut patched = original;
+        apply_json_patch(&mut patched, &patch).unwrap();
+        assert_eq!(patched, document);
+
+        assert!(parse_path_mappings("/a /b").is_err());
+    }
+
+    #[test]
+    fn merge_patch() {
+        let mut document = serde_json::json!({"a": "b", "c": {"d": "e", "f": "g"}});
+
+        apply_merge_patch(
+            &mut document,
+            serde_json::json!({"a": "z", "c": {"f": null}, "h": [1]}),
+        );
+
+        assert_eq!(
+            document,
+            serde_json::json!({"a": "z", "c": {"d": "e"}, "h": [1]})
+        );
+    }
+}
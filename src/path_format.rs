@@ -0,0 +1,911 @@
+//! Rendering paths as text: JSON Pointers, gron paths, JSONPath expressions, and XPaths.
+//!
+//! The builtin writers use these functions, and custom sinks can too,
+//! so that every sink escapes paths the same way.
+
+use crate::PathComponent;
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::{anyhow, Result};
+use core::fmt::Write as _;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+const TILDE: char = '~';
+const FORWARD_SLASH: char = '/';
+const JSON_POINTER_SPECIAL_CHARS: &[char] = &[TILDE, FORWARD_SLASH];
+
+/// Whether `s` can be written as a bare key in a gron path, like `json.s`,
+/// rather than quoted, like `json["s"]`
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars.next().is_some_and(unicode_ident::is_xid_start)
+        && chars.all(unicode_ident::is_xid_continue)
+}
+
+/// Whether `s` is an identifier by JavaScript's rules, restricted to ASCII:
+/// letters, digits, `_`, and `$`, not starting with a digit
+pub fn is_ascii_js_identifier(s: &str) -> bool {
+    let mut bytes = s.bytes();
+
+    bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_' || b == b'$')
+        && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$')
+}
+
+/// Which object keys a gron path writes as bare identifiers, like `json.key`,
+/// rather than in brackets, like `json["key"]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdentifierPolicy {
+    /// Unicode identifiers (see [is_identifier]), like `json.café`
+    #[default]
+    UnicodeXid,
+    /// ASCII JavaScript identifiers (see [is_ascii_js_identifier]), like `json.$ref`,
+    /// for consumers that only handle ASCII dot-paths
+    AsciiJs,
+    /// No keys: every key is written in brackets
+    AlwaysBracket,
+}
+
+/// How gron output quotes strings, both the keys of paths written in brackets and string values.
+///
+/// Every quoting escapes keys in brackets with [write_js_string];
+/// with the default (JSON) quoting, a key in brackets is a JSON string, like `["a\"b"]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GronQuoting {
+    /// Quote strings with `'` rather than `"`, like JavaScript tooling that prefers them
+    pub single_quotes: bool,
+    /// Escape every non-ASCII character as `\uXXXX` (as a surrogate pair beyond the BMP),
+    /// rather than writing it as UTF-8
+    pub ascii_only: bool,
+}
+
+/// Write `s` as a JavaScript string literal quoted according to `quoting`,
+/// like `'it\'s'` with single quotes
+#[cfg(feature = "std")]
+pub fn write_js_string<W: Write + ?Sized>(
+    writer: &mut W,
+    s: &str,
+    quoting: GronQuoting,
+) -> Result<()> {
+    let mut buf = String::with_capacity(s.len() + 2);
+    push_js_string(&mut buf, s, quoting);
+    writer.write_all(buf.as_bytes())?;
+
+    Ok(())
+}
+
+/// Append `s` to `buf` as a JavaScript string literal quoted according to `quoting`;
+/// see [write_js_string]
+pub fn push_js_string(buf: &mut String, s: &str, quoting: GronQuoting) {
+    let quote = if quoting.single_quotes { '\'' } else { '"' };
+
+    buf.push(quote);
+
+    for c in s.chars() {
+        match c {
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\u{8}' => buf.push_str("\\b"),
+            '\u{c}' => buf.push_str("\\f"),
+            c if c == quote => {
+                buf.push('\\');
+                buf.push(c);
+            }
+            c if c.is_ascii_control() || (quoting.ascii_only && !c.is_ascii()) => {
+                let mut utf16 = [0; 2];
+                for unit in c.encode_utf16(&mut utf16) {
+                    // writing to a `String` cannot fail
+                    write!(buf, "\\u{:04x}", unit).unwrap();
+                }
+            }
+            c => buf.push(c),
+        }
+    }
+
+    buf.push(quote);
+}
+
+/// Write `value` as compact JSON, like `serde_json::to_writer`,
+/// but with its strings (and keys) quoted according to `quoting`
+#[cfg(feature = "std")]
+pub fn write_js_value<W: Write + ?Sized>(
+    writer: &mut W,
+    value: &serde_json::Value,
+    quoting: GronQuoting,
+) -> Result<()> {
+    match value {
+        serde_json::Value::String(s) => write_js_string(writer, s, quoting)?,
+        serde_json::Value::Array(a) => {
+            writer.write_all(b"[")?;
+
+            for (i, element) in a.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_js_value(writer, element, quoting)?;
+            }
+
+            writer.write_all(b"]")?;
+        }
+        serde_json::Value::Object(o) => {
+            writer.write_all(b"{")?;
+
+            for (i, (k, v)) in o.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_js_string(writer, k, quoting)?;
+                writer.write_all(b":")?;
+                write_js_value(writer, v, quoting)?;
+            }
+
+            writer.write_all(b"}")?;
+        }
+        scalar => serde_json::to_writer(writer, scalar)?,
+    }
+
+    Ok(())
+}
+
+/// Escape `~` as `~0` and `/` as `~1` in a JSON Pointer reference token, as required by
+/// https://datatracker.ietf.org/doc/html/rfc6901#section-3
+pub fn escape_json_pointer_token(s: &str) -> Cow<'_, str> {
+    // this conditional exists because `replace` allocates even
+    // if it doesn't find any matches, and I've benchmarked this conditional
+    // as increasing throughput by ~30-50%.
+    if s.contains(JSON_POINTER_SPECIAL_CHARS) {
+        Cow::Owned(s.replace(TILDE, "~0").replace(FORWARD_SLASH, "~1"))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// Write the array index `i` to `writer` in decimal
+#[inline]
+#[cfg(feature = "std")]
+pub fn write_index<W: Write + ?Sized>(writer: &mut W, i: usize) -> Result<()> {
+    let mut buf = itoa::Buffer::new();
+    writer.write_all(buf.format(i).as_bytes())?;
+    Ok(())
+}
+
+/// Write the array index `i` to `writer` in decimal,
+/// padded with leading zeros to at least `width` digits, like `00042`,
+/// so that padded indexes sort lexicographically in numeric order
+#[inline]
+#[cfg(feature = "std")]
+pub fn write_padded_index<W: Write + ?Sized>(writer: &mut W, i: usize, width: usize) -> Result<()> {
+    let mut buf = itoa::Buffer::new();
+    let digits = buf.format(i);
+
+    for _ in digits.len()..width {
+        writer.write_all(b"0")?;
+    }
+
+    writer.write_all(digits.as_bytes())?;
+    Ok(())
+}
+
+/// How [write_gron_path_with_style] writes keys and indexes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GronPathStyle {
+    /// Which keys are written bare, rather than in brackets
+    pub identifier_policy: IdentifierPolicy,
+    /// How keys in brackets are quoted
+    pub quoting: GronQuoting,
+    /// Pad array indexes with leading zeros to at least this many digits; see [write_padded_index]
+    pub index_width: usize,
+}
+
+/// Write the given path components to `writer` as a gron path starting with `prefix`,
+/// like `json.items[0]["content-type"]`
+#[cfg(feature = "std")]
+pub fn write_gron_path<W: Write + ?Sized>(
+    writer: &mut W,
+    prefix: &str,
+    path_components: &[PathComponent],
+) -> Result<()> {
+    write_gron_path_with_policy(writer, prefix, path_components, IdentifierPolicy::default())
+}
+
+/// Like [write_gron_path], with `identifier_policy` choosing which keys are written bare
+#[cfg(feature = "std")]
+pub fn write_gron_path_with_policy<W: Write + ?Sized>(
+    writer: &mut W,
+    prefix: &str,
+    path_components: &[PathComponent],
+    identifier_policy: IdentifierPolicy,
+) -> Result<()> {
+    write_gron_path_with_style(
+        writer,
+        prefix,
+        path_components,
+        GronPathStyle {
+            identifier_policy,
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [write_gron_path], with `style` choosing how keys and indexes are written
+#[cfg(feature = "std")]
+pub fn write_gron_path_with_style<W: Write + ?Sized>(
+    writer: &mut W,
+    prefix: &str,
+    path_components: &[PathComponent],
+    style: GronPathStyle,
+) -> Result<()> {
+    writer.write_all(prefix.as_bytes())?;
+
+    for path_component in path_components {
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+                let bare = match style.identifier_policy {
+                    IdentifierPolicy::UnicodeXid => {
+                        matches!(path_component, PathComponent::Identifier(_))
+                    }
+                    IdentifierPolicy::AsciiJs => is_ascii_js_identifier(s),
+                    IdentifierPolicy::AlwaysBracket => false,
+                };
+
+                if bare {
+                    writer.write_all(b".")?;
+                    writer.write_all(s.as_bytes())?;
+                } else {
+                    writer.write_all(b"[")?;
+                    write_js_string(writer, s, style.quoting)?;
+                    writer.write_all(b"]")?;
+                }
+            }
+            PathComponent::Index(i) => {
+                writer.write_all(b"[")?;
+                write_padded_index(writer, *i, style.index_width)?;
+                writer.write_all(b"]")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The gron path of the given path components starting with `prefix`,
+/// with every array index written as `[*]`, like `json.items[*].id`,
+/// so that the paths of the corresponding values in each element of an array are the same
+pub fn gron_shape(prefix: &str, path_components: &[PathComponent]) -> String {
+    let mut shape = String::from(prefix);
+
+    for path_component in path_components {
+        match path_component {
+            PathComponent::Identifier(s) => {
+                shape.push('.');
+                shape.push_str(s);
+            }
+            PathComponent::NonIdentifier(s) => {
+                shape.push('[');
+                push_js_string(&mut shape, s, GronQuoting::default());
+                shape.push(']');
+            }
+            PathComponent::Index(_) => shape.push_str("[*]"),
+        }
+    }
+
+    shape
+}
+
+/// Write the given path components to `writer` as a JSON Pointer,
+/// escaping `~` and `/` as required by
+/// https://datatracker.ietf.org/doc/html/rfc6901#section-3
+#[cfg(feature = "std")]
+pub fn write_json_pointer<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+) -> Result<()> {
+    write_json_pointer_with_index_width(writer, path_components, 0)
+}
+
+/// Like [write_json_pointer], with array indexes padded with leading zeros
+/// to at least `index_width` digits; see [write_padded_index].
+/// RFC 6901 does not allow leading zeros in indexes,
+/// so padded pointers are for sorting and display, not for resolving
+#[cfg(feature = "std")]
+pub fn write_json_pointer_with_index_width<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+    index_width: usize,
+) -> Result<()> {
+    for path_component in path_components {
+        writer.write_all(b"/")?;
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+                writer.write_all(escape_json_pointer_token(s).as_bytes())?
+            }
+            PathComponent::Index(i) => write_padded_index(writer, *i, index_width)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the given path components to `writer` as a JSON Pointer
+/// in URI fragment identifier representation, percent-encoding
+/// every byte that is not allowed in a URI fragment.
+/// See https://datatracker.ietf.org/doc/html/rfc6901#section-6
+#[cfg(feature = "std")]
+pub fn write_uri_fragment_json_pointer<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+) -> Result<()> {
+    write_uri_fragment_json_pointer_with_index_width(writer, path_components, 0)
+}
+
+/// Like [write_uri_fragment_json_pointer], with padded array indexes;
+/// see [write_json_pointer_with_index_width]
+#[cfg(feature = "std")]
+pub fn write_uri_fragment_json_pointer_with_index_width<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+    index_width: usize,
+) -> Result<()> {
+    let mut pointer = Vec::new();
+    write_json_pointer_with_index_width(&mut pointer, path_components, index_width)?;
+
+    writer.write_all(b"#")?;
+
+    for byte in pointer {
+        if is_uri_fragment_byte(byte) {
+            writer.write_all(&[byte])?;
+        } else {
+            write!(writer, "%{:02X}", byte)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `byte` can appear unencoded in a URI fragment.
+/// See https://datatracker.ietf.org/doc/html/rfc3986#section-3.5
+pub fn is_uri_fragment_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'!'
+                | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+                | b'/'
+                | b'?'
+        )
+}
+
+/// Write the given path components to `writer` as a JSONPath expression,
+/// like `$.items[0]['content-type']`
+#[cfg(feature = "std")]
+pub fn write_jsonpath<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+) -> Result<()> {
+    writer.write_all(b"$")?;
+
+    for path_component in path_components {
+        match path_component {
+            PathComponent::Identifier(s) => {
+                writer.write_all(b".")?;
+                writer.write_all(s.as_bytes())?;
+            }
+            PathComponent::NonIdentifier(s) => {
+                writer.write_all(b"['")?;
+                for c in s.chars() {
+                    if c == '\'' || c == '\\' {
+                        writer.write_all(b"\\")?;
+                    }
+                    let mut buf = [0; 4];
+                    writer.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+                }
+                writer.write_all(b"']")?;
+            }
+            PathComponent::Index(i) => {
+                writer.write_all(b"[")?;
+                write_index(writer, *i)?;
+                writer.write_all(b"]")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the given path components to `writer` as an XPath,
+/// like `/root/item[1]/@id`, for documents converted from XML
+/// with the usual conventions: attributes are keys starting with `@`,
+/// text content is the key `#text`, and repeated elements are arrays.
+///
+/// Indexes are 1-based, as in XPath. Keys that are not XML names
+/// are matched by name, like `/*[name()='content type']`,
+/// and indexes that do not follow an element are written as `/*[1]`.
+#[cfg(feature = "std")]
+pub fn write_xpath<W: Write + ?Sized>(
+    writer: &mut W,
+    path_components: &[PathComponent],
+) -> Result<()> {
+    if path_components.is_empty() {
+        writer.write_all(b"/")?;
+        return Ok(());
+    }
+
+    let mut after_element = false;
+
+    for path_component in path_components {
+        match path_component {
+            PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+                if *s == "#text" {
+                    writer.write_all(b"/text()")?;
+                    after_element = false;
+                } else if let Some(attribute) = s.strip_prefix('@').filter(|a| is_xml_name(a)) {
+                    writer.write_all(b"/@")?;
+                    writer.write_all(attribute.as_bytes())?;
+                    after_element = false;
+                } else if is_xml_name(s) {
+                    writer.write_all(b"/")?;
+                    writer.write_all(s.as_bytes())?;
+                    after_element = true;
+                } else {
+                    writer.write_all(b"/*[name()=")?;
+                    write_xpath_literal(writer, s)?;
+                    writer.write_all(b"]")?;
+                    after_element = false;
+                }
+            }
+            PathComponent::Index(i) => {
+                if !after_element {
+                    writer.write_all(b"/*")?;
+                }
+                writer.write_all(b"[")?;
+                write_index(writer, i + 1)?;
+                writer.write_all(b"]")?;
+                after_element = false;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `s` is an XML name (simplified to Unicode letters and digits),
+/// which can be written as an XPath step, like `/s`
+pub fn is_xml_name(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// Write `s` as an XPath 1.0 string literal, which has no escapes,
+/// so a string with both kinds of quote is written with `concat`
+#[cfg(feature = "std")]
+fn write_xpath_literal<W: Write + ?Sized>(writer: &mut W, s: &str) -> Result<()> {
+    if !s.contains('\'') {
+        writer.write_all(b"'")?;
+        writer.write_all(s.as_bytes())?;
+        writer.write_all(b"'")?;
+    } else if !s.contains('"') {
+        writer.write_all(b"\"")?;
+        writer.write_all(s.as_bytes())?;
+        writer.write_all(b"\"")?;
+    } else {
+        writer.write_all(b"concat(")?;
+        for (i, part) in s.split('\'').enumerate() {
+            if i > 0 {
+                writer.write_all(b", \"'\", ")?;
+            }
+            writer.write_all(b"'")?;
+            writer.write_all(part.as_bytes())?;
+            writer.write_all(b"'")?;
+        }
+        writer.write_all(b")")?;
+    }
+
+    Ok(())
+}
+
+/// Parse the gron path at the start of `line`, like `json.items[0]["content-type"]`,
+/// which must start with `prefix` (like `json`),
+/// returning its path components and the rest of `line` after the path
+pub fn parse_gron_path<'line>(
+    line: &'line str,
+    prefix: &str,
+) -> Result<(Vec<PathComponent<'static>>, &'line str)> {
+    let mut rest = line
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("gron path does not start with {:?}: {:?}", prefix, line))?;
+
+    let mut path_components = vec![];
+
+    loop {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot
+                .char_indices()
+                .find(|(_, c)| !unicode_ident::is_xid_continue(*c) && *c != '$')
+                .map_or(after_dot.len(), |(i, _)| i);
+
+            let key = &after_dot[..end];
+
+            if is_identifier(key) {
+                path_components.push(PathComponent::Identifier(key.to_string().into()));
+            } else if is_ascii_js_identifier(key) {
+                path_components.push(PathComponent::NonIdentifier(key.to_string().into()));
+            } else {
+                return Err(anyhow!("invalid identifier in gron path: {:?}", line));
+            }
+
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix("[\"") {
+            let mut escaped = false;
+
+            let end = after_bracket
+                .bytes()
+                .position(|b| {
+                    let is_end = b == b'"' && !escaped;
+                    escaped = b == b'\\' && !escaped;
+                    is_end
+                })
+                .ok_or_else(|| anyhow!("unterminated key in gron path: {:?}", line))?;
+
+            // include the opening and closing quotes
+            let key: String =
+                serde_json::from_str(&rest[1..end + 3]).map_err(anyhow::Error::msg)?;
+
+            path_components.push(PathComponent::key(key));
+
+            rest = after_bracket[end + 1..]
+                .strip_prefix(']')
+                .ok_or_else(|| anyhow!("expected ] in gron path: {:?}", line))?;
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket
+                .find(']')
+                .ok_or_else(|| anyhow!("expected ] in gron path: {:?}", line))?;
+
+            let index = after_bracket[..end]
+                .parse()
+                .map_err(|_| anyhow!("invalid index in gron path: {:?}", line))?;
+
+            path_components.push(PathComponent::Index(index));
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Ok((path_components, rest));
+        }
+    }
+}
+
+/// Parse a JSON Pointer, like `/items/0/content-type`, unescaping `~1` and `~0`.
+///
+/// JSON Pointers do not distinguish array indexes from object keys,
+/// so every token that is a decimal number without leading zeros
+/// is taken to be an array index.
+pub fn parse_json_pointer(pointer: &str) -> Result<Vec<PathComponent<'static>>> {
+    Ok(json_pointer_tokens(pointer)?
+        .into_iter()
+        .map(|token| {
+            if let Some(i) = parse_array_index(&token) {
+                PathComponent::Index(i)
+            } else {
+                PathComponent::key(token)
+            }
+        })
+        .collect())
+}
+
+/// Split a JSON Pointer into its reference tokens, unescaping `~1` and `~0`
+pub fn json_pointer_tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let tokens = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| anyhow!("JSON Pointer does not start with /: {:?}", pointer))?;
+
+    tokens
+        .split('/')
+        .map(|token| {
+            if token
+                .match_indices('~')
+                .any(|(i, _)| !matches!(token.as_bytes().get(i + 1), Some(b'0' | b'1')))
+            {
+                return Err(anyhow!("invalid escape in JSON Pointer: {:?}", pointer));
+            }
+
+            Ok(token.replace("~1", "/").replace("~0", "~"))
+        })
+        .collect()
+}
+
+/// Parse a JSON Pointer reference token as an array index,
+/// which must be a decimal number without leading zeros
+pub fn parse_array_index(token: &str) -> Option<usize> {
+    let is_index = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+
+    if is_index {
+        token.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(
+        f: fn(&mut Vec<u8>, &[PathComponent]) -> Result<()>,
+        path_components: &[PathComponent],
+    ) -> String {
+        let mut out = Vec::new();
+        f(&mut out, path_components).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn identifiers() {
+        assert!(is_identifier("abc"));
+        assert!(is_identifier("café"));
+        assert!(!is_identifier("1abc"));
+        assert!(!is_identifier("content-type"));
+        assert!(!is_identifier(""));
+    }
+
+    #[test]
+    fn json_pointer_tokens() {
+        assert!(matches!(
+            escape_json_pointer_token("abc"),
+            Cow::Borrowed("abc")
+        ));
+        assert_eq!(escape_json_pointer_token("a/b~c"), "a~1b~0c");
+        assert_eq!(escape_json_pointer_token("~1"), "~01");
+    }
+
+    #[test]
+    fn parse_paths() {
+        let (path_components, rest) =
+            parse_gron_path(r#"json.a["b \"c\"]"][10]["é"] = 1;"#, "json").unwrap();
+
+        assert_eq!(
+            path_components,
+            vec![
+                PathComponent::Identifier("a".into()),
+                PathComponent::NonIdentifier("b \"c\"]".into()),
+                PathComponent::Index(10),
+                PathComponent::Identifier("é".into()),
+            ]
+        );
+        assert_eq!(rest, " = 1;");
+
+        assert!(parse_gron_path("jsn.a", "json").is_err());
+        assert!(parse_gron_path("json[\"a", "json").is_err());
+
+        assert_eq!(
+            parse_json_pointer("/a~1b/0/01/~0").unwrap(),
+            vec![
+                PathComponent::NonIdentifier("a/b".into()),
+                PathComponent::Index(0),
+                PathComponent::NonIdentifier("01".into()),
+                PathComponent::NonIdentifier("~".into()),
+            ]
+        );
+        assert_eq!(parse_json_pointer("").unwrap(), vec![]);
+        assert!(parse_json_pointer("a").is_err());
+        assert!(parse_json_pointer("/a~2").is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let path_components = [
+            PathComponent::Identifier("a".into()),
+            PathComponent::NonIdentifier("b/c 'd'".into()),
+            PathComponent::Index(10),
+        ];
+
+        assert_eq!(
+            render(write_json_pointer, &path_components),
+            "/a/b~1c 'd'/10"
+        );
+        assert_eq!(
+            render(write_uri_fragment_json_pointer, &path_components),
+            "#/a/b~1c%20'd'/10"
+        );
+        assert_eq!(
+            render(write_jsonpath, &path_components),
+            r"$.a['b/c \'d\''][10]"
+        );
+
+        let mut gron = Vec::new();
+        write_gron_path(&mut gron, "json", &path_components).unwrap();
+        assert_eq!(String::from_utf8(gron).unwrap(), r#"json.a["b/c 'd'"][10]"#);
+    }
+
+    #[test]
+    fn xpaths() {
+        let path_components = [
+            PathComponent::Identifier("root".into()),
+            PathComponent::Identifier("item".into()),
+            PathComponent::Index(0),
+            PathComponent::NonIdentifier("@id".into()),
+        ];
+
+        assert_eq!(render(write_xpath, &path_components), "/root/item[1]/@id");
+
+        let path_components = [
+            PathComponent::Index(2),
+            PathComponent::NonIdentifier("#text".into()),
+        ];
+
+        assert_eq!(render(write_xpath, &path_components), "/*[3]/text()");
+
+        let path_components = [
+            PathComponent::NonIdentifier("a b".into()),
+            PathComponent::NonIdentifier("it's".into()),
+            PathComponent::NonIdentifier(r#"'"'"#.into()),
+        ];
+
+        assert_eq!(
+            render(write_xpath, &path_components),
+            r#"/*[name()='a b']/*[name()="it's"]/*[name()=concat('', "'", '"', "'", '')]"#
+        );
+
+        assert_eq!(render(write_xpath, &[]), "/");
+    }
+
+    #[test]
+    fn padded_indexes() {
+        let path_components = [
+            PathComponent::Identifier("a".into()),
+            PathComponent::Index(42),
+            PathComponent::Index(123456),
+        ];
+
+        let mut out = Vec::new();
+        write_gron_path_with_style(
+            &mut out,
+            "json",
+            &path_components,
+            GronPathStyle {
+                index_width: 5,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "json.a[00042][123456]");
+
+        let mut out = Vec::new();
+        write_uri_fragment_json_pointer_with_index_width(&mut out, &path_components, 3).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "#/a/042/123456");
+    }
+
+    #[test]
+    fn quoting() {
+        let quoting = GronQuoting {
+            single_quotes: true,
+            ascii_only: true,
+        };
+
+        let mut out = Vec::new();
+        write_gron_path_with_style(
+            &mut out,
+            "json",
+            &[PathComponent::NonIdentifier("it's é".into())],
+            GronPathStyle {
+                quoting,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        out.extend(b" = ");
+        write_js_value(&mut out, &serde_json::json!(["\"😀\"\n"]), quoting).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"json['it\'s \u00e9'] = ['"\ud83d\ude00"\n']"#
+        );
+
+        let mut out = Vec::new();
+        write_js_string(&mut out, "é\"", GronQuoting::default()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), r#""é\"""#);
+    }
+
+    #[test]
+    fn escaped_keys() {
+        for key in ["a\"b", "a\\b", "a\nb", "a\u{1}b\u{7f}", "\"]\\\""] {
+            let path_components = [PathComponent::NonIdentifier(key.into())];
+
+            let mut gron = Vec::new();
+            write_gron_path(&mut gron, "json", &path_components).unwrap();
+            let gron = String::from_utf8(gron).unwrap();
+
+            // the key in brackets is a JSON string
+            let quoted = gron
+                .strip_prefix("json[")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .unwrap();
+            assert_eq!(serde_json::from_str::<String>(quoted).unwrap(), key);
+            assert!(!gron.contains('\n'));
+
+            assert_eq!(
+                parse_gron_path(&gron, "json").unwrap(),
+                (
+                    vec![PathComponent::NonIdentifier(key.to_string().into())],
+                    ""
+                )
+            );
+            assert_eq!(gron_shape("json", &path_components), gron);
+        }
+
+        let mut gron = Vec::new();
+        write_gron_path(
+            &mut gron,
+            "json",
+            &[PathComponent::NonIdentifier("a\"b\\c\td".into())],
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(gron).unwrap(), r#"json["a\"b\\c\td"]"#);
+    }
+
+    #[test]
+    fn identifier_policies() {
+        let path_components = [
+            PathComponent::Identifier("café".into()),
+            PathComponent::NonIdentifier("$ref".into()),
+            PathComponent::Identifier("id".into()),
+        ];
+
+        let render_with = |policy| {
+            let mut out = Vec::new();
+            write_gron_path_with_policy(&mut out, "json", &path_components, policy).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            render_with(IdentifierPolicy::UnicodeXid),
+            r#"json.café["$ref"].id"#
+        );
+        assert_eq!(
+            render_with(IdentifierPolicy::AsciiJs),
+            r#"json["café"].$ref.id"#
+        );
+        assert_eq!(
+            render_with(IdentifierPolicy::AlwaysBracket),
+            r#"json["café"]["$ref"]["id"]"#
+        );
+
+        let (parsed, _) = parse_gron_path(&render_with(IdentifierPolicy::AsciiJs), "json").unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                PathComponent::Identifier("café".into()),
+                PathComponent::NonIdentifier("$ref".into()),
+                PathComponent::Identifier("id".into()),
+            ]
+        );
+    }
+}
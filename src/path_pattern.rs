@@ -0,0 +1,177 @@
+use crate::path_format::write_json_pointer;
+use crate::PathComponent;
+use anyhow::Result;
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// A pattern that matches the paths of nodes in a JSON document.
+///
+/// Glob patterns are `.`-separated segments that match path components
+/// from the root of the document, like `items.*.rawPayload`.
+/// In a segment, `*` matches any run of characters,
+/// so a segment of only `*` matches exactly one path component.
+/// A segment of `**` matches any number of path components (including none),
+/// so `**.rawPayload` matches a `rawPayload` key at any depth.
+/// Array indexes are matched by their decimal representation, like `items.0`.
+///
+/// Regex patterns match against the path rendered as a JSON Pointer, like `/items/0/rawPayload`.
+///
+/// Both kinds of pattern can be made to match keys regardless of case
+/// or Unicode normalization form; see [KeyMatching].
+#[derive(Clone, Debug)]
+pub enum PathPattern {
+    Glob(Vec<String>, KeyMatching),
+    Regex(regex::Regex, KeyMatching),
+}
+
+/// How a [PathPattern] compares object keys
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyMatching {
+    /// Match keys regardless of case, so that `id` matches `Id` and `ID`
+    pub case_insensitive: bool,
+    /// Compare keys (and the pattern) in Unicode Normalization Form C,
+    /// so that a precomposed `é` matches `e` followed by a combining accent
+    pub normalize: bool,
+}
+
+impl KeyMatching {
+    /// `s` in the form that keys are compared in
+    fn fold<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        let s = if self.normalize && !unicode_normalization::is_nfc(s) {
+            Cow::Owned(s.nfc().collect())
+        } else {
+            Cow::Borrowed(s)
+        };
+
+        if self.case_insensitive {
+            Cow::Owned(s.to_lowercase())
+        } else {
+            s
+        }
+    }
+}
+
+impl PathPattern {
+    pub fn glob(pattern: &str) -> Self {
+        Self::glob_with(pattern, KeyMatching::default())
+    }
+
+    pub fn glob_with(pattern: &str, key_matching: KeyMatching) -> Self {
+        Self::Glob(
+            pattern
+                .split('.')
+                .map(|s| key_matching.fold(s).into_owned())
+                .collect(),
+            key_matching,
+        )
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Self::regex_with(pattern, KeyMatching::default())
+    }
+
+    pub fn regex_with(pattern: &str, key_matching: KeyMatching) -> Result<Self> {
+        let pattern = KeyMatching {
+            case_insensitive: false,
+            ..key_matching
+        }
+        .fold(pattern);
+
+        Ok(Self::Regex(
+            regex::RegexBuilder::new(&pattern)
+                .case_insensitive(key_matching.case_insensitive)
+                .build()?,
+            key_matching,
+        ))
+    }
+
+    /// The segments at the start of a glob pattern that match only themselves,
+    /// so that every path it matches starts with those keys or indexes,
+    /// like `users` for `users.*.email`.
+    /// Empty for a regex, or for a glob that compares keys other than exactly
+    pub fn literal_prefix(&self) -> &[String] {
+        match self {
+            PathPattern::Glob(segments, key_matching)
+                if *key_matching == KeyMatching::default() =>
+            {
+                let literal = segments
+                    .iter()
+                    .take_while(|segment| !segment.contains('*'))
+                    .count();
+
+                &segments[..literal]
+            }
+            _ => &[],
+        }
+    }
+
+    /// Whether this pattern matches the path made of `path_components`
+    pub fn matches(&self, path_components: &[PathComponent]) -> bool {
+        match self {
+            PathPattern::Glob(segments, key_matching) => {
+                glob_matches(segments, path_components, key_matching)
+            }
+            PathPattern::Regex(regex, key_matching) => {
+                let mut pointer = Vec::new();
+                // writing to a `Vec` cannot fail
+                write_json_pointer(&mut pointer, path_components).unwrap();
+                let pointer = String::from_utf8_lossy(&pointer);
+
+                if key_matching.normalize {
+                    regex.is_match(&pointer.nfc().collect::<String>())
+                } else {
+                    regex.is_match(&pointer)
+                }
+            }
+        }
+    }
+}
+
+fn glob_matches(
+    segments: &[String],
+    path_components: &[PathComponent],
+    key_matching: &KeyMatching,
+) -> bool {
+    match segments.split_first() {
+        None => path_components.is_empty(),
+        Some((segment, rest)) if segment == "**" => (0..=path_components.len())
+            .any(|skipped| glob_matches(rest, &path_components[skipped..], key_matching)),
+        Some((segment, rest)) => match path_components.split_first() {
+            Some((path_component, path_components)) => {
+                segment_matches(segment, path_component, key_matching)
+                    && glob_matches(rest, path_components, key_matching)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(
+    segment: &str,
+    path_component: &PathComponent,
+    key_matching: &KeyMatching,
+) -> bool {
+    match path_component {
+        PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
+            wildcard_matches(segment.as_bytes(), key_matching.fold(s).as_bytes())
+        }
+        PathComponent::Index(i) => {
+            let mut buf = itoa::Buffer::new();
+            wildcard_matches(segment.as_bytes(), buf.format(*i).as_bytes())
+        }
+    }
+}
+
+/// Match `text` against `pattern`, where `*` in `pattern` matches any run of bytes
+fn wildcard_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => {
+            (0..=text.len()).any(|skipped| wildcard_matches(rest, &text[skipped..]))
+        }
+        Some((c, rest)) => match text.split_first() {
+            Some((t, text)) => c == t && wildcard_matches(rest, text),
+            None => false,
+        },
+    }
+}
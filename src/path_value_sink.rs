@@ -1,7 +1,13 @@
-use std::io::Write;
+use crate::path_format::GronPathStyle;
+use crate::PathValue;
+use alloc::boxed::Box;
+use anyhow::{anyhow, Result};
 
-use crate::{PathComponent, PathValue};
-use anyhow::Result;
+#[cfg(feature = "std")]
+mod writers;
+
+#[cfg(feature = "std")]
+pub use writers::*;
 
 /// `jindex` will call this trait's `handle_pathvalue` method
 /// exactly once for each `PathValue` in the given JSON document.
@@ -14,214 +20,98 @@ use anyhow::Result;
 /// Note that `handle_pathvalue` is on the hot path of `jindex`,
 /// so the performance of `jindex` will depend heavily on how a
 /// given type implements `handle_pathvalue`.
+///
+/// Sinks that buffer or need to write a trailer implement `finish`,
+/// which callers should call once, after the last document
+/// has been passed to `jindex`.
+///
+/// When traversing many documents (like multiple files or newline-delimited JSON),
+/// callers may call `start_document` with the index of each document
+/// before passing it to `jindex`, for sinks that record which document
+/// each `PathValue` came from.
+///
+/// Sinks that write to an underlying writer implement `flush`,
+/// which callers may call between documents so that output
+/// appears as soon as each document has been traversed.
 pub trait PathValueSink {
     fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()>;
-}
-
-/// Write `PathValue`s to the given `writer` in the style of
-/// https://github.com/tomnomnom/gron
-#[derive(Debug)]
-pub struct GronWriter<'writer, W: Write> {
-    writer: &'writer mut W,
-    options: GronWriterOptions,
-}
-
-impl<'writer, W: Write> GronWriter<'writer, W> {
-    pub fn new(writer: &'writer mut W, options: GronWriterOptions) -> Self {
-        Self { writer, options }
-    }
-}
 
-#[derive(Debug)]
-pub struct GronWriterOptions {
-    pub only_scalars: bool,
-}
-
-impl Default for GronWriterOptions {
-    fn default() -> Self {
-        Self { only_scalars: true }
+    fn start_document(&mut self, _document_index: usize) -> Result<()> {
+        Ok(())
     }
-}
-
-impl<'writer, W: Write> PathValueSink for GronWriter<'writer, W> {
-    #[inline]
-    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
-        let should_write = if self.options.only_scalars {
-            is_scalar(pathvalue.value)
-        } else {
-            true
-        };
-
-        let should_write = should_write && !pathvalue.path_components.is_empty();
-
-        if should_write {
-            self.writer.write_all(b"json")?;
-
-            for path_component in &pathvalue.path_components {
-                match path_component {
-                    PathComponent::Identifier(s) => {
-                        self.writer.write_all(b".")?;
-                        self.writer.write_all(s.as_bytes())?;
-                    }
-                    PathComponent::NonIdentifier(s) => {
-                        self.writer.write_all(b"[\"")?;
-                        self.writer.write_all(s.as_bytes())?;
-                        self.writer.write_all(b"\"]")?;
-                    }
-                    PathComponent::Index(i) => {
-                        self.writer.write_all(b"[")?;
-                        let mut buf = itoa::Buffer::new();
-                        let out = buf.format(*i);
-                        self.writer.write_all(out.as_bytes())?;
-                        self.writer.write_all(b"]")?;
-                    }
-                }
-            }
-
-            self.writer.write_all(b" = ")?;
-
-            serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
-
-            self.writer.write_all(b";\n")?;
-        }
 
+    fn finish(&mut self) -> Result<()> {
         Ok(())
     }
-}
 
-/// Write `PathValue`s to the given `writer` as
-/// JSON Pointers.
-/// See https://datatracker.ietf.org/doc/html/rfc6901
-#[derive(Debug)]
-pub struct JSONPointerWriter<'writer, W: Write> {
-    writer: &'writer mut W,
-    options: JSONPointerWriterOptions<'writer>,
-}
-
-impl<'writer, W: Write> JSONPointerWriter<'writer, W> {
-    pub fn new(writer: &'writer mut W, options: JSONPointerWriterOptions<'writer>) -> Self {
-        Self { writer, options }
+    /// Whether `jindex` should visit the keys of each object in sorted order.
+    /// Keys are already sorted unless serde_json's `preserve_order` feature is enabled
+    /// (as by this crate's `preserve_order` feature), in which case they are visited
+    /// in document order unless this returns `true`.
+    /// Sorted keys are visited first to last,
+    /// or last to first in [TraversalOrder::Reverse](crate::TraversalOrder::Reverse).
+    /// Writers with a `sort_keys` option return it.
+    fn sort_keys(&self) -> bool {
+        false
     }
-}
 
-#[derive(Debug)]
-pub struct JSONPointerWriterOptions<'options> {
-    pub only_scalars: bool,
-    pub separator: &'options str,
-}
-
-impl Default for JSONPointerWriterOptions<'_> {
-    fn default() -> Self {
-        Self {
-            only_scalars: true,
-            separator: "\t",
-        }
+    /// For sinks that write only leaves (scalars, and empty objects and arrays)
+    /// with their paths in gron notation, the style of those paths.
+    /// When this is `Some`, [jindex_with_options](crate::jindex_with_options) renders
+    /// each path incrementally into one buffer, passing each leaf to `handle_rendered_leaf`
+    /// and each other object and array to `handle_rendered_branch`, rather than
+    /// allocating a path for every node to pass to `handle_pathvalue`; see [crate::rendered]
+    fn rendered_path_style(&self) -> Option<GronPathStyle> {
+        None
     }
-}
 
-const TILDE: char = '~';
-const FORWARD_SLASH: char = '/';
-const JSON_POINTER_SPECIAL_CHARS: &[char] = &[TILDE, FORWARD_SLASH];
-
-impl<'writer, W: Write> PathValueSink for JSONPointerWriter<'writer, W> {
-    #[inline]
-    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
-        let should_write = if self.options.only_scalars {
-            is_scalar(pathvalue.value)
-        } else {
-            true
-        };
-
-        let should_write = should_write && !pathvalue.path_components.is_empty();
-
-        if should_write {
-            for path_component in &pathvalue.path_components {
-                self.writer.write_all(b"/")?;
-                match path_component {
-                    PathComponent::Identifier(s) | PathComponent::NonIdentifier(s) => {
-                        // this conditional exists because `replace` allocates even
-                        // if it doesn't find any matches, and I've benchmarked this conditional
-                        // as increasing throughput by ~30-50%.
-                        if s.contains(JSON_POINTER_SPECIAL_CHARS) {
-                            let s = s.replace(TILDE, "~0");
-                            let s = s.replace(FORWARD_SLASH, "~1");
-                            self.writer.write_all(s.as_bytes())?
-                        } else {
-                            self.writer.write_all(s.as_bytes())?
-                        }
-                    }
-                    PathComponent::Index(i) => {
-                        let mut buf = itoa::Buffer::new();
-                        let out = buf.format(*i);
-                        self.writer.write_all(out.as_bytes())?;
-                    }
-                }
-            }
-
-            self.writer.write_all(self.options.separator.as_bytes())?;
-            serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
-            self.writer.write_all(b"\n")?;
-        }
+    /// Handle a leaf whose path is rendered as a gron path without a prefix, like `.a[0]`,
+    /// in the style of [PathValueSink::rendered_path_style]
+    fn handle_rendered_leaf(&mut self, _path: &[u8], _value: &serde_json::Value) -> Result<()> {
+        Err(anyhow!("this sink does not handle rendered paths"))
+    }
 
+    /// Handle an object or array that is not a leaf, when paths are rendered,
+    /// before its children
+    fn handle_rendered_branch(&mut self, _value: &serde_json::Value) -> Result<()> {
         Ok(())
     }
-}
 
-/// Write `PathValue`s to the given `writer` as
-/// JSON objects separated by newlines,
-/// like `{"path_components":["some","paths"],"value":"foo"}
-#[derive(Debug)]
-pub struct JSONWriter<'writer, W: Write> {
-    writer: &'writer mut W,
-    options: JsonWriterOptions,
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
-impl<'writer, W: Write> JSONWriter<'writer, W> {
-    pub fn new(writer: &'writer mut W, options: JsonWriterOptions) -> Self {
-        Self { writer, options }
+impl<S: PathValueSink + ?Sized> PathValueSink for Box<S> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        (**self).handle_pathvalue(pathvalue)
     }
-}
 
-#[derive(Debug)]
-pub struct JsonWriterOptions {
-    pub only_scalars: bool,
-}
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        (**self).start_document(document_index)
+    }
 
-impl Default for JsonWriterOptions {
-    fn default() -> Self {
-        Self { only_scalars: true }
+    fn finish(&mut self) -> Result<()> {
+        (**self).finish()
     }
-}
 
-impl<'writer, W: Write> PathValueSink for JSONWriter<'writer, W> {
-    #[inline]
-    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
-        let should_write = if self.options.only_scalars {
-            is_scalar(pathvalue.value)
-        } else {
-            true
-        };
+    fn sort_keys(&self) -> bool {
+        (**self).sort_keys()
+    }
 
-        let should_write = should_write && !pathvalue.path_components.is_empty();
+    fn rendered_path_style(&self) -> Option<GronPathStyle> {
+        (**self).rendered_path_style()
+    }
 
-        if should_write {
-            serde_json::to_writer(&mut *self.writer, pathvalue)?;
-            self.writer.write_all(b"\n")?;
-        }
+    fn handle_rendered_leaf(&mut self, path: &[u8], value: &serde_json::Value) -> Result<()> {
+        (**self).handle_rendered_leaf(path, value)
+    }
 
-        Ok(())
+    fn handle_rendered_branch(&mut self, value: &serde_json::Value) -> Result<()> {
+        (**self).handle_rendered_branch(value)
     }
-}
 
-#[inline]
-fn is_scalar(value: &serde_json::Value) -> bool {
-    match value {
-        serde_json::Value::String(_)
-        | serde_json::Value::Number(_)
-        | serde_json::Value::Bool(_)
-        | serde_json::Value::Null => true,
-        serde_json::Value::Array(a) if a.is_empty() => true,
-        serde_json::Value::Object(o) if o.is_empty() => true,
-        _ => false,
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
     }
 }
@@ -0,0 +1,1936 @@
+//! The writers of `PathValue`s, which need `std::io`
+
+use std::io::Write;
+
+use super::PathValueSink;
+use crate::color::{self, write_colored, write_colored_value};
+use crate::path_format::{
+    gron_shape, write_gron_path, write_gron_path_with_style, write_js_value, write_json_pointer,
+    write_json_pointer_with_index_width, write_uri_fragment_json_pointer_with_index_width,
+    write_xpath, GronPathStyle, GronQuoting, IdentifierPolicy,
+};
+use crate::spill::{RecordSpill, SpillOptions};
+use crate::{OwnedPathValue, PathComponent, PathValue};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+/// The string written after each record by all of the writers in this module,
+/// unless overridden by their `record_terminator` option.
+pub const DEFAULT_RECORD_TERMINATOR: &str = "\n";
+
+/// Write `PathValue`s to the given `writer` in the style of
+/// https://github.com/tomnomnom/gron
+#[derive(Debug)]
+pub struct GronWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: GronWriterOptions<'writer>,
+    timestamp: Option<String>,
+}
+
+impl<'writer, W: Write + ?Sized> GronWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: GronWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            timestamp: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GronWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Written between the path and the value, like ` = ` (the default), `=`, or `: `
+    pub assignment: &'options str,
+    /// Whether to write a `;` after each value
+    pub semicolon: bool,
+    /// Which keys are written as bare identifiers rather than in brackets
+    pub identifier_policy: IdentifierPolicy,
+    /// How keys in brackets and string values are quoted
+    pub quoting: GronQuoting,
+    /// Pad array indexes with leading zeros to at least this many digits, like `[00042]`,
+    /// so that sorting records sorts indexes in numeric order
+    pub index_width: usize,
+    /// Start each record with the time its document was ingested and a tab;
+    /// see [JsonWriterOptions::include_timestamp]
+    pub include_timestamp: bool,
+    /// Color paths and values with ANSI escapes, for a terminal; see [crate::color]
+    pub color: bool,
+}
+
+impl Default for GronWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            assignment: " = ",
+            semicolon: true,
+            identifier_policy: IdentifierPolicy::default(),
+            quoting: GronQuoting::default(),
+            index_width: 0,
+            include_timestamp: false,
+            color: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> GronWriter<'writer, W> {
+    fn path_style(&self) -> GronPathStyle {
+        GronPathStyle {
+            identifier_policy: self.options.identifier_policy,
+            quoting: self.options.quoting,
+            index_width: self.options.index_width,
+        }
+    }
+
+    /// Write a record for `value`, with its path written by `write_path`
+    #[inline]
+    fn write_record(
+        &mut self,
+        write_path: impl FnOnce(&mut W) -> Result<()>,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        if self.options.include_timestamp {
+            write_timestamp(self.writer, &mut self.timestamp)?;
+            self.writer.write_all(b"\t")?;
+        }
+
+        if self.options.color {
+            self.writer.write_all(color::PATH.as_bytes())?;
+        }
+
+        write_path(self.writer)?;
+
+        if self.options.color {
+            self.writer.write_all(color::RESET.as_bytes())?;
+        }
+
+        self.writer.write_all(self.options.assignment.as_bytes())?;
+
+        match (
+            self.options.color,
+            self.options.quoting == GronQuoting::default(),
+        ) {
+            (false, true) => serde_json::to_writer(&mut *self.writer, value)?,
+            (true, true) => write_colored_value(self.writer, value)?,
+            (false, false) => write_js_value(self.writer, value, self.options.quoting)?,
+            (true, false) => {
+                let mut written = Vec::new();
+                write_js_value(&mut written, value, self.options.quoting)?;
+
+                match color::value_color(value) {
+                    Some(color) => write_colored(self.writer, color, &written)?,
+                    None => self.writer.write_all(&written)?,
+                }
+            }
+        }
+
+        if self.options.semicolon {
+            self.writer.write_all(b";")?;
+        }
+        self.writer
+            .write_all(self.options.record_terminator.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for GronWriter<'writer, W> {
+    #[inline]
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            let style = self.path_style();
+            self.write_record(
+                |writer| {
+                    write_gron_path_with_style(writer, "json", &pathvalue.path_components, style)
+                },
+                pathvalue.value,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn rendered_path_style(&self) -> Option<GronPathStyle> {
+        self.options.only_scalars.then(|| self.path_style())
+    }
+
+    #[inline]
+    fn handle_rendered_leaf(&mut self, path: &[u8], value: &serde_json::Value) -> Result<()> {
+        if path.is_empty() || (self.options.skip_empty_containers && is_empty_container(value)) {
+            return Ok(());
+        }
+
+        self.write_record(
+            |writer| {
+                writer.write_all(b"json")?;
+                writer.write_all(path)?;
+                Ok(())
+            },
+            value,
+        )
+    }
+
+    fn start_document(&mut self, _document_index: usize) -> Result<()> {
+        if self.options.include_timestamp {
+            self.timestamp = Some(rfc3339_now());
+        }
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write `PathValue`s to the given `writer` as
+/// JSON Pointers.
+/// See https://datatracker.ietf.org/doc/html/rfc6901
+#[derive(Debug)]
+pub struct JSONPointerWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: JSONPointerWriterOptions<'writer>,
+    timestamp: Option<String>,
+}
+
+impl<'writer, W: Write + ?Sized> JSONPointerWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: JSONPointerWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            timestamp: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JSONPointerWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub separator: &'options str,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// What to do when `separator` appears in a record's path or value
+    pub separator_collision: SeparatorCollision,
+    /// Write pointers in URI fragment form, like `#/a/b%20c/0`.
+    /// See https://datatracker.ietf.org/doc/html/rfc6901#section-6
+    pub uri_fragment: bool,
+    /// Pad array indexes with leading zeros to at least this many digits, like `/00042`;
+    /// see [write_json_pointer_with_index_width]
+    pub index_width: usize,
+    /// Start each record with the time its document was ingested and the separator;
+    /// see [JsonWriterOptions::include_timestamp]
+    pub include_timestamp: bool,
+    /// Color paths and values with ANSI escapes, for a terminal; see [crate::color]
+    pub color: bool,
+}
+
+impl Default for JSONPointerWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            separator: "\t",
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            separator_collision: SeparatorCollision::default(),
+            uri_fragment: false,
+            index_width: 0,
+            include_timestamp: false,
+            color: false,
+        }
+    }
+}
+
+/// How `JSONPointerWriter` handles records whose path or value
+/// contain its separator, which would make the output ambiguous
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SeparatorCollision {
+    /// Write the record as-is
+    #[default]
+    Ignore,
+    /// Replace the first character of each occurrence of the separator
+    /// inside a JSON string value with its `\uXXXX` escape,
+    /// which leaves the value as valid, equivalent JSON.
+    /// A path containing the separator is written as a URI fragment
+    /// (see `uri_fragment`), with the first character of each occurrence percent-encoded.
+    /// Occurrences that cannot be escaped this way
+    /// (starting with `#`, `/`, or `%` in the path, or outside of strings in the value)
+    /// are an error.
+    Escape,
+    /// Return an error
+    Error,
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for JSONPointerWriter<'writer, W> {
+    #[inline]
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            if self.options.include_timestamp {
+                write_timestamp(self.writer, &mut self.timestamp)?;
+                self.writer.write_all(self.options.separator.as_bytes())?;
+            }
+
+            if self.options.separator_collision == SeparatorCollision::Ignore
+                || self.options.separator.is_empty()
+            {
+                if self.options.color {
+                    self.writer.write_all(color::PATH.as_bytes())?;
+                    self.write_pointer(&pathvalue.path_components)?;
+                    self.writer.write_all(color::RESET.as_bytes())?;
+                } else {
+                    self.write_pointer(&pathvalue.path_components)?;
+                }
+
+                self.writer.write_all(self.options.separator.as_bytes())?;
+
+                if self.options.color {
+                    write_colored_value(self.writer, pathvalue.value)?;
+                } else {
+                    serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
+                }
+            } else {
+                self.write_checked(pathvalue)?;
+            }
+
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn start_document(&mut self, _document_index: usize) -> Result<()> {
+        if self.options.include_timestamp {
+            self.timestamp = Some(rfc3339_now());
+        }
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<'writer, W: Write + ?Sized> JSONPointerWriter<'writer, W> {
+    fn write_pointer(&mut self, path_components: &[PathComponent]) -> Result<()> {
+        if self.options.uri_fragment {
+            write_uri_fragment_json_pointer_with_index_width(
+                &mut *self.writer,
+                path_components,
+                self.options.index_width,
+            )
+        } else {
+            write_json_pointer_with_index_width(
+                &mut *self.writer,
+                path_components,
+                self.options.index_width,
+            )
+        }
+    }
+
+    /// Write the path and value of a record, first checking them for
+    /// occurrences of the separator according to the `separator_collision` option
+    fn write_checked(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let mut pointer = Vec::new();
+        if self.options.uri_fragment {
+            write_uri_fragment_json_pointer_with_index_width(
+                &mut pointer,
+                &pathvalue.path_components,
+                self.options.index_width,
+            )?;
+        } else {
+            write_json_pointer_with_index_width(
+                &mut pointer,
+                &pathvalue.path_components,
+                self.options.index_width,
+            )?;
+        }
+        let mut pointer = String::from_utf8(pointer)?;
+
+        if pointer.contains(self.options.separator) {
+            if self.options.separator_collision != SeparatorCollision::Escape {
+                return Err(anyhow!(
+                    "separator {:?} appears in path {}",
+                    self.options.separator,
+                    pointer
+                ));
+            }
+
+            let mut fragment = Vec::new();
+            write_uri_fragment_json_pointer_with_index_width(
+                &mut fragment,
+                &pathvalue.path_components,
+                self.options.index_width,
+            )?;
+
+            pointer =
+                escape_pointer_separator(&String::from_utf8(fragment)?, self.options.separator)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "separator {:?} cannot be escaped in path {}",
+                            self.options.separator,
+                            pointer
+                        )
+                    })?;
+        }
+
+        let value = serde_json::to_string(pathvalue.value)?;
+        let escaped = value.contains(self.options.separator);
+
+        let value = if escaped {
+            match self.options.separator_collision {
+                SeparatorCollision::Escape => escape_separator(&value, self.options.separator)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "separator {:?} cannot be escaped in the value at {}",
+                            self.options.separator,
+                            pointer
+                        )
+                    })?,
+                _ => {
+                    return Err(anyhow!(
+                        "separator {:?} appears in the value at {}",
+                        self.options.separator,
+                        pointer
+                    ))
+                }
+            }
+        } else {
+            value
+        };
+
+        if self.options.color {
+            write_colored(self.writer, color::PATH, pointer.as_bytes())?;
+        } else {
+            self.writer.write_all(pointer.as_bytes())?;
+        }
+
+        self.writer.write_all(self.options.separator.as_bytes())?;
+
+        match (self.options.color, escaped) {
+            (true, false) => write_colored_value(self.writer, pathvalue.value)?,
+            // the escaped value is colored as a whole
+            (true, true) => match color::value_color(pathvalue.value) {
+                Some(color) => write_colored(self.writer, color, value.as_bytes())?,
+                None => self.writer.write_all(value.as_bytes())?,
+            },
+            (false, _) => self.writer.write_all(value.as_bytes())?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape every occurrence of `separator` in `fragment` (a URI fragment JSON Pointer)
+/// by percent-encoding the occurrence's first character.
+/// Returns `None` if an occurrence begins with `#`, `/`, or `%`,
+/// as those cannot be escaped without changing the pointer.
+fn escape_pointer_separator(fragment: &str, separator: &str) -> Option<String> {
+    let mut out = String::with_capacity(fragment.len() + 3);
+
+    for (i, c) in fragment.char_indices() {
+        if fragment[i..].starts_with(separator) {
+            if matches!(c, '#' | '/' | '%') {
+                return None;
+            }
+
+            let mut utf8 = [0; 4];
+            for byte in c.encode_utf8(&mut utf8).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+
+            continue;
+        }
+
+        out.push(c);
+    }
+
+    if out.contains(separator) {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Escape every occurrence of `separator` in `serialized` (a serialized JSON value)
+/// by replacing the occurrence's first character with its `\uXXXX` escape.
+/// Returns `None` if an occurrence does not begin with a literal character
+/// inside of a JSON string, as those cannot be escaped.
+fn escape_separator(serialized: &str, separator: &str) -> Option<String> {
+    let mut out = String::with_capacity(serialized.len() + 6);
+    let mut in_string = false;
+    let mut in_escape = false;
+    let mut hex_digits_remaining = 0;
+
+    for (i, c) in serialized.char_indices() {
+        if serialized[i..].starts_with(separator) {
+            if !in_string || in_escape || hex_digits_remaining > 0 {
+                return None;
+            }
+
+            let mut utf16 = [0; 2];
+            for unit in c.encode_utf16(&mut utf16) {
+                out.push_str(&format!("\\u{:04x}", unit));
+            }
+
+            continue;
+        }
+
+        out.push(c);
+
+        if hex_digits_remaining > 0 {
+            hex_digits_remaining -= 1;
+        } else if in_escape {
+            in_escape = false;
+            if c == 'u' {
+                hex_digits_remaining = 4;
+            }
+        } else if in_string {
+            match c {
+                '\\' => in_escape = true,
+                '"' => in_string = false,
+                _ => (),
+            }
+        } else if c == '"' {
+            in_string = true;
+        }
+    }
+
+    if out.contains(separator) {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Write `PathValue`s to the given `writer` as
+/// JSON objects separated by newlines,
+/// like `{"path_components":["some","paths"],"value":"foo"}
+///
+/// With the `envelope` option, the records are instead written
+/// as the elements of a single top-level JSON array,
+/// which is closed by `finish`.
+/// The elements are separated by newlines, so that the array is valid JSON
+/// whatever the `record_terminator`, which is written only after the array.
+#[derive(Debug)]
+pub struct JSONWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: JsonWriterOptions<'writer>,
+    records_written: usize,
+    document_index: usize,
+    timestamp: Option<String>,
+}
+
+impl<'writer, W: Write + ?Sized> JSONWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: JsonWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            records_written: 0,
+            document_index: 0,
+            timestamp: None,
+        }
+    }
+
+    fn type_name(&self, value: &serde_json::Value) -> &'static str {
+        if self.options.number_types {
+            detailed_type_name(value)
+        } else {
+            value_type_name(value)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Include the path rendered as a JSON Pointer, like `"pointer":"/a/b/0"`
+    pub include_pointer: bool,
+    /// Include the JSON type of the value, like `"type":"string"`
+    pub include_type: bool,
+    /// Write the type of numbers as the narrowest of `i64`, `u64`, and `f64`
+    /// that holds them, rather than as `number`; see [number_type_name]
+    pub number_types: bool,
+    /// Include the number of path components, like `"depth":3`
+    pub include_depth: bool,
+    /// Write all records as a single JSON array rather than as JSON Lines,
+    /// one element to a line
+    pub envelope: bool,
+    /// Include the index of the document the record came from
+    /// (as given to `start_document`), like `"doc":3`
+    pub include_document: bool,
+    /// Include the time (in UTC) that the record's document was ingested,
+    /// which is when `start_document` was called for it
+    /// (or when its first record was written, if it was not called),
+    /// like `"ts":"2024-01-31T12:00:00.000Z"`
+    pub include_timestamp: bool,
+    /// Write exactly these fields in each record, in this order,
+    /// instead of `path_components` and `value` and the optional fields above
+    pub fields: Option<&'options [JsonField]>,
+}
+
+/// A field of the records written by [JSONWriter]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonField {
+    /// `"path_components":["a","b",0]`
+    Path,
+    /// `"value":1`
+    Value,
+    /// `"pointer":"/a/b/0"`
+    Pointer,
+    /// `"type":"number"`
+    Type,
+    /// `"depth":3`
+    Depth,
+    /// `"doc":0`
+    Document,
+    /// `"ts":"2024-01-31T12:00:00.000Z"`
+    Timestamp,
+}
+
+impl JsonField {
+    /// The key of this field in a record
+    pub fn key(&self) -> &'static str {
+        match self {
+            JsonField::Path => "path_components",
+            JsonField::Value => "value",
+            JsonField::Pointer => "pointer",
+            JsonField::Type => "type",
+            JsonField::Depth => "depth",
+            JsonField::Document => "doc",
+            JsonField::Timestamp => "ts",
+        }
+    }
+}
+
+impl Default for JsonWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            include_pointer: false,
+            include_type: false,
+            number_types: false,
+            include_depth: false,
+            envelope: false,
+            include_document: false,
+            include_timestamp: false,
+            fields: None,
+        }
+    }
+}
+
+impl JsonWriterOptions<'_> {
+    fn has_extra_fields(&self) -> bool {
+        self.include_pointer
+            || self.include_type
+            || self.include_depth
+            || self.include_document
+            || self.include_timestamp
+    }
+}
+
+impl<W: Write + ?Sized> JSONWriter<'_, W> {
+    /// Write `pathvalue` as an object with exactly `fields`, in order
+    fn write_fields(&mut self, fields: &[JsonField], pathvalue: &PathValue) -> Result<()> {
+        self.writer.write_all(b"{")?;
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+
+            self.writer.write_all(b"\"")?;
+            self.writer.write_all(field.key().as_bytes())?;
+            self.writer.write_all(b"\":")?;
+
+            match field {
+                JsonField::Path => {
+                    serde_json::to_writer(&mut *self.writer, &pathvalue.path_components)?
+                }
+                JsonField::Value => serde_json::to_writer(&mut *self.writer, pathvalue.value)?,
+                JsonField::Pointer => {
+                    let mut pointer = Vec::new();
+                    write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+                    serde_json::to_writer(&mut *self.writer, std::str::from_utf8(&pointer)?)?
+                }
+                JsonField::Type => {
+                    let type_name = self.type_name(pathvalue.value);
+                    serde_json::to_writer(&mut *self.writer, type_name)?
+                }
+                JsonField::Depth => {
+                    serde_json::to_writer(&mut *self.writer, &pathvalue.path_components.len())?
+                }
+                JsonField::Document => {
+                    serde_json::to_writer(&mut *self.writer, &self.document_index)?
+                }
+                JsonField::Timestamp => {
+                    let timestamp = self.timestamp.get_or_insert_with(rfc3339_now);
+                    serde_json::to_writer(&mut *self.writer, timestamp)?
+                }
+            }
+        }
+
+        self.writer.write_all(b"}")?;
+
+        Ok(())
+    }
+}
+
+/// A `PathValue` with the optional extra fields of `JsonWriterOptions`
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    path_components: &'a [PathComponent<'a>],
+    value: &'a serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pointer: Option<&'a str>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    value_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+    #[serde(rename = "doc", skip_serializing_if = "Option::is_none")]
+    document: Option<usize>,
+    #[serde(rename = "ts", skip_serializing_if = "Option::is_none")]
+    timestamp: Option<&'a str>,
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for JSONWriter<'writer, W> {
+    #[inline]
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            if self.options.envelope {
+                if self.records_written == 0 {
+                    self.writer.write_all(b"[")?;
+                } else {
+                    self.writer.write_all(b",")?;
+                }
+                self.writer.write_all(b"\n")?;
+            }
+
+            if let Some(fields) = self.options.fields {
+                self.write_fields(fields, pathvalue)?;
+            } else if self.options.has_extra_fields() {
+                let mut pointer = Vec::new();
+
+                if self.options.include_timestamp && self.timestamp.is_none() {
+                    self.timestamp = Some(rfc3339_now());
+                }
+
+                if self.options.include_pointer {
+                    write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+                }
+
+                let record = JsonRecord {
+                    path_components: &pathvalue.path_components,
+                    value: pathvalue.value,
+                    pointer: if self.options.include_pointer {
+                        Some(std::str::from_utf8(&pointer)?)
+                    } else {
+                        None
+                    },
+                    value_type: if self.options.include_type {
+                        Some(self.type_name(pathvalue.value))
+                    } else {
+                        None
+                    },
+                    depth: if self.options.include_depth {
+                        Some(pathvalue.path_components.len())
+                    } else {
+                        None
+                    },
+                    document: if self.options.include_document {
+                        Some(self.document_index)
+                    } else {
+                        None
+                    },
+                    timestamp: if self.options.include_timestamp {
+                        self.timestamp.as_deref()
+                    } else {
+                        None
+                    },
+                };
+
+                serde_json::to_writer(&mut *self.writer, &record)?;
+            } else {
+                serde_json::to_writer(&mut *self.writer, pathvalue)?;
+            }
+
+            if !self.options.envelope {
+                self.writer
+                    .write_all(self.options.record_terminator.as_bytes())?;
+            }
+
+            self.records_written += 1;
+        }
+
+        Ok(())
+    }
+
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        self.document_index = document_index;
+        if self.options.include_timestamp
+            || self
+                .options
+                .fields
+                .is_some_and(|fields| fields.contains(&JsonField::Timestamp))
+        {
+            self.timestamp = Some(rfc3339_now());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.options.envelope {
+            if self.records_written == 0 {
+                self.writer.write_all(b"[")?;
+            } else {
+                self.writer.write_all(b"\n")?;
+            }
+            self.writer.write_all(b"]")?;
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Collapse `PathValue`s whose paths differ only in their array indexes
+/// into a single line per path "shape", and write those lines to `writer`
+/// when `finish` is called, in the order each shape was first seen.
+/// Each line has the number of occurrences of the shape,
+/// a tab, and then the shape in gron style with indexes written as `[*]`,
+/// along with the value of its first occurrence, like:
+/// `3\tjson.items[*].id = 1;`
+#[derive(Debug)]
+pub struct ShapeDedupWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: ShapeDedupWriterOptions<'writer>,
+    shape_indexes: std::collections::HashMap<String, usize>,
+    shapes: Vec<(String, usize, serde_json::Value)>,
+}
+
+impl<'writer, W: Write + ?Sized> ShapeDedupWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: ShapeDedupWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            shape_indexes: std::collections::HashMap::new(),
+            shapes: vec![],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapeDedupWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+}
+
+impl Default for ShapeDedupWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for ShapeDedupWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            let shape = gron_shape("json", &pathvalue.path_components);
+
+            if let Some(i) = self.shape_indexes.get(&shape) {
+                self.shapes[*i].1 += 1;
+            } else {
+                self.shape_indexes.insert(shape.clone(), self.shapes.len());
+                self.shapes.push((shape, 1, pathvalue.value.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (shape, count, value) in &self.shapes {
+            let mut buf = itoa::Buffer::new();
+            self.writer.write_all(buf.format(*count).as_bytes())?;
+            self.writer.write_all(b"\t")?;
+            self.writer.write_all(shape.as_bytes())?;
+            self.writer.write_all(b" = ")?;
+            serde_json::to_writer(&mut *self.writer, value)?;
+            self.writer.write_all(b";")?;
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        self.shape_indexes.clear();
+        self.shapes.clear();
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write the path of each `PathValue` to the given `writer` in gron style,
+/// followed by a tab and the type of its value, like `json.items[0].id\tnumber`.
+///
+/// Types are named as in the `type` field of `JSONWriter`'s records,
+/// with numbers optionally named by [number_type_name],
+/// and containers have their lengths, like `array[3]` and `object{2}`.
+/// Unlike the other writers, containers are written by default,
+/// as this is a view of a document's schema rather than its data.
+#[derive(Debug)]
+pub struct TypesWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: TypesWriterOptions<'writer>,
+}
+
+impl<'writer, W: Write + ?Sized> TypesWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: TypesWriterOptions<'writer>) -> Self {
+        Self { writer, options }
+    }
+}
+
+#[derive(Debug)]
+pub struct TypesWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Name numbers as `i64`, `u64`, or `f64`; see [number_type_name]
+    pub number_types: bool,
+}
+
+impl Default for TypesWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: false,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            number_types: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for TypesWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            write_gron_path(self.writer, "json", &pathvalue.path_components)?;
+            self.writer.write_all(b"\t")?;
+
+            let mut buf = itoa::Buffer::new();
+
+            match pathvalue.value {
+                serde_json::Value::Array(a) => {
+                    self.writer.write_all(b"array[")?;
+                    self.writer.write_all(buf.format(a.len()).as_bytes())?;
+                    self.writer.write_all(b"]")?;
+                }
+                serde_json::Value::Object(o) => {
+                    self.writer.write_all(b"object{")?;
+                    self.writer.write_all(buf.format(o.len()).as_bytes())?;
+                    self.writer.write_all(b"}")?;
+                }
+                value if self.options.number_types => self
+                    .writer
+                    .write_all(detailed_type_name(value).as_bytes())?,
+                value => self.writer.write_all(value_type_name(value).as_bytes())?,
+            }
+
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write each node as its key (or `[index]`) and value on its own line,
+/// indented by its depth, like YAML:
+///
+/// ```text
+/// items:
+///   [0]:
+///     id: 1
+///     "content-type": "text/plain"
+///   [1]: []
+/// ```
+///
+/// Keys that are not identifiers are quoted, and scalars are written as JSON.
+/// Objects and arrays are written with nothing after the colon, unless they are empty.
+/// The nesting is only meaningful if every node is passed to this writer,
+/// in an order in which every node comes before its children,
+/// as it is by `jindex`.
+#[derive(Debug)]
+pub struct IndentedWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: IndentedWriterOptions<'writer>,
+}
+
+impl<'writer, W: Write + ?Sized> IndentedWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: IndentedWriterOptions<'writer>) -> Self {
+        Self { writer, options }
+    }
+}
+
+#[derive(Debug)]
+pub struct IndentedWriterOptions<'options> {
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Written once for each level of depth, before each line
+    pub indent: &'options str,
+}
+
+impl Default for IndentedWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            indent: "  ",
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for IndentedWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(pathvalue, false, self.options.skip_empty_containers) {
+            let (last, parents) = pathvalue
+                .path_components
+                .split_last()
+                .expect("should_write skips the root");
+
+            for _ in parents {
+                self.writer.write_all(self.options.indent.as_bytes())?;
+            }
+
+            match last {
+                PathComponent::Identifier(key) => self.writer.write_all(key.as_bytes())?,
+                PathComponent::NonIdentifier(key) => serde_json::to_writer(&mut *self.writer, key)?,
+                PathComponent::Index(i) => {
+                    self.writer.write_all(b"[")?;
+                    self.writer
+                        .write_all(itoa::Buffer::new().format(*i).as_bytes())?;
+                    self.writer.write_all(b"]")?;
+                }
+            }
+
+            self.writer.write_all(b":")?;
+
+            if is_scalar(pathvalue.value) || is_empty_container(pathvalue.value) {
+                self.writer.write_all(b" ")?;
+                serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
+            }
+
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write `PathValue`s as a YAML sequence of mappings, one for each record,
+/// with the path as a JSON Pointer, the JSON type, and the value:
+///
+/// ```yaml
+/// - pointer: "/items/0/id"
+///   type: number
+///   value: 1
+/// ```
+///
+/// Values are written as JSON, which YAML also reads.
+/// If there are no records, the output is the empty sequence `[]`.
+#[derive(Debug)]
+pub struct YamlWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: YamlWriterOptions<'writer>,
+    records_written: usize,
+}
+
+impl<'writer, W: Write + ?Sized> YamlWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: YamlWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            records_written: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct YamlWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    /// Written after each line, rather than after each record
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+}
+
+impl Default for YamlWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for YamlWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            let terminator = self.options.record_terminator.as_bytes();
+
+            let mut pointer = Vec::new();
+            write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+
+            self.writer.write_all(b"- pointer: ")?;
+            serde_json::to_writer(&mut *self.writer, std::str::from_utf8(&pointer)?)?;
+            self.writer.write_all(terminator)?;
+
+            self.writer.write_all(b"  type: ")?;
+            self.writer
+                .write_all(value_type_name(pathvalue.value).as_bytes())?;
+            self.writer.write_all(terminator)?;
+
+            self.writer.write_all(b"  value: ")?;
+            serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
+            self.writer.write_all(terminator)?;
+
+            self.records_written += 1;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.records_written == 0 {
+            self.writer.write_all(b"[]")?;
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write `PathValue`s as an XPath (see [write_xpath]), a separator, and a value,
+/// like `/root/item[1]/@id\t"a"`, for documents converted from XML
+#[derive(Debug)]
+pub struct XPathWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: XPathWriterOptions<'writer>,
+}
+
+impl<'writer, W: Write + ?Sized> XPathWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: XPathWriterOptions<'writer>) -> Self {
+        Self { writer, options }
+    }
+}
+
+#[derive(Debug)]
+pub struct XPathWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    pub separator: &'options str,
+}
+
+impl Default for XPathWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            separator: "\t",
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for XPathWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            write_xpath(self.writer, &pathvalue.path_components)?;
+            self.writer.write_all(self.options.separator.as_bytes())?;
+            serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write scalars as TOML key/value pairs with dotted keys,
+/// like `server.ports."0" = 8080`
+///
+/// Keys that are not bare TOML keys (ASCII letters, digits, `_`, and `-`) are quoted,
+/// as are array indexes, so arrays become tables keyed by index.
+/// TOML has no null, so null values are skipped.
+/// Empty objects and arrays are written as `{}` and `[]`, unless skipped.
+#[derive(Debug)]
+pub struct TomlWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: TomlWriterOptions<'writer>,
+}
+
+impl<'writer, W: Write + ?Sized> TomlWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: TomlWriterOptions<'writer>) -> Self {
+        Self { writer, options }
+    }
+}
+
+#[derive(Debug)]
+pub struct TomlWriterOptions<'options> {
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+}
+
+impl Default for TomlWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for TomlWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(pathvalue, true, self.options.skip_empty_containers)
+            && !pathvalue.value.is_null()
+        {
+            for (i, path_component) in pathvalue.path_components.iter().enumerate() {
+                if i > 0 {
+                    self.writer.write_all(b".")?;
+                }
+
+                match path_component {
+                    PathComponent::Identifier(key) | PathComponent::NonIdentifier(key)
+                        if is_bare_toml_key(key) =>
+                    {
+                        self.writer.write_all(key.as_bytes())?
+                    }
+                    PathComponent::Identifier(key) | PathComponent::NonIdentifier(key) => {
+                        serde_json::to_writer(&mut *self.writer, key)?
+                    }
+                    PathComponent::Index(i) => {
+                        self.writer.write_all(b"\"")?;
+                        self.writer
+                            .write_all(itoa::Buffer::new().format(*i).as_bytes())?;
+                        self.writer.write_all(b"\"")?;
+                    }
+                }
+            }
+
+            self.writer.write_all(b" = ")?;
+            // JSON strings, numbers, and bools, and `{}` and `[]`, are all valid TOML
+            serde_json::to_writer(&mut *self.writer, pathvalue.value)?;
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Whether `key` can be written as a TOML key without quotes
+fn is_bare_toml_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// Write scalars as [logfmt](https://brandur.org/logfmt) `key=value` pairs,
+/// like `user.name="Ada Lovelace" user.roles.0=admin`,
+/// all on one line for each document, or each on its own line with `line_per_record`.
+///
+/// Keys are paths with their components joined by `.`,
+/// with any space, `=`, `"`, or control character replaced by `_`.
+/// Strings are quoted (and escaped, as in JSON) when they are empty,
+/// contain any of those characters or a `\`, or would otherwise read as a bool or null.
+/// Empty objects and arrays are written as `{}` and `[]`, unless skipped.
+///
+/// The line of each document is ended when the next document starts, or when finished.
+#[derive(Debug)]
+pub struct LogfmtWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: LogfmtWriterOptions<'writer>,
+    /// Whether a pair has been written on the current line
+    line_started: bool,
+}
+
+impl<'writer, W: Write + ?Sized> LogfmtWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: LogfmtWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            line_started: false,
+        }
+    }
+
+    fn end_line(&mut self) -> Result<()> {
+        if self.line_started {
+            self.writer
+                .write_all(self.options.record_terminator.as_bytes())?;
+            self.line_started = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct LogfmtWriterOptions<'options> {
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Write each pair on its own line, rather than one line for each document
+    pub line_per_record: bool,
+}
+
+impl Default for LogfmtWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            line_per_record: false,
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for LogfmtWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if !should_write(pathvalue, true, self.options.skip_empty_containers) {
+            return Ok(());
+        }
+
+        if self.line_started {
+            self.writer.write_all(b" ")?;
+        }
+
+        for (i, path_component) in pathvalue.path_components.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b".")?;
+            }
+
+            match path_component {
+                PathComponent::Identifier(key) => self.writer.write_all(key.as_bytes())?,
+                PathComponent::NonIdentifier(key) => {
+                    for c in key.chars() {
+                        if needs_logfmt_quotes(c) {
+                            self.writer.write_all(b"_")?;
+                        } else {
+                            self.writer
+                                .write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?;
+                        }
+                    }
+                }
+                PathComponent::Index(i) => {
+                    self.writer
+                        .write_all(itoa::Buffer::new().format(*i).as_bytes())?;
+                }
+            }
+        }
+
+        self.writer.write_all(b"=")?;
+
+        match pathvalue.value {
+            serde_json::Value::String(s)
+                if s.is_empty()
+                    || matches!(s.as_str(), "true" | "false" | "null")
+                    || s.chars().any(|c| c == '\\' || needs_logfmt_quotes(c)) =>
+            {
+                serde_json::to_writer(&mut *self.writer, s)?
+            }
+            serde_json::Value::String(s) => self.writer.write_all(s.as_bytes())?,
+            value => serde_json::to_writer(&mut *self.writer, value)?,
+        }
+
+        self.line_started = true;
+
+        if self.options.line_per_record {
+            self.end_line()?;
+        }
+
+        Ok(())
+    }
+
+    fn start_document(&mut self, _document_index: usize) -> Result<()> {
+        self.end_line()
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.end_line()
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Whether `c` cannot appear in a bare logfmt key or value
+fn needs_logfmt_quotes(c: char) -> bool {
+    c == ' ' || c == '=' || c == '"' || c.is_control()
+}
+
+/// Write the JSON Pointer, type, and value (as JSON) of each `PathValue`
+/// as the rows of a table with a header, with each column padded to align:
+///
+/// ```text
+/// PATH         TYPE    VALUE
+/// /items/0/id  number  1
+/// /name        string  "jindex"
+/// ```
+///
+/// The rows are kept until `finish` is called, when the width of each column is known,
+/// in memory or, beyond `spill.memory_limit`, in temporary files.
+#[derive(Debug)]
+pub struct TableWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: TableWriterOptions<'writer>,
+    /// Each row, as its cells separated by `0xFF`, which is never part of UTF-8
+    rows: RecordSpill,
+    /// The width of each column so far, in characters
+    widths: [usize; 3],
+}
+
+impl<'writer, W: Write + ?Sized> TableWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: TableWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            rows: RecordSpill::new(options.spill.clone()),
+            options,
+            widths: TABLE_HEADER.map(|cell| cell.chars().count()),
+        }
+    }
+}
+
+const TABLE_HEADER: [&str; 3] = ["PATH", "TYPE", "VALUE"];
+
+#[derive(Debug)]
+pub struct TableWriterOptions<'options> {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    pub record_terminator: &'options str,
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+    /// Truncate paths and values longer than this many characters, ending them with `…`
+    pub max_width: Option<usize>,
+    /// Where to keep the rows until the table is written
+    pub spill: SpillOptions,
+}
+
+impl Default for TableWriterOptions<'_> {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            record_terminator: DEFAULT_RECORD_TERMINATOR,
+            sort_keys: false,
+            max_width: None,
+            spill: SpillOptions::default(),
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for TableWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            let mut pointer = Vec::new();
+            write_json_pointer(&mut pointer, &pathvalue.path_components)?;
+
+            let row = [
+                truncate(String::from_utf8(pointer)?, self.options.max_width),
+                value_type_name(pathvalue.value).to_string(),
+                truncate(pathvalue.value.to_string(), self.options.max_width),
+            ];
+
+            for (width, cell) in self.widths.iter_mut().zip(&row) {
+                *width = (*width).max(cell.chars().count());
+            }
+
+            let mut record = Vec::new();
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    record.push(0xFF);
+                }
+                record.extend_from_slice(cell.as_bytes());
+            }
+
+            self.rows.push(record)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.write_table_row(&TABLE_HEADER)?;
+
+        let rows = std::mem::replace(&mut self.rows, RecordSpill::new(self.options.spill.clone()));
+
+        for row in rows.into_records()? {
+            let row = row?;
+            let cells: Vec<&str> = row
+                .split(|b| *b == 0xFF)
+                .map(std::str::from_utf8)
+                .collect::<Result<_, _>>()?;
+
+            self.write_table_row(&cells)?;
+        }
+
+        self.widths = TABLE_HEADER.map(|cell| cell.chars().count());
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<'writer, W: Write + ?Sized> TableWriter<'writer, W> {
+    fn write_table_row(&mut self, cells: &[&str]) -> Result<()> {
+        for (i, (cell, width)) in cells.iter().zip(self.widths).enumerate() {
+            self.writer.write_all(cell.as_bytes())?;
+
+            // no padding after the last column
+            if i + 1 < cells.len() {
+                let padding = width - cell.chars().count() + 2;
+                write!(self.writer, "{:padding$}", "")?;
+            }
+        }
+
+        self.writer
+            .write_all(self.options.record_terminator.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// `s`, cut to `max_width` characters (ending with `…`) if it is longer
+fn truncate(s: String, max_width: Option<usize>) -> String {
+    match max_width {
+        Some(max_width) if s.chars().count() > max_width => {
+            let mut truncated: String = s.chars().take(max_width.saturating_sub(1)).collect();
+            truncated.push('…');
+            truncated
+        }
+        _ => s,
+    }
+}
+
+/// Keep a uniformly random sample of `size` `PathValue`s
+/// (using reservoir sampling), and pass them to the wrapped `sink`
+/// in the order they were visited when `finish` is called.
+///
+/// Like the writers, only scalars are sampled by default,
+/// and the root value is never sampled.
+#[derive(Debug)]
+pub struct SamplingSink<'sink, S: PathValueSink> {
+    sink: &'sink mut S,
+    options: SamplingSinkOptions,
+    rng: Rng,
+    seen: usize,
+    document_index: usize,
+    reservoir: Vec<(usize, usize, OwnedPathValue)>,
+}
+
+impl<'sink, S: PathValueSink> SamplingSink<'sink, S> {
+    pub fn new(sink: &'sink mut S, options: SamplingSinkOptions) -> Self {
+        let seed = options.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+
+        Self {
+            sink,
+            reservoir: Vec::with_capacity(options.size),
+            options,
+            rng: Rng::new(seed),
+            seen: 0,
+            document_index: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SamplingSinkOptions {
+    pub only_scalars: bool,
+    pub skip_empty_containers: bool,
+    /// The number of `PathValue`s to keep
+    pub size: usize,
+    /// Seed for the random number generator, for reproducible samples.
+    /// If `None`, the current time is used.
+    pub seed: Option<u64>,
+}
+
+impl Default for SamplingSinkOptions {
+    fn default() -> Self {
+        Self {
+            only_scalars: true,
+            skip_empty_containers: false,
+            size: 100,
+            seed: None,
+        }
+    }
+}
+
+impl<'sink, S: PathValueSink> PathValueSink for SamplingSink<'sink, S> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if should_write(
+            pathvalue,
+            self.options.only_scalars,
+            self.options.skip_empty_containers,
+        ) {
+            if self.reservoir.len() < self.options.size {
+                self.reservoir.push((
+                    self.seen,
+                    self.document_index,
+                    pathvalue.to_owned_pathvalue(),
+                ));
+            } else {
+                let j = self.rng.below(self.seen + 1);
+                if j < self.options.size {
+                    self.reservoir[j] = (
+                        self.seen,
+                        self.document_index,
+                        pathvalue.to_owned_pathvalue(),
+                    );
+                }
+            }
+
+            self.seen += 1;
+        }
+
+        Ok(())
+    }
+
+    fn start_document(&mut self, document_index: usize) -> Result<()> {
+        self.document_index = document_index;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.reservoir.sort_unstable_by_key(|(seen, _, _)| *seen);
+
+        let mut current_document_index = None;
+
+        for (_, document_index, pathvalue) in self.reservoir.drain(..) {
+            if current_document_index != Some(document_index) {
+                self.sink.start_document(document_index)?;
+                current_document_index = Some(document_index);
+            }
+
+            self.sink.handle_pathvalue(&pathvalue.as_pathvalue())?;
+        }
+
+        self.sink.finish()
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sink.sort_keys()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Write the ingestion `timestamp` of the current document,
+/// taking it to be now if `start_document` has not set it
+fn write_timestamp<W: Write + ?Sized>(
+    writer: &mut W,
+    timestamp: &mut Option<String>,
+) -> Result<()> {
+    writer.write_all(timestamp.get_or_insert_with(rfc3339_now).as_bytes())?;
+    Ok(())
+}
+
+/// The current time in UTC as an RFC 3339 timestamp with milliseconds,
+/// like `2024-01-31T12:00:00.000Z`
+pub(crate) fn rfc3339_now() -> String {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// A small, fast, non-cryptographic random number generator (splitmix64)
+#[derive(Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random number in `0..n`
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// Whether a sink should write the given `PathValue`.
+/// The root value (which has no path components) is never written.
+/// `skip_empty_containers` drops `{}` and `[]` values entirely,
+/// even though `is_scalar` considers them scalars.
+#[inline]
+fn should_write(pathvalue: &PathValue, only_scalars: bool, skip_empty_containers: bool) -> bool {
+    if pathvalue.path_components.is_empty() {
+        return false;
+    }
+
+    if skip_empty_containers && is_empty_container(pathvalue.value) {
+        return false;
+    }
+
+    if only_scalars {
+        is_scalar(pathvalue.value)
+    } else {
+        true
+    }
+}
+
+#[inline]
+fn is_empty_container(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Array(a) => a.is_empty(),
+        serde_json::Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+/// The narrowest of `i64`, `u64`, and `f64` that holds `n`,
+/// so that integers can be told from floats,
+/// and integers too large for an `i64` from those that are not
+pub fn number_type_name(n: &serde_json::Number) -> &'static str {
+    if n.is_i64() {
+        "i64"
+    } else if n.is_u64() {
+        "u64"
+    } else {
+        "f64"
+    }
+}
+
+/// Like [value_type_name], but with the [number_type_name] of numbers
+pub(crate) fn detailed_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Number(n) => number_type_name(n),
+        value => value_type_name(value),
+    }
+}
+
+/// The name of the JSON type of `value`
+pub(crate) fn value_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+#[inline]
+fn is_scalar(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::String(_)
+        | serde_json::Value::Number(_)
+        | serde_json::Value::Bool(_)
+        | serde_json::Value::Null => true,
+        serde_json::Value::Array(a) if a.is_empty() => true,
+        serde_json::Value::Object(o) if o.is_empty() => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,74 @@
+//! Writing the values at chosen JSON Pointers of each document as a row of text,
+//! like a line of a TSV file, for tools that split lines into fields
+
+use crate::path_format::json_pointer_tokens;
+use anyhow::Result;
+use std::io::Write;
+
+/// Write the values at `pointers` in `document` on one line, separated by `separator`,
+/// like a row of a TSV file with a column for each pointer.
+///
+/// Strings are written without quotes, with `\`, tab, newline, and carriage return
+/// escaped as `\\`, `\t`, `\n`, and `\r`; other values are written as JSON.
+/// The field of a pointer that does not exist in `document` is empty.
+pub fn write_picked<W: Write>(
+    writer: &mut W,
+    document: &serde_json::Value,
+    pointers: &[&str],
+    separator: &str,
+) -> Result<()> {
+    for (i, pointer) in pointers.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(separator.as_bytes())?;
+        }
+
+        match document.pointer(pointer) {
+            Some(serde_json::Value::String(s)) => {
+                for c in s.chars() {
+                    match c {
+                        '\\' => writer.write_all(b"\\\\")?,
+                        '\t' => writer.write_all(b"\\t")?,
+                        '\n' => writer.write_all(b"\\n")?,
+                        '\r' => writer.write_all(b"\\r")?,
+                        c => writer.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?,
+                    }
+                }
+            }
+            Some(value) => serde_json::to_writer(&mut *writer, value)?,
+            // `pointer` finds nothing for malformed pointers, too
+            None => {
+                json_pointer_tokens(pointer)?;
+            }
+        }
+    }
+
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_pointers() {
+        let v = serde_json::json!({"id": 7, "msg": "a\tb\\c", "tags": ["x"], "none": null});
+
+        let mut row = Vec::new();
+        write_picked(
+            &mut row,
+            &v,
+            &["/id", "/msg", "/missing", "/tags", "/none"],
+            "\t",
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&row).unwrap(),
+            "7\ta\\tb\\\\c\t\t[\"x\"]\tnull\n"
+        );
+
+        assert!(write_picked(&mut row, &v, &["id"], "\t").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Writing the numeric leaves of documents as Prometheus metrics,
+//! in the [text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+
+use crate::path_value_sink::PathValueSink;
+use crate::{PathComponent, PathValue};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Write each number (and each bool, as `1` or `0`) as a gauge
+/// named for its path, like `{"http": {"requests": 3}}` as `http_requests 3`.
+///
+/// Array indexes become labels, named `index` (then `index_1`, and so on, when nested),
+/// as does the key at each depth given in `labels`,
+/// so `{"services": {"api": {"up": true}}}` with the label `(1, "service")` is
+/// `services_up{service="api"} 1`.
+///
+/// Samples are grouped by metric name, as the format requires,
+/// so they are kept in memory until `finish` is called.
+#[derive(Debug)]
+pub struct PrometheusWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: PrometheusWriterOptions<'writer>,
+    /// The samples of each metric, as their labels and values
+    metrics: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl<'writer, W: Write + ?Sized> PrometheusWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: PrometheusWriterOptions<'writer>) -> Self {
+        Self {
+            writer,
+            options,
+            metrics: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PrometheusWriterOptions<'options> {
+    /// Prepended to every metric name, with a `_`
+    pub prefix: &'options str,
+    /// The depths (0 being the first path component) of keys
+    /// to write as labels, with the names of those labels,
+    /// rather than as part of the metric name
+    pub labels: &'options [(usize, String)],
+    /// Returned by [PathValueSink::sort_keys]
+    pub sort_keys: bool,
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for PrometheusWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let value = match pathvalue.value {
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => u8::from(*b).to_string(),
+            _ => return Ok(()),
+        };
+
+        let mut name = String::new();
+        let mut labels = String::new();
+        let mut indexes = 0;
+
+        if !self.options.prefix.is_empty() {
+            push_metric_name_part(&mut name, self.options.prefix);
+        }
+
+        for (depth, path_component) in pathvalue.path_components.iter().enumerate() {
+            let label = self
+                .options
+                .labels
+                .iter()
+                .find(|(label_depth, _)| *label_depth == depth);
+
+            match (path_component, label) {
+                (
+                    PathComponent::Identifier(key) | PathComponent::NonIdentifier(key),
+                    Some((_, label)),
+                ) => push_label(&mut labels, label, key),
+                (PathComponent::Identifier(key) | PathComponent::NonIdentifier(key), None) => {
+                    push_metric_name_part(&mut name, key)
+                }
+                (PathComponent::Index(i), label) => {
+                    let label = match label {
+                        Some((_, label)) => label.clone(),
+                        None if indexes == 0 => "index".to_string(),
+                        None => format!("index_{}", indexes),
+                    };
+                    indexes += 1;
+                    push_label(&mut labels, &label, &i.to_string());
+                }
+            }
+        }
+
+        if name.is_empty() {
+            name.push_str("value");
+        } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+
+        self.metrics.entry(name).or_default().push((labels, value));
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (name, samples) in &self.metrics {
+            writeln!(self.writer, "# TYPE {} gauge", name)?;
+
+            for (labels, value) in samples {
+                if labels.is_empty() {
+                    writeln!(self.writer, "{} {}", name, value)?;
+                } else {
+                    writeln!(self.writer, "{}{{{}}} {}", name, labels, value)?;
+                }
+            }
+        }
+
+        self.metrics.clear();
+
+        Ok(())
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.options.sort_keys
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Append `part` to the metric `name`, after a `_`,
+/// replacing any character that cannot be in a metric name with `_`
+fn push_metric_name_part(name: &mut String, part: &str) {
+    if !name.is_empty() {
+        name.push('_');
+    }
+
+    name.extend(part.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+            c
+        } else {
+            '_'
+        }
+    }));
+}
+
+/// Append the label `label="value"` to `labels`, after a `,`
+fn push_label(labels: &mut String, label: &str, value: &str) {
+    if !labels.is_empty() {
+        labels.push(',');
+    }
+
+    labels.extend(label.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            c
+        } else {
+            '_'
+        }
+    }));
+    labels.push_str("=\"");
+
+    for c in value.chars() {
+        match c {
+            '\\' => labels.push_str("\\\\"),
+            '"' => labels.push_str("\\\""),
+            '\n' => labels.push_str("\\n"),
+            c => labels.push(c),
+        }
+    }
+
+    labels.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jindex_with_options, JindexOptions, TraversalOrder};
+
+    #[test]
+    fn metrics() {
+        let v = serde_json::json!({
+            "services": {
+                "api": {"up": true, "latency-ms": [12, 15.5]},
+                "db \"main\"": {"up": false, "version": "1.2"}
+            },
+            "2xx": 3
+        });
+
+        let labels = [(1, "service".to_string())];
+
+        let mut output = Vec::new();
+        let mut sink = PrometheusWriter::new(
+            &mut output,
+            PrometheusWriterOptions {
+                prefix: "app",
+                labels: &labels,
+                ..Default::default()
+            },
+        );
+
+        jindex_with_options(
+            &mut sink,
+            &v,
+            &JindexOptions {
+                order: TraversalOrder::Document,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            r#"# TYPE app_2xx gauge
+app_2xx 3
+# TYPE app_services_latency_ms gauge
+app_services_latency_ms{service="api",index="0"} 12
+app_services_latency_ms{service="api",index="1"} 15.5
+# TYPE app_services_up gauge
+app_services_up{service="api"} 1
+app_services_up{service="db \"main\""} 0
+"#
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Documents whose scalar values are kept as the exact text they had in the input,
+//! so that they can be output byte-for-byte, without reformatting numbers or re-escaping strings.
+
+use crate::traversable::{Node, ScalarKind, TraversableValue};
+use anyhow::Result;
+use serde::de::IgnoredAny;
+use serde_json::value::RawValue;
+use std::collections::hash_map::{Entry, HashMap};
+
+/// A JSON document whose leaves (scalars, and empty objects and arrays)
+/// are the raw text they had in the input
+#[derive(Debug)]
+pub enum RawNode {
+    /// The entries of an object, in the order of the input.
+    /// Of the entries with the same key, only the last is kept, in the place of the first
+    Object(Vec<(String, RawNode)>),
+    Array(Vec<RawNode>),
+    Leaf(Box<RawValue>),
+}
+
+impl RawNode {
+    /// The raw input text of this node, if it is a leaf
+    pub fn raw(&self) -> Option<&str> {
+        match self {
+            RawNode::Leaf(raw) => Some(raw.get()),
+            _ => None,
+        }
+    }
+
+    /// Sort the entries of every object in this node by key
+    pub fn sort_keys(&mut self) {
+        let mut stack = vec![self];
+
+        while let Some(node) = stack.pop() {
+            match node {
+                RawNode::Object(object) => {
+                    object.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    stack.extend(object.iter_mut().map(|(_, v)| v));
+                }
+                RawNode::Array(array) => stack.extend(array.iter_mut()),
+                RawNode::Leaf(_) => (),
+            }
+        }
+    }
+}
+
+/// Parse `json`, keeping the raw text of its leaves.
+///
+/// `json` is checked by serde_json, and then split into nodes in one pass over its text,
+/// so that each byte is only read once more however deeply it is nested
+pub fn parse_raw(json: &str) -> Result<RawNode> {
+    serde_json::from_str::<IgnoredAny>(json)?;
+
+    RawParser { json, pos: 0 }.node()
+}
+
+/// Splits a document that is known to be valid JSON into [RawNode]s
+struct RawParser<'a> {
+    json: &'a str,
+    pos: usize,
+}
+
+impl RawParser<'_> {
+    fn peek(&mut self) -> u8 {
+        while self.json.as_bytes()[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        self.json.as_bytes()[self.pos]
+    }
+
+    /// Skip the string that starts at `self.pos`
+    fn skip_string(&mut self) {
+        let bytes = self.json.as_bytes();
+        self.pos += 1;
+
+        while bytes[self.pos] != b'"' {
+            self.pos += if bytes[self.pos] == b'\\' { 2 } else { 1 };
+        }
+
+        self.pos += 1;
+    }
+
+    fn leaf(&self, start: usize) -> Result<RawNode> {
+        Ok(RawNode::Leaf(RawValue::from_string(
+            self.json[start..self.pos].to_string(),
+        )?))
+    }
+
+    fn node(&mut self) -> Result<RawNode> {
+        let first = self.peek();
+        let start = self.pos;
+
+        match first {
+            b'{' => {
+                self.pos += 1;
+
+                if self.peek() == b'}' {
+                    self.pos += 1;
+                    return self.leaf(start);
+                }
+
+                let mut object: Vec<(String, RawNode)> = vec![];
+                let mut positions: HashMap<String, usize> = HashMap::new();
+
+                loop {
+                    self.peek();
+                    let key_start = self.pos;
+                    self.skip_string();
+                    let key: String = serde_json::from_str(&self.json[key_start..self.pos])?;
+
+                    // the colon
+                    self.peek();
+                    self.pos += 1;
+
+                    let value = self.node()?;
+
+                    match positions.entry(key) {
+                        Entry::Occupied(entry) => object[*entry.get()].1 = value,
+                        Entry::Vacant(entry) => {
+                            object.push((entry.key().clone(), value));
+                            entry.insert(object.len() - 1);
+                        }
+                    }
+
+                    // the comma or the end of the object
+                    let separator = self.peek();
+                    self.pos += 1;
+                    if separator == b'}' {
+                        return Ok(RawNode::Object(object));
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+
+                if self.peek() == b']' {
+                    self.pos += 1;
+                    return self.leaf(start);
+                }
+
+                let mut array = vec![];
+
+                loop {
+                    array.push(self.node()?);
+
+                    let separator = self.peek();
+                    self.pos += 1;
+                    if separator == b']' {
+                        return Ok(RawNode::Array(array));
+                    }
+                }
+            }
+            b'"' => {
+                self.skip_string();
+                self.leaf(start)
+            }
+            _ => {
+                let bytes = self.json.as_bytes();
+
+                while self.pos < bytes.len()
+                    && !matches!(bytes[self.pos], b',' | b']' | b'}')
+                    && !bytes[self.pos].is_ascii_whitespace()
+                {
+                    self.pos += 1;
+                }
+
+                self.leaf(start)
+            }
+        }
+    }
+}
+
+type RawEntry<'v> = fn(&'v (String, RawNode)) -> (&'v str, &'v RawNode);
+
+impl TraversableValue for RawNode {
+    type Entries<'v> = std::iter::Map<std::slice::Iter<'v, (String, Self)>, RawEntry<'v>>;
+    type Elements<'v> = std::slice::Iter<'v, Self>;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>> {
+        match self {
+            RawNode::Object(object) => Node::Object(object.iter().map(|(k, v)| (k.as_str(), v))),
+            RawNode::Array(array) => Node::Array(array.iter()),
+            RawNode::Leaf(raw) => Node::Scalar(match raw.get().as_bytes().first() {
+                Some(b'n') => ScalarKind::Null,
+                Some(b't' | b'f') => ScalarKind::Bool,
+                Some(b'"') => ScalarKind::String,
+                Some(b'{' | b'[') => ScalarKind::Other,
+                _ => ScalarKind::Number,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_format::write_json_pointer;
+    use crate::traversable::traverse;
+
+    #[test]
+    fn raw_leaves() {
+        let node = parse_raw(r#"{"a": [1.50, 1e3, "é"], "b": { }, "c": {"d": -0}}"#).unwrap();
+
+        let mut leaves = vec![];
+
+        traverse(&node, |path_components, node| {
+            if let Some(raw) = node.raw() {
+                let mut pointer = Vec::new();
+                write_json_pointer(&mut pointer, path_components)?;
+                leaves.push((String::from_utf8(pointer)?, raw.to_string()));
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        leaves.sort();
+
+        assert_eq!(
+            leaves,
+            vec![
+                ("/a/0".to_string(), "1.50".to_string()),
+                ("/a/1".to_string(), "1e3".to_string()),
+                ("/a/2".to_string(), r#""é""#.to_string()),
+                ("/b".to_string(), "{ }".to_string()),
+                ("/c/d".to_string(), "-0".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn document_order() {
+        let mut node =
+            parse_raw(r#" {"z": 1, "a": {"y": "\"}", "b": [ 2 , {} ]}, "z" : 3 } "#).unwrap();
+
+        let keys = |node: &RawNode| -> Vec<String> {
+            match node {
+                RawNode::Object(object) => object.iter().map(|(k, _)| k.clone()).collect(),
+                _ => vec![],
+            }
+        };
+        let RawNode::Object(object) = &node else {
+            panic!("not an object: {:?}", node);
+        };
+
+        assert_eq!(keys(&node), ["z", "a"]);
+        assert_eq!(object[0].1.raw(), Some("3"));
+        assert_eq!(keys(&object[1].1), ["y", "b"]);
+
+        node.sort_keys();
+        let RawNode::Object(object) = &node else {
+            panic!("not an object: {:?}", node);
+        };
+
+        assert_eq!(keys(&node), ["a", "z"]);
+        assert_eq!(keys(&object[0].1), ["b", "y"]);
+
+        assert_eq!(parse_raw(" -1.0 ").unwrap().raw(), Some("-1.0"));
+        assert!(parse_raw("[1,]").is_err());
+    }
+}
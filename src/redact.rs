@@ -0,0 +1,90 @@
+use crate::path_pattern::PathPattern;
+use crate::{PathComponent, PathValue, ValueTransform};
+
+/// Key fragments that suggest a value is a credential.
+/// Keys are lowercased and stripped of `_` and `-` before comparison,
+/// so `apiKey`, `api_key`, and `API-KEY` all match `apikey`.
+const SECRET_KEY_FRAGMENTS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "apikey",
+    "accesskey",
+    "privatekey",
+    "authorization",
+    "credential",
+];
+
+/// A [ValueTransform] that replaces the values at paths matching
+/// any of `patterns` (or, with `detect_secrets`, values whose keys look like credentials)
+/// with `replacement`
+#[derive(Debug)]
+pub struct Redactor {
+    pub patterns: Vec<PathPattern>,
+    pub detect_secrets: bool,
+    pub replacement: serde_json::Value,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            patterns: vec![],
+            detect_secrets: false,
+            replacement: serde_json::Value::String("[REDACTED]".to_string()),
+        }
+    }
+}
+
+impl Redactor {
+    fn should_redact(&self, path_components: &[PathComponent]) -> bool {
+        (self.detect_secrets
+            && matches!(
+                path_components.last(),
+                Some(PathComponent::Identifier(key) | PathComponent::NonIdentifier(key))
+                    if looks_like_secret(key)
+            ))
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches(path_components))
+    }
+}
+
+impl ValueTransform for Redactor {
+    fn transform(&mut self, pathvalue: &PathValue) -> Option<serde_json::Value> {
+        if self.should_redact(&pathvalue.path_components) {
+            Some(self.replacement.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `key` looks like the name of a credential, like `password` or `apiKey`
+pub fn looks_like_secret(key: &str) -> bool {
+    let normalized: String = key
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    SECRET_KEY_FRAGMENTS
+        .iter()
+        .any(|fragment| normalized.contains(fragment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_keys() {
+        assert!(looks_like_secret("password"));
+        assert!(looks_like_secret("apiKey"));
+        assert!(looks_like_secret("GITHUB_TOKEN"));
+        assert!(looks_like_secret("x-api-key"));
+        assert!(!looks_like_secret("name"));
+        assert!(!looks_like_secret("keyboard"));
+    }
+}
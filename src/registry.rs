@@ -0,0 +1,298 @@
+//! A runtime registry of sinks by name, so that tools like benchmarks
+//! can run every output format without naming each one
+
+use crate::avro::{AvroWriter, AvroWriterOptions};
+use crate::path_value_sink::{
+    GronWriter, GronWriterOptions, IndentedWriter, IndentedWriterOptions, JSONPointerWriter,
+    JSONPointerWriterOptions, JSONWriter, JsonWriterOptions, LogfmtWriter, LogfmtWriterOptions,
+    PathValueSink, ShapeDedupWriter, ShapeDedupWriterOptions, TableWriter, TableWriterOptions,
+    TomlWriter, TomlWriterOptions, TypesWriter, TypesWriterOptions, XPathWriter,
+    XPathWriterOptions, YamlWriter, YamlWriterOptions,
+};
+use crate::prometheus::{PrometheusWriter, PrometheusWriterOptions};
+use crate::schema::{SchemaFormat, SchemaWriter, SchemaWriterOptions};
+use std::io::Write;
+
+/// Constructs a sink with its default options, writing to the given writer
+pub type SinkConstructor = for<'w> fn(&'w mut dyn Write) -> Box<dyn PathValueSink + 'w>;
+
+/// A sink in a [SinkRegistry]
+#[derive(Clone, Copy, Debug)]
+pub struct RegisteredSink {
+    /// The sink's name, like `gron`
+    pub name: &'static str,
+    /// The file extension for the sink's output, like `.gron`
+    pub extension: &'static str,
+    /// The long names of the options of the `jindex` binary that apply only to the sink's
+    /// format, like `gron-single-quotes`, or none for sinks that are not formats of the binary
+    pub options: &'static [&'static str],
+    pub new: SinkConstructor,
+}
+
+/// A list of sinks by name, starting with this crate's writers
+/// (see [SinkRegistry::builtin]), to which other sinks can be added
+#[derive(Clone, Debug)]
+pub struct SinkRegistry {
+    sinks: Vec<RegisteredSink>,
+}
+
+impl Default for SinkRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl SinkRegistry {
+    /// A registry of the writers of this crate, with every output format of the binary
+    pub fn builtin() -> Self {
+        Self {
+            sinks: vec![
+                RegisteredSink {
+                    name: "gron",
+                    extension: ".gron",
+                    options: &[
+                        "gron-assignment",
+                        "gron-no-semicolon",
+                        "gron-single-quotes",
+                        "gron-ascii",
+                        "gron-identifiers",
+                        "color",
+                        "index-width",
+                        "timestamp",
+                    ],
+                    new: |writer| Box::new(GronWriter::new(writer, GronWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "json-pointer",
+                    extension: ".txt",
+                    options: &[
+                        "separator",
+                        "separator-collision",
+                        "uri-fragment",
+                        "color",
+                        "index-width",
+                        "timestamp",
+                    ],
+                    new: |writer| {
+                        Box::new(JSONPointerWriter::new(
+                            writer,
+                            JSONPointerWriterOptions::default(),
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "json",
+                    extension: ".jsonl",
+                    options: &[
+                        "include-pointer",
+                        "include-type",
+                        "number-types",
+                        "include-depth",
+                        "include-document",
+                        "timestamp",
+                        "fields",
+                        "envelope",
+                    ],
+                    new: |writer| Box::new(JSONWriter::new(writer, JsonWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "types",
+                    extension: ".tsv",
+                    options: &[],
+                    new: |writer| Box::new(TypesWriter::new(writer, TypesWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "indented",
+                    extension: ".indented",
+                    options: &[],
+                    new: |writer| {
+                        Box::new(IndentedWriter::new(
+                            writer,
+                            IndentedWriterOptions::default(),
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "yaml",
+                    extension: ".yaml",
+                    options: &[],
+                    new: |writer| Box::new(YamlWriter::new(writer, YamlWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "toml",
+                    extension: ".toml",
+                    options: &[],
+                    new: |writer| Box::new(TomlWriter::new(writer, TomlWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "xpath",
+                    extension: ".xpath",
+                    options: &["separator"],
+                    new: |writer| Box::new(XPathWriter::new(writer, XPathWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "avro",
+                    extension: ".avro",
+                    options: &[],
+                    new: |writer| {
+                        Box::new(AvroWriter::new(
+                            writer,
+                            AvroWriterOptions {
+                                // rather than a random one, so that the output is reproducible
+                                sync_marker: Some([0; 16]),
+                                ..Default::default()
+                            },
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    // the records, which `--format duckdb` writes to a file of their own
+                    // and loads into DuckDB with a script from `write_duckdb_script`
+                    name: "duckdb",
+                    extension: ".jsonl",
+                    options: &["duckdb-records", "duckdb-table"],
+                    new: |writer| {
+                        Box::new(JSONWriter::new(
+                            writer,
+                            JsonWriterOptions {
+                                include_pointer: true,
+                                include_type: true,
+                                ..Default::default()
+                            },
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "prometheus",
+                    extension: ".prom",
+                    options: &["metric-prefix", "metric-label"],
+                    new: |writer| {
+                        Box::new(PrometheusWriter::new(
+                            writer,
+                            PrometheusWriterOptions::default(),
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "logfmt",
+                    extension: ".logfmt",
+                    options: &["logfmt-line-per-record"],
+                    new: |writer| {
+                        Box::new(LogfmtWriter::new(writer, LogfmtWriterOptions::default()))
+                    },
+                },
+                RegisteredSink {
+                    name: "table",
+                    extension: ".table",
+                    options: &["table-max-width"],
+                    new: |writer| Box::new(TableWriter::new(writer, TableWriterOptions::default())),
+                },
+                RegisteredSink {
+                    name: "graphql",
+                    extension: ".graphql",
+                    options: &[],
+                    new: |writer| {
+                        Box::new(SchemaWriter::new(
+                            writer,
+                            SchemaWriterOptions {
+                                format: SchemaFormat::GraphQl,
+                                ..Default::default()
+                            },
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "openapi",
+                    extension: ".openapi.json",
+                    options: &["schema-name"],
+                    new: |writer| {
+                        Box::new(SchemaWriter::new(
+                            writer,
+                            SchemaWriterOptions {
+                                format: SchemaFormat::OpenApi,
+                                ..Default::default()
+                            },
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "proto",
+                    extension: ".proto",
+                    options: &["schema-name"],
+                    new: |writer| {
+                        Box::new(SchemaWriter::new(
+                            writer,
+                            SchemaWriterOptions {
+                                format: SchemaFormat::Proto,
+                                ..Default::default()
+                            },
+                        ))
+                    },
+                },
+                RegisteredSink {
+                    name: "dedup-shapes",
+                    extension: ".txt",
+                    options: &[],
+                    new: |writer| {
+                        Box::new(ShapeDedupWriter::new(
+                            writer,
+                            ShapeDedupWriterOptions::default(),
+                        ))
+                    },
+                },
+            ],
+        }
+    }
+
+    /// Add `sink`, replacing any sink already registered with the same name
+    pub fn register(&mut self, sink: RegisteredSink) {
+        match self.sinks.iter_mut().find(|s| s.name == sink.name) {
+            Some(existing) => *existing = sink,
+            None => self.sinks.push(sink),
+        }
+    }
+
+    /// The sink registered as `name`, if any
+    pub fn get(&self, name: &str) -> Option<&RegisteredSink> {
+        self.sinks.iter().find(|sink| sink.name == name)
+    }
+
+    /// Every registered sink, in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredSink> {
+        self.sinks.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex;
+
+    #[test]
+    fn builtin_sinks() {
+        let v = serde_json::json!({"a": [1, "b"]});
+
+        for sink in SinkRegistry::builtin().iter() {
+            let mut output = Vec::new();
+            let mut s = (sink.new)(&mut output);
+            jindex(&mut s, &v).unwrap();
+            s.finish().unwrap();
+            drop(s);
+
+            assert!(!output.is_empty(), "{} wrote nothing", sink.name);
+        }
+
+        let mut registry = SinkRegistry::builtin();
+        let count = registry.iter().count();
+        registry.register(RegisteredSink {
+            name: "gron",
+            extension: ".g",
+            options: &[],
+            new: |writer| Box::new(GronWriter::new(writer, GronWriterOptions::default())),
+        });
+
+        assert_eq!(registry.get("gron").unwrap().extension, ".g");
+        assert_eq!(registry.iter().count(), count);
+        assert!(registry.get("csv").is_none());
+    }
+}
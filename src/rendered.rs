@@ -0,0 +1,141 @@
+//! Traversal for sinks that write only leaves, with their paths in gron notation,
+//! like [GronWriter](crate::path_value_sink::GronWriter) with its default options.
+//!
+//! Rather than allocating a `Vec` of [PathComponent]s for every node,
+//! [jindex_rendered] keeps the path to the current node rendered in one buffer,
+//! appending each component on [enter](PathEventSink::enter) and truncating it on
+//! [leave](PathEventSink::leave), and passes leaves to [PathValueSink::handle_rendered_leaf]
+//! with a borrow of that buffer.
+
+use crate::events::{jindex_events, PathEventSink};
+use crate::path_format::{write_gron_path_with_style, GronPathStyle};
+use crate::path_value_sink::PathValueSink;
+use crate::{JindexOptions, PathComponent};
+use anyhow::Result;
+
+/// Like [jindex_with_options](crate::jindex_with_options), which calls this
+/// for sinks whose [PathValueSink::rendered_path_style] is `Some`,
+/// passing each leaf to `sink.handle_rendered_leaf` with its path rendered in `style`,
+/// and each other object and array to `sink.handle_rendered_branch`.
+/// Leaves are passed in the same order as by `jindex_with_options`
+pub fn jindex_rendered<'pv, S: PathValueSink>(
+    sink: &mut S,
+    json: &'pv serde_json::Value,
+    options: &JindexOptions<'pv>,
+    style: GronPathStyle,
+) -> Result<()> {
+    jindex_events(
+        &mut RenderedPaths {
+            sink,
+            style,
+            path: Vec::with_capacity(256),
+            path_lens: vec![],
+        },
+        json,
+        options,
+    )
+}
+
+/// Renders the current path into `path`, passing leaves and branches to `sink`
+struct RenderedPaths<'sink, S: PathValueSink> {
+    sink: &'sink mut S,
+    style: GronPathStyle,
+    path: Vec<u8>,
+    /// The length of `path` before each component entered
+    path_lens: Vec<usize>,
+}
+
+impl<S: PathValueSink> PathEventSink for RenderedPaths<'_, S> {
+    fn enter(&mut self, component: PathComponent) -> Result<()> {
+        self.path_lens.push(self.path.len());
+        write_gron_path_with_style(&mut self.path, "", &[component], self.style)
+    }
+
+    fn leave(&mut self) -> Result<()> {
+        if let Some(len) = self.path_lens.pop() {
+            self.path.truncate(len);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn value(&mut self, value: &serde_json::Value) -> Result<()> {
+        match value {
+            serde_json::Value::Object(object) if !object.is_empty() => {
+                self.sink.handle_rendered_branch(value)
+            }
+            serde_json::Value::Array(array) if !array.is_empty() => {
+                self.sink.handle_rendered_branch(value)
+            }
+            leaf => self.sink.handle_rendered_leaf(&self.path, leaf),
+        }
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sink.sort_keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_pattern::PathPattern;
+    use crate::path_value_sink::{GronWriter, GronWriterOptions};
+    use crate::{
+        jindex_with_options, jindex_with_transform, ArraySample, PathValue, TraversalOrder,
+    };
+
+    #[test]
+    fn rendered_paths() {
+        let v = serde_json::json!({
+            "a": [1, {"b c": null, "d": {}}, [], "x"],
+            "e": {"f": [true, false], "g-h": {"i": 2.5}},
+            "j": "k"
+        });
+
+        for options in [
+            JindexOptions::default(),
+            JindexOptions {
+                order: TraversalOrder::Document,
+                exclude: vec![PathPattern::glob("e.f")],
+                array_sample: Some(ArraySample::Tail(2)),
+                ..Default::default()
+            },
+            JindexOptions {
+                root: Some("/e"),
+                absolute_paths: true,
+                path_prefix: vec![PathComponent::Index(3)],
+                ..Default::default()
+            },
+        ] {
+            for sorted in [false, true] {
+                let gron_options = || GronWriterOptions {
+                    sort_keys: sorted,
+                    skip_empty_containers: sorted,
+                    index_width: if sorted { 2 } else { 0 },
+                    ..Default::default()
+                };
+
+                let mut rendered = Vec::new();
+                let mut sink = GronWriter::new(&mut rendered, gron_options());
+                assert!(sink.rendered_path_style().is_some());
+                jindex_with_options(&mut sink, &v, &options).unwrap();
+
+                // a transform turns off rendered paths
+                let mut unrendered = Vec::new();
+                jindex_with_transform(
+                    &mut GronWriter::new(&mut unrendered, gron_options()),
+                    &v,
+                    &options,
+                    &mut |_: &PathValue| None,
+                )
+                .unwrap();
+
+                assert_eq!(
+                    std::str::from_utf8(&rendered).unwrap(),
+                    std::str::from_utf8(&unrendered).unwrap()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// A writer that splits its output across numbered files, like `split(1)`,
+/// starting a new file once the current one has `max_records` records
+/// or would grow past `max_bytes` bytes.
+///
+/// Records are the runs of bytes ending with `record_terminator`,
+/// and are never split across files, so a single record larger than `max_bytes`
+/// gets a file of its own.
+#[derive(Debug)]
+pub struct RotatingWriter {
+    options: RotatingWriterOptions,
+    file: Option<BufWriter<std::fs::File>>,
+    files_created: usize,
+    records_in_file: usize,
+    bytes_in_file: usize,
+    /// Bytes written since the end of the last complete record
+    pending: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct RotatingWriterOptions {
+    /// Files are named this, followed by their zero-padded number, like `part-00000`
+    pub prefix: PathBuf,
+    pub record_terminator: Vec<u8>,
+    pub max_records: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for RotatingWriterOptions {
+    fn default() -> Self {
+        Self {
+            prefix: PathBuf::from("part-"),
+            record_terminator: b"\n".to_vec(),
+            max_records: None,
+            max_bytes: None,
+        }
+    }
+}
+
+impl RotatingWriter {
+    pub fn new(options: RotatingWriterOptions) -> Self {
+        Self {
+            options,
+            file: None,
+            files_created: 0,
+            records_in_file: 0,
+            bytes_in_file: 0,
+            pending: vec![],
+        }
+    }
+
+    /// Write one complete record to the current file, starting a new file first if needed
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        let records_full = self
+            .options
+            .max_records
+            .is_some_and(|max_records| self.records_in_file >= max_records);
+
+        let bytes_full = self.options.max_bytes.is_some_and(|max_bytes| {
+            self.bytes_in_file > 0 && self.bytes_in_file + record.len() > max_bytes
+        });
+
+        if self.file.is_none() || records_full || bytes_full {
+            self.rotate()?;
+        }
+
+        if let Some(file) = &mut self.file {
+            file.write_all(record)?;
+        }
+
+        self.records_in_file += 1;
+        self.bytes_in_file += record.len();
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+
+        let mut path = self.options.prefix.clone().into_os_string();
+        path.push(format!("{:05}", self.files_created));
+
+        self.file = Some(BufWriter::new(std::fs::File::create(path)?));
+        self.files_created += 1;
+        self.records_in_file = 0;
+        self.bytes_in_file = 0;
+
+        Ok(())
+    }
+
+    /// Write any trailing bytes that did not end with a record terminator
+    /// as a final record, and flush the current file
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.write_record(&pending)?;
+        }
+
+        self.flush()
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let terminator_len = self.options.record_terminator.len();
+
+        if terminator_len == 0 {
+            return Ok(buf.len());
+        }
+
+        let mut record_start = 0;
+        let mut i = 0;
+
+        while i + terminator_len <= self.pending.len() {
+            if self.pending[i..i + terminator_len] == self.options.record_terminator[..] {
+                let record_end = i + terminator_len;
+                let record = self.pending[record_start..record_end].to_vec();
+                self.write_record(&record)?;
+                record_start = record_end;
+                i = record_end;
+            } else {
+                i += 1;
+            }
+        }
+
+        self.pending.drain(..record_start);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_on_record_boundaries() {
+        let dir = std::env::temp_dir().join(format!("jindex-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut writer = RotatingWriter::new(RotatingWriterOptions {
+            prefix: dir.join("lines-"),
+            max_records: Some(2),
+            ..Default::default()
+        });
+
+        writer.write_all(b"a\nb").unwrap();
+        writer.write_all(b"\nc\nd").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(std::fs::read(dir.join("lines-00000")).unwrap(), b"a\nb\n");
+        assert_eq!(std::fs::read(dir.join("lines-00001")).unwrap(), b"c\nd");
+
+        let mut writer = RotatingWriter::new(RotatingWriterOptions {
+            prefix: dir.join("bytes-"),
+            record_terminator: b"\0".to_vec(),
+            max_bytes: Some(4),
+            ..Default::default()
+        });
+
+        writer.write_all(b"ab\0cd\0efghi\0j\0").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(std::fs::read(dir.join("bytes-00000")).unwrap(), b"ab\0");
+        assert_eq!(std::fs::read(dir.join("bytes-00001")).unwrap(), b"cd\0");
+        assert_eq!(std::fs::read(dir.join("bytes-00002")).unwrap(), b"efghi\0");
+        assert_eq!(std::fs::read(dir.join("bytes-00003")).unwrap(), b"j\0");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
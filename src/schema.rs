@@ -0,0 +1,615 @@
+//! Inferring the structure of documents from their paths and values,
+//! with the elements of each array merged into one shape,
+//! and writing that structure as a schema or query in other languages
+
+use crate::path_value_sink::PathValueSink;
+use crate::{PathComponent, PathValue};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// The inferred structure of the values at one path,
+/// with how many values of each type were seen there
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Shape {
+    /// How many values were seen at this path
+    pub count: usize,
+    pub nulls: usize,
+    pub bools: usize,
+    pub integers: usize,
+    /// Numbers that are not integers
+    pub floats: usize,
+    pub strings: usize,
+    pub arrays: usize,
+    pub objects: usize,
+    /// The merged shape of the elements of every array seen at this path
+    pub elements: Option<Box<Shape>>,
+    /// The shape of each key of every object seen at this path
+    pub fields: BTreeMap<String, Shape>,
+}
+
+impl Shape {
+    /// Count `value` as seen at this path
+    pub fn observe(&mut self, value: &serde_json::Value) {
+        self.count += 1;
+
+        match value {
+            serde_json::Value::Null => self.nulls += 1,
+            serde_json::Value::Bool(_) => self.bools += 1,
+            serde_json::Value::Number(n) if n.is_f64() => self.floats += 1,
+            serde_json::Value::Number(_) => self.integers += 1,
+            serde_json::Value::String(_) => self.strings += 1,
+            serde_json::Value::Array(_) => self.arrays += 1,
+            serde_json::Value::Object(_) => self.objects += 1,
+        }
+    }
+
+    /// The shape at `path_components` below this one, creating it if it has not been seen.
+    /// Every index of an array leads to the same shape, `elements`
+    pub fn descendant_mut(&mut self, path_components: &[PathComponent]) -> &mut Shape {
+        let mut shape = self;
+
+        for path_component in path_components {
+            shape = match path_component {
+                PathComponent::Identifier(key) | PathComponent::NonIdentifier(key) => {
+                    if !shape.fields.contains_key(key.as_ref()) {
+                        shape.fields.insert(key.to_string(), Shape::default());
+                    }
+                    shape.fields.get_mut(key.as_ref()).expect("inserted above")
+                }
+                PathComponent::Index(_) => shape.elements.get_or_insert_with(Default::default),
+            };
+        }
+
+        shape
+    }
+
+    /// Whether every value seen at `key` of this shape's objects was present,
+    /// which is to say whether the field is required
+    pub fn is_required(&self, key: &str) -> bool {
+        self.fields
+            .get(key)
+            .is_some_and(|field| field.count >= self.objects)
+    }
+
+    /// The shape of the values inside any arrays at this path,
+    /// through any number of nested arrays
+    pub fn innermost(&self) -> &Shape {
+        let mut shape = self;
+
+        while let Some(elements) = &shape.elements {
+            shape = elements;
+        }
+
+        shape
+    }
+}
+
+/// Infer the [Shape] of every document passed to `jindex`,
+/// which must pass every node (not only the leaves) to this sink, as `jindex` does
+#[derive(Debug, Default)]
+pub struct ShapeSink {
+    pub root: Shape,
+}
+
+impl ShapeSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PathValueSink for ShapeSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        self.root
+            .descendant_mut(&pathvalue.path_components)
+            .observe(pathvalue.value);
+
+        Ok(())
+    }
+}
+
+/// The language in which a [SchemaWriter] writes the inferred shape
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchemaFormat {
+    /// A GraphQL query with a selection set of every field, like
+    /// `query {\n  user {\n    id\n  }\n}`
+    #[default]
+    GraphQl,
+    /// An OpenAPI 3.1 `components.schemas` fragment, as JSON
+    OpenApi,
+    /// A proto3 message definition, with nested messages for objects
+    /// and repeated fields for arrays
+    Proto,
+}
+
+#[derive(Debug)]
+pub struct SchemaWriterOptions {
+    pub format: SchemaFormat,
+    /// The name of the schema (or type) of the root of the documents
+    pub name: String,
+}
+
+impl Default for SchemaWriterOptions {
+    fn default() -> Self {
+        Self {
+            format: SchemaFormat::default(),
+            name: "Root".to_string(),
+        }
+    }
+}
+
+/// Infer the [Shape] of the documents passed to it (see [ShapeSink]),
+/// and write it in the given [SchemaFormat] when finished
+#[derive(Debug)]
+pub struct SchemaWriter<'writer, W: Write + ?Sized> {
+    writer: &'writer mut W,
+    options: SchemaWriterOptions,
+    shapes: ShapeSink,
+}
+
+impl<'writer, W: Write + ?Sized> SchemaWriter<'writer, W> {
+    pub fn new(writer: &'writer mut W, options: SchemaWriterOptions) -> Self {
+        Self {
+            writer,
+            options,
+            shapes: ShapeSink::new(),
+        }
+    }
+}
+
+impl<'writer, W: Write + ?Sized> PathValueSink for SchemaWriter<'writer, W> {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        self.shapes.handle_pathvalue(pathvalue)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self.options.format {
+            SchemaFormat::GraphQl => write_graphql_query(self.writer, &self.shapes.root),
+            SchemaFormat::OpenApi => {
+                write_openapi_schemas(self.writer, &self.options.name, &self.shapes.root)
+            }
+            SchemaFormat::Proto => write_proto(self.writer, &self.options.name, &self.shapes.root),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write a GraphQL query selecting every field of `shape`,
+/// with the fields of arrays of objects selected as for a single object.
+///
+/// Keys that are not GraphQL names have every other character replaced with `_`
+/// (and are prefixed with `_` if they start with a digit), so the query may need editing.
+pub fn write_graphql_query<W: Write + ?Sized>(writer: &mut W, shape: &Shape) -> Result<()> {
+    writer.write_all(b"query {\n")?;
+    write_graphql_selections(writer, shape.innermost(), 1)?;
+    writer.write_all(b"}\n")?;
+    Ok(())
+}
+
+fn write_graphql_selections<W: Write + ?Sized>(
+    writer: &mut W,
+    shape: &Shape,
+    depth: usize,
+) -> Result<()> {
+    for (key, field) in &shape.fields {
+        write_indent(writer, depth)?;
+        writer.write_all(graphql_name(key).as_bytes())?;
+
+        let field = field.innermost();
+
+        if field.fields.is_empty() {
+            writer.write_all(b"\n")?;
+        } else {
+            writer.write_all(b" {\n")?;
+            write_graphql_selections(writer, field, depth + 1)?;
+            write_indent(writer, depth)?;
+            writer.write_all(b"}\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `shape` as the OpenAPI 3.1 schema `name`, in a `components.schemas` fragment,
+/// like `{"components":{"schemas":{"Root":{"type":"object",...}}}}`
+pub fn write_openapi_schemas<W: Write + ?Sized>(
+    writer: &mut W,
+    name: &str,
+    shape: &Shape,
+) -> Result<()> {
+    let fragment = serde_json::json!({
+        "components": {
+            "schemas": {
+                name: json_schema(shape)
+            }
+        }
+    });
+
+    serde_json::to_writer_pretty(&mut *writer, &fragment)?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// The JSON Schema (as used by OpenAPI 3.1) for the values of `shape`.
+/// A shape with values of more than one type has a list of types, like `["string","null"]`,
+/// and object keys that were not in every object are not `required`
+pub fn json_schema(shape: &Shape) -> serde_json::Value {
+    let mut types = vec![];
+
+    if shape.objects > 0 {
+        types.push("object");
+    }
+    if shape.arrays > 0 {
+        types.push("array");
+    }
+    if shape.strings > 0 {
+        types.push("string");
+    }
+    if shape.floats > 0 {
+        types.push("number");
+    } else if shape.integers > 0 {
+        types.push("integer");
+    }
+    if shape.bools > 0 {
+        types.push("boolean");
+    }
+    if shape.nulls > 0 {
+        types.push("null");
+    }
+
+    let mut schema = serde_json::Map::new();
+
+    match types.as_slice() {
+        [] => (),
+        [t] => {
+            schema.insert("type".to_string(), (*t).into());
+        }
+        types => {
+            schema.insert("type".to_string(), types.into());
+        }
+    }
+
+    if shape.arrays > 0 {
+        let items = shape
+            .elements
+            .as_deref()
+            .map(json_schema)
+            .unwrap_or_else(|| serde_json::json!({}));
+        schema.insert("items".to_string(), items);
+    }
+
+    if shape.objects > 0 {
+        let properties: serde_json::Map<String, serde_json::Value> = shape
+            .fields
+            .iter()
+            .map(|(key, field)| (key.clone(), json_schema(field)))
+            .collect();
+
+        let required: Vec<&str> = shape
+            .fields
+            .keys()
+            .filter(|key| shape.is_required(key))
+            .map(String::as_str)
+            .collect();
+
+        schema.insert("properties".to_string(), properties.into());
+
+        if !required.is_empty() {
+            schema.insert("required".to_string(), required.into());
+        }
+    }
+
+    schema.into()
+}
+
+/// Write a proto3 file with a message `name` for `shape`, as a starting point
+/// for a schema, as the types of fields are only guessed from the values seen.
+///
+/// Objects are nested messages named for their keys, and arrays are repeated fields.
+/// Fields that were sometimes null or missing are `optional`, and field names
+/// are `snake_case`, with a `json_name` when that differs from the key.
+/// Values of mixed types, and arrays of arrays, are `google.protobuf.Value`s
+/// and `google.protobuf.ListValue`s.
+pub fn write_proto<W: Write + ?Sized>(writer: &mut W, name: &str, shape: &Shape) -> Result<()> {
+    let mut messages = Vec::new();
+    let mut uses_struct = false;
+
+    write_proto_message(
+        &mut messages,
+        &proto_message_name(name),
+        shape.innermost(),
+        0,
+        &mut uses_struct,
+    )?;
+
+    writer.write_all(b"syntax = \"proto3\";\n\n")?;
+    if uses_struct {
+        writer.write_all(b"import \"google/protobuf/struct.proto\";\n\n")?;
+    }
+    writer.write_all(&messages)?;
+
+    Ok(())
+}
+
+fn write_proto_message<W: Write + ?Sized>(
+    writer: &mut W,
+    name: &str,
+    shape: &Shape,
+    depth: usize,
+    uses_struct: &mut bool,
+) -> Result<()> {
+    write_indent(writer, depth)?;
+    writeln!(writer, "message {} {{", name)?;
+
+    let mut nested = vec![];
+
+    for (number, (key, field)) in shape.fields.iter().enumerate() {
+        let repeated = field.arrays > 0 && field.count == field.arrays;
+        let element = if repeated {
+            field.elements.as_deref()
+        } else {
+            Some(field)
+        };
+
+        let field_type = match element {
+            Some(element) if repeated && element.arrays > 0 => {
+                *uses_struct = true;
+                "google.protobuf.ListValue".to_string()
+            }
+            Some(element)
+                if element.objects > 0 && element.count == element.objects + element.nulls =>
+            {
+                let message_name = proto_message_name(key);
+                nested.push((message_name.clone(), element));
+                message_name
+            }
+            Some(element) => match proto_scalar_type(element) {
+                Some(scalar_type) => scalar_type.to_string(),
+                None => {
+                    *uses_struct = true;
+                    "google.protobuf.Value".to_string()
+                }
+            },
+            None => {
+                *uses_struct = true;
+                "google.protobuf.Value".to_string()
+            }
+        };
+
+        let optional = !repeated
+            && proto_scalar_type(field).is_some()
+            && (field.nulls > 0 || !shape.is_required(key));
+
+        let field_name = proto_field_name(key);
+
+        write_indent(writer, depth + 1)?;
+        if repeated {
+            writer.write_all(b"repeated ")?;
+        } else if optional {
+            writer.write_all(b"optional ")?;
+        }
+        write!(writer, "{} {} = {}", field_type, field_name, number + 1)?;
+        if field_name != *key {
+            write!(writer, " [json_name = {}]", serde_json::to_string(key)?)?;
+        }
+        writer.write_all(b";\n")?;
+    }
+
+    for (message_name, element) in nested {
+        writer.write_all(b"\n")?;
+        write_proto_message(writer, &message_name, element, depth + 1, uses_struct)?;
+    }
+
+    write_indent(writer, depth)?;
+    writer.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// The proto3 scalar type of the values of `shape`, ignoring nulls,
+/// if they are all of one type
+fn proto_scalar_type(shape: &Shape) -> Option<&'static str> {
+    let non_null = shape.count - shape.nulls;
+
+    if non_null == 0 {
+        None
+    } else if shape.strings == non_null {
+        Some("string")
+    } else if shape.bools == non_null {
+        Some("bool")
+    } else if shape.integers == non_null {
+        Some("int64")
+    } else if shape.integers + shape.floats == non_null {
+        Some("double")
+    } else {
+        None
+    }
+}
+
+/// `key` as a `snake_case` proto field name
+fn proto_field_name(key: &str) -> String {
+    let mut name = String::new();
+
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            if !name.is_empty() && !name.ends_with('_') {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            name.push(c);
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "field_");
+    }
+
+    name
+}
+
+/// `key` as a `PascalCase` proto message name
+fn proto_message_name(key: &str) -> String {
+    let mut name = String::new();
+    let mut upper = true;
+
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() {
+            if upper {
+                name.push(c.to_ascii_uppercase());
+            } else {
+                name.push(c);
+            }
+            upper = false;
+        } else {
+            upper = true;
+        }
+    }
+
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'M');
+    }
+
+    name
+}
+
+/// `key` as a GraphQL name, which must match `[_A-Za-z][_0-9A-Za-z]*`
+fn graphql_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+fn write_indent<W: Write + ?Sized>(writer: &mut W, depth: usize) -> Result<()> {
+    for _ in 0..depth {
+        writer.write_all(b"  ")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex;
+
+    fn infer(value: serde_json::Value) -> Shape {
+        let mut sink = ShapeSink::new();
+        jindex(&mut sink, &value).unwrap();
+        sink.root
+    }
+
+    #[test]
+    fn merged_elements() {
+        let shape = infer(serde_json::json!(
+            {"items": [{"id": 1, "name": "a"}, {"id": 2.5}, {"id": null}]}
+        ));
+
+        let items = shape.fields["items"].elements.as_deref().unwrap();
+
+        assert_eq!(items.objects, 3);
+        assert_eq!(items.fields["id"].integers, 1);
+        assert_eq!(items.fields["id"].floats, 1);
+        assert_eq!(items.fields["id"].nulls, 1);
+        assert!(items.is_required("id"));
+        assert!(!items.is_required("name"));
+    }
+
+    #[test]
+    fn openapi() {
+        let shape = infer(serde_json::json!(
+            {"items": [{"id": 1, "name": "a"}, {"id": 2.5, "name": null}, {"id": 3}], "empty": []}
+        ));
+
+        assert_eq!(
+            json_schema(&shape),
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "empty": {"type": "array", "items": {}},
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "id": {"type": "number"},
+                                "name": {"type": ["string", "null"]}
+                            },
+                            "required": ["id"]
+                        }
+                    }
+                },
+                "required": ["empty", "items"]
+            })
+        );
+    }
+
+    #[test]
+    fn proto() {
+        let shape = infer(serde_json::json!([
+            {"userId": 1, "tags": ["a"], "address": {"zip-code": "x"}, "score": 0.5, "any": 1},
+            {"userId": 2, "tags": [], "address": {"zip-code": "y"}, "score": null, "any": "a"}
+        ]));
+
+        let mut proto = Vec::new();
+        write_proto(&mut proto, "user", &shape).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&proto).unwrap(),
+            r#"syntax = "proto3";
+
+import "google/protobuf/struct.proto";
+
+message User {
+  Address address = 1;
+  google.protobuf.Value any = 2;
+  optional double score = 3;
+  repeated string tags = 4;
+  int64 user_id = 5 [json_name = "userId"];
+
+  message Address {
+    string zip_code = 1 [json_name = "zip-code"];
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn graphql() {
+        let shape = infer(serde_json::json!(
+            {"user": {"id": 1, "first-name": "a", "posts": [[{"title": "t"}]], "tags": ["x"]}}
+        ));
+
+        let mut query = Vec::new();
+        write_graphql_query(&mut query, &shape).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&query).unwrap(),
+            "query {
+  user {
+    first_name
+    id
+    posts {
+      title
+    }
+    tags
+  }
+}
+"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Searching the paths of a document through its [index](crate::index).
+//!
+//! The entries of an index are sorted by pointer, so they are the leaves of a trie
+//! of the pointers in order: the entries under a prefix are one contiguous run,
+//! found by binary search, and only that run is read.
+//! A glob is searched for under the literal segments at its start, like `users`
+//! for `users.*.email`, while a fuzzy search reads every entry.
+
+use crate::index::{IndexEntry, PathIndex, ValueKind};
+use crate::path_format::{escape_json_pointer_token, json_pointer_tokens, parse_array_index};
+use crate::path_pattern::PathPattern;
+use crate::PathComponent;
+use anyhow::Result;
+use std::io::{Read, Seek};
+
+/// What [search_paths] looks for
+#[derive(Clone, Debug)]
+pub enum PathQuery {
+    /// The value at this JSON Pointer and all of its descendants
+    Prefix(String),
+    /// The values whose paths match this pattern
+    Glob(PathPattern),
+    /// The values whose JSON Pointers are within `max_distance` edits
+    /// (insertions, deletions, or substitutions of a character) of `pointer`
+    Fuzzy {
+        pointer: String,
+        max_distance: usize,
+    },
+}
+
+/// The entries of `index` that match `query`, in order of their pointers
+pub fn search_paths<R: Read + Seek>(
+    index: &mut PathIndex<R>,
+    query: &PathQuery,
+) -> Result<Vec<IndexEntry>> {
+    let mut matches = vec![];
+
+    match query {
+        PathQuery::Prefix(prefix) => {
+            for_each_under(index, prefix, |entry| {
+                matches.push(entry);
+                Ok(())
+            })?;
+        }
+        PathQuery::Glob(pattern) => {
+            let prefix: String = pattern
+                .literal_prefix()
+                .iter()
+                .map(|segment| format!("/{}", escape_json_pointer_token(segment)))
+                .collect();
+
+            let mut candidates = vec![];
+            for_each_under(index, &prefix, |entry| {
+                candidates.push(entry);
+                Ok(())
+            })?;
+
+            for entry in candidates {
+                let path_components = entry_path(index, &entry.pointer)?;
+
+                if pattern.matches(&path_components) {
+                    matches.push(entry);
+                }
+            }
+        }
+        PathQuery::Fuzzy {
+            pointer,
+            max_distance,
+        } => {
+            for i in 0..index.len() {
+                let entry = index.entry(i)?;
+                if edit_distance(pointer, &entry.pointer, *max_distance) <= *max_distance {
+                    matches.push(entry);
+                }
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Pass `f` each entry of `index` at `prefix` or under it, in order of their pointers
+fn for_each_under<R: Read + Seek>(
+    index: &mut PathIndex<R>,
+    prefix: &str,
+    mut f: impl FnMut(IndexEntry) -> Result<()>,
+) -> Result<()> {
+    // every pointer under `prefix` continues with `/`, which sorts just before `0`
+    let start = index.lower_bound(prefix)?;
+    let end = index.lower_bound(&format!("{}0", prefix))?;
+
+    for i in start..end {
+        let entry = index.entry(i)?;
+        let rest = &entry.pointer[prefix.len()..];
+
+        if rest.is_empty() || rest.starts_with('/') {
+            f(entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The path components of the value at `pointer` in the document indexed by `index`,
+/// with the tokens of arrays' elements as indexes and those of objects' keys as keys,
+/// which cannot be told apart from the pointer alone when a key is a number
+pub fn entry_path<R: Read + Seek>(
+    index: &mut PathIndex<R>,
+    pointer: &str,
+) -> Result<Vec<PathComponent<'static>>> {
+    let mut path_components = vec![];
+    let mut parent = String::new();
+
+    for token in json_pointer_tokens(pointer)? {
+        let array_index = match parse_array_index(&token) {
+            Some(i) if index.get(&parent)?.map(|entry| entry.kind) == Some(ValueKind::Array) => {
+                Some(i)
+            }
+            _ => None,
+        };
+
+        parent.push('/');
+        parent.push_str(&escape_json_pointer_token(&token));
+
+        path_components.push(match array_index {
+            Some(i) => PathComponent::Index(i),
+            None => PathComponent::key(token),
+        });
+    }
+
+    Ok(path_components)
+}
+
+/// The Levenshtein distance between `a` and `b`, in characters,
+/// or some distance greater than `max` as soon as it must be greater than `max`
+fn edit_distance(a: &str, b: &str, max: usize) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    if a.len().abs_diff(b.len()) > max {
+        return max + 1;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        if current.iter().all(|distance| *distance > max) {
+            return max + 1;
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{index_document, write_index, SourceState};
+    use std::io::Cursor;
+    use std::path::Path;
+
+    fn pointers(index: &mut PathIndex<Cursor<Vec<u8>>>, query: PathQuery) -> Vec<String> {
+        search_paths(index, &query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.pointer)
+            .collect()
+    }
+
+    #[test]
+    fn search() {
+        let json = r#"{
+            "users": [{"email": "a@x", "name": "a"}, {"email": "b@x"}],
+            "users2": 1,
+            "counts": {"0": 5}
+        }"#;
+
+        let mut buf = Vec::new();
+        write_index(
+            &mut buf,
+            Path::new("doc.json"),
+            SourceState::default(),
+            &index_document(json).unwrap(),
+        )
+        .unwrap();
+        let mut index = PathIndex::open(Cursor::new(buf)).unwrap();
+
+        assert_eq!(
+            pointers(&mut index, PathQuery::Prefix("/users/0".to_string())),
+            vec!["/users/0", "/users/0/email", "/users/0/name"]
+        );
+        assert_eq!(
+            pointers(
+                &mut index,
+                PathQuery::Glob(PathPattern::glob("users.*.email"))
+            ),
+            vec!["/users/0/email", "/users/1/email"]
+        );
+        assert_eq!(
+            pointers(&mut index, PathQuery::Glob(PathPattern::glob("**.0"))),
+            vec!["/counts/0", "/users/0"]
+        );
+        assert_eq!(
+            pointers(
+                &mut index,
+                PathQuery::Fuzzy {
+                    pointer: "/users/0/emial".to_string(),
+                    max_distance: 2,
+                }
+            ),
+            vec!["/users/0/email"]
+        );
+
+        assert_eq!(
+            entry_path(&mut index, "/counts/0").unwrap(),
+            vec![
+                PathComponent::Identifier("counts".into()),
+                PathComponent::NonIdentifier("0".into())
+            ]
+        );
+        assert_eq!(
+            entry_path(&mut index, "/users/1/email").unwrap(),
+            vec![
+                PathComponent::Identifier("users".into()),
+                PathComponent::Index(1),
+                PathComponent::Identifier("email".into())
+            ]
+        );
+    }
+}
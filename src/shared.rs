@@ -0,0 +1,233 @@
+//! Content-addressed gron output, in which each object or array that appears
+//! more than once in a document is written once, under its hash,
+//! and referred to by that hash wherever it appears
+
+use crate::path_format::write_gron_path;
+use crate::PathComponent;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+type Hash = [u8; 32];
+
+/// Write `json` in gron notation, like [GronWriter](crate::path_value_sink::GronWriter),
+/// but with each repeated non-empty object or array written once
+/// as a definition rooted at `ref:<hash>`, before its first occurrence,
+/// and each occurrence written as `path = ref:<hash>;`:
+///
+/// ```text
+/// json = {};
+/// ref:4f1d0c2a9b3e8d7f = {};
+/// ref:4f1d0c2a9b3e8d7f.id = 1;
+/// json.a = ref:4f1d0c2a9b3e8d7f;
+/// json.b = ref:4f1d0c2a9b3e8d7f;
+/// ```
+///
+/// The hash is of the subtree's content, regardless of the order of its keys,
+/// shortened to 16 hex digits. A subtree that only repeats within copies
+/// of a larger repeated subtree is written out within that subtree's definition.
+pub fn write_shared_subtrees<W: Write>(
+    writer: &mut W,
+    json: &serde_json::Value,
+    prefix: &str,
+) -> Result<()> {
+    let mut shared = SharedSubtrees {
+        writer,
+        hashes: HashMap::new(),
+        occurrences: HashMap::new(),
+        defined: HashSet::new(),
+    };
+
+    shared.hash(json);
+    shared.count(json);
+    shared.write_expanded(&mut Vec::new(), prefix, json)
+}
+
+struct SharedSubtrees<'writer, W: Write> {
+    writer: &'writer mut W,
+    /// The hash of every non-empty object and array, by its address
+    hashes: HashMap<*const serde_json::Value, Hash>,
+    /// The number of occurrences of each hash outside of other repeated subtrees
+    occurrences: HashMap<Hash, usize>,
+    /// The hashes whose definitions have been written
+    defined: HashSet<Hash>,
+}
+
+impl<'writer, W: Write> SharedSubtrees<'writer, W> {
+    /// Hash `value` and all of its descendants
+    fn hash(&mut self, value: &serde_json::Value) -> Hash {
+        let mut hasher = Sha256::new();
+
+        match value {
+            serde_json::Value::Array(a) => {
+                hasher.update(b"[");
+                for element in a {
+                    hasher.update(self.hash(element));
+                }
+            }
+            serde_json::Value::Object(o) => {
+                let mut entries: Vec<_> = o.iter().collect();
+                entries.sort_unstable_by_key(|(k, _)| *k);
+
+                hasher.update(b"{");
+                for (k, v) in entries {
+                    hasher.update(serde_json::to_string(k).expect("strings serialize"));
+                    hasher.update(self.hash(v));
+                }
+            }
+            scalar => hasher.update(serde_json::to_string(scalar).expect("scalars serialize")),
+        }
+
+        let hash = hasher.finalize().into();
+
+        if is_non_empty_container(value) {
+            self.hashes.insert(value, hash);
+        }
+
+        hash
+    }
+
+    /// Count the occurrences of the subtrees of `value`,
+    /// not descending into any but the first occurrence of each,
+    /// as the others will be written as references
+    fn count(&mut self, value: &serde_json::Value) {
+        if let Some(hash) = self.hashes.get(&(value as *const _)) {
+            let occurrences = self.occurrences.entry(*hash).or_default();
+            *occurrences += 1;
+            if *occurrences > 1 {
+                return;
+            }
+        }
+
+        match value {
+            serde_json::Value::Array(a) => a.iter().for_each(|element| self.count(element)),
+            serde_json::Value::Object(o) => o.values().for_each(|v| self.count(v)),
+            _ => (),
+        }
+    }
+
+    /// Write `value` at `path`, as a reference if it is repeated
+    fn write<'v>(
+        &mut self,
+        path: &mut Vec<PathComponent<'v>>,
+        prefix: &str,
+        value: &'v serde_json::Value,
+    ) -> Result<()> {
+        let hash = match self.hashes.get(&(value as *const _)) {
+            Some(hash) if self.occurrences[hash] > 1 => *hash,
+            _ => return self.write_expanded(path, prefix, value),
+        };
+
+        let name = format!(
+            "ref:{}",
+            hash[..8]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+
+        if self.defined.insert(hash) {
+            self.write_expanded(&mut Vec::new(), &name, value)?;
+        }
+
+        write_gron_path(self.writer, prefix, path)?;
+        writeln!(self.writer, " = {};", name)?;
+
+        Ok(())
+    }
+
+    /// Write `value` at `path`, then each of its children
+    fn write_expanded<'v>(
+        &mut self,
+        path: &mut Vec<PathComponent<'v>>,
+        prefix: &str,
+        value: &'v serde_json::Value,
+    ) -> Result<()> {
+        write_gron_path(self.writer, prefix, path)?;
+
+        match value {
+            serde_json::Value::Array(a) => {
+                self.writer.write_all(b" = [];\n")?;
+
+                for (i, element) in a.iter().enumerate() {
+                    path.push(PathComponent::Index(i));
+                    self.write(path, prefix, element)?;
+                    path.pop();
+                }
+            }
+            serde_json::Value::Object(o) => {
+                self.writer.write_all(b" = {};\n")?;
+
+                for (k, v) in o {
+                    path.push(PathComponent::key(k));
+                    self.write(path, prefix, v)?;
+                    path.pop();
+                }
+            }
+            scalar => {
+                self.writer.write_all(b" = ")?;
+                serde_json::to_writer(&mut *self.writer, scalar)?;
+                self.writer.write_all(b";\n")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_non_empty_container(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Array(a) => !a.is_empty(),
+        serde_json::Value::Object(o) => !o.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_subtrees() {
+        let v = serde_json::json!({
+            "a": {"user": {"id": 1, "tags": ["x"]}},
+            "b": {"user": {"tags": ["x"], "id": 1}, "z": 0},
+            "c": {"tags": ["x"]},
+            "d": []
+        });
+
+        let mut output = Vec::new();
+        write_shared_subtrees(&mut output, &v, "json").unwrap();
+        let output = std::str::from_utf8(&output).unwrap();
+
+        let lines: Vec<&str> = output.lines().collect();
+        let user = &lines[2][..20];
+        let tags = &lines[4][..20];
+
+        assert!(user.starts_with("ref:"));
+        assert!(tags.starts_with("ref:"));
+        assert_ne!(user, tags);
+
+        assert_eq!(
+            output,
+            format!(
+                "json = {{}};
+json.a = {{}};
+{user} = {{}};
+{user}.id = 1;
+{tags} = [];
+{tags}[0] = \"x\";
+{user}.tags = {tags};
+json.a.user = {user};
+json.b = {{}};
+json.b.user = {user};
+json.b.z = 0;
+json.c = {{}};
+json.c.tags = {tags};
+json.d = [];
+"
+            )
+        );
+    }
+}
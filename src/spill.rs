@@ -0,0 +1,520 @@
+//! Holding more records than fit in memory, by spilling them to temporary files
+
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Debug)]
+pub struct SpillOptions {
+    /// The number of bytes of records to keep in memory before spilling them to a file,
+    /// or [MIN_CHUNK_BYTES] if that is more
+    pub memory_limit: usize,
+    /// The directory for spill files, or the system's temporary directory if `None`
+    pub dir: Option<PathBuf>,
+    /// Compress spill files with gzip, trading time for disk space
+    #[cfg(feature = "gzip")]
+    pub compress: bool,
+}
+
+impl Default for SpillOptions {
+    fn default() -> Self {
+        Self {
+            memory_limit: usize::MAX,
+            dir: None,
+            #[cfg(feature = "gzip")]
+            compress: false,
+        }
+    }
+}
+
+/// A sequence of records (byte strings) that is kept in memory until it outgrows
+/// `memory_limit`, when the records in memory are written to a new spill file as a chunk.
+///
+/// Records are read back, in the order they were pushed, with [RecordSpill::into_records],
+/// or, from a spill created with [RecordSpill::sorted], in sorted order with
+/// [RecordSpill::into_sorted_records].
+/// Spill files are deleted when they have been read, or when the spill is dropped.
+#[derive(Debug)]
+pub struct RecordSpill {
+    options: SpillOptions,
+    records: Vec<Vec<u8>>,
+    memory_used: usize,
+    chunks: Vec<SpillFile>,
+    /// Whether each chunk is sorted before it is spilled
+    sorted: bool,
+}
+
+impl RecordSpill {
+    pub fn new(options: SpillOptions) -> Self {
+        Self {
+            options,
+            records: vec![],
+            memory_used: 0,
+            chunks: vec![],
+            sorted: false,
+        }
+    }
+
+    /// A spill whose records can be read back in sorted order,
+    /// by an external merge sort of its chunks
+    pub fn sorted(options: SpillOptions) -> Self {
+        Self {
+            sorted: true,
+            ..Self::new(options)
+        }
+    }
+
+    pub fn push(&mut self, record: Vec<u8>) -> Result<()> {
+        self.memory_used += record.len() + std::mem::size_of::<Vec<u8>>();
+        self.records.push(record);
+
+        if self.memory_used > self.options.memory_limit.max(MIN_CHUNK_BYTES) {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of chunks written to spill files so far
+    pub fn spilled_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Write the records in memory to a new spill file
+    fn spill(&mut self) -> Result<()> {
+        if self.sorted {
+            self.records.sort_unstable();
+        }
+
+        let spill_file = SpillFile::create(&self.options)?;
+        let mut writer = spill_file.writer(&self.options)?;
+
+        for record in self.records.drain(..) {
+            write_record(&mut writer, &record)?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+
+        self.chunks.push(spill_file);
+        self.memory_used = 0;
+
+        Ok(())
+    }
+
+    /// Every record, in the order they were pushed.
+    /// Spill files are opened one at a time, as the records in each are reached
+    pub fn into_records(self) -> Result<Records> {
+        Ok(Records {
+            chunks: self.chunks.into_iter(),
+            options: self.options,
+            current: None,
+            records: self.records.into_iter(),
+        })
+    }
+}
+
+impl RecordSpill {
+    /// Every record, in sorted order, merging the sorted chunks.
+    ///
+    /// At most [MAX_FAN_IN] spill files are open at once: while there are more chunks
+    /// than that, groups of them are merged into intermediate spill files first.
+    ///
+    /// # Panics
+    ///
+    /// If the spill was not created with [RecordSpill::sorted]
+    pub fn into_sorted_records(self) -> Result<SortedRecords> {
+        self.into_sorted_records_with_fan_in(MAX_FAN_IN)
+    }
+
+    fn into_sorted_records_with_fan_in(mut self, fan_in: usize) -> Result<SortedRecords> {
+        assert!(self.sorted, "the chunks of the spill are not sorted");
+        assert!(fan_in >= 2, "cannot merge fewer than 2 chunks at a time");
+
+        self.records.sort_unstable();
+
+        while self.chunks.len() > fan_in {
+            let mut chunks = std::mem::take(&mut self.chunks).into_iter().peekable();
+
+            while chunks.peek().is_some() {
+                let mut group: Vec<SpillFile> = chunks.by_ref().take(fan_in).collect();
+
+                let merged = if group.len() == 1 {
+                    group.pop().unwrap()
+                } else {
+                    self.merge_chunks(group)?
+                };
+
+                self.chunks.push(merged);
+            }
+        }
+
+        let chunks = std::mem::take(&mut self.chunks);
+        let mut sources = self.open_chunks(chunks)?;
+        sources.push(Box::new(
+            std::mem::take(&mut self.records).into_iter().map(Ok),
+        ));
+
+        SortedRecords::new(sources)
+    }
+
+    fn open_chunks(&self, chunks: Vec<SpillFile>) -> Result<Vec<RecordSource>> {
+        chunks
+            .into_iter()
+            .map(|spill_file| {
+                Ok(Box::new(ChunkReader::open(spill_file, &self.options)?) as RecordSource)
+            })
+            .collect()
+    }
+
+    /// Merge the sorted `chunks` into one sorted chunk in a new spill file
+    fn merge_chunks(&self, chunks: Vec<SpillFile>) -> Result<SpillFile> {
+        let merged = SortedRecords::new(self.open_chunks(chunks)?)?;
+
+        let spill_file = SpillFile::create(&self.options)?;
+        let mut writer = spill_file.writer(&self.options)?;
+
+        for record in merged {
+            write_record(&mut writer, &record?)?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+
+        Ok(spill_file)
+    }
+}
+
+/// The most spill files [RecordSpill::into_sorted_records] has open at once
+pub const MAX_FAN_IN: usize = 64;
+
+/// The fewest bytes of records written to a spill file, whatever the memory limit,
+/// so that a small limit does not make a file (and, when merging, an open file) of each record
+pub const MIN_CHUNK_BYTES: usize = 64 * 1024;
+
+type RecordSource = Box<dyn Iterator<Item = Result<Vec<u8>>>>;
+
+fn write_record(writer: &mut dyn Write, record: &[u8]) -> Result<()> {
+    writer.write_all(&(record.len() as u64).to_le_bytes())?;
+    writer.write_all(record)?;
+    Ok(())
+}
+
+/// The records of a sorted [RecordSpill], in sorted order
+pub struct SortedRecords {
+    sources: Vec<RecordSource>,
+    /// The next record of each source that has not ended, with the index of the source
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize)>>,
+}
+
+impl SortedRecords {
+    /// Merge `sources`, each of which is sorted
+    fn new(mut sources: Vec<RecordSource>) -> Result<Self> {
+        let mut heap = BinaryHeap::new();
+        for (i, source) in sources.iter_mut().enumerate() {
+            if let Some(record) = source.next() {
+                heap.push(Reverse((record?, i)));
+            }
+        }
+
+        Ok(Self { sources, heap })
+    }
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((record, i)) = self.heap.pop()?;
+
+        match self.sources[i].next() {
+            Some(Ok(next)) => self.heap.push(Reverse((next, i))),
+            Some(Err(e)) => return Some(Err(e)),
+            None => (),
+        }
+
+        Some(Ok(record))
+    }
+}
+
+/// The records of a [RecordSpill]
+pub struct Records {
+    chunks: std::vec::IntoIter<SpillFile>,
+    options: SpillOptions,
+    current: Option<ChunkReader>,
+    records: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl Iterator for Records {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(chunk) = &mut self.current {
+                match chunk.next_record().transpose() {
+                    Some(record) => return Some(record),
+                    None => self.current = None,
+                }
+            }
+
+            match self.chunks.next() {
+                Some(spill_file) => match ChunkReader::open(spill_file, &self.options) {
+                    Ok(chunk) => self.current = Some(chunk),
+                    Err(e) => return Some(Err(e)),
+                },
+                None => return self.records.next().map(Ok),
+            }
+        }
+    }
+}
+
+/// Reads the records of one chunk back from its spill file
+struct ChunkReader {
+    reader: Box<dyn Read>,
+    /// Deletes the file once it has been read
+    _spill_file: SpillFile,
+}
+
+impl ChunkReader {
+    fn open(spill_file: SpillFile, options: &SpillOptions) -> Result<Self> {
+        let reader = BufReader::new(File::open(&spill_file.path)?);
+
+        #[cfg(feature = "gzip")]
+        let reader: Box<dyn Read> = if options.compress {
+            Box::new(flate2::read::GzDecoder::new(reader))
+        } else {
+            Box::new(reader)
+        };
+        #[cfg(not(feature = "gzip"))]
+        let reader: Box<dyn Read> = {
+            let _ = options;
+            Box::new(reader)
+        };
+
+        Ok(Self {
+            reader,
+            _spill_file: spill_file,
+        })
+    }
+
+    /// The next record of the chunk, or `None` at its end
+    fn next_record(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut len = [0; 8];
+
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut record = vec![0; u64::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut record)?;
+
+        Ok(Some(record))
+    }
+}
+
+impl Iterator for ChunkReader {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Pushes each line written to it (without its `\n`) to a [RecordSpill]
+pub struct SpillLines<'spill> {
+    spill: &'spill mut RecordSpill,
+    /// The start of a line whose end has not been written yet
+    partial: Vec<u8>,
+}
+
+impl<'spill> SpillLines<'spill> {
+    pub fn new(spill: &'spill mut RecordSpill) -> Self {
+        Self {
+            spill,
+            partial: vec![],
+        }
+    }
+
+    /// Push the last line, if it was not ended by a `\n`
+    pub fn finish(mut self) -> Result<()> {
+        if !self.partial.is_empty() {
+            self.spill.push(std::mem::take(&mut self.partial))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for SpillLines<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut lines = buf.split(|b| *b == b'\n');
+        let last = lines.next_back().unwrap_or_default();
+
+        for line in lines {
+            self.partial.extend_from_slice(line);
+            self.spill
+                .push(std::mem::take(&mut self.partial))
+                .map_err(std::io::Error::other)?;
+        }
+
+        self.partial.extend_from_slice(last);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A spill file, deleted when dropped
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+}
+
+/// Distinguishes the spill files of one process
+static SPILL_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl SpillFile {
+    fn create(options: &SpillOptions) -> Result<Self> {
+        let dir = options.dir.clone().unwrap_or_else(std::env::temp_dir);
+
+        let path = dir.join(format!(
+            "jindex-spill-{}-{}",
+            std::process::id(),
+            SPILL_FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        File::options().write(true).create_new(true).open(&path)?;
+
+        Ok(Self { path })
+    }
+
+    fn writer(&self, options: &SpillOptions) -> Result<Box<dyn Write>> {
+        let writer = BufWriter::new(File::options().write(true).open(&self.path)?);
+
+        #[cfg(feature = "gzip")]
+        if options.compress {
+            return Ok(Box::new(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::fast(),
+            )));
+        }
+        #[cfg(not(feature = "gzip"))]
+        let _ = options;
+
+        Ok(Box::new(writer))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spill_and_read(name: &str, mut options: SpillOptions) {
+        let dir = std::env::temp_dir().join(format!("jindex-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        options.memory_limit = 100;
+        options.dir = Some(dir.clone());
+        let mut spill = RecordSpill::new(options);
+
+        let records: Vec<Vec<u8>> = (0..10000).map(|i| format!("record {}", i).into()).collect();
+        for record in &records {
+            spill.push(record.clone()).unwrap();
+        }
+        spill.push(vec![]).unwrap();
+
+        // about 340K of records, in chunks of at least `MIN_CHUNK_BYTES` whatever the memory limit
+        assert!((2..=5).contains(&spill.spilled_chunks()));
+
+        let read: Vec<Vec<u8>> = spill.into_records().unwrap().map(Result::unwrap).collect();
+        assert_eq!(&read[..10000], &records[..]);
+        assert_eq!(read[10000], b"");
+
+        // the spill files are deleted once read
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    fn sorted_spill_lines() -> (RecordSpill, Vec<Vec<u8>>) {
+        let mut spill = RecordSpill::sorted(SpillOptions {
+            memory_limit: 100,
+            ..Default::default()
+        });
+
+        let mut lines = SpillLines::new(&mut spill);
+        for i in (0..20000).rev() {
+            write!(lines, "{:05}", i).unwrap();
+            lines
+                .write_all(if i % 2 == 0 { b"\n" } else { b"x\n" })
+                .unwrap();
+        }
+        lines.write_all(b"last").unwrap();
+        lines.finish().unwrap();
+
+        let mut expected: Vec<Vec<u8>> = (0..20000)
+            .map(|i| format!("{:05}{}", i, if i % 2 == 0 { "" } else { "x" }).into())
+            .collect();
+        expected.push(b"last".to_vec());
+
+        (spill, expected)
+    }
+
+    #[test]
+    fn sorted_spill() {
+        let (spill, expected) = sorted_spill_lines();
+        assert!(spill.spilled_chunks() > 1);
+
+        let sorted: Vec<Vec<u8>> = spill
+            .into_sorted_records()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn merge_passes() {
+        let (spill, expected) = sorted_spill_lines();
+        assert!(spill.spilled_chunks() > 4);
+
+        // merged in passes of at most 2 files
+        let sorted: Vec<Vec<u8>> = spill
+            .into_sorted_records_with_fan_in(2)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn spill() {
+        spill_and_read("spill", SpillOptions::default());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compressed_spill() {
+        spill_and_read(
+            "compressed-spill",
+            SpillOptions {
+                compress: true,
+                ..Default::default()
+            },
+        );
+    }
+}
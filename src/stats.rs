@@ -0,0 +1,728 @@
+//! Sinks that profile a document by shape, collecting statistics about
+//! the values at each path with its array indexes wildcarded (see [gron_shape]),
+//! and writing a report when they are finished
+
+use crate::path_format::gron_shape;
+use crate::path_value_sink::{detailed_type_name, value_type_name, PathValueSink};
+use crate::{PathComponent, PathValue};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Count the JSON types of the values at each shape,
+/// to find the shapes whose values do not all have the same type,
+/// like an `id` that is sometimes a string and sometimes a number
+#[derive(Debug, Default)]
+pub struct TypeConsistencySink {
+    types: BTreeMap<String, BTreeMap<&'static str, usize>>,
+    number_types: bool,
+}
+
+impl TypeConsistencySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count numbers by the narrowest of `i64`, `u64`, and `f64` that holds them,
+    /// rather than all as `number`, so that a shape with both integers and floats
+    /// is inconsistent; see [number_type_name](crate::path_value_sink::number_type_name)
+    pub fn with_number_types() -> Self {
+        Self {
+            number_types: true,
+            ..Self::default()
+        }
+    }
+
+    /// Each shape whose values have more than one type,
+    /// with the number of values of each type
+    pub fn inconsistent(&self) -> impl Iterator<Item = (&str, &BTreeMap<&'static str, usize>)> {
+        self.types
+            .iter()
+            .filter(|(_, types)| types.len() > 1)
+            .map(|(shape, types)| (shape.as_str(), types))
+    }
+
+    /// Write a line for each inconsistent shape, like
+    /// `json.items[*].id\tnumber:3 string:1`
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (shape, types) in self.inconsistent() {
+            writer.write_all(shape.as_bytes())?;
+
+            let mut separator = "\t";
+
+            for (type_name, count) in types {
+                write!(writer, "{}{}:{}", separator, type_name, count)?;
+                separator = " ";
+            }
+
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathValueSink for TypeConsistencySink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if pathvalue.path_components.is_empty() {
+            return Ok(());
+        }
+
+        *self
+            .types
+            .entry(gron_shape("json", &pathvalue.path_components))
+            .or_default()
+            .entry(if self.number_types {
+                detailed_type_name(pathvalue.value)
+            } else {
+                value_type_name(pathvalue.value)
+            })
+            .or_default() += 1;
+
+        Ok(())
+    }
+}
+
+/// For arrays of objects, count how many elements have each field,
+/// and how many of those have it set to `null`,
+/// to show how reliably each field is present
+#[derive(Debug, Default)]
+pub struct FieldCoverageSink {
+    /// The number of objects in arrays at each shape
+    elements: BTreeMap<String, usize>,
+    /// The shape of the elements each field is in,
+    /// and the number of elements where it is present and where it is `null`
+    fields: BTreeMap<String, (String, usize, usize)>,
+}
+
+impl FieldCoverageSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a line for each field of objects in arrays, with the percentage of elements
+    /// where it is present (and not `null`), `null`, and absent, like
+    /// `json.items[*].email\tpresent:75.0% null:5.0% absent:20.0%`
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (field, (element_shape, present, null)) in &self.fields {
+            let elements = self
+                .elements
+                .get(element_shape)
+                .copied()
+                .unwrap_or(0)
+                .max(1);
+            let percentage = |count: usize| 100.0 * count as f64 / elements as f64;
+
+            writeln!(
+                writer,
+                "{}\tpresent:{:.1}% null:{:.1}% absent:{:.1}%",
+                field,
+                percentage(present - null),
+                percentage(*null),
+                percentage(elements.saturating_sub(*present)),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathValueSink for FieldCoverageSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let path_components = &pathvalue.path_components;
+
+        match path_components.as_slice() {
+            [.., PathComponent::Index(_)] if pathvalue.value.is_object() => {
+                *self
+                    .elements
+                    .entry(gron_shape("json", path_components))
+                    .or_default() += 1;
+            }
+            [parent @ .., PathComponent::Index(_), PathComponent::Identifier(_) | PathComponent::NonIdentifier(_)] =>
+            {
+                let element_shape_len = parent.len() + 1;
+
+                let (_, present, null) = self
+                    .fields
+                    .entry(gron_shape("json", path_components))
+                    .or_insert_with(|| {
+                        (
+                            gron_shape("json", &path_components[..element_shape_len]),
+                            0,
+                            0,
+                        )
+                    });
+
+                *present += 1;
+
+                if pathvalue.value.is_null() {
+                    *null += 1;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// The count, minimum, maximum, and sum of the numbers at one shape
+#[derive(Clone, Copy, Debug)]
+pub struct NumericStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+}
+
+impl NumericStats {
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+/// Collect [NumericStats] for the numbers at each shape,
+/// for a quick profile of documents full of metrics.
+/// Values of other types are ignored.
+#[derive(Debug, Default)]
+pub struct NumericStatsSink {
+    stats: BTreeMap<String, NumericStats>,
+}
+
+impl NumericStatsSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The stats for each shape with at least one number
+    pub fn stats(&self) -> &BTreeMap<String, NumericStats> {
+        &self.stats
+    }
+
+    /// Write a line for each shape with at least one number, like
+    /// `json.items[*].price\tcount:3 min:1 max:9.5 mean:4.5`
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (shape, stats) in &self.stats {
+            writeln!(
+                writer,
+                "{}\tcount:{} min:{} max:{} mean:{}",
+                shape,
+                stats.count,
+                stats.min,
+                stats.max,
+                stats.mean()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathValueSink for NumericStatsSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let Some(n) = pathvalue.value.as_f64() else {
+            return Ok(());
+        };
+
+        self.stats
+            .entry(gron_shape("json", &pathvalue.path_components))
+            .and_modify(|stats| {
+                stats.count += 1;
+                stats.min = stats.min.min(n);
+                stats.max = stats.max.max(n);
+                stats.sum += n;
+            })
+            .or_insert(NumericStats {
+                count: 1,
+                min: n,
+                max: n,
+                sum: n,
+            });
+
+        Ok(())
+    }
+}
+
+/// The character set of the strings at one shape, from most to least specific
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Charset {
+    /// Every string looks like base64 (see [looks_like_base64])
+    Base64,
+    /// Every string is ASCII
+    Ascii,
+    /// Some strings have non-ASCII characters
+    Utf8,
+}
+
+impl Charset {
+    fn of(s: &str) -> Self {
+        if looks_like_base64(s) {
+            Charset::Base64
+        } else if s.is_ascii() {
+            Charset::Ascii
+        } else {
+            Charset::Utf8
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Base64 => "base64",
+            Charset::Ascii => "ascii",
+            Charset::Utf8 => "utf-8",
+        }
+    }
+}
+
+/// Whether `s` looks like base64-encoded data (in the standard or URL-safe alphabet):
+/// at least 16 characters, a multiple of 4 long (with padding),
+/// and only `=` as padding at the end
+pub fn looks_like_base64(s: &str) -> bool {
+    let unpadded = s.trim_end_matches('=');
+
+    s.len() >= 16
+        && s.len().is_multiple_of(4)
+        && s.len() - unpadded.len() <= 2
+        && unpadded
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_'))
+}
+
+/// The count, lengths, and [Charset] of the strings at one shape
+#[derive(Clone, Copy, Debug)]
+pub struct StringProfile {
+    pub count: usize,
+    /// The length of the longest string, in characters
+    pub max_len: usize,
+    /// The length of the longest string, in UTF-8 bytes
+    pub max_bytes: usize,
+    /// The total length of the strings, in characters
+    pub total_len: usize,
+    pub charset: Charset,
+}
+
+impl StringProfile {
+    /// The mean length of the strings, in characters
+    pub fn avg_len(&self) -> f64 {
+        self.total_len as f64 / self.count as f64
+    }
+}
+
+/// Collect a [StringProfile] for the strings at each shape,
+/// to help choose column types when loading documents into a database.
+/// Values of other types are ignored.
+#[derive(Debug, Default)]
+pub struct StringProfileSink {
+    profiles: BTreeMap<String, StringProfile>,
+}
+
+impl StringProfileSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The profile of each shape with at least one string
+    pub fn profiles(&self) -> &BTreeMap<String, StringProfile> {
+        &self.profiles
+    }
+
+    /// Write a line for each shape with at least one string, like
+    /// `json.items[*].name\tcount:3 max_len:12 max_bytes:14 avg_len:7.3 charset:utf-8`
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (shape, profile) in &self.profiles {
+            writeln!(
+                writer,
+                "{}\tcount:{} max_len:{} max_bytes:{} avg_len:{:.1} charset:{}",
+                shape,
+                profile.count,
+                profile.max_len,
+                profile.max_bytes,
+                profile.avg_len(),
+                profile.charset.name()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathValueSink for StringProfileSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        let Some(s) = pathvalue.value.as_str() else {
+            return Ok(());
+        };
+
+        let len = s.chars().count();
+        let charset = Charset::of(s);
+
+        self.profiles
+            .entry(gron_shape("json", &pathvalue.path_components))
+            .and_modify(|profile| {
+                profile.count += 1;
+                profile.max_len = profile.max_len.max(len);
+                profile.max_bytes = profile.max_bytes.max(s.len());
+                profile.total_len += len;
+                profile.charset = profile.charset.max(charset);
+            })
+            .or_insert(StringProfile {
+                count: 1,
+                max_len: len,
+                max_bytes: s.len(),
+                total_len: len,
+                charset,
+            });
+
+        Ok(())
+    }
+}
+
+/// A common timestamp format, as recognized by [detect_timestamp]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimestampFormat {
+    /// An ISO 8601 date, like `2024-01-31`
+    Iso8601Date,
+    /// An ISO 8601 date and time, like `2024-01-31T12:00:00Z`
+    /// or `2024-01-31 12:00:00.123+01:00`
+    Iso8601DateTime,
+    /// Seconds since the Unix epoch, between 2001 and 2286
+    EpochSeconds,
+    /// Milliseconds since the Unix epoch, between 2001 and 2286
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimestampFormat::Iso8601Date => "iso8601-date",
+            TimestampFormat::Iso8601DateTime => "iso8601-datetime",
+            TimestampFormat::EpochSeconds => "epoch-seconds",
+            TimestampFormat::EpochMillis => "epoch-millis",
+        }
+    }
+}
+
+/// The timestamp format of `value`, if it is a string in an ISO 8601 format,
+/// or an integer (or string of digits) that is plausibly a recent epoch timestamp
+/// in seconds or milliseconds
+pub fn detect_timestamp(value: &serde_json::Value) -> Option<TimestampFormat> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64().and_then(epoch_format),
+        serde_json::Value::String(s) => {
+            if s.len() >= 10 && s.bytes().all(|b| b.is_ascii_digit()) {
+                s.parse().ok().and_then(epoch_format)
+            } else {
+                iso8601_format(s)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn epoch_format(n: u64) -> Option<TimestampFormat> {
+    match n {
+        1_000_000_000..=9_999_999_999 => Some(TimestampFormat::EpochSeconds),
+        1_000_000_000_000..=9_999_999_999_999 => Some(TimestampFormat::EpochMillis),
+        _ => None,
+    }
+}
+
+fn iso8601_format(s: &str) -> Option<TimestampFormat> {
+    let bytes = s.as_bytes();
+
+    let digits = |range: std::ops::Range<usize>| {
+        bytes
+            .get(range)
+            .is_some_and(|digits| digits.iter().all(u8::is_ascii_digit))
+    };
+
+    let is_date = digits(0..4)
+        && bytes.get(4) == Some(&b'-')
+        && digits(5..7)
+        && bytes.get(7) == Some(&b'-')
+        && digits(8..10);
+
+    if !is_date {
+        return None;
+    }
+
+    if bytes.len() == 10 {
+        return Some(TimestampFormat::Iso8601Date);
+    }
+
+    let is_time = matches!(bytes[10], b'T' | b't' | b' ')
+        && digits(11..13)
+        && bytes.get(13) == Some(&b':')
+        && digits(14..16);
+
+    if !is_time {
+        return None;
+    }
+
+    let mut rest = &bytes[16..];
+
+    if let [b':', s1, s2, tail @ ..] = rest {
+        if !(s1.is_ascii_digit() && s2.is_ascii_digit()) {
+            return None;
+        }
+        rest = tail;
+
+        if let [b'.' | b',', tail @ ..] = rest {
+            let fraction_len = tail.iter().take_while(|b| b.is_ascii_digit()).count();
+            if fraction_len == 0 {
+                return None;
+            }
+            rest = &tail[fraction_len..];
+        }
+    }
+
+    let is_offset = match rest {
+        [] | [b'Z' | b'z'] => true,
+        [b'+' | b'-', h1, h2, b':', m1, m2] | [b'+' | b'-', h1, h2, m1, m2] => {
+            [h1, h2, m1, m2].iter().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    };
+
+    is_offset.then_some(TimestampFormat::Iso8601DateTime)
+}
+
+/// Count the values at each shape that look like timestamps (see [detect_timestamp]),
+/// to help map documents onto typed schemas
+#[derive(Debug, Default)]
+pub struct TimestampSink {
+    /// The number of scalars at each shape,
+    /// and how many of them are in each timestamp format
+    shapes: BTreeMap<String, (usize, BTreeMap<TimestampFormat, usize>)>,
+}
+
+impl TimestampSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a line for each shape with at least one timestamp,
+    /// with the number of values in each format out of all of the values there, like
+    /// `json.items[*].created\tiso8601-datetime:3/4`
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (shape, (count, formats)) in &self.shapes {
+            if formats.is_empty() {
+                continue;
+            }
+
+            writer.write_all(shape.as_bytes())?;
+
+            let mut separator = "\t";
+
+            for (format, matched) in formats {
+                write!(
+                    writer,
+                    "{}{}:{}/{}",
+                    separator,
+                    format.name(),
+                    matched,
+                    count
+                )?;
+                separator = " ";
+            }
+
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathValueSink for TimestampSink {
+    fn handle_pathvalue(&mut self, pathvalue: &PathValue) -> Result<()> {
+        if pathvalue.value.is_object() || pathvalue.value.is_array() {
+            return Ok(());
+        }
+
+        let (count, formats) = self
+            .shapes
+            .entry(gron_shape("json", &pathvalue.path_components))
+            .or_default();
+
+        *count += 1;
+
+        if let Some(format) = detect_timestamp(pathvalue.value) {
+            *formats.entry(format).or_default() += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex;
+
+    #[test]
+    fn inconsistent_types() {
+        let v = serde_json::json!({
+            "items": [
+                {"id": 1, "tags": ["a"]},
+                {"id": "2", "tags": "b"},
+                {"id": 3, "tags": []},
+            ]
+        });
+
+        let mut sink = TypeConsistencySink::new();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.items[*].id\tnumber:2 string:1\njson.items[*].tags\tarray:2 string:1\n"
+        );
+    }
+
+    #[test]
+    fn inconsistent_number_types() {
+        let v = serde_json::json!({
+            "items": [
+                {"id": 1, "score": 1},
+                {"id": 18446744073709551615u64, "score": 2.5},
+            ]
+        });
+
+        let mut sink = TypeConsistencySink::with_number_types();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.items[*].id\ti64:1 u64:1\njson.items[*].score\tf64:1 i64:1\n"
+        );
+    }
+
+    #[test]
+    fn field_coverage() {
+        let v = serde_json::json!({
+            "items": [
+                {"id": 1, "email": "a@example.com"},
+                {"id": 2, "email": null},
+                {"id": 3},
+                {"id": 4, "email": "d@example.com"},
+            ]
+        });
+
+        let mut sink = FieldCoverageSink::new();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.items[*].email\tpresent:50.0% null:25.0% absent:25.0%\n\
+             json.items[*].id\tpresent:100.0% null:0.0% absent:0.0%\n"
+        );
+    }
+
+    #[test]
+    fn numeric_stats() {
+        let v = serde_json::json!({
+            "items": [{"price": 1}, {"price": 9.5}, {"price": "3"}, {"price": 3}],
+            "total": 13.5,
+        });
+
+        let mut sink = NumericStatsSink::new();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.items[*].price\tcount:3 min:1 max:9.5 mean:4.5\n\
+             json.total\tcount:1 min:13.5 max:13.5 mean:13.5\n"
+        );
+    }
+
+    #[test]
+    fn string_profile() {
+        assert!(looks_like_base64("aGVsbG8gd29ybGQhIQ=="));
+        assert!(!looks_like_base64("aGVsbG8="));
+        assert!(!looks_like_base64("hello world, hi!"));
+        assert!(!looks_like_base64("aGVsbG8gd29y=GQh"));
+
+        let v = serde_json::json!({
+            "items": [
+                {"name": "ab", "key": "aGVsbG8gd29ybGQhIQ=="},
+                {"name": "café", "key": "c2VjcmV0c2VjcmV0c2VjcmV0"},
+            ],
+            "id": "x1",
+        });
+
+        let mut sink = StringProfileSink::new();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.id\tcount:1 max_len:2 max_bytes:2 avg_len:2.0 charset:ascii\n\
+             json.items[*].key\tcount:2 max_len:24 max_bytes:24 avg_len:22.0 charset:base64\n\
+             json.items[*].name\tcount:2 max_len:4 max_bytes:5 avg_len:3.0 charset:utf-8\n"
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        use serde_json::json;
+
+        assert_eq!(
+            detect_timestamp(&json!("2024-01-31")),
+            Some(TimestampFormat::Iso8601Date)
+        );
+        for s in [
+            "2024-01-31T12:00Z",
+            "2024-01-31T12:00:00",
+            "2024-01-31 12:00:00.123+01:00",
+            "2024-01-31T12:00:00,5-0800",
+        ] {
+            assert_eq!(
+                detect_timestamp(&json!(s)),
+                Some(TimestampFormat::Iso8601DateTime),
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            detect_timestamp(&json!(1706702400)),
+            Some(TimestampFormat::EpochSeconds)
+        );
+        assert_eq!(
+            detect_timestamp(&json!("1706702400000")),
+            Some(TimestampFormat::EpochMillis)
+        );
+        assert_eq!(detect_timestamp(&json!(42)), None);
+        assert_eq!(detect_timestamp(&json!("2024-01-31T12")), None);
+        assert_eq!(detect_timestamp(&json!("2024-01-31T12:00:00+1")), None);
+
+        let v = json!({
+            "items": [
+                {"created": "2024-01-31T12:00:00Z", "n": 1},
+                {"created": null, "n": 2},
+            ],
+        });
+
+        let mut sink = TimestampSink::new();
+        jindex(&mut sink, &v).unwrap();
+
+        let mut report = Vec::new();
+        sink.write_report(&mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "json.items[*].created\tiso8601-datetime:1/2\n"
+        );
+    }
+}
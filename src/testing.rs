@@ -0,0 +1,125 @@
+//! Property-testing helpers for sink authors (behind the `testing` feature):
+//! a [proptest] strategy for arbitrary documents, and round-trip checks
+//! that a flattened document can be rebuilt exactly
+
+use crate::compare::{first_difference, EqOptions};
+use crate::jindex;
+use crate::jindex_owned;
+use crate::path_value_sink::{GronWriter, GronWriterOptions};
+use crate::unflatten::{unflatten, ungron};
+use anyhow::{anyhow, Result};
+use proptest::prelude::*;
+
+/// A strategy for arbitrary JSON documents, nested up to 4 levels deep
+/// with up to 8 elements in each array or object
+pub fn arb_value() -> impl Strategy<Value = serde_json::Value> {
+    arb_value_with(4, 8)
+}
+
+/// A strategy for arbitrary JSON documents (with an object or array at the root,
+/// as [jindex] requires), nested up to `depth` levels deep
+/// with up to `max_len` elements in each array or object.
+///
+/// Keys are arbitrary strings of up to 8 characters, including quotes,
+/// backslashes, and control characters, which need escaping in paths,
+/// and numbers include integers across the whole `i64` and `u64` ranges and finite floats.
+pub fn arb_value_with(depth: u32, max_len: usize) -> impl Strategy<Value = serde_json::Value> {
+    let leaf = prop_oneof![
+        Just(serde_json::Value::Null),
+        any::<bool>().prop_map(serde_json::Value::Bool),
+        any::<i64>().prop_map(serde_json::Value::from),
+        any::<u64>().prop_map(serde_json::Value::from),
+        any::<f64>()
+            .prop_filter("JSON numbers are finite", |f| f.is_finite())
+            .prop_map(serde_json::Value::from),
+        any::<String>().prop_map(serde_json::Value::String),
+    ];
+
+    let container = move |inner: BoxedStrategy<serde_json::Value>| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..max_len).prop_map(serde_json::Value::Array),
+            prop::collection::btree_map("(?s:.){0,8}", inner, 0..max_len)
+                .prop_map(|object| serde_json::Value::Object(object.into_iter().collect())),
+        ]
+    };
+
+    let value = leaf.prop_recursive(
+        depth,
+        depth * max_len as u32,
+        max_len as u32,
+        move |inner| container(inner.boxed()),
+    );
+
+    container(value.boxed())
+}
+
+/// Check that `value` survives a round trip through `flatten` and then `unflatten`,
+/// returning an error with the JSON Pointer of the first difference if it does not
+pub fn check_round_trip<T>(
+    value: &serde_json::Value,
+    flatten: impl FnOnce(&serde_json::Value) -> Result<T>,
+    unflatten: impl FnOnce(T) -> Result<serde_json::Value>,
+) -> Result<()> {
+    let rebuilt = unflatten(flatten(value)?)?;
+
+    match first_difference(value, &rebuilt, &EqOptions::default()) {
+        Some(pointer) => Err(anyhow!(
+            "round trip changed the value at {:?}: {} became {}",
+            pointer,
+            value,
+            rebuilt
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Check that `value` can be rebuilt exactly from the path-value pairs of [jindex_owned]
+pub fn check_owned_round_trip(value: &serde_json::Value) -> Result<()> {
+    check_round_trip(
+        value,
+        |value| {
+            Ok(jindex_owned(value.clone())
+                .map(|pathvalue| (pathvalue.path_components, pathvalue.value))
+                .collect::<Vec<_>>())
+        },
+        unflatten,
+    )
+}
+
+/// Check that `value` can be rebuilt exactly from the output of [GronWriter]
+pub fn check_gron_round_trip(value: &serde_json::Value) -> Result<()> {
+    check_round_trip(
+        value,
+        |value| {
+            let mut gron = Vec::new();
+            let mut sink = GronWriter::new(&mut gron, GronWriterOptions::default());
+            jindex(&mut sink, value)?;
+            Ok(String::from_utf8(gron)?)
+        },
+        |gron| {
+            // an empty root is not written, so it cannot be rebuilt from the gron
+            if gron.is_empty() {
+                Ok(value.clone())
+            } else {
+                ungron(&gron)
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn owned_round_trip(value in arb_value()) {
+            check_owned_round_trip(&value).unwrap();
+        }
+
+        #[test]
+        fn gron_round_trip(value in arb_value()) {
+            check_gron_round_trip(&value).unwrap();
+        }
+    }
+}
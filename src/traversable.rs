@@ -0,0 +1,247 @@
+//! Traversal over value trees other than [serde_json::Value],
+//! like TOML, YAML, or simd-json values, without converting them to `serde_json::Value` first.
+
+use crate::{PathComponent, TraversalOrder};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::Result;
+
+/// What kind of node a value is, and for objects and arrays, how to reach its children
+#[derive(Debug)]
+pub enum Node<Entries, Elements> {
+    Object(Entries),
+    Array(Elements),
+    Scalar(ScalarKind),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    /// A scalar that JSON does not have, like a TOML datetime
+    Other,
+}
+
+/// A tree of values that [traverse] can enumerate the paths through
+pub trait TraversableValue: Sized {
+    type Entries<'v>: Iterator<Item = (&'v str, &'v Self)>
+    where
+        Self: 'v;
+    type Elements<'v>: Iterator<Item = &'v Self>
+    where
+        Self: 'v;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>>;
+}
+
+/// Like [jindex](crate::jindex), for any [TraversableValue]:
+/// call `visit` with the path to and value of every node in `value`
+pub fn traverse<'v, V, F>(value: &'v V, visit: F) -> Result<()>
+where
+    V: TraversableValue,
+    F: FnMut(&[PathComponent<'v>], &'v V) -> Result<()>,
+{
+    traverse_in_order(value, TraversalOrder::Unspecified, visit)
+}
+
+/// Like [traverse], visiting the children of each object and array in `order`
+pub fn traverse_in_order<'v, V, F>(value: &'v V, order: TraversalOrder, mut visit: F) -> Result<()>
+where
+    V: TraversableValue,
+    F: FnMut(&[PathComponent<'v>], &'v V) -> Result<()>,
+{
+    let mut traversal_stack: Vec<(Vec<PathComponent<'v>>, &'v V)> = vec![(vec![], value)];
+
+    while let Some((path_components, value)) = traversal_stack.pop() {
+        let start = traversal_stack.len();
+
+        match value.node() {
+            Node::Object(entries) => traversal_stack.extend(entries.map(|(k, v)| {
+                let mut cloned = path_components.clone();
+                cloned.push(PathComponent::key(k));
+                (cloned, v)
+            })),
+            Node::Array(elements) => traversal_stack.extend(elements.enumerate().map(|(i, v)| {
+                let mut cloned = path_components.clone();
+                cloned.push(PathComponent::Index(i));
+                (cloned, v)
+            })),
+            Node::Scalar(_) => (),
+        }
+
+        // the stack is popped from its end, so the first child to visit goes last
+        if order == TraversalOrder::Document {
+            traversal_stack[start..].reverse();
+        }
+
+        visit(&path_components, value)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) type StrEntry<'v, V> = fn((&'v String, &'v V)) -> (&'v str, &'v V);
+
+impl TraversableValue for serde_json::Value {
+    type Entries<'v> = core::iter::Map<serde_json::map::Iter<'v>, StrEntry<'v, Self>>;
+    type Elements<'v> = core::slice::Iter<'v, Self>;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>> {
+        match self {
+            serde_json::Value::Object(object) => {
+                Node::Object(object.iter().map(|(k, v)| (k.as_str(), v)))
+            }
+            serde_json::Value::Array(array) => Node::Array(array.iter()),
+            serde_json::Value::Null => Node::Scalar(ScalarKind::Null),
+            serde_json::Value::Bool(_) => Node::Scalar(ScalarKind::Bool),
+            serde_json::Value::Number(_) => Node::Scalar(ScalarKind::Number),
+            serde_json::Value::String(_) => Node::Scalar(ScalarKind::String),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl TraversableValue for toml::Value {
+    type Entries<'v> = core::iter::Map<toml::map::Iter<'v>, StrEntry<'v, Self>>;
+    type Elements<'v> = core::slice::Iter<'v, Self>;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>> {
+        match self {
+            toml::Value::Table(table) => Node::Object(table.iter().map(|(k, v)| (k.as_str(), v))),
+            toml::Value::Array(array) => Node::Array(array.iter()),
+            toml::Value::Boolean(_) => Node::Scalar(ScalarKind::Bool),
+            toml::Value::Integer(_) | toml::Value::Float(_) => Node::Scalar(ScalarKind::Number),
+            toml::Value::String(_) => Node::Scalar(ScalarKind::String),
+            toml::Value::Datetime(_) => Node::Scalar(ScalarKind::Other),
+        }
+    }
+}
+
+#[cfg(feature = "yaml")]
+type YamlEntry<'v> =
+    fn((&'v serde_yaml::Value, &'v serde_yaml::Value)) -> Option<(&'v str, &'v serde_yaml::Value)>;
+
+/// The entries of mappings whose keys are not strings, like `1: a`, are skipped,
+/// as there is no path to them, and tagged values are traversed as the values they tag
+#[cfg(feature = "yaml")]
+impl TraversableValue for serde_yaml::Value {
+    type Entries<'v> = core::iter::FilterMap<serde_yaml::mapping::Iter<'v>, YamlEntry<'v>>;
+    type Elements<'v> = core::slice::Iter<'v, Self>;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>> {
+        match self {
+            serde_yaml::Value::Mapping(mapping) => {
+                Node::Object(mapping.iter().filter_map(|(k, v)| Some((k.as_str()?, v))))
+            }
+            serde_yaml::Value::Sequence(sequence) => Node::Array(sequence.iter()),
+            serde_yaml::Value::Null => Node::Scalar(ScalarKind::Null),
+            serde_yaml::Value::Bool(_) => Node::Scalar(ScalarKind::Bool),
+            serde_yaml::Value::Number(_) => Node::Scalar(ScalarKind::Number),
+            serde_yaml::Value::String(_) => Node::Scalar(ScalarKind::String),
+            serde_yaml::Value::Tagged(tagged) => tagged.value.node(),
+        }
+    }
+}
+
+#[cfg(feature = "simd-json")]
+impl TraversableValue for simd_json::OwnedValue {
+    type Entries<'v> = core::iter::Map<
+        <&'v simd_json::owned::Object as IntoIterator>::IntoIter,
+        StrEntry<'v, Self>,
+    >;
+    type Elements<'v> = core::slice::Iter<'v, Self>;
+
+    fn node(&self) -> Node<Self::Entries<'_>, Self::Elements<'_>> {
+        use simd_json::StaticNode;
+
+        match self {
+            simd_json::OwnedValue::Object(object) => {
+                Node::Object(object.iter().map(|(k, v)| (k.as_str(), v)))
+            }
+            simd_json::OwnedValue::Array(array) => Node::Array(array.iter()),
+            simd_json::OwnedValue::String(_) => Node::Scalar(ScalarKind::String),
+            simd_json::OwnedValue::Static(StaticNode::Null) => Node::Scalar(ScalarKind::Null),
+            simd_json::OwnedValue::Static(StaticNode::Bool(_)) => Node::Scalar(ScalarKind::Bool),
+            simd_json::OwnedValue::Static(_) => Node::Scalar(ScalarKind::Number),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_format::write_json_pointer;
+
+    fn pointers<V: TraversableValue>(value: &V) -> Vec<String> {
+        let mut pointers = vec![];
+
+        traverse(value, |path_components, _value| {
+            let mut pointer = Vec::new();
+            write_json_pointer(&mut pointer, path_components)?;
+            pointers.push(String::from_utf8(pointer)?);
+            Ok(())
+        })
+        .unwrap();
+
+        pointers.sort();
+        pointers
+    }
+
+    #[test]
+    fn order() {
+        let v = serde_json::json!({"a": [1, 2], "b": 3});
+        let mut pointers = vec![];
+
+        traverse_in_order(&v, TraversalOrder::Document, |path_components, _value| {
+            let mut pointer = Vec::new();
+            write_json_pointer(&mut pointer, path_components)?;
+            pointers.push(String::from_utf8(pointer)?);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(pointers, vec!["", "/a", "/a/0", "/a/1", "/b"]);
+    }
+
+    #[test]
+    fn json() {
+        let v = serde_json::json!({"a": [1, {"b": null}], "c d": "e"});
+
+        assert_eq!(
+            pointers(&v),
+            vec!["", "/a", "/a/0", "/a/1", "/a/1/b", "/c d"]
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml() {
+        let v: toml::Value = toml::from_str("a = [1, 2]\n[b]\nc = 1979-05-27").unwrap();
+
+        assert_eq!(pointers(&v), vec!["", "/a", "/a/0", "/a/1", "/b", "/b/c"]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml() {
+        let v: serde_yaml::Value =
+            serde_yaml::from_str("a: [1, {b: ~}]\n1: skipped\nc: !point {x: 1}\n").unwrap();
+
+        assert_eq!(
+            pointers(&v),
+            vec!["", "/a", "/a/0", "/a/1", "/a/1/b", "/c", "/c/x"]
+        );
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn simd_json() {
+        let mut buf = br#"{"a": [1, {"b": null}]}"#.to_vec();
+        let v = simd_json::to_owned_value(&mut buf).unwrap();
+
+        assert_eq!(pointers(&v), vec!["", "/a", "/a/0", "/a/1", "/a/1/b"]);
+    }
+}
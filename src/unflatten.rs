@@ -0,0 +1,197 @@
+//! Rebuilding documents from their paths and values, the inverse of [jindex](crate::jindex)
+
+use crate::path_format::{json_pointer_tokens, parse_array_index, parse_gron_path};
+use crate::PathComponent;
+use anyhow::{anyhow, Result};
+
+/// Build a document from `(path, value)` pairs, like those yielded by
+/// [jindex_owned](crate::jindex_owned), in any order.
+/// Objects and arrays along each path are created as needed,
+/// and arrays are padded with `null` up to the highest index given.
+pub fn unflatten<I, P>(pathvalues: I) -> Result<serde_json::Value>
+where
+    I: IntoIterator<Item = (P, serde_json::Value)>,
+    P: AsRef<[PathComponent<'static>]>,
+{
+    let mut document = serde_json::Value::Null;
+
+    for (path_components, value) in pathvalues {
+        insert(&mut document, path_components.as_ref(), value)?;
+    }
+
+    Ok(document)
+}
+
+fn insert(
+    mut node: &mut serde_json::Value,
+    path_components: &[PathComponent],
+    value: serde_json::Value,
+) -> Result<()> {
+    for path_component in path_components {
+        node = match path_component {
+            PathComponent::Identifier(k) | PathComponent::NonIdentifier(k) => {
+                if node.is_null() {
+                    *node = serde_json::Value::Object(serde_json::Map::new());
+                }
+
+                node.as_object_mut()
+                    .ok_or_else(|| anyhow!("path goes through a non-object at key {:?}", k))?
+                    .entry(k.to_string())
+                    .or_insert(serde_json::Value::Null)
+            }
+            PathComponent::Index(i) => {
+                if node.is_null() {
+                    *node = serde_json::Value::Array(vec![]);
+                }
+
+                let array = node
+                    .as_array_mut()
+                    .ok_or_else(|| anyhow!("path goes through a non-array at index {}", i))?;
+
+                if array.len() <= *i {
+                    array.resize(i + 1, serde_json::Value::Null);
+                }
+
+                &mut array[*i]
+            }
+        };
+    }
+
+    match (&*node, &value) {
+        (serde_json::Value::Null, _) => *node = value,
+        (serde_json::Value::Object(_), serde_json::Value::Object(o)) if o.is_empty() => (),
+        (serde_json::Value::Array(_), serde_json::Value::Array(a)) if a.is_empty() => (),
+        (existing, _) if *existing == value => (),
+        (existing, _) => {
+            return Err(anyhow!(
+                "conflicting values at the same path: {} and {}",
+                existing,
+                value
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuild a document from gron output, like `json.a[0] = 1;` on each line.
+///
+/// A line assigning the root, like gron's `json = {};` or `json = [];`, gives its type,
+/// so that an empty root object or array, which has no other lines, is rebuilt
+pub fn ungron(gron: &str) -> Result<serde_json::Value> {
+    let pathvalues = gron
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (path_components, rest) = parse_gron_path(line, "json")?;
+
+            let value = rest
+                .trim_end()
+                .strip_suffix(';')
+                .unwrap_or(rest)
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or_else(|| anyhow!("expected = after gron path: {:?}", line))?;
+
+            Ok((path_components, serde_json::from_str(value)?))
+        })
+        .collect::<Result<Vec<(Vec<PathComponent<'static>>, serde_json::Value)>>>()?;
+
+    unflatten(pathvalues)
+}
+
+/// Build a new document containing only the values at `pointers` in `document`,
+/// at the same paths. Pointers that do not exist in `document` are ignored.
+/// Arrays keep the original indexes of their selected elements,
+/// so unselected elements before them are `null`.
+pub fn extract(document: &serde_json::Value, pointers: &[&str]) -> Result<serde_json::Value> {
+    let mut pointers = pointers
+        .iter()
+        .map(|pointer| json_pointer_tokens(pointer))
+        .collect::<Result<Vec<_>>>()?;
+
+    // ancestors first, so that their descendants are found to be already present
+    pointers.sort_by_key(|tokens| tokens.len());
+
+    let mut pathvalues = vec![];
+
+    'pointers: for tokens in pointers {
+        let mut node = document;
+        let mut path_components = vec![];
+
+        for token in tokens {
+            match node {
+                serde_json::Value::Object(object) => match object.get(&token) {
+                    Some(v) => {
+                        node = v;
+                        path_components.push(PathComponent::key(token));
+                    }
+                    None => continue 'pointers,
+                },
+                serde_json::Value::Array(array) => {
+                    match parse_array_index(&token).and_then(|i| Some((i, array.get(i)?))) {
+                        Some((i, v)) => {
+                            node = v;
+                            path_components.push(PathComponent::Index(i));
+                        }
+                        None => continue 'pointers,
+                    }
+                }
+                _ => continue 'pointers,
+            }
+        }
+
+        pathvalues.push((path_components, node.clone()));
+    }
+
+    unflatten(pathvalues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jindex_owned;
+
+    #[test]
+    fn round_trip() {
+        let v = serde_json::json!({"a": [1, {"b c": null}, []], "d": {"e": {}}, "f": "g"});
+
+        let unflattened = unflatten(
+            jindex_owned(v.clone()).map(|pathvalue| (pathvalue.path_components, pathvalue.value)),
+        )
+        .unwrap();
+
+        assert_eq!(unflattened, v);
+
+        let ungronned = ungron(
+            "json.a[1][\"b c\"] = null;\njson.a[0] = 1;\njson.a[2] = [];\njson.d.e = {};\njson.f = \"g\";\n",
+        )
+        .unwrap();
+
+        assert_eq!(ungronned, v);
+
+        assert!(ungron("json.a = 1;\njson.a.b = 2;").is_err());
+
+        assert_eq!(ungron("json = {};\n").unwrap(), serde_json::json!({}));
+        assert_eq!(ungron("json = [];\n").unwrap(), serde_json::json!([]));
+        assert_eq!(
+            ungron("json = [];\njson[1] = {};\n").unwrap(),
+            serde_json::json!([null, {}])
+        );
+        assert_eq!(
+            ungron("json[\"a\\\"b\\n\"] = 1;\njson = {};\n").unwrap(),
+            serde_json::json!({"a\"b\n": 1})
+        );
+        assert!(ungron("json = [];\njson.a = 1;\n").is_err());
+    }
+
+    #[test]
+    fn extract_pointers() {
+        let v = serde_json::json!({"a": {"b": 1, "x": 2}, "c": [3, 4, 5], "0": {"d": 6}});
+
+        assert_eq!(
+            extract(&v, &["/c/1", "/0/d", "/a/b", "/a", "/missing", "/c/9"]).unwrap(),
+            serde_json::json!({"a": {"b": 1, "x": 2}, "c": [null, 4], "0": {"d": 6}})
+        );
+    }
+}
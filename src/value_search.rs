@@ -0,0 +1,156 @@
+//! Full-text search over the scalar values of a document, with a [tantivy] index
+//! of the text of every scalar keyed by its JSON Pointer.
+//!
+//! Strings are indexed by their contents, and numbers, booleans, and nulls
+//! by their JSON text, so `true` and `404` can be searched for as words.
+//! Queries are in tantivy's query syntax, like `+timeout -retry`,
+//! and matches are ranked by relevance.
+
+use crate::jindex_owned;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexWriter, TantivyDocument};
+
+/// The memory used by the index writer while indexing, in bytes
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// A value that matched a query
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueMatch {
+    /// The JSON Pointer to the value
+    pub pointer: String,
+    pub value: serde_json::Value,
+    /// How relevant the value is to the query; higher is more relevant
+    pub score: f32,
+}
+
+struct Fields {
+    pointer: Field,
+    text: Field,
+    value: Field,
+}
+
+impl Fields {
+    fn schema() -> Schema {
+        let mut builder = Schema::builder();
+        builder.add_text_field("pointer", STRING | STORED);
+        builder.add_text_field("text", TEXT);
+        builder.add_text_field("value", STORED);
+        builder.build()
+    }
+
+    fn of(schema: &Schema) -> Result<Self> {
+        Ok(Self {
+            pointer: schema.get_field("pointer")?,
+            text: schema.get_field("text")?,
+            value: schema.get_field("value")?,
+        })
+    }
+}
+
+/// Index the scalar values of `value` into a new index in the directory `dir`,
+/// which is created if it does not exist, and must otherwise be empty
+pub fn index_values(dir: &Path, value: serde_json::Value) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let index = Index::create_in_dir(dir, Fields::schema())?;
+    let fields = Fields::of(&index.schema())?;
+    let mut writer: IndexWriter = index.writer(WRITER_MEMORY)?;
+
+    for pathvalue in jindex_owned(value) {
+        let text = match &pathvalue.value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => continue,
+            scalar => scalar.to_string(),
+        };
+
+        writer.add_document(doc!(
+            fields.pointer => pathvalue.as_pathvalue().to_json_pointer(),
+            fields.text => text,
+            fields.value => pathvalue.value.to_string(),
+        ))?;
+    }
+
+    writer.commit()?;
+
+    Ok(())
+}
+
+/// The values in the index in `dir` that match `query`, at most `limit` of them,
+/// most relevant first
+pub fn search_values(dir: &Path, query: &str, limit: usize) -> Result<Vec<ValueMatch>> {
+    let index = Index::open_in_dir(dir)?;
+    let fields = Fields::of(&index.schema())?;
+
+    let query = QueryParser::for_index(&index, vec![fields.text]).parse_query(query)?;
+    let searcher = index.reader()?.searcher();
+
+    searcher
+        .search(&query, &TopDocs::with_limit(limit))?
+        .into_iter()
+        .map(|(score, address)| {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let stored = |field| {
+                document
+                    .get_first(field)
+                    .and_then(|value| value.as_str())
+                    .ok_or_else(|| anyhow!("value index is missing a stored field"))
+            };
+
+            Ok(ValueMatch {
+                pointer: stored(fields.pointer)?.to_string(),
+                value: serde_json::from_str(stored(fields.value)?)?,
+                score,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search() {
+        let dir = std::env::temp_dir().join(format!("jindex-values-{}", std::process::id()));
+
+        index_values(
+            &dir,
+            serde_json::json!({
+                "events": [
+                    {"message": "connection timeout after retry", "code": 504},
+                    {"message": "request timeout", "ok": false},
+                    {"message": "ok"}
+                ],
+                "empty": {}
+            }),
+        )
+        .unwrap();
+
+        let pointers = |query| -> Vec<String> {
+            search_values(&dir, query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|value_match| value_match.pointer)
+                .collect()
+        };
+
+        assert_eq!(
+            pointers("timeout"),
+            vec!["/events/1/message", "/events/0/message"]
+        );
+        assert_eq!(pointers("+timeout -retry"), vec!["/events/1/message"]);
+        assert_eq!(pointers("false"), vec!["/events/1/ok"]);
+
+        let matches = search_values(&dir, "504", 10).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].value, serde_json::json!(504));
+
+        assert!(search_values(&dir, "AND (", 10).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,442 @@
+#![cfg(feature = "std")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Write `contents` to a file named `name` in a directory of its own for `test`
+fn input(test: &str, name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jindex-cli-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn jindex(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jindex"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn jindex_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jindex"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn verify() {
+    for (name, json, paths) in [
+        ("object.json", "{}", 1),
+        ("array.json", "[]", 1),
+        ("escaped.json", r#"{"a\"b": 1, "c\\d\ne": [{}]}"#, 2),
+    ] {
+        let path = input("verify", name, json.as_bytes());
+        let output = jindex(&["verify", path.to_str().unwrap()]);
+
+        assert!(output.status.success(), "{}: {}", json, stdout(&output));
+        assert_eq!(
+            stdout(&output),
+            format!("ok: {} paths round-tripped\n", paths)
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn utf16_ndjson() {
+    let text = "\u{feff}{\"a\": \"é\"}\n{\"b\": [1]}\n";
+
+    for big_endian in [false, true] {
+        let utf16: Vec<u8> = text
+            .encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect();
+
+        let path = input("utf16", "input.ndjson", &utf16);
+        let output = jindex(&["--ndjson", path.to_str().unwrap()]);
+
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "json.a = \"é\";\njson.b[0] = 1;\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn stale_index() {
+    let json = input("stale-index", "doc.json", br#"{"a": [1, 2]}"#);
+    let index = json.with_extension("jdx");
+
+    let output = jindex(&[
+        "index",
+        json.to_str().unwrap(),
+        "-o",
+        index.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let output = jindex(&["lookup", index.to_str().unwrap(), "/a/1"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2\n");
+
+    std::fs::write(&json, br#"{"b": [1, 2, 3]}"#).unwrap();
+
+    let output = jindex(&["lookup", index.to_str().unwrap(), "/a/1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the index is stale"));
+
+    std::fs::remove_file(json).unwrap();
+    std::fs::remove_file(index).unwrap();
+}
+
+#[test]
+fn parse_limits() {
+    let path = input(
+        "parse-limits",
+        "doc.json",
+        br#"{"a": "long", "b": [1, 2, 3]}"#,
+    );
+
+    for (limit, pointer) in [("--max-string-len", "/a"), ("--max-container-len", "/b")] {
+        let output = jindex(&[limit, "2", "--errors", "json", path.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(7));
+
+        let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+        assert_eq!(report["code"], "limit");
+        assert_eq!(report["path"], pointer);
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn formats_are_registered() {
+    let output = jindex(&["--list-formats", "--json"]);
+    assert!(output.status.success());
+
+    let formats: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let registry = jindex::registry::SinkRegistry::builtin();
+
+    for format in &formats {
+        let name = format["name"].as_str().unwrap();
+        assert!(registry.get(name).is_some(), "{} is not registered", name);
+
+        for format_option in format["options"].as_array().unwrap() {
+            assert!(format_option["help"].is_string(), "{}", format_option);
+        }
+    }
+
+    assert_eq!(formats[1]["name"], "json-pointer");
+    assert_eq!(formats[1]["options"][0]["name"], "--separator");
+}
+
+#[test]
+fn list_formats() {
+    let output = jindex(&["--list-formats"]);
+    assert!(output.status.success());
+
+    let listed = stdout(&output);
+    assert!(listed.starts_with("gron  "));
+    assert!(listed.contains("\njson-pointer  The JSON Pointer"));
+    assert!(listed.contains("\n              --separator\n"));
+
+    assert_eq!(stdout(&jindex(&["--format", "list"])), listed);
+    assert_eq!(
+        jindex(&["--format", "list", "--json"]).stdout,
+        jindex(&["--list-formats", "--json"]).stdout
+    );
+    assert!(!jindex(&["--json"]).status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn split_by_top_level_key() {
+    let document: serde_json::Map<String, serde_json::Value> = (0..300)
+        .map(|i| (format!("k{}", i), serde_json::json!([i, {"a": i}])))
+        .collect();
+    let document = serde_json::Value::Object(document).to_string();
+
+    let path = input(
+        "split",
+        "doc.ndjson",
+        format!("{}\n{}\n", document, document).as_bytes(),
+    );
+    let dir = path.with_file_name("out");
+
+    // with far fewer files open at a time than there are keys
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg("ulimit -n 32 && exec \"$0\" \"$@\"")
+        .args([
+            env!("CARGO_BIN_EXE_jindex"),
+            "--ndjson",
+            "--split-by-top-level-key",
+        ])
+        .args([&dir, &path])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 300);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("k7.gron")).unwrap(),
+        "json.k7[1].a = 7;\njson.k7[0] = 7;\n".repeat(2)
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn dedup_shapes() {
+    let path = input(
+        "dedup-shapes",
+        "doc.json",
+        br#"{"a": [{"b": 1}, {"b": 2}]}"#,
+    );
+
+    let output = jindex(&["--dedup-shapes", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2\tjson.a[*].b = 2;\n");
+
+    let output = jindex(&["--dedup-shapes", "-f", "json", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn float_formats() {
+    let path = input("float-formats", "doc.json", br#"[2.50, 1e3, 7, 0.125]"#);
+    let path = path.to_str().unwrap();
+
+    for (args, expected) in [
+        (
+            &["--float-decimals", "2"][..],
+            ["2.5", "1000.0", "7", "0.12"],
+        ),
+        (
+            &["--raw-values", "--float-decimals", "2"],
+            ["2.5", "1000.0", "7", "0.12"],
+        ),
+        (
+            &[
+                "--raw-values",
+                "--float-decimals",
+                "2",
+                "--float-format",
+                "fixed",
+            ],
+            ["2.50", "1000.00", "7", "0.12"],
+        ),
+        (
+            &["--raw-values", "--float-format", "shortest"],
+            ["2.5", "1000.0", "7", "0.125"],
+        ),
+    ] {
+        let output = jindex(&[args, &["-f", "json-pointer", "--sort-keys", path]].concat());
+        assert!(output.status.success(), "{:?}", args);
+
+        let mut lines: Vec<&str> = stdout(&output).lines().collect();
+        lines.sort();
+        let values: Vec<&str> = lines
+            .iter()
+            .map(|line| line.split_once('\t').unwrap().1)
+            .collect();
+        assert_eq!(values, expected, "{:?}", args);
+    }
+
+    let output = jindex(&["--float-decimals", "2", "--float-format", "fixed", path]);
+    assert_eq!(output.status.code(), Some(2));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn deterministic() {
+    let path = input(
+        "deterministic",
+        "doc.json",
+        br#"{"b": [1, 1.0, 1e0, -0.0, 2.50, 1e300], "a": {"z": 10.0, "y": -7.0}}"#,
+    );
+
+    let output = jindex(&["--deterministic", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "json.a.y = -7;\n\
+         json.a.z = 10;\n\
+         json.b[0] = 1;\n\
+         json.b[1] = 1;\n\
+         json.b[2] = 1;\n\
+         json.b[3] = 0;\n\
+         json.b[4] = 2.5;\n\
+         json.b[5] = 1e300;\n"
+    );
+
+    // floats stay floats where the output tells them from integers
+    let output = jindex(&[
+        "--deterministic",
+        "-f",
+        "json",
+        "--include-type",
+        "--number-types",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(r#"{"path_components":["a","z"],"value":10.0,"type":"f64"}"#));
+
+    let output = jindex(&["--deterministic", "-f", "types", path.to_str().unwrap()]);
+    assert!(stdout(&output).contains("json.a.z\tnumber\n"));
+
+    // formats that are not a record per line are written in document order
+    let output = jindex(&["--deterministic", "-f", "logfmt", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "a.y=-7 a.z=10 b.0=1 b.1=1 b.2=1 b.3=0 b.4=2.5 b.5=1e300\n"
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn manifest_of_stdin() {
+    let manifest = input("manifest", "manifest.json", b"");
+
+    for args in [&[][..], &["--ndjson"]] {
+        let output = jindex_with_stdin(
+            &[args, &["--manifest", manifest.to_str().unwrap(), "-"]].concat(),
+            b"{\"a\":1}\n",
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "json.a = 1;\n");
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+        assert_eq!(manifest["inputs"][0]["path"], "-");
+        assert_eq!(manifest["inputs"][0]["bytes"], 8);
+        assert_eq!(
+            manifest["inputs"][0]["sha256"],
+            "e346432021b04179518d9614f3560ccd71354a4ee101ddcb893d6959a9d6301c"
+        );
+    }
+
+    std::fs::remove_file(manifest).unwrap();
+}
+
+#[test]
+fn pick_streams_ndjson() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jindex"))
+        .args(["--ndjson", "--lenient", "--pick", "/id"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+
+    // each row is written before the next document is, or stdin is closed
+    for (document, row) in [("{id: 1,}\n", "1\n"), ("{\"id\": \"b\"}\n", "b\n")] {
+        stdin.write_all(document.as_bytes()).unwrap();
+        stdin.flush().unwrap();
+
+        line.clear();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!(line, row);
+    }
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn record_terminator_escapes() {
+    for (terminator, expected) in [
+        (r"\n", "json.a = 1;\n"),
+        (r"\\n", "json.a = 1;\\n"),
+        (r"|\t|", "json.a = 1;|\t|"),
+    ] {
+        let output = jindex_with_stdin(&["--record-terminator", terminator], b"{\"a\":1}");
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), expected);
+    }
+
+    for terminator in [r"\x", r"a\"] {
+        let output = jindex_with_stdin(&["--record-terminator", terminator], b"{\"a\":1}");
+        assert!(!output.status.success());
+    }
+}
+
+#[test]
+fn number_types() {
+    let output = jindex_with_stdin(&["--number-types"], b"{\"a\":1}");
+    assert!(!output.status.success());
+
+    let output = jindex_with_stdin(
+        &[
+            "-f",
+            "types",
+            "--include-type",
+            "--number-types",
+            "--sort-keys",
+        ],
+        b"{\"a\":1,\"b\":true}",
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "json.a\ti64\njson.b\tboolean\n");
+}
+
+#[test]
+fn raw_values_order() {
+    for (args, expected) in [
+        (
+            &["--order", "document"][..],
+            "json.b[0] = 1;\njson.b[1] = 2.50;\njson.a = 3;\n",
+        ),
+        (
+            &["--order", "reverse"][..],
+            "json.a = 3;\njson.b[1] = 2.50;\njson.b[0] = 1;\n",
+        ),
+        (
+            &["--sort-keys"][..],
+            "json.a = 3;\njson.b[0] = 1;\njson.b[1] = 2.50;\n",
+        ),
+    ] {
+        let output = jindex_with_stdin(
+            &[&["--raw-values"], args].concat(),
+            br#"{"b": [1, 2.50], "a": 3}"#,
+        );
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), expected);
+    }
+}